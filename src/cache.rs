@@ -0,0 +1,123 @@
+use crate::crawler::url_key;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// Default time a GitBook detection result stays valid in a [`CrawlCache`]
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Remembers the GitBook detection results of the URLs, to skip the HTTP round-trip
+/// when a URL is checked again
+///
+/// Each entry records whether the URL is a GitBook and when it was detected; entries
+/// older than the TTL are ignored. URLs are compared after
+/// [`normalize_url`](crate::normalize_url), their `http` and `https` forms sharing an
+/// entry. The cache can be shared between tasks and is attached to a crawl through
+/// [`CrawlConfig::detection_cache`](crate::CrawlConfig::detection_cache).
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{is_gitbook_cached, CrawlCache};
+/// use std::time::Duration;
+///
+/// let cache = CrawlCache::new(Duration::from_secs(60));
+/// assert_eq!(is_gitbook_cached("https://docs.example.com", &cache), None);
+/// cache.insert("https://docs.example.com/", true);
+/// assert_eq!(is_gitbook_cached("https://docs.example.com", &cache), Some(true));
+/// ```
+#[derive(Debug)]
+pub struct CrawlCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (bool, SystemTime)>>,
+}
+
+impl CrawlCache {
+    /// Creates an empty cache whose entries stay valid for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        CrawlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns how long an entry stays valid
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the detection result of a URL, `None` if it is unknown or expired
+    pub fn get(&self, url: &str) -> Option<bool> {
+        let entries = self.entries.lock().unwrap();
+        let (is_gitbook, detected_at) = *entries.get(&cache_key(url))?;
+        // A clock set back gives an error: the entry is then considered fresh
+        let age = detected_at.elapsed().unwrap_or_default();
+        (age < self.ttl).then_some(is_gitbook)
+    }
+
+    /// Records the detection result of a URL, detected now
+    pub fn insert(&self, url: &str, is_gitbook: bool) {
+        self.insert_at(url, is_gitbook, SystemTime::now());
+    }
+
+    /// Records the detection result of a URL, detected at `detected_at`
+    pub fn insert_at(&self, url: &str, is_gitbook: bool, detected_at: SystemTime) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(cache_key(url), (is_gitbook, detected_at));
+    }
+
+    /// Removes the expired entries
+    pub fn purge_expired(&self) {
+        let ttl = self.ttl;
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|_, (_, detected_at)| detected_at.elapsed().unwrap_or_default() < ttl);
+    }
+
+    /// Returns the number of entries, expired ones included
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns `true` if the cache has no entry
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for CrawlCache {
+    fn default() -> Self {
+        CrawlCache::new(DEFAULT_CACHE_TTL)
+    }
+}
+
+/// The key of a URL: its normalized form, `http` and `https` sharing an entry
+fn cache_key(url: &str) -> String {
+    let base = Url::parse(url).ok();
+    url_key(url, base.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crawl_cache_ttl() {
+        let cache = CrawlCache::new(Duration::from_secs(60));
+        cache.insert("http://Docs.Example.com/guide/", false);
+        assert_eq!(cache.get("https://docs.example.com/guide"), Some(false));
+
+        let old = SystemTime::now() - Duration::from_secs(120);
+        cache.insert_at("https://docs.example.com/old", true, old);
+        assert_eq!(cache.get("https://docs.example.com/old"), None);
+        assert_eq!(cache.len(), 2);
+
+        cache.purge_expired();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("https://docs.example.com/other"), None);
+    }
+}
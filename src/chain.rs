@@ -0,0 +1,271 @@
+use crate::page::GitBookPage;
+use std::collections::HashMap;
+use url::Url;
+
+/// A page of a GitBook and the pages under it, in reading order
+///
+/// Built by [`build_page_chain`]. The root stands for the site itself: its `url` is the
+/// origin of the site, its `title` the host, and it has no page; the chapters are its
+/// children.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageChain<'a> {
+    /// The URL of the page
+    pub url: String,
+
+    /// The title of the page, or the last segment of its URL when the page has none
+    pub title: String,
+
+    /// The page, `None` when it is not among the downloaded pages
+    pub page: Option<&'a GitBookPage>,
+
+    /// The pages under this one, in the order of the summary
+    pub children: Vec<PageChain<'a>>,
+}
+
+impl<'a> PageChain<'a> {
+    /// Returns the table of contents of the pages under this one, as a nested markdown list
+    ///
+    /// Each level is indented by two spaces; the node itself is not listed.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::{build_page_chain, GitBookPage};
+    /// use std::collections::HashMap;
+    ///
+    /// let links = vec![
+    ///     "https://docs.example.com/guide".to_string(),
+    ///     "https://docs.example.com/guide/install".to_string(),
+    /// ];
+    /// let pages = HashMap::from([(
+    ///     links[0].clone(),
+    ///     GitBookPage::parse(&links[0], None, "# User guide"),
+    /// )]);
+    /// assert_eq!(
+    ///     build_page_chain(&links, &pages).to_markdown_toc(),
+    ///     "- [User guide](https://docs.example.com/guide)\n  \
+    ///      - [install](https://docs.example.com/guide/install)\n"
+    /// );
+    /// ```
+    pub fn to_markdown_toc(&self) -> String {
+        let mut toc = String::new();
+        self.write_toc(&mut toc, 0);
+        toc
+    }
+
+    fn write_toc(&self, toc: &mut String, level: usize) {
+        for child in &self.children {
+            toc.push_str(&format!(
+                "{}- [{}]({})\n",
+                "  ".repeat(level),
+                child.title.replace('[', "\\[").replace(']', "\\]"),
+                child.url
+            ));
+            child.write_toc(toc, level + 1);
+        }
+    }
+
+    /// Returns the pages in reading order: each page, then the pages under it
+    ///
+    /// The nodes without a page are left out, their children are not.
+    pub fn flatten_dfs(&self) -> Vec<&'a GitBookPage> {
+        let mut pages = Vec::new();
+        self.collect_pages(&mut pages);
+        pages
+    }
+
+    fn collect_pages(&self, pages: &mut Vec<&'a GitBookPage>) {
+        pages.extend(self.page);
+        for child in &self.children {
+            child.collect_pages(pages);
+        }
+    }
+}
+
+/// Builds the navigation tree of a GitBook from the links of its summary
+///
+/// A link goes under the listed link whose URL path is the longest prefix of its own
+/// (`/api/users` under `/api`), or under the root when there is none. The children
+/// keep the order of `summary_links`, and a link listed twice is kept once. The site
+/// root itself, if listed, is a chapter like the others. The titles come from the
+/// pages found in `pages` by URL, with or without the `.md` suffix.
+///
+/// The links of a `SUMMARY.md` are read with
+/// [`extract_links_from_markdown`](crate::extract_links_from_markdown).
+///
+/// # Arguments
+///
+/// * `summary_links` - The URLs of the summary, in reading order
+/// * `pages` - The downloaded pages, by URL
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{build_page_chain, GitBookPage};
+/// use std::collections::HashMap;
+///
+/// let links: Vec<String> = ["/", "/api", "/api/users", "/guide"]
+///     .iter()
+///     .map(|path| format!("https://docs.example.com{}", path))
+///     .collect();
+/// let pages: HashMap<String, GitBookPage> = links
+///     .iter()
+///     .map(|url| (url.clone(), GitBookPage::parse(url, None, "# Page")))
+///     .collect();
+///
+/// let chain = build_page_chain(&links, &pages);
+/// assert_eq!(chain.children.len(), 3);
+/// assert_eq!(chain.children[1].children[0].url, "https://docs.example.com/api/users");
+/// assert_eq!(chain.flatten_dfs().len(), 4);
+/// ```
+pub fn build_page_chain<'a>(
+    summary_links: &[String],
+    pages: &'a HashMap<String, GitBookPage>,
+) -> PageChain<'a> {
+    let mut links: Vec<(&str, Option<Url>)> = Vec::new();
+    for link in summary_links {
+        if !links.iter().any(|(listed, _)| listed == link) {
+            links.push((link, Url::parse(link).ok()));
+        }
+    }
+
+    let root_url = links.iter().find_map(|(_, url)| url.as_ref());
+    let host = root_url.and_then(|url| url.host_str()).unwrap_or_default();
+    let origin = root_url.map_or(String::new(), |url| url.origin().ascii_serialization());
+
+    // The parent of each link: the listed link with the longest path prefix, on the same host
+    let parents: Vec<Option<usize>> = links
+        .iter()
+        .map(|(_, url)| {
+            let url = url.as_ref().filter(|url| url.host_str() == Some(host))?;
+            let segments = path_segments(url);
+            links
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (_, other))| {
+                    let other = other
+                        .as_ref()
+                        .filter(|other| other.host_str() == Some(host))?;
+                    let prefix = path_segments(other);
+                    (!prefix.is_empty()
+                        && prefix.len() < segments.len()
+                        && segments.starts_with(&prefix))
+                    .then_some((prefix.len(), i))
+                })
+                .max()
+                .map(|(_, i)| i)
+        })
+        .collect();
+
+    PageChain {
+        url: origin,
+        title: host.to_string(),
+        page: None,
+        children: chain_children(None, &links, &parents, pages),
+    }
+}
+
+/// Builds the nodes of the links whose parent is `parent`
+fn chain_children<'a>(
+    parent: Option<usize>,
+    links: &[(&str, Option<Url>)],
+    parents: &[Option<usize>],
+    pages: &'a HashMap<String, GitBookPage>,
+) -> Vec<PageChain<'a>> {
+    (0..links.len())
+        .filter(|&i| parents[i] == parent)
+        .map(|i| {
+            let (link, url) = &links[i];
+            let page = pages.get(*link).or_else(|| match link.strip_suffix(".md") {
+                Some(stripped) => pages.get(stripped),
+                None => pages.get(&format!("{}.md", link)),
+            });
+            let title = page
+                .and_then(|page| page.metadata.title.clone())
+                .or_else(|| url.as_ref().and_then(|url| path_segments(url).pop()))
+                .unwrap_or_else(|| link.to_string());
+            PageChain {
+                url: link.to_string(),
+                title,
+                page,
+                children: chain_children(Some(i), links, parents, pages),
+            }
+        })
+        .collect()
+}
+
+/// The non-empty segments of the path of a URL, without the `.md` suffix of the last one
+fn path_segments(url: &Url) -> Vec<String> {
+    let mut segments: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect();
+    if let Some(last) = segments.last_mut() {
+        if let Some(stripped) = last.strip_suffix(".md") {
+            *last = stripped.to_string();
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_page_chain() {
+        let base = "https://docs.example.com";
+        let links: Vec<String> = [
+            "/api/users/create.md",
+            "/",
+            "/api",
+            "/api/users",
+            "/guide",
+            "/api",
+            "https://other.example.com/api/users/x",
+        ]
+        .iter()
+        .map(|path| match path.starts_with('/') {
+            true => format!("{}{}", base, path),
+            false => path.to_string(),
+        })
+        .collect();
+        let pages = HashMap::from([
+            (
+                format!("{}/api/users/create", base),
+                GitBookPage::parse(&format!("{}/api/users/create", base), None, "# Create"),
+            ),
+            (
+                format!("{}/api", base),
+                GitBookPage::parse(&format!("{}/api", base), None, "# API [v2]"),
+            ),
+        ]);
+
+        let chain = build_page_chain(&links, &pages);
+        assert_eq!(chain.url, base);
+        assert_eq!(chain.title, "docs.example.com");
+        assert_eq!(
+            chain.to_markdown_toc(),
+            format!(
+                "- [{b}/]({b}/)\n\
+                 - [API \\[v2\\]]({b}/api)\n  \
+                 - [users]({b}/api/users)\n    \
+                 - [Create]({b}/api/users/create.md)\n\
+                 - [guide]({b}/guide)\n\
+                 - [x](https://other.example.com/api/users/x)\n",
+                b = base
+            )
+        );
+
+        let titles: Vec<&str> = chain
+            .flatten_dfs()
+            .iter()
+            .map(|page| page.metadata.title.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(titles, ["API [v2]", "Create"]);
+        assert!(build_page_chain(&[], &pages).children.is_empty());
+    }
+}
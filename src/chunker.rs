@@ -0,0 +1,130 @@
+/// Counts the tokens of a text, as a language model would
+pub trait Tokenize {
+    /// Returns the number of tokens of `text`
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Approximates the token count from the number of characters, without a tokenizer library
+///
+/// The default of 4 characters per token is a common estimate for English text.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{CharacterTokenizer, Tokenize};
+///
+/// assert_eq!(CharacterTokenizer(4.0).count_tokens("Hello, world"), 3);
+/// assert_eq!(CharacterTokenizer(4.0).count_tokens("Hello"), 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterTokenizer(pub f32);
+
+impl Default for CharacterTokenizer {
+    fn default() -> Self {
+        CharacterTokenizer(4.0)
+    }
+}
+
+impl Tokenize for CharacterTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        let chars_per_token = if self.0 > 0.0 { self.0 } else { 1.0 };
+        (text.chars().count() as f32 / chars_per_token).ceil() as usize
+    }
+}
+
+/// Splits a text into chunks of at most `max_tokens` tokens
+///
+/// Paragraphs are kept together when they fit in a chunk; longer paragraphs are
+/// split between words. A single word longer than a chunk gets a chunk of its own.
+///
+/// # Arguments
+///
+/// * `text` - The text to split
+/// * `max_tokens` - Maximum number of tokens of a chunk
+/// * `tokenizer` - Counts the tokens of the chunks
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{split_into_chunks, CharacterTokenizer};
+///
+/// let chunks = split_into_chunks("First paragraph.\n\nSecond one.", 5, &CharacterTokenizer(4.0));
+/// assert_eq!(chunks, vec!["First paragraph.", "Second one."]);
+/// ```
+pub fn split_into_chunks(text: &str, max_tokens: usize, tokenizer: &impl Tokenize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if fits(&current, "\n\n", paragraph, max_tokens, tokenizer) {
+            push_part(&mut current, "\n\n", paragraph);
+            continue;
+        }
+        if !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if tokenizer.count_tokens(paragraph) <= max_tokens {
+            current.push_str(paragraph);
+            continue;
+        }
+
+        // A paragraph longer than a chunk is split between words
+        for word in paragraph.split_whitespace() {
+            if !fits(&current, " ", word, max_tokens, tokenizer) && !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+            }
+            push_part(&mut current, " ", word);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Returns whether `part` can be appended to `chunk` without exceeding `max_tokens`
+fn fits(
+    chunk: &str,
+    separator: &str,
+    part: &str,
+    max_tokens: usize,
+    tokenizer: &impl Tokenize,
+) -> bool {
+    let mut candidate = chunk.to_string();
+    push_part(&mut candidate, separator, part);
+    tokenizer.count_tokens(&candidate) <= max_tokens
+}
+
+fn push_part(chunk: &mut String, separator: &str, part: &str) {
+    if !chunk.is_empty() {
+        chunk.push_str(separator);
+    }
+    chunk.push_str(part);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct WordTokenizer;
+
+    impl Tokenize for WordTokenizer {
+        fn count_tokens(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    #[test]
+    fn test_split_into_chunks() {
+        assert_eq!(
+            split_into_chunks("a b\n\nc\n\nd e f g h\n\ni", 3, &WordTokenizer),
+            vec!["a b\n\nc", "d e f", "g h\n\ni"]
+        );
+        assert!(split_into_chunks(" \n\n ", 3, &WordTokenizer).is_empty());
+
+        let chunks = split_into_chunks("abcdefgh ijkl", 2, &CharacterTokenizer(4.0));
+        assert_eq!(chunks, vec!["abcdefgh", "ijkl"]);
+        assert_eq!(CharacterTokenizer::default().count_tokens(""), 0);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// The state of the circuit breaker of a domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitBreakerState {
+    /// Requests are sent normally
+    Closed,
+
+    /// Too many consecutive errors: requests wait until `until`
+    Open { until: Instant },
+
+    /// The pause is over: the next request is a probe deciding whether to close
+    /// the circuit again or to reopen it
+    HalfOpen,
+}
+
+/// Pauses the requests to a domain after too many consecutive errors
+///
+/// After `threshold` consecutive failures the circuit of the domain opens for
+/// `cooldown`. Then one probe request is let through: the circuit closes if it
+/// succeeds and opens again if it fails.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{CircuitBreaker, CircuitBreakerState};
+/// use std::time::Duration;
+///
+/// let mut breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+/// breaker.record_failure("docs.example.com");
+/// assert_eq!(breaker.state("docs.example.com"), CircuitBreakerState::Closed);
+/// breaker.record_failure("docs.example.com");
+/// assert!(matches!(
+///     breaker.state("docs.example.com"),
+///     CircuitBreakerState::Open { .. }
+/// ));
+/// assert_eq!(breaker.tripped_domains(), ["docs.example.com"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    domains: HashMap<String, DomainCircuit>,
+    /// Domains whose circuit opened at least once, in order
+    tripped: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct DomainCircuit {
+    state: CircuitBreakerState,
+    consecutive_failures: u32,
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker opening after `threshold` consecutive failures; `0` never opens
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            domains: HashMap::new(),
+            tripped: Vec::new(),
+        }
+    }
+
+    /// Returns how long an open circuit stays open
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Returns the state of the circuit of a domain
+    pub fn state(&self, domain: &str) -> CircuitBreakerState {
+        self.domains
+            .get(domain)
+            .map_or(CircuitBreakerState::Closed, |circuit| circuit.state)
+    }
+
+    /// Waits until a request to the domain may be sent
+    ///
+    /// Returns immediately unless the circuit is open; an open circuit becomes
+    /// half-open once its pause is over.
+    pub async fn wait(&mut self, domain: &str) {
+        if let Some(until) = self.start_probe(domain) {
+            tokio::time::sleep_until(until.into()).await;
+        }
+    }
+
+    /// Lets the probe of an open circuit through without waiting: the circuit becomes
+    /// half-open and the end of its pause, when the probe may be sent, is returned
+    ///
+    /// Returns `None` unless the circuit is open.
+    pub(crate) fn start_probe(&mut self, domain: &str) -> Option<Instant> {
+        let circuit = self.domains.get_mut(domain)?;
+        let CircuitBreakerState::Open { until } = circuit.state else {
+            return None;
+        };
+        circuit.state = CircuitBreakerState::HalfOpen;
+        Some(until)
+    }
+
+    /// Records a successful request, closing the circuit of the domain
+    pub fn record_success(&mut self, domain: &str) {
+        self.domains.remove(domain);
+    }
+
+    /// Records a failed request, opening the circuit of the domain when the
+    /// threshold is reached or when the probe of a half-open circuit fails
+    ///
+    /// Returns `true` if the circuit opened.
+    pub fn record_failure(&mut self, domain: &str) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        let circuit = self
+            .domains
+            .entry(domain.to_string())
+            .or_insert(DomainCircuit {
+                state: CircuitBreakerState::Closed,
+                consecutive_failures: 0,
+            });
+        circuit.consecutive_failures += 1;
+
+        let open = match circuit.state {
+            CircuitBreakerState::HalfOpen => true,
+            CircuitBreakerState::Closed => circuit.consecutive_failures >= self.threshold,
+            CircuitBreakerState::Open { .. } => false,
+        };
+        if open {
+            circuit.state = CircuitBreakerState::Open {
+                until: Instant::now() + self.cooldown,
+            };
+            if !self.tripped.iter().any(|d| d == domain) {
+                self.tripped.push(domain.to_string());
+            }
+        }
+        open
+    }
+
+    /// Returns the domains whose circuit opened at least once
+    pub fn tripped_domains(&self) -> &[String] {
+        &self.tripped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_circuit_breaker_transitions() {
+        let mut breaker = CircuitBreaker::new(3, Duration::from_millis(50));
+        let domain = "docs.example.com";
+
+        assert!(!breaker.record_failure(domain));
+        breaker.record_success(domain);
+        assert!(!breaker.record_failure(domain));
+        assert!(!breaker.record_failure(domain));
+        assert_eq!(breaker.state(domain), CircuitBreakerState::Closed);
+        assert!(breaker.record_failure(domain));
+
+        let start = Instant::now();
+        breaker.wait(domain).await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(breaker.state(domain), CircuitBreakerState::HalfOpen);
+
+        // A failed probe reopens the circuit at once
+        assert!(breaker.record_failure(domain));
+        breaker.wait(domain).await;
+        breaker.record_success(domain);
+        assert_eq!(breaker.state(domain), CircuitBreakerState::Closed);
+        assert_eq!(breaker.state("other.com"), CircuitBreakerState::Closed);
+        assert_eq!(breaker.tripped_domains(), [domain]);
+    }
+
+    #[test]
+    fn test_circuit_breaker_disabled() {
+        let mut breaker = CircuitBreaker::new(0, Duration::from_secs(60));
+        for _ in 0..10 {
+            assert!(!breaker.record_failure("docs.example.com"));
+        }
+        assert!(breaker.tripped_domains().is_empty());
+    }
+}
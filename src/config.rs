@@ -0,0 +1,955 @@
+use crate::cache::CrawlCache;
+use crate::crawler::{LinkFilter, DEFAULT_MIN_CONFIDENCE, DEFAULT_SKIP_EXTENSIONS};
+use crate::render::TextRenderer;
+use crate::utils::DEFAULT_PAGE_SEPARATOR;
+use crate::GitBookError;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use termcolor::ColorChoice;
+use whatlang::Lang;
+
+/// Environment variable holding the bearer token read by [`CrawlConfig::from_env`]
+pub const TOKEN_ENV_VAR: &str = "GITBOOK_TOKEN";
+
+/// Environment variable holding the GitHub token read by [`CrawlConfig::from_env`]
+pub const GITHUB_TOKEN_ENV_VAR: &str = "GITHUB_TOKEN";
+
+/// Environment variable holding the cookies read by [`CrawlConfig::from_env`],
+/// as `name=value` pairs separated by `;`
+pub const COOKIE_ENV_VAR: &str = "GITBOOK_COOKIE";
+
+/// Ordering of [`CrawlConfig::link_sorter`], see the [`sorters`](crate::sorters) module
+pub type LinkSorter = Arc<dyn Fn(&str, &str) -> Ordering + Send + Sync>;
+
+/// Predicate of [`CrawlConfig::pre_visited`], `true` if the URL was already visited
+pub type PreVisited = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Options controlling how a GitBook is crawled and where its pages are saved
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::CrawlConfig;
+///
+/// let config = CrawlConfig {
+///     preserve_path_structure: true,
+///     ..Default::default()
+/// };
+/// assert!(config.preserve_path_structure);
+/// ```
+#[derive(Clone, JsonSchema)]
+#[schemars(
+    description = "Options controlling how a GitBook is crawled and where its pages are saved"
+)]
+pub struct CrawlConfig {
+    /// Mirror the URL path as a directory tree (`data/md/guide/intro.md`)
+    /// instead of flat file names (`data/md/https___docs.example.com_guide_intro.md`)
+    pub preserve_path_structure: bool,
+
+    /// Drop the `https://` prefix from flat file names (`example.com_docs_page`)
+    pub strip_scheme: bool,
+
+    /// Minimum GitBook detection confidence (0.0 to 1.0) required to crawl a site
+    pub min_confidence: f32,
+
+    /// Minimum delay between two successive requests to the same domain
+    #[schemars(schema_with = "duration_schema")]
+    pub politeness_delay: Duration,
+
+    /// Number of pages of the same depth fetched at once; above `1`, the crawl explores
+    /// one depth level at a time (see [`extract_gitbook_links_parallel`](crate::extract_gitbook_links_parallel))
+    pub concurrency: usize,
+
+    /// List the pages from the sitemaps of the site instead of crawling its HTML
+    /// (see [`crawl_sitemap_only`](crate::crawl_sitemap_only))
+    pub sitemap_only: bool,
+
+    /// Number of consecutive failed requests to a domain after which the crawler pauses
+    /// it (see [`CircuitBreaker`](crate::CircuitBreaker)); `0` disables the breaker
+    pub circuit_breaker_threshold: u32,
+
+    /// How long a domain is paused once its circuit breaker opens
+    #[schemars(schema_with = "duration_schema")]
+    pub circuit_breaker_cooldown: Duration,
+
+    /// Cookies (`name`, `value`) sent with every request
+    pub cookies: Vec<(String, String)>,
+
+    /// Extra HTTP headers (`name`, `value`) sent with every request
+    pub extra_headers: Vec<(String, String)>,
+
+    /// Token sent with every request in an `Authorization: Bearer` header
+    pub bearer_token: Option<String>,
+
+    /// Token sent to GitHub, instead of `bearer_token`, when reading a
+    /// [`Source::GitHub`](crate::Source::GitHub) repository
+    pub github_token: Option<String>,
+
+    /// Start the links file with a `#` comment block (version, date, source, page count)
+    pub links_header: bool,
+
+    /// Command line recorded in the links file header
+    pub command_line: Option<String>,
+
+    /// Whether the links file is written as text, JSON, or both
+    pub output_format: CrawlOutputFormat,
+
+    /// Don't print the crawl progress (explored pages, pages found, saved links file) to stdout
+    pub quiet: bool,
+
+    /// Only count the crawled pages: [`crawl_and_save_with_config`](crate::crawl_and_save_with_config)
+    /// writes neither the links file nor the manifest
+    pub count_only: bool,
+
+    /// Additional domains whose links are followed; the domain of the base URL is always allowed
+    pub allowed_domains: Vec<String>,
+
+    /// Follow links to every domain, ignoring `allowed_domains`
+    pub follow_all_external: bool,
+
+    /// Maximum number of pages listed per domain (`"docs.example.com"`), compared
+    /// case-insensitively; the `"*"` key sets the limit of the unlisted domains. The pages
+    /// beyond the limit are neither listed nor explored
+    pub page_limit_per_domain: HashMap<String, usize>,
+
+    /// Tune the HTTP client for HTTP/2 servers (adaptive flow-control window, `TCP_NODELAY`)
+    pub prefer_http2: bool,
+
+    /// Let [`is_gitbook_with_config`](crate::is_gitbook_with_config) answer from the host name
+    /// alone when it names GitBook; disable to always perform the HTTP check
+    pub skip_http_detection: bool,
+
+    /// Keep the links with a `#fragment`, for GitBooks routing their pages through the
+    /// fragment; the links to the sections of a page are listed but the page is fetched once
+    pub include_fragments: bool,
+
+    /// CSS selector of the elements whose `href` is followed by the crawler
+    pub link_selector: String,
+
+    /// Extensions of the files whose links are not followed (`"docx"`, `"tar.gz"`), compared
+    /// in lowercase with the end of the URL path; defaults to the
+    /// [`DEFAULT_SKIP_EXTENSIONS`](crate::DEFAULT_SKIP_EXTENSIONS)
+    pub skip_extensions: HashSet<String>,
+
+    /// Filters a link must pass to be followed, in addition to the domain filter built
+    /// from `allowed_domains` and `follow_all_external` and to the `skip_extensions` filter
+    #[schemars(skip)]
+    pub link_filters: Vec<Arc<dyn LinkFilter>>,
+
+    /// Called before a discovered URL is queued; returning `true` treats the URL as
+    /// already visited, so it is listed but neither fetched nor explored. Lets the
+    /// caller skip the pages recorded in its own database
+    #[schemars(skip)]
+    pub pre_visited: Option<PreVisited>,
+
+    /// Remembers the GitBook detection results: a URL found in the cache is not checked
+    /// again over HTTP, and the new results are added to it. Share the same cache between
+    /// configurations to reuse the results across crawls
+    #[schemars(skip)]
+    pub detection_cache: Option<Arc<CrawlCache>>,
+
+    /// Save one chapter file per group of pages sharing their first N URL path segments
+    /// (`data/api.md`, `data/guide.md`) instead of one file per page
+    pub split_by_depth: Option<usize>,
+
+    /// Maximum number of links followed from the base URL; deeper pages are not crawled
+    pub max_depth: Option<usize>,
+
+    /// Sort the crawled links by URL; when disabled they keep their breadth-first discovery order
+    pub sort_output: bool,
+
+    /// Sorts the crawled links in place of the default order, whatever `sort_output`;
+    /// the sort is stable (see the [`sorters`](crate::sorters) module)
+    #[schemars(skip)]
+    pub link_sorter: Option<LinkSorter>,
+
+    /// Name the output files after the first `# Heading` of the page (`authentication.md`),
+    /// falling back to the URL for pages without one
+    pub title_from_h1: bool,
+
+    /// Word separator of the file names derived from headings
+    pub slug_separator: char,
+
+    /// Maximum length, in characters, of the file names derived from URLs; longer names
+    /// are shortened with a hash (see [`url_to_filename_with_max`](crate::url_to_filename_with_max))
+    pub max_filename_length: usize,
+
+    /// Where the crawl and download runs record their state (see [`Manifest`](crate::Manifest));
+    /// `None` disables the manifest
+    pub manifest_path: Option<PathBuf>,
+
+    /// Directory receiving the `md/` and `txt/` directories and the chapter files
+    pub output_dir: PathBuf,
+}
+
+// Written by hand because `pre_visited` and `link_sorter` are closures
+impl fmt::Debug for CrawlConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CrawlConfig")
+            .field("preserve_path_structure", &self.preserve_path_structure)
+            .field("strip_scheme", &self.strip_scheme)
+            .field("min_confidence", &self.min_confidence)
+            .field("politeness_delay", &self.politeness_delay)
+            .field("concurrency", &self.concurrency)
+            .field("sitemap_only", &self.sitemap_only)
+            .field("circuit_breaker_threshold", &self.circuit_breaker_threshold)
+            .field("circuit_breaker_cooldown", &self.circuit_breaker_cooldown)
+            .field("cookies", &self.cookies)
+            .field("extra_headers", &self.extra_headers)
+            .field(
+                "bearer_token",
+                &self.bearer_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field(
+                "github_token",
+                &self.github_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("links_header", &self.links_header)
+            .field("command_line", &self.command_line)
+            .field("output_format", &self.output_format)
+            .field("quiet", &self.quiet)
+            .field("count_only", &self.count_only)
+            .field("allowed_domains", &self.allowed_domains)
+            .field("follow_all_external", &self.follow_all_external)
+            .field("page_limit_per_domain", &self.page_limit_per_domain)
+            .field("prefer_http2", &self.prefer_http2)
+            .field("skip_http_detection", &self.skip_http_detection)
+            .field("include_fragments", &self.include_fragments)
+            .field("link_selector", &self.link_selector)
+            .field("skip_extensions", &self.skip_extensions)
+            .field("link_filters", &self.link_filters)
+            .field(
+                "pre_visited",
+                &self.pre_visited.as_ref().map(|_| "Fn(&str) -> bool"),
+            )
+            .field("detection_cache", &self.detection_cache)
+            .field("split_by_depth", &self.split_by_depth)
+            .field("max_depth", &self.max_depth)
+            .field("sort_output", &self.sort_output)
+            .field(
+                "link_sorter",
+                &self
+                    .link_sorter
+                    .as_ref()
+                    .map(|_| "Fn(&str, &str) -> Ordering"),
+            )
+            .field("title_from_h1", &self.title_from_h1)
+            .field("slug_separator", &self.slug_separator)
+            .field("max_filename_length", &self.max_filename_length)
+            .field("manifest_path", &self.manifest_path)
+            .field("output_dir", &self.output_dir)
+            .finish()
+    }
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        CrawlConfig {
+            preserve_path_structure: false,
+            strip_scheme: false,
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+            politeness_delay: Duration::ZERO,
+            concurrency: 1,
+            sitemap_only: false,
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown: Duration::from_secs(60),
+            cookies: Vec::new(),
+            extra_headers: Vec::new(),
+            bearer_token: None,
+            github_token: None,
+            links_header: true,
+            command_line: None,
+            output_format: CrawlOutputFormat::Text,
+            quiet: false,
+            count_only: false,
+            allowed_domains: Vec::new(),
+            follow_all_external: false,
+            page_limit_per_domain: HashMap::new(),
+            prefer_http2: true,
+            skip_http_detection: true,
+            include_fragments: false,
+            link_selector: "a".to_string(),
+            skip_extensions: DEFAULT_SKIP_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            link_filters: Vec::new(),
+            pre_visited: None,
+            detection_cache: None,
+            split_by_depth: None,
+            max_depth: None,
+            sort_output: true,
+            link_sorter: None,
+            title_from_h1: false,
+            slug_separator: '-',
+            max_filename_length: 200,
+            manifest_path: None,
+            output_dir: PathBuf::from("data"),
+        }
+    }
+}
+
+impl CrawlConfig {
+    /// Returns the default configuration authenticated with the credentials of the
+    /// environment, so that they appear neither in the shell history nor in the
+    /// process list
+    ///
+    /// [`TOKEN_ENV_VAR`] (`GITBOOK_TOKEN`) sets the `bearer_token`,
+    /// [`GITHUB_TOKEN_ENV_VAR`] (`GITHUB_TOKEN`) the `github_token` and
+    /// [`COOKIE_ENV_VAR`] (`GITBOOK_COOKIE`) the `cookies`, e.g. `session=abc; theme=dark`.
+    /// Empty variables are ignored.
+    ///
+    /// # Exemples
+    ///
+    /// ```no_run
+    /// use gitbook2text::{crawl_and_save_with_config, CrawlConfig};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let config = CrawlConfig::from_env();
+    ///     crawl_and_save_with_config("https://docs.example.com", "links.txt", &config).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_env() -> Self {
+        CrawlConfig::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Builds the configuration of [`CrawlConfig::from_env`] from the variables `lookup`
+    /// returns, so that it can be tested without the process environment
+    fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name| lookup(name).filter(|v| !v.trim().is_empty());
+        CrawlConfig {
+            bearer_token: var(TOKEN_ENV_VAR).map(|token| token.trim().to_string()),
+            github_token: var(GITHUB_TOKEN_ENV_VAR).map(|token| token.trim().to_string()),
+            cookies: var(COOKIE_ENV_VAR)
+                .map(|cookies| parse_cookie_list(&cookies))
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parses `name=value` pairs separated by `;`, skipping the malformed ones
+fn parse_cookie_list(cookies: &str) -> Vec<(String, String)> {
+    cookies
+        .split(';')
+        .filter_map(|cookie| cookie.split_once('='))
+        .filter(|(name, _)| !name.trim().is_empty())
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// How pages with the same content are detected and skipped by the download pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+pub enum DedupStrategy {
+    /// Save every page
+    #[default]
+    None,
+
+    /// Skip pages whose SHA-256 matches a previously saved page
+    ExactHash,
+
+    /// Skip pages whose SimHash is within `threshold` bits of a previously saved page
+    SimHash {
+        /// Maximum Hamming distance between two near-identical pages
+        threshold: u32,
+    },
+}
+
+/// The format of the links file written by [`crawl_and_save_with_config`](crate::crawl_and_save_with_config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+pub enum CrawlOutputFormat {
+    /// One URL per line, after a `#` comment header
+    #[default]
+    Text,
+
+    /// A JSON array of `{"url", "title", "depth"}` objects, see [`json_links_path`](crate::json_links_path)
+    Json,
+
+    /// Both the text file and the JSON file
+    Both,
+}
+
+/// The format of the converted file saved next to the markdown of each page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+pub enum OutputFormat {
+    /// Plain text in `<output_dir>/txt/`
+    #[default]
+    Text,
+
+    /// AsciiDoc in `<output_dir>/adoc/`, see [`markdown_to_asciidoc`](crate::markdown_to_asciidoc)
+    AsciiDoc,
+}
+
+impl OutputFormat {
+    /// The extension of the converted files, also the name of their directory
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::AsciiDoc => "adoc",
+        }
+    }
+}
+
+/// When the text printed to a terminal is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Color when stdout is a terminal
+    #[default]
+    Auto,
+
+    /// Always color
+    Always,
+
+    /// Never color
+    Never,
+}
+
+impl ColorMode {
+    /// The `termcolor` choice of a stream written to stdout
+    pub fn color_choice(self) -> ColorChoice {
+        match self {
+            ColorMode::Auto if std::io::stdout().is_terminal() => ColorChoice::Auto,
+            ColorMode::Auto | ColorMode::Never => ColorChoice::Never,
+            ColorMode::Always => ColorChoice::Always,
+        }
+    }
+}
+
+/// Options controlling how the pages are downloaded and processed
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{DedupStrategy, DownloadConfig};
+///
+/// let config = DownloadConfig {
+///     dedup: DedupStrategy::ExactHash,
+///     ..Default::default()
+/// };
+/// assert_eq!(config.parallel_writes, 4);
+/// ```
+#[derive(Debug, Clone, JsonSchema)]
+#[schemars(description = "Options controlling how the pages are downloaded and processed")]
+pub struct DownloadConfig {
+    /// Only save pages whose detected language matches this one (ISO 639-3 code)
+    #[schemars(with = "Option<String>")]
+    pub language: Option<Lang>,
+
+    /// Minimum detection confidence (0.0 to 1.0) for a page to be filtered out by `language`.
+    /// Pages detected below this confidence are always kept. The download fails with
+    /// [`GitBookError::InvalidConfig`](crate::GitBookError::InvalidConfig) outside of this range.
+    pub language_confidence: f64,
+
+    /// Compress the saved files with Zstd at this level (1 to 22)
+    pub compression_level: Option<i32>,
+
+    /// Save the code blocks of each page as separate files in this directory
+    pub extract_code_dir: Option<PathBuf>,
+
+    /// Maximum number of files written concurrently
+    pub parallel_writes: usize,
+
+    /// Maximum number of pages downloaded concurrently
+    pub parallel_downloads: usize,
+
+    /// Remove navigation boilerplate (menus, `Next →`, ...) from the plain text
+    pub strip_nav: bool,
+
+    /// How duplicated pages are detected and skipped
+    pub dedup: DedupStrategy,
+
+    /// Hard-wrap the plain text at this number of columns (0 disables wrapping)
+    pub wrap: usize,
+
+    /// Maximum time to download a single page; a page that takes longer fails
+    /// without affecting the other downloads
+    #[schemars(schema_with = "optional_duration_schema")]
+    pub per_request_timeout: Option<Duration>,
+
+    /// Line written at the top of every `.txt` file, e.g. `# -*- coding: utf-8 -*-`
+    pub encoding_declaration: Option<String>,
+
+    /// Stop queuing pages once the saved markdown and text content reaches this size in bytes
+    ///
+    /// The downloads already in flight when the limit is reached still complete, up to
+    /// `parallel_downloads` of them, but their pages are not saved; only the page that
+    /// reaches the limit is, so the saved content exceeds it by at most one page.
+    pub max_total_bytes: Option<u64>,
+
+    /// Replaces the site origin in the absolute link and image URLs of the saved markdown,
+    /// e.g. `file:///data/md/` to browse an offline copy
+    pub base_url_override: Option<String>,
+
+    /// Once all pages are saved, rewrite the links between them in the markdown files
+    /// as relative paths (see [`rewrite_markdown_links`](crate::rewrite_markdown_links));
+    /// compressed files are left as they are
+    pub rewrite_links: bool,
+
+    /// With [`CrawlConfig::manifest_path`], leave the saved files of a page as they are
+    /// when its markdown still has the `content_hash` recorded in the manifest and its
+    /// file still exists; the page is reported as skipped (`unchanged`)
+    pub skip_unchanged: bool,
+
+    /// Save every page again, overriding `skip_unchanged`; with
+    /// [`CrawlConfig::manifest_path`], the pages that still have their recorded hash are
+    /// counted in [`DownloadReport::pages_forced`](crate::DownloadReport::pages_forced)
+    pub force_redownload: bool,
+
+    /// Fetch the HTML of each page and skip the pages whose `<head>` has a
+    /// `<meta name="robots" content="noindex">`; they are counted in
+    /// [`DownloadReport::pages_noindex`](crate::DownloadReport::pages_noindex)
+    pub respect_noindex: bool,
+
+    /// The format of the converted file of each page
+    pub format: OutputFormat,
+
+    /// Also save the text of all pages, in the order of the URLs, in `<output_dir>/combined.txt`
+    pub combine: bool,
+
+    /// Written before each page of `combined.txt`; `{url}`, `{title}`, `{index}` and
+    /// `{total}` are replaced (see [`combine_texts`](crate::combine_texts))
+    pub page_separator: String,
+
+    /// Split the text file of each page into numbered chunks of at most this many tokens
+    /// (`page.001.txt`, `page.002.txt`, ...), counted with a default
+    /// [`CharacterTokenizer`](crate::CharacterTokenizer)
+    pub chunk_size: Option<usize>,
+
+    /// Options of the markdown to plain-text conversion
+    pub text: TextConfig,
+
+    /// Converts the markdown of the pages to text instead of a [`PulldownRenderer`](crate::PulldownRenderer)
+    /// following `text`
+    #[schemars(skip)]
+    pub renderer: Option<Arc<dyn TextRenderer>>,
+
+    /// Options of the plain-text sanitization
+    pub sanitize: SanitizeConfig,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            language: None,
+            language_confidence: 0.9,
+            compression_level: None,
+            extract_code_dir: None,
+            parallel_writes: 4,
+            parallel_downloads: 8,
+            strip_nav: false,
+            dedup: DedupStrategy::None,
+            wrap: 0,
+            per_request_timeout: None,
+            encoding_declaration: None,
+            max_total_bytes: None,
+            base_url_override: None,
+            rewrite_links: false,
+            skip_unchanged: false,
+            force_redownload: false,
+            respect_noindex: false,
+            format: OutputFormat::Text,
+            combine: false,
+            page_separator: DEFAULT_PAGE_SEPARATOR.to_string(),
+            chunk_size: None,
+            text: TextConfig::default(),
+            renderer: None,
+            sanitize: SanitizeConfig::default(),
+        }
+    }
+}
+
+/// Options controlling how markdown is converted to plain text
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{markdown_to_text_with_config, TextConfig};
+///
+/// let config = TextConfig {
+///     blank_lines_between_blocks: false,
+///     ..Default::default()
+/// };
+/// let text = markdown_to_text_with_config("# Title\n\nParagraph", &config);
+/// assert_eq!(text, "TitleParagraph");
+/// ```
+#[derive(Debug, Clone, JsonSchema)]
+#[schemars(description = "Options controlling how markdown is converted to plain text")]
+pub struct TextConfig {
+    /// Separate paragraphs, headings, lists and code blocks with a blank line
+    pub blank_lines_between_blocks: bool,
+
+    /// Keep the target of links, rendering `[text](url)` as `text (url)`
+    pub preserve_urls: bool,
+
+    /// Unicode normalization form applied to the plain text
+    pub unicode_normalization: Option<UnicodeForm>,
+
+    /// Written before the items of unordered lists
+    pub list_bullet: String,
+
+    /// Numbering of the items of ordered lists
+    pub ordered_list_prefix: OrderedListStyle,
+
+    /// Number of spaces added before the items of each nested list level
+    pub indent_width: usize,
+
+    /// Drop the images entirely, alt text included
+    pub strip_images: bool,
+
+    /// Drop the HTML of the markdown (`<details>`, embeds, ...); when `false`, its tags
+    /// are removed and its text is kept
+    pub strip_inline_html: bool,
+}
+
+/// How the items of ordered lists are numbered in the plain text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+pub enum OrderedListStyle {
+    /// `1.`, `2.`, `3.`
+    #[default]
+    Numeric,
+
+    /// `a.`, `b.`, `c.`, then `aa.` after `z.`
+    Alpha,
+
+    /// `i.`, `ii.`, `iii.`
+    Roman,
+}
+
+impl OrderedListStyle {
+    /// Returns the prefix of the item numbered `n`, starting at 1
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::OrderedListStyle;
+    ///
+    /// assert_eq!(OrderedListStyle::Numeric.prefix(4), "4. ");
+    /// assert_eq!(OrderedListStyle::Alpha.prefix(28), "ab. ");
+    /// assert_eq!(OrderedListStyle::Roman.prefix(14), "xiv. ");
+    /// ```
+    pub fn prefix(self, n: u64) -> String {
+        let label = match self {
+            OrderedListStyle::Numeric => n.to_string(),
+            OrderedListStyle::Alpha if n > 0 => {
+                let mut label = Vec::new();
+                let mut n = n;
+                while n > 0 {
+                    n -= 1;
+                    label.push(b'a' + (n % 26) as u8);
+                    n /= 26;
+                }
+                label.iter().rev().map(|&b| b as char).collect()
+            }
+            OrderedListStyle::Roman if n > 0 => {
+                const NUMERALS: [(u64, &str); 13] = [
+                    (1000, "m"),
+                    (900, "cm"),
+                    (500, "d"),
+                    (400, "cd"),
+                    (100, "c"),
+                    (90, "xc"),
+                    (50, "l"),
+                    (40, "xl"),
+                    (10, "x"),
+                    (9, "ix"),
+                    (5, "v"),
+                    (4, "iv"),
+                    (1, "i"),
+                ];
+                let mut label = String::new();
+                let mut n = n;
+                for (value, numeral) in NUMERALS {
+                    while n >= value {
+                        label.push_str(numeral);
+                        n -= value;
+                    }
+                }
+                label
+            }
+            // Lists may start at 0, which has no letter or numeral
+            OrderedListStyle::Alpha | OrderedListStyle::Roman => n.to_string(),
+        };
+        format!("{}. ", label)
+    }
+}
+
+/// A Unicode normalization form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum UnicodeForm {
+    /// Canonical composition
+    Nfc,
+
+    /// Canonical decomposition
+    Nfd,
+
+    /// Compatibility composition
+    Nfkc,
+
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl Default for TextConfig {
+    fn default() -> Self {
+        TextConfig {
+            blank_lines_between_blocks: true,
+            preserve_urls: false,
+            unicode_normalization: None,
+            list_bullet: "• ".to_string(),
+            ordered_list_prefix: OrderedListStyle::Numeric,
+            indent_width: 2,
+            strip_images: false,
+            strip_inline_html: true,
+        }
+    }
+}
+
+/// Options controlling how the plain text is sanitized
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::SanitizeConfig;
+///
+/// assert!(SanitizeConfig::default().remove_gitbook_tags);
+/// assert!(!SanitizeConfig::default().remove_dashes);
+/// assert!(!SanitizeConfig::default().join_lines);
+/// ```
+#[derive(Debug, Clone, JsonSchema)]
+#[schemars(description = "Options controlling how the plain text is sanitized")]
+pub struct SanitizeConfig {
+    /// Remove or render the GitBook `{% ... %}` tags; when disabled they are kept verbatim
+    pub remove_gitbook_tags: bool,
+
+    /// Remove the `-` characters, as 0.3 did; off by default since it breaks
+    /// `type-safe`, `--output` or `Content-Type`
+    pub remove_dashes: bool,
+
+    /// Join the lines into one, collapsing every run of whitespace to a space, as 0.3
+    /// did; off by default, the lines are kept and runs of blank lines collapsed to one
+    pub join_lines: bool,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        SanitizeConfig {
+            remove_gitbook_tags: true,
+            remove_dashes: false,
+            join_lines: false,
+        }
+    }
+}
+
+/// Options controlling how [`html_to_markdown`](crate::html_to_markdown) converts a page
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::HtmlToMdConfig;
+///
+/// let config = HtmlToMdConfig::default();
+/// assert!(config.include_images && config.include_links);
+/// assert_eq!(config.base_url, None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HtmlToMdConfig {
+    /// URL the relative links and image sources are resolved against; kept as is when `None`
+    pub base_url: Option<String>,
+
+    /// Render the images as `![alt](src)`; when disabled they are dropped
+    pub include_images: bool,
+
+    /// Render the links as `[text](href)`; when disabled only their text is kept
+    pub include_links: bool,
+}
+
+impl Default for HtmlToMdConfig {
+    fn default() -> Self {
+        HtmlToMdConfig {
+            base_url: None,
+            include_images: true,
+            include_links: true,
+        }
+    }
+}
+
+/// The structure of a gitbook2text configuration file
+// Only its schema is used, by `config_json_schema`
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct ConfigFile {
+    /// Options controlling the crawl and the output file locations
+    crawl: CrawlConfig,
+
+    /// Options controlling the download pipeline, the plain-text conversion included
+    download: DownloadConfig,
+}
+
+/// The schema of a duration in the configuration file, written like `500ms` or `1m 30s`
+fn duration_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({
+        "type": "string",
+        "examples": ["500ms", "1m 30s"]
+    })
+}
+
+/// Like [`duration_schema`], for an optional duration
+fn optional_duration_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({
+        "type": ["string", "null"],
+        "examples": ["30s"]
+    })
+}
+
+/// Matches a rustdoc link to an item, ``[`item`](crate::item)`` or ``[`item`]``
+static DOC_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[(`[^`\]]*`)\](\([^)\s]*\))?").expect("static regex is valid"));
+
+/// Turns the rustdoc links of a schema description into plain code spans
+fn strip_doc_links(schema: &mut schemars::Schema) {
+    if let Some(serde_json::Value::String(description)) = schema.get_mut("description") {
+        *description = DOC_LINK.replace_all(description, "$1").into_owned();
+    }
+}
+
+/// Returns the JSON Schema (draft-07) describing the configuration file format
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::config_json_schema;
+///
+/// let schema = config_json_schema();
+/// assert!(schema.contains("http://json-schema.org/draft-07/schema#"));
+/// assert!(schema.contains("preserve_path_structure"));
+/// ```
+pub fn config_json_schema() -> String {
+    let schema = schemars::generate::SchemaSettings::draft07()
+        .with_transform(schemars::transform::RecursiveTransform(strip_doc_links))
+        .into_generator()
+        .into_root_schema_for::<ConfigFile>();
+    serde_json::to_string_pretty(&schema).expect("a JSON schema is always serializable")
+}
+
+/// The book structure declared by a GitBook `.gitbook.yaml` file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitBookConfig {
+    /// Directory containing the documentation, relative to the repository root
+    pub root: Option<String>,
+
+    /// Path of the first page of the book, relative to `root`
+    pub readme: Option<String>,
+
+    /// Path of the table of contents, relative to `root`
+    pub summary: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitBookYaml {
+    root: Option<String>,
+    #[serde(default)]
+    structure: GitBookYamlStructure,
+}
+
+#[derive(Deserialize, Default)]
+struct GitBookYamlStructure {
+    readme: Option<String>,
+    summary: Option<String>,
+}
+
+/// Parses the content of a `.gitbook.yaml` file
+///
+/// # Arguments
+///
+/// * `yaml` - The content of the file
+///
+/// # Errors
+///
+/// Returns `GitBookError::InvalidGitBookYaml` if the content is not a valid `.gitbook.yaml`.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::parse_gitbook_yaml;
+///
+/// let config = parse_gitbook_yaml("root: ./docs/\nstructure:\n  readme: intro.md\n").unwrap();
+/// assert_eq!(config.root.as_deref(), Some("./docs/"));
+/// assert_eq!(config.readme.as_deref(), Some("intro.md"));
+/// assert_eq!(config.summary, None);
+/// ```
+pub fn parse_gitbook_yaml(yaml: &str) -> Result<GitBookConfig, GitBookError> {
+    if yaml.trim().is_empty() {
+        return Ok(GitBookConfig::default());
+    }
+
+    let parsed: GitBookYaml =
+        serde_yaml::from_str(yaml).map_err(|e| GitBookError::InvalidGitBookYaml(e.to_string()))?;
+
+    Ok(GitBookConfig {
+        root: parsed.root,
+        readme: parsed.structure.readme,
+        summary: parsed.structure.summary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crawl_config_from_vars() {
+        let vars = HashMap::from([
+            (TOKEN_ENV_VAR, " secret-token "),
+            (GITHUB_TOKEN_ENV_VAR, "ghp_secret"),
+            (COOKIE_ENV_VAR, "session=abc; theme = dark;broken"),
+        ]);
+        let config = CrawlConfig::from_vars(|name| vars.get(name).map(|v| v.to_string()));
+
+        assert_eq!(config.bearer_token.as_deref(), Some("secret-token"));
+        assert_eq!(config.github_token.as_deref(), Some("ghp_secret"));
+        assert_eq!(
+            config.cookies,
+            vec![
+                ("session".to_string(), "abc".to_string()),
+                ("theme".to_string(), "dark".to_string())
+            ]
+        );
+        assert!(!format!("{:?}", config).contains("secret-token"));
+        assert!(!format!("{:?}", config).contains("ghp_secret"));
+
+        let config = CrawlConfig::from_vars(|_| Some("  ".to_string()));
+        assert_eq!(config.bearer_token, None);
+        assert_eq!(config.github_token, None);
+        assert!(config.cookies.is_empty());
+    }
+
+    #[test]
+    fn test_config_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&config_json_schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        assert_eq!(properties.keys().collect::<Vec<_>>(), ["crawl", "download"]);
+
+        let crawl = &schema["definitions"]["CrawlConfig"]["properties"];
+        assert_eq!(crawl["politeness_delay"]["type"], "string");
+        assert_eq!(
+            crawl["github_token"]["description"],
+            "Token sent to GitHub, instead of `bearer_token`, when reading a\n`Source::GitHub` repository"
+        );
+        let download = &schema["definitions"]["DownloadConfig"]["properties"];
+        assert_eq!(
+            download["per_request_timeout"]["type"],
+            serde_json::json!(["string", "null"])
+        );
+        assert!(!schema.to_string().contains("](crate::"));
+    }
+}
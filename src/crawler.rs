@@ -1,9 +1,74 @@
-use scraper::{Html, Selector};
-use std::collections::HashSet;
+use crate::cache::CrawlCache;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{parse_gitbook_yaml, CrawlConfig, CrawlOutputFormat, PreVisited};
+use crate::download::DownloadReport;
+use crate::manifest::{Manifest, ManifestPage};
+use crate::page::PageMetadata;
+use crate::utils::write_atomic;
+use crate::GitBookError;
+use futures::stream::{self, FuturesUnordered, Stream};
+use futures::StreamExt;
+use regex::Regex;
+use reqwest::cookie::Jar;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 use url::Url;
+use whatlang::Lang;
+
+/// Information gathered about a single GitBook page
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PageInfo {
+    /// The URL of the page
+    pub url: String,
+
+    /// The language detected from the page plain text, if conclusive
+    pub detected_language: Option<Lang>,
+
+    /// The number of links followed from the base URL to discover the page
+    pub depth: usize,
+
+    /// The title, description and canonical URL read from the HTML of the page, once
+    /// the crawler visited it
+    pub metadata: Option<PageMetadata>,
+}
+
+impl PageInfo {
+    /// Creates the information of a page with only its URL known
+    pub fn new(url: impl Into<String>) -> Self {
+        PageInfo {
+            url: url.into(),
+            detected_language: None,
+            depth: 0,
+            metadata: None,
+        }
+    }
+}
+
+/// Confidence from which a site is considered a GitBook
+pub const DEFAULT_MIN_CONFIDENCE: f32 = 0.5;
+
+/// Extensions of the binary files the crawler does not follow by default:
+/// documents, archives, images, audio, video, fonts and executables
+pub const DEFAULT_SKIP_EXTENSIONS: &[&str] = &[
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "epub", "zip", "tar",
+    "gz", "tgz", "tar.gz", "bz2", "xz", "7z", "rar", "jpg", "jpeg", "png", "gif", "svg", "webp",
+    "bmp", "ico", "tif", "tiff", "avif", "mp3", "wav", "ogg", "flac", "m4a", "mp4", "webm", "mov",
+    "avi", "mkv", "woff", "woff2", "ttf", "otf", "eot", "exe", "msi", "dmg", "deb", "rpm", "apk",
+    "iso", "jar", "bin",
+];
 
 /// Checks if a URL points to a GitBook site
 ///
+/// URLs whose host names GitBook are accepted without any request (see
+/// [`is_gitbook_url_heuristic`]); other URLs go through [`detect_gitbook_confidence`]
+/// with [`DEFAULT_MIN_CONFIDENCE`] as threshold.
+///
 /// # Arguments
 ///
 /// * `url` - The URL to check
@@ -20,20 +85,317 @@ use url::Url;
 ///     Ok(())
 /// }
 /// ```
-pub async fn is_gitbook(url: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
-        .build()?;
+pub async fn is_gitbook(url: &str) -> Result<bool, GitBookError> {
+    is_gitbook_with_config(url, &CrawlConfig::default()).await
+}
 
-    let response = client.get(url).send().await?;
-    let html = response.text().await?;
+/// Checks if a URL points to a GitBook site, following the given configuration
+///
+/// With `skip_http_detection` enabled, a URL matching [`is_gitbook_url_heuristic`]
+/// is accepted without any HTTP request. Otherwise the result recorded in
+/// `detection_cache` is used (see [`is_gitbook_cached`]) and, when there is none, the
+/// page is downloaded and its confidence compared to `min_confidence`.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{is_gitbook_with_config, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = CrawlConfig {
+///         skip_http_detection: false,
+///         ..Default::default()
+///     };
+///     let is_gb = is_gitbook_with_config("https://docs.gitbook.io", &config).await?;
+///     println!("Est un GitBook: {}", is_gb);
+///     Ok(())
+/// }
+/// ```
+pub async fn is_gitbook_with_config(url: &str, config: &CrawlConfig) -> Result<bool, GitBookError> {
+    if config.skip_http_detection && is_gitbook_url_heuristic(url) {
+        return Ok(true);
+    }
+    let cache = config.detection_cache.as_deref();
+    if let Some(is_gb) = cache.and_then(|cache| is_gitbook_cached(url, cache)) {
+        return Ok(is_gb);
+    }
+    let is_gb = detect_gitbook_confidence_with_config(url, config).await? >= config.min_confidence;
+    if let Some(cache) = cache {
+        cache.insert(url, is_gb);
+    }
+    Ok(is_gb)
+}
+
+/// Returns the GitBook detection result of a URL recorded in a cache
+///
+/// Returns `None` when the URL was never detected or when its result is older than
+/// the TTL of the cache, in which case the detection has to be done again.
+///
+/// # Arguments
+///
+/// * `url` - The URL to look up
+/// * `cache` - The detection results recorded so far
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{is_gitbook_cached, CrawlCache};
+///
+/// let cache = CrawlCache::default();
+/// cache.insert("https://example.com", false);
+/// assert_eq!(is_gitbook_cached("https://example.com", &cache), Some(false));
+/// assert_eq!(is_gitbook_cached("https://docs.example.com", &cache), None);
+/// ```
+pub fn is_gitbook_cached(url: &str, cache: &CrawlCache) -> Option<bool> {
+    cache.get(url)
+}
+
+/// Checks, without any network call, whether the host of a URL names GitBook
+///
+/// Matches `*.gitbook.io`, `*.gitbook.com` and any host containing `gitbook`.
+/// Returns `false` for invalid URLs.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::is_gitbook_url_heuristic;
+///
+/// assert!(is_gitbook_url_heuristic("https://myproject.gitbook.io/docs"));
+/// assert!(!is_gitbook_url_heuristic("https://example.com/gitbook"));
+/// ```
+pub fn is_gitbook_url_heuristic(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+        .is_some_and(|host| host.contains("gitbook"))
+}
+
+/// Estimates how likely a URL is to point to a GitBook site
+///
+/// Each GitBook marker found in the URL or the HTML adds to the score, which is capped at 1.0:
+///
+/// * `gitbook.com` in a script `src`: 0.95
+/// * `__GITBOOK__` in the HTML: 0.9
+/// * `gitbook.io` in the host: 0.8
+/// * `data-gitbook` attribute: 0.7
+/// * `gitbook.com` anywhere in the HTML: 0.5
+/// * `gitbook` anywhere in the HTML: 0.3
+/// * `User-agent: Gitbook` rule in the `/robots.txt` of the site: 0.3
+///
+/// # Arguments
+///
+/// * `url` - The URL to check
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::detect_gitbook_confidence;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let confidence = detect_gitbook_confidence("https://docs.example.com").await?;
+///     println!("GitBook confidence: {:.2}", confidence);
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the URL is invalid or if the HTTP request fails
+pub async fn detect_gitbook_confidence(url: &str) -> Result<f32, GitBookError> {
+    detect_gitbook_confidence_with_config(url, &CrawlConfig::default()).await
+}
+
+/// Estimates how likely a URL is to point to a GitBook site, sending the
+/// requests as configured in `config` (cookies, ...)
+///
+/// See [`detect_gitbook_confidence`] for the scoring.
+///
+/// # Errors
+///
+/// Returns an error if the URL is invalid or if the HTTP request fails
+pub async fn detect_gitbook_confidence_with_config(
+    url: &str,
+    config: &CrawlConfig,
+) -> Result<f32, GitBookError> {
+    let parsed = Url::parse(url).map_err(|_| GitBookError::InvalidUrl(url.to_string()))?;
+
+    let client = build_client(config, [&parsed])?;
+
+    let page = async { client.get(url).send().await?.text().await };
+    let (html, robots_txt) = tokio::join!(page, fetch_robots_txt(&client, &parsed));
+
+    Ok(gitbook_confidence(&parsed, &html?, &robots_txt))
+}
+
+/// Downloads the `/robots.txt` of the site of `url`, empty if it has none
+async fn fetch_robots_txt(client: &reqwest::Client, url: &Url) -> String {
+    let Ok(robots_url) = url.join("/robots.txt") else {
+        return String::new();
+    };
+    match client.get(robots_url).send().await {
+        Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// Returns the URLs of the `Sitemap:` lines of a `robots.txt`
+fn robots_txt_sitemaps(robots_txt: &str) -> Vec<String> {
+    robots_txt
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| key.trim().eq_ignore_ascii_case("sitemap"))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect()
+}
+
+/// Returns the `<loc>` URLs of a sitemap, and whether it is a sitemap index listing
+/// other sitemaps rather than pages
+fn parse_sitemap(xml: &str) -> (bool, Vec<String>) {
+    static RE_LOC: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)<loc>\s*(?:<!\[CDATA\[)?(.*?)(?:\]\]>)?\s*</loc>")
+            .expect("static regex is valid")
+    });
+
+    let is_index = xml.contains("<sitemapindex");
+    let locs = RE_LOC
+        .captures_iter(xml)
+        .map(|caps| {
+            caps[1]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect();
+    (is_index, locs)
+}
+
+/// Returns whether a `robots.txt` has a `User-agent` rule naming GitBook
+fn robots_txt_names_gitbook(robots_txt: &str) -> bool {
+    robots_txt.lines().any(|line| {
+        line.split_once(':').is_some_and(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("user-agent")
+                && value.to_lowercase().contains("gitbook")
+        })
+    })
+}
+
+/// Builds the HTTP client used for the requests, sending the configured
+/// cookies to the hosts of `urls` and the extra headers and bearer token to every host
+///
+/// HTTP/2 is negotiated through ALPN when the server supports it; with
+/// `prefer_http2` the client is tuned to multiplex the pages over that connection.
+pub(crate) fn build_client<'a>(
+    config: &CrawlConfig,
+    urls: impl IntoIterator<Item = &'a Url>,
+) -> Result<reqwest::Client, GitBookError> {
+    let jar = cookie_jar(config);
+    if let Some(jar) = &jar {
+        for url in urls {
+            add_cookies(jar, config, url);
+        }
+    }
+    build_client_with_jar(config, jar)
+}
+
+/// Returns an empty cookie jar when the configuration has cookies
+pub(crate) fn cookie_jar(config: &CrawlConfig) -> Option<Arc<Jar>> {
+    (!config.cookies.is_empty()).then(|| Arc::new(Jar::default()))
+}
 
-    let indicators = ["gitbook", "data-gitbook", "__GITBOOK__", "gitbook.com"];
+/// Adds the cookies of the configuration to the jar, for the domain of `url`
+pub(crate) fn add_cookies(jar: &Jar, config: &CrawlConfig, url: &Url) {
+    for (name, value) in &config.cookies {
+        jar.add_cookie_str(&format!("{}={}", name, value), url);
+    }
+}
+
+/// Builds the HTTP client like [`build_client`], with a jar filled by the caller
+pub(crate) fn build_client_with_jar(
+    config: &CrawlConfig,
+    jar: Option<Arc<Jar>>,
+) -> Result<reqwest::Client, GitBookError> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36");
+
+    if let Some(jar) = jar {
+        builder = builder.cookie_provider(jar);
+    }
+
+    if !config.extra_headers.is_empty() || config.bearer_token.is_some() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(token) = &config.bearer_token {
+            let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| GitBookError::InvalidHeader("invalid bearer token".to_string()))?;
+            value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+        for (name, value) in &config.extra_headers {
+            let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| GitBookError::InvalidHeader(format!("invalid name {:?}", name)))?;
+            let header_value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| GitBookError::InvalidHeader(format!("invalid value for {}", name)))?;
+            headers.append(header_name, header_value);
+        }
+        builder = builder.default_headers(headers);
+    }
+
+    if config.prefer_http2 {
+        builder = builder.http2_adaptive_window(true).tcp_nodelay(true);
+    } else {
+        builder = builder.http1_only();
+    }
+
+    Ok(builder.build()?)
+}
 
+/// Computes the GitBook confidence score of an already downloaded page and `robots.txt`
+fn gitbook_confidence(url: &Url, html: &str, robots_txt: &str) -> f32 {
     let html_lower = html.to_lowercase();
-    Ok(indicators
-        .iter()
-        .any(|&indicator| html_lower.contains(indicator)))
+    let mut score = 0.0;
+
+    let script_selector = Selector::parse("script[src]").unwrap();
+    let document = Html::parse_document(html);
+    if document
+        .select(&script_selector)
+        .filter_map(|script| script.value().attr("src"))
+        .any(|src| src.to_lowercase().contains("gitbook.com"))
+    {
+        score += 0.95;
+    }
+
+    if html.contains("__GITBOOK__") {
+        score += 0.9;
+    }
+
+    if url
+        .host_str()
+        .is_some_and(|host| host.to_lowercase().contains("gitbook.io"))
+    {
+        score += 0.8;
+    }
+
+    if html_lower.contains("data-gitbook") {
+        score += 0.7;
+    }
+
+    if html_lower.contains("gitbook.com") {
+        score += 0.5;
+    }
+
+    if html_lower.contains("gitbook") {
+        score += 0.3;
+    }
+
+    if robots_txt_names_gitbook(robots_txt) {
+        score += 0.3;
+    }
+
+    f32::min(score, 1.0)
 }
 
 /// Extracts all documentation links from a GitBook site
@@ -56,130 +418,2784 @@ pub async fn is_gitbook(url: &str) -> Result<bool, Box<dyn std::error::Error>> {
 ///     Ok(())
 /// }
 /// ```
-pub async fn extract_gitbook_links(
-    base_url: &str,
-) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
-        .build()?;
-
-    let base = Url::parse(base_url)?;
-    let mut visited = HashSet::new();
-    let mut to_visit = vec![base_url.to_string()];
-    let mut all_links = HashSet::new();
+#[must_use = "the crawl may have failed and the links are lost"]
+pub async fn extract_gitbook_links(base_url: &str) -> Result<Vec<String>, GitBookError> {
+    extract_gitbook_links_with_config(base_url, &CrawlConfig::default()).await
+}
 
-    let link_selector = Selector::parse("a").unwrap();
+/// Extracts all documentation links from a GitBook site, following the given configuration
+///
+/// Pages that cannot be retrieved are reported on stderr and skipped.
+/// The links are sorted with `link_sorter` if set, by URL otherwise, or in
+/// breadth-first discovery order when `sort_output` is disabled.
+///
+/// # Arguments
+///
+/// * `base_url` - The base URL of the GitBook
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if the base URL is invalid or if the HTTP client cannot be built
+#[must_use = "the crawl may have failed and the links are lost"]
+pub async fn extract_gitbook_links_with_config(
+    base_url: &str,
+    config: &CrawlConfig,
+) -> Result<Vec<String>, GitBookError> {
+    let mut state = CrawlState::new(base_url, config)?;
+    let mut result = Vec::new();
 
-    while let Some(current_url) = to_visit.pop() {
-        if visited.contains(&current_url) {
-            continue;
+    while let Some(link) = state.next_link().await {
+        match link {
+            Ok(link) => result.push(link),
+            Err(e) => eprintln!("⚠️ {}", e),
         }
+    }
 
-        visited.insert(current_url.clone());
-
-        println!("🔍 Exploration: {}", current_url);
-
-        let response = match client.get(&current_url).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                eprintln!("⚠️ Error while retrieving {}: {}", current_url, e);
-                continue;
-            }
-        };
-
-        let html = match response.text().await {
-            Ok(h) => h,
-            Err(e) => {
-                eprintln!("⚠️ Error while reading HTML: {}", e);
-                continue;
-            }
-        };
+    sort_links(&mut result, config, |link| link);
 
-        let document = Html::parse_document(&html);
+    if !config.quiet {
+        println!("✅ {} page(s) trouvée(s)", result.len());
+    }
 
-        for element in document.select(&link_selector) {
-            if let Some(href) = element.value().attr("href") {
-                if let Ok(link_url) = base.join(href) {
-                    let link_str = link_url.to_string();
+    Ok(result)
+}
 
-                    if link_url.domain() == base.domain()
-                        && !link_str.contains('#')
-                        && !link_str.ends_with(".pdf")
-                        && !link_str.ends_with(".zip")
-                        && !link_str.ends_with(".jpg")
-                        && !link_str.ends_with(".png")
-                    {
-                        let normalized = link_str.trim_end_matches('/').to_string();
+/// Extracts all documentation pages from a GitBook site with their crawl depth
+///
+/// Works like [`extract_gitbook_links_with_config`], but returns a [`PageInfo`] per
+/// page with its `depth` set: the pages linked from the base URL have depth 1, the
+/// pages linked from them depth 2, and so on. Pages are explored in breadth-first
+/// order, so the depth is the smallest number of links leading to the page. The
+/// result can be filtered on `depth` without crawling again.
+///
+/// The pages are sorted with `link_sorter` if set, by URL otherwise, or in discovery
+/// order when `sort_output` is disabled.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{extract_gitbook_links_with_info, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let pages =
+///         extract_gitbook_links_with_info("https://docs.example.com", &CrawlConfig::default())
+///             .await?;
+///     let shallow: Vec<_> = pages.iter().filter(|page| page.depth <= 2).collect();
+///     println!("{} page(s) within two links", shallow.len());
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the base URL is invalid or if the HTTP client cannot be built
+#[must_use = "the crawl may have failed and the links are lost"]
+pub async fn extract_gitbook_links_with_info(
+    base_url: &str,
+    config: &CrawlConfig,
+) -> Result<Vec<PageInfo>, GitBookError> {
+    Ok(crawl_pages(base_url, config).await?.0)
+}
 
-                        all_links.insert(normalized.clone());
+/// Crawls the pages like [`extract_gitbook_links_with_info`], also returning the
+/// domains whose circuit breaker opened during the crawl and the domains that
+/// reached their [`CrawlConfig::page_limit_per_domain`]
+///
+/// With [`CrawlConfig::sitemap_only`], the pages are listed by [`crawl_sitemap_only`].
+///
+/// # Errors
+///
+/// Returns an error if the base URL is invalid or if the HTTP client cannot be built
+pub async fn crawl_pages(
+    base_url: &str,
+    config: &CrawlConfig,
+) -> Result<(Vec<PageInfo>, Vec<String>, Vec<String>), GitBookError> {
+    let mut state = CrawlState::new(base_url, config)?;
+    if config.sitemap_only {
+        let pages = state.explore_sitemaps().await?;
+        let result = finish_crawl(&state, pages, config);
+        return Ok((result, Vec::new(), state.limited_domains));
+    }
+    if config.concurrency > 1 {
+        let pages = state.explore_levels(config.concurrency).await;
+        let result = finish_crawl(&state, pages, config);
+        return Ok((
+            result,
+            state.breaker.tripped_domains().to_vec(),
+            state.limited_domains,
+        ));
+    }
 
-                        if !visited.contains(&normalized) && !to_visit.contains(&normalized) {
-                            to_visit.push(normalized);
-                        }
-                    }
-                }
-            }
+    let mut result = Vec::new();
+    while let Some(page) = state.next_page().await {
+        match page {
+            Ok(page) => result.push(page),
+            Err(e) => eprintln!("⚠️ {}", e),
         }
     }
+    let result = finish_crawl(&state, result, config);
 
-    let mut result: Vec<String> = all_links.into_iter().collect();
-    result.sort();
-
-    println!("✅ {} page(s) trouvée(s)", result.len());
+    Ok((
+        result,
+        state.breaker.tripped_domains().to_vec(),
+        state.limited_domains,
+    ))
+}
 
-    Ok(result)
+/// Crawls the pages one depth level at a time, fetching the pages of a level concurrently
+///
+/// All the pages at the same distance from the base URL are fetched together, up to
+/// [`CrawlConfig::concurrency`] at once, and the links they contain form the next
+/// level. This is much faster than [`extract_gitbook_links_with_info`] on wide,
+/// shallow sites, and finds the same pages with the same `depth`. The politeness
+/// delay and the circuit breaker still apply to each request.
+///
+/// The pages are sorted with `link_sorter` if set, by URL otherwise, or in discovery
+/// order when `sort_output` is disabled.
+/// Within a level, the discovery order follows the order in which the responses arrive.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{extract_gitbook_links_parallel, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = CrawlConfig {
+///         concurrency: 8,
+///         ..Default::default()
+///     };
+///     let pages = extract_gitbook_links_parallel("https://docs.example.com", &config).await?;
+///     println!("{} page(s)", pages.len());
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the base URL is invalid or if the HTTP client cannot be built
+#[must_use = "the crawl may have failed and the links are lost"]
+pub async fn extract_gitbook_links_parallel(
+    base_url: &str,
+    config: &CrawlConfig,
+) -> Result<Vec<PageInfo>, GitBookError> {
+    let mut state = CrawlState::new(base_url, config)?;
+    let pages = state.explore_levels(config.concurrency).await;
+    Ok(finish_crawl(&state, pages, config))
 }
 
-/// Extracts links from a GitBook and saves them to a file
+/// Lists the pages of a site from its sitemaps only, without crawling the HTML
+///
+/// The sitemaps are the ones declared by the `Sitemap:` lines of `/robots.txt`, or
+/// `/sitemap.xml` when there is none. Sitemap indexes are followed, and a nested
+/// sitemap that cannot be read is reported on stderr and skipped. Relative URLs are
+/// resolved against the sitemap listing them. The `<loc>` URLs
+/// go through the same filters as the crawled links (domains, extensions,
+/// `link_filters`, `page_limit_per_domain`), and their variants are listed once.
+///
+/// Much faster than a crawl on large sites, but only finds the pages the sitemap
+/// lists. No page is fetched, so the pages have no metadata; their `depth` is 1.
+/// They are sorted like the crawled pages.
 ///
 /// # Arguments
 ///
-/// * `base_url` - The base URL of the GitBook
-/// * `output_file` - The path to the output file (default: "links.txt")
+/// * `base_url` - The base URL of the site
+/// * `config` - The crawl configuration
 ///
 /// # Exemples
 ///
 /// ```no_run
-/// use gitbook2text::crawl_and_save;
+/// use gitbook2text::{crawl_sitemap_only, CrawlConfig};
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     crawl_and_save("https://docs.example.com", "links.txt").await?;
+///     let pages = crawl_sitemap_only("https://docs.example.com", &CrawlConfig::default()).await?;
+///     println!("{} page(s) in the sitemap", pages.len());
 ///     Ok(())
 /// }
 /// ```
-pub async fn crawl_and_save(
+///
+/// # Errors
+///
+/// Returns an error if the base URL is invalid, if the HTTP client cannot be built,
+/// or if none of the top-level sitemaps can be read
+#[must_use = "the crawl may have failed and the links are lost"]
+pub async fn crawl_sitemap_only(
     base_url: &str,
-    output_file: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔍 Checking that {} is a GitBook...", base_url);
+    config: &CrawlConfig,
+) -> Result<Vec<PageInfo>, GitBookError> {
+    let mut state = CrawlState::new(base_url, config)?;
+    let pages = state.explore_sitemaps().await?;
+    Ok(finish_crawl(&state, pages, config))
+}
 
-    if !is_gitbook(base_url).await? {
-        return Err(format!("⚠️ {} does not seem to be a GitBook site", base_url).into());
+/// Attaches the metadata of the visited pages to the crawled ones, then sorts them
+/// following `link_sorter` and `sort_output`
+fn finish_crawl(
+    state: &CrawlState,
+    mut pages: Vec<PageInfo>,
+    config: &CrawlConfig,
+) -> Vec<PageInfo> {
+    for page in &mut pages {
+        page.metadata = state
+            .metadata
+            .get(&url_key(strip_fragment(&page.url), Some(&state.base)))
+            .cloned();
     }
 
-    println!("✅ GitBook detected !");
-    println!("🕷️ Starting crawling...");
-
-    let links = extract_gitbook_links(base_url).await?;
+    sort_links(&mut pages, config, |page| &page.url);
 
-    let content = links.join("\n");
-    tokio::fs::write(output_file, content).await?;
+    if !config.quiet {
+        println!("✅ {} page(s) trouvée(s)", pages.len());
+    }
+    pages
+}
 
-    println!("💾 {} saved links in {}", links.len(), output_file);
+/// Sorts the crawled links with `link_sorter`, or by URL when `sort_output` is enabled
+fn sort_links<T>(items: &mut [T], config: &CrawlConfig, url: impl Fn(&T) -> &str) {
+    match &config.link_sorter {
+        Some(sorter) => items.sort_by(|a, b| sorter(url(a), url(b))),
+        None if config.sort_output => items.sort_by(|a, b| url(a).cmp(url(b))),
+        None => {}
+    }
+}
 
-    Ok(())
+/// Finds the GitBook sites linked from a portal page
+///
+/// Crawls `portal_url` up to `depth` links away, following links to any domain,
+/// and returns the discovered URLs that pass [`is_gitbook`]. Pages inside an
+/// already found GitBook (URLs under it) are left out, so each site appears
+/// once. The portal itself is not included.
+///
+/// # Arguments
+///
+/// * `portal_url` - The page linking to the GitBook sites
+/// * `depth` - The maximum number of links followed from the portal
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::find_gitbooks;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     for url in find_gitbooks("https://example.com/docs", 1).await? {
+///         println!("{}", url);
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the portal URL is invalid or if the HTTP client cannot be built
+pub async fn find_gitbooks(portal_url: &str, depth: usize) -> Result<Vec<String>, GitBookError> {
+    find_gitbooks_with_config(portal_url, depth, &CrawlConfig::default()).await
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Finds the GitBook sites linked from a portal page, following the given configuration
+///
+/// See [`find_gitbooks`]. `follow_all_external` and `max_depth` are overridden;
+/// the other options apply to the crawl and to the GitBook checks.
+///
+/// # Errors
+///
+/// Returns an error if the portal URL is invalid or if the HTTP client cannot be built
+pub async fn find_gitbooks_with_config(
+    portal_url: &str,
+    depth: usize,
+    config: &CrawlConfig,
+) -> Result<Vec<String>, GitBookError> {
+    let config = CrawlConfig {
+        follow_all_external: true,
+        max_depth: Some(depth),
+        ..config.clone()
+    };
+    let mut state = CrawlState::new(portal_url, &config)?;
+    state.quiet = true;
 
-    #[tokio::test]
-    async fn test_is_gitbook() {
-        // This test requires an internet connection
-        // You can skip it with #[ignore] if necessary
-        let result = is_gitbook("https://docs.gitbook.com").await;
-        assert!(result.is_ok());
+    let mut candidates = Vec::new();
+    while let Some(page) = state.next_page().await {
+        match page {
+            Ok(page) => candidates.push(page.url),
+            Err(e) => eprintln!("⚠️ {}", e),
+        }
+    }
+    // Shorter URLs first, so a site is found before its pages
+    candidates.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+    let mut found: Vec<String> = Vec::new();
+    for url in candidates {
+        let inside_found = found
+            .iter()
+            .any(|site| url.starts_with(&format!("{}/", site.trim_end_matches('/'))));
+        if inside_found {
+            continue;
+        }
+        match is_gitbook_with_config(&url, &config).await {
+            Ok(true) => found.push(url),
+            Ok(false) => {}
+            Err(e) => eprintln!("⚠️ {}: {}", url, e),
+        }
+    }
+
+    found.sort();
+    Ok(found)
+}
+
+/// Streams the documentation links of a GitBook site as they are discovered
+///
+/// Each link is yielded as soon as the page containing it has been fetched,
+/// so callers can start processing pages while the crawl is still running.
+/// Pages that cannot be retrieved are yielded as errors and the crawl goes on.
+///
+/// # Arguments
+///
+/// * `base_url` - The base URL of the GitBook
+/// * `config` - The crawl configuration
+///
+/// # Exemples
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use gitbook2text::{extract_links_stream, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = CrawlConfig::default();
+///     let links = extract_links_stream("https://docs.example.com", &config);
+///     futures::pin_mut!(links);
+///     while let Some(link) = links.next().await {
+///         match link {
+///             Ok(url) => println!("{}", url),
+///             Err(e) => eprintln!("{}", e),
+///         }
+///     }
+/// }
+/// ```
+pub fn extract_links_stream(
+    base_url: &str,
+    config: &CrawlConfig,
+) -> impl Stream<Item = Result<String, GitBookError>> {
+    let state = CrawlState::new(base_url, config);
+
+    stream::unfold(Some(state), |state| async move {
+        match state? {
+            Ok(mut state) => {
+                let link = state.next_link().await?;
+                Some((link, Some(Ok(state))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// The state of a crawl, shared by the collecting and the streaming APIs
+struct CrawlState {
+    client: reqwest::Client,
+    base: Url,
+    /// The [`url_key`] of the explored pages
+    visited: HashSet<String>,
+    /// Pages left to explore, with their depth, in breadth-first order
+    to_visit: VecDeque<(String, usize)>,
+    /// The [`url_key`] of the pages ever queued in `to_visit`
+    queued: HashSet<String>,
+    /// The [`url_key`] of the listed links
+    all_links: HashSet<String>,
+    pending: VecDeque<PageInfo>,
+    /// URL prefix declared by the `.gitbook.yaml` root, links outside of it are ignored
+    scope: Option<String>,
+    gitbook_yaml_checked: bool,
+    link_filters: Vec<Arc<dyn LinkFilter>>,
+    /// Caller predicate marking URLs as already visited
+    pre_visited: Option<PreVisited>,
+    politeness_delay: Duration,
+    /// Time of the last request sent to each domain
+    last_request: HashMap<String, Instant>,
+    /// Pauses the domains returning too many consecutive errors
+    breaker: CircuitBreaker,
+    link_selector: Selector,
+    max_depth: Option<usize>,
+    /// Keep the links with a `#fragment`
+    include_fragments: bool,
+    /// The metadata of the visited pages
+    metadata: HashMap<String, PageMetadata>,
+    /// Maximum number of pages per lowercase domain, `"*"` for the unlisted ones
+    domain_limits: HashMap<String, usize>,
+    /// The pages listed for each domain with a limit
+    domain_pages: HashMap<String, HashSet<String>>,
+    /// The domains that reached their limit, in order
+    limited_domains: Vec<String>,
+    /// Do not print the crawl progress
+    quiet: bool,
+}
+
+impl CrawlState {
+    fn new(base_url: &str, config: &CrawlConfig) -> Result<Self, GitBookError> {
+        let base =
+            Url::parse(base_url).map_err(|_| GitBookError::InvalidUrl(base_url.to_string()))?;
+
+        let client = build_client(config, [&base])?;
+        let link_filters = crawl_link_filters(&base, config);
+        let link_selector = parse_link_selector(&config.link_selector)?;
+        let base_key = url_key(base_url, Some(&base));
+
+        Ok(CrawlState {
+            client,
+            base,
+            visited: HashSet::new(),
+            to_visit: VecDeque::from([(base_url.to_string(), 0)]),
+            queued: HashSet::from([base_key]),
+            all_links: HashSet::new(),
+            pending: VecDeque::new(),
+            scope: None,
+            gitbook_yaml_checked: false,
+            link_filters,
+            pre_visited: config.pre_visited.clone(),
+            politeness_delay: config.politeness_delay,
+            last_request: HashMap::new(),
+            breaker: CircuitBreaker::new(
+                config.circuit_breaker_threshold,
+                config.circuit_breaker_cooldown,
+            ),
+            link_selector,
+            max_depth: config.max_depth,
+            include_fragments: config.include_fragments,
+            metadata: HashMap::new(),
+            domain_limits: config
+                .page_limit_per_domain
+                .iter()
+                .map(|(domain, limit)| (domain.trim().to_lowercase(), *limit))
+                .collect(),
+            domain_pages: HashMap::new(),
+            limited_domains: Vec::new(),
+            quiet: config.quiet,
+        })
+    }
+
+    /// Lists the pages of the sitemaps of the site, see [`crawl_sitemap_only`]
+    async fn explore_sitemaps(&mut self) -> Result<Vec<PageInfo>, GitBookError> {
+        self.gitbook_yaml_checked = true;
+        self.load_gitbook_yaml().await;
+
+        let robots_txt = fetch_robots_txt(&self.client, &self.base).await;
+        // Relative URLs are resolved against the document listing them
+        let mut roots: Vec<Url> = robots_txt_sitemaps(&robots_txt)
+            .iter()
+            .filter_map(|sitemap| self.base.join(sitemap).ok())
+            .collect();
+        if roots.is_empty() {
+            roots.extend(self.base.join("/sitemap.xml").ok());
+        }
+
+        let mut seen: HashSet<String> = roots.iter().map(|url| url.to_string()).collect();
+        let mut to_read: VecDeque<(Url, bool)> = roots.into_iter().map(|url| (url, true)).collect();
+        let mut first_error = None;
+        let mut read_any = false;
+        let mut pages = Vec::new();
+
+        while let Some((sitemap, top_level)) = to_read.pop_front() {
+            let xml = match self.fetch_sitemap(&sitemap).await {
+                Ok(xml) => xml,
+                Err(e) => {
+                    eprintln!("⚠️ {}: {}", sitemap, e);
+                    if top_level {
+                        first_error.get_or_insert(e);
+                    }
+                    continue;
+                }
+            };
+            read_any = true;
+            if !self.quiet {
+                println!("🗺️ Reading {}", sitemap);
+            }
+
+            let (is_index, locs) = parse_sitemap(&xml);
+            let locs = locs.iter().filter_map(|loc| sitemap.join(loc).ok());
+            if is_index {
+                for loc in locs {
+                    if seen.insert(loc.to_string()) {
+                        to_read.push_back((loc, false));
+                    }
+                }
+                continue;
+            }
+            for url in locs {
+                if !(self.include_fragments || url.fragment().is_none())
+                    || !self.link_filters.iter().all(|f| f.accept(&url))
+                {
+                    continue;
+                }
+                let normalized = normalize_link(&url);
+                if self.in_scope(&normalized)
+                    && self.within_domain_limit(strip_fragment(&normalized))
+                    && self
+                        .all_links
+                        .insert(url_key(&normalized, Some(&self.base)))
+                {
+                    let mut page = PageInfo::new(normalized);
+                    page.depth = 1;
+                    pages.push(page);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if !read_any => Err(e),
+            _ => Ok(pages),
+        }
+    }
+
+    /// Downloads a sitemap; any non-2xx response is an error
+    async fn fetch_sitemap(&mut self, url: &Url) -> Result<String, GitBookError> {
+        self.wait_for_domain(url).await;
+        let response = self.client.get(url.clone()).send().await?;
+        if !response.status().is_success() {
+            return Err(GitBookError::HttpError(
+                response.status().as_u16(),
+                url.to_string(),
+            ));
+        }
+        Ok(response.text().await?)
+    }
+
+    /// Asks the `pre_visited` predicate whether the caller already visited `url`
+    fn is_pre_visited(&mut self, url: &str) -> bool {
+        let pre_visited = self.pre_visited.as_ref().is_some_and(|f| f(url));
+        if pre_visited {
+            self.visited.insert(url_key(url, Some(&self.base)));
+        }
+        pre_visited
+    }
+
+    /// Counts the page `url` against the page limit of its domain, returning `false`
+    /// if the limit is already reached
+    fn within_domain_limit(&mut self, url: &str) -> bool {
+        if self.domain_limits.is_empty() {
+            return true;
+        }
+        let Some(domain) = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        else {
+            return true;
+        };
+        let Some(&limit) = self
+            .domain_limits
+            .get(&domain)
+            .or_else(|| self.domain_limits.get("*"))
+        else {
+            return true;
+        };
+
+        let pages = self.domain_pages.entry(domain.clone()).or_default();
+        if pages.contains(url) || pages.len() < limit {
+            pages.insert(url.to_string());
+            return true;
+        }
+        if !self.limited_domains.contains(&domain) {
+            self.limited_domains.push(domain);
+        }
+        false
+    }
+
+    /// Waits until the politeness delay has elapsed since the last request to the domain of `url`
+    async fn wait_for_domain(&mut self, url: &Url) {
+        let domain = url.host_str().unwrap_or_default();
+        let slot = self.reserve_slot(domain, Instant::now());
+        tokio::time::sleep_until(slot.into()).await;
+    }
+
+    /// Reserves the next request to `domain`, no sooner than `earliest` and the politeness
+    /// delay after the previous one, and returns when it may be sent
+    fn reserve_slot(&mut self, domain: &str, earliest: Instant) -> Instant {
+        if self.politeness_delay.is_zero() {
+            return earliest;
+        }
+        let slot = match self.last_request.get(domain) {
+            Some(last) => earliest.max(*last + self.politeness_delay),
+            None => earliest,
+        };
+        self.last_request.insert(domain.to_string(), slot);
+        slot
+    }
+
+    /// Restricts the crawl to the `root` directory declared by `/.gitbook.yaml`, if any
+    async fn load_gitbook_yaml(&mut self) {
+        let Ok(yaml_url) = self.base.join("/.gitbook.yaml") else {
+            return;
+        };
+        self.wait_for_domain(&yaml_url).await;
+        let Ok(response) = self.client.get(yaml_url).send().await else {
+            return;
+        };
+        if !response.status().is_success() {
+            return;
+        }
+        let Ok(yaml) = response.text().await else {
+            return;
+        };
+
+        if let Ok(config) = parse_gitbook_yaml(&yaml) {
+            if let Some(scope) = config.root.and_then(|root| scope_url(&self.base, &root)) {
+                if !self.quiet {
+                    println!("📘 .gitbook.yaml found, crawling {}", scope);
+                }
+                self.queued.insert(url_key(&scope, Some(&self.base)));
+                self.to_visit.push_back((scope.clone(), 0));
+                self.scope = Some(scope);
+            }
+        }
+    }
+
+    fn in_scope(&self, link: &str) -> bool {
+        if !link.starts_with(&self.base.origin().ascii_serialization()) {
+            return true;
+        }
+
+        match &self.scope {
+            Some(scope) => {
+                link == scope
+                    || link
+                        .strip_prefix(scope.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+            None => true,
+        }
+    }
+
+    /// Returns the next discovered link, exploring pages until one is found
+    async fn next_link(&mut self) -> Option<Result<String, GitBookError>> {
+        self.next_page().await.map(|page| page.map(|page| page.url))
+    }
+
+    /// Returns the next discovered page with its depth, exploring pages until one is found
+    async fn next_page(&mut self) -> Option<Result<PageInfo, GitBookError>> {
+        if !self.gitbook_yaml_checked {
+            self.gitbook_yaml_checked = true;
+            self.load_gitbook_yaml().await;
+        }
+
+        loop {
+            if let Some(page) = self.pending.pop_front() {
+                return Some(Ok(page));
+            }
+
+            let (current_url, depth) = self.to_visit.pop_front()?;
+            if !self.visited.insert(url_key(&current_url, Some(&self.base))) {
+                continue;
+            }
+            // The links of the page would be deeper than the limit
+            if self.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+
+            if !self.quiet {
+                println!("🔍 Exploration: {}", current_url);
+            }
+
+            if let Err(e) = self.explore(&current_url, depth).await {
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// Fetches a page found at `depth` and queues the links it contains
+    async fn explore(&mut self, current_url: &str, depth: usize) -> Result<(), GitBookError> {
+        let domain = self.before_fetch(current_url).await;
+        let html = fetch_html(&self.client, current_url).await;
+        let html = self.record_fetch(&domain, html)?;
+        self.queue_links(current_url, depth, &html);
+        Ok(())
+    }
+
+    /// Explores the pages level by level, fetching up to `concurrency` pages of a
+    /// level at once, and returns the discovered pages
+    ///
+    /// The errors are reported on stderr and the pages that failed are skipped.
+    async fn explore_levels(&mut self, concurrency: usize) -> Vec<PageInfo> {
+        if !self.gitbook_yaml_checked {
+            self.gitbook_yaml_checked = true;
+            self.load_gitbook_yaml().await;
+        }
+
+        let mut pages = Vec::new();
+        while !self.to_visit.is_empty() {
+            // The queue holds one level; the links found while exploring it are the next one
+            let level: Vec<(String, usize)> = std::mem::take(&mut self.to_visit)
+                .into_iter()
+                .filter(|(url, depth)| {
+                    self.visited.insert(url_key(url, Some(&self.base)))
+                        && self.max_depth.is_none_or(|max| *depth < max)
+                })
+                .collect();
+
+            let mut level = level.into_iter();
+            let mut fetches = FuturesUnordered::new();
+            loop {
+                while fetches.len() < concurrency.max(1) {
+                    let Some((url, depth)) = level.next() else {
+                        break;
+                    };
+                    if !self.quiet {
+                        println!("🔍 Exploration: {}", url);
+                    }
+                    // The wait happens in the fetch, so the fetches in flight keep going
+                    let (domain, slot) = self.reserve_fetch(&url);
+                    let client = self.client.clone();
+                    fetches.push(async move {
+                        tokio::time::sleep_until(slot.into()).await;
+                        let html = fetch_html(&client, &url).await;
+                        (url, depth, domain, html)
+                    });
+                }
+
+                let Some((url, depth, domain, html)) = fetches.next().await else {
+                    break;
+                };
+                match self.record_fetch(&domain, html) {
+                    Ok(html) => self.queue_links(&url, depth, &html),
+                    Err(e) => eprintln!("⚠️ {}", e),
+                }
+            }
+            pages.extend(self.pending.drain(..));
+        }
+        pages
+    }
+
+    /// Waits for the circuit breaker and the politeness delay of the domain of `url`,
+    /// returning the domain
+    async fn before_fetch(&mut self, url: &str) -> String {
+        let (domain, slot) = self.reserve_fetch(url);
+        tokio::time::sleep_until(slot.into()).await;
+        domain
+    }
+
+    /// Reserves a request to `url` under the circuit breaker and the politeness delay of
+    /// its domain without waiting, returning the domain and when the request may be sent
+    fn reserve_fetch(&mut self, url: &str) -> (String, Instant) {
+        let now = Instant::now();
+        let Some(domain) = Url::parse(url)
+            .ok()
+            .map(|url| url.host_str().unwrap_or_default().to_string())
+        else {
+            return (String::new(), now);
+        };
+        let earliest = self
+            .breaker
+            .start_probe(&domain)
+            .map_or(now, |until| until.max(now));
+        let slot = self.reserve_slot(&domain, earliest);
+        (domain, slot)
+    }
+
+    /// Records the outcome of a request in the circuit breaker of its domain
+    fn record_fetch(
+        &mut self,
+        domain: &str,
+        html: Result<String, GitBookError>,
+    ) -> Result<String, GitBookError> {
+        match html {
+            Ok(html) => {
+                self.breaker.record_success(domain);
+                Ok(html)
+            }
+            Err(e) => {
+                if self.breaker.record_failure(domain) && !self.quiet {
+                    println!(
+                        "⏸️ Too many errors from {}, pausing for {}s",
+                        domain,
+                        self.breaker.cooldown().as_secs()
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Queues the links of a page found at `depth`
+    fn queue_links(&mut self, current_url: &str, depth: usize, html: &str) {
+        // Relative links of external pages are resolved against the page itself
+        let page = Url::parse(current_url)
+            .ok()
+            .filter(|page| page.host_str() != self.base.host_str())
+            .unwrap_or_else(|| self.base.clone());
+
+        let document = Html::parse_document(html);
+        let links = extract_page_links(
+            &page,
+            &document,
+            &self.link_selector,
+            &self.link_filters,
+            self.include_fragments,
+        );
+        self.metadata.insert(
+            url_key(current_url, Some(&self.base)),
+            extract_metadata(&document),
+        );
+
+        for normalized in links {
+            if !self.in_scope(&normalized) {
+                continue;
+            }
+            // The links to the sections of a page share its visit
+            let page = strip_fragment(&normalized).to_string();
+            if !self.within_domain_limit(&page) {
+                continue;
+            }
+
+            // Variants of a URL (scheme, trailing `/`, query order) are listed once,
+            // under the first form found
+            if self
+                .all_links
+                .insert(url_key(&normalized, Some(&self.base)))
+            {
+                let mut page = PageInfo::new(normalized.clone());
+                page.depth = depth + 1;
+                self.pending.push_back(page);
+            }
+
+            let key = url_key(&page, Some(&self.base));
+            if !self.visited.contains(&key)
+                && !self.queued.contains(&key)
+                && !self.is_pre_visited(&page)
+            {
+                self.queued.insert(key);
+                self.to_visit.push_back((page, depth + 1));
+            }
+        }
+    }
+}
+
+/// Downloads the HTML of a page; network errors and 5xx responses are failures of its domain
+async fn fetch_html(client: &reqwest::Client, url: &str) -> Result<String, GitBookError> {
+    let response = client.get(url).send().await?;
+    if response.status().is_server_error() {
+        return Err(GitBookError::HttpError(
+            response.status().as_u16(),
+            url.to_string(),
+        ));
+    }
+    Ok(response.text().await?)
+}
+
+/// Returns the URL of the `.gitbook.yaml` root directory, or `None` for the site root
+fn scope_url(base: &Url, root: &str) -> Option<String> {
+    let root = root.trim_start_matches("./").trim_matches('/');
+    if root.is_empty() || root == "." {
+        return None;
+    }
+
+    base.join(&format!("/{}", root))
+        .ok()
+        .map(|url| url.to_string().trim_end_matches('/').to_string())
+}
+
+/// Decides whether the crawler follows a link
+///
+/// Filters are added to [`CrawlConfig::link_filters`]; a link is followed only if
+/// every filter accepts it.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{CrawlConfig, LinkFilter};
+/// use std::sync::Arc;
+/// use url::Url;
+///
+/// struct NoQuery;
+///
+/// impl LinkFilter for NoQuery {
+///     fn accept(&self, url: &Url) -> bool {
+///         url.query().is_none()
+///     }
+/// }
+///
+/// let mut config = CrawlConfig::default();
+/// config.link_filters.push(Arc::new(NoQuery));
+/// ```
+pub trait LinkFilter: Send + Sync {
+    /// Returns `true` if the crawler may follow the link to `url`
+    fn accept(&self, url: &Url) -> bool;
+}
+
+impl fmt::Debug for dyn LinkFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LinkFilter")
+    }
+}
+
+/// Accepts the links to a list of domains, or to any domain
+///
+/// The crawler always applies the filter built from the base URL,
+/// `allowed_domains` and `follow_all_external`, before the configured filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SameDomainFilter {
+    /// The accepted domains in lowercase, `None` for any domain
+    domains: Option<Vec<String>>,
+}
+
+impl SameDomainFilter {
+    /// Accepts the links to these domains, compared case-insensitively
+    pub fn new<S: AsRef<str>>(domains: impl IntoIterator<Item = S>) -> Self {
+        SameDomainFilter {
+            domains: Some(
+                domains
+                    .into_iter()
+                    .map(|d| d.as_ref().trim().to_lowercase())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Accepts the links to any domain
+    pub fn any() -> Self {
+        SameDomainFilter { domains: None }
+    }
+
+    /// The filter of a crawl starting at `base`
+    fn for_crawl(base: &Url, config: &CrawlConfig) -> Self {
+        if config.follow_all_external {
+            return SameDomainFilter::any();
+        }
+        SameDomainFilter::new(
+            config
+                .allowed_domains
+                .iter()
+                .map(String::as_str)
+                .chain(base.host_str()),
+        )
+    }
+}
+
+impl LinkFilter for SameDomainFilter {
+    fn accept(&self, url: &Url) -> bool {
+        match &self.domains {
+            None => true,
+            Some(domains) => url
+                .host_str()
+                .is_some_and(|host| domains.iter().any(|d| d.eq_ignore_ascii_case(host))),
+        }
+    }
+}
+
+/// Rejects the links to files with one of the given extensions
+///
+/// The default filter rejects the [`DEFAULT_SKIP_EXTENSIONS`]. The crawler always
+/// applies one built from [`CrawlConfig::skip_extensions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionFilter {
+    /// The rejected extensions in lowercase, without the dot
+    extensions: Vec<String>,
+}
+
+impl ExtensionFilter {
+    /// Rejects the links whose path ends with one of these extensions (`"pdf"` or `".pdf"`)
+    pub fn new<S: AsRef<str>>(extensions: impl IntoIterator<Item = S>) -> Self {
+        ExtensionFilter {
+            extensions: extensions
+                .into_iter()
+                .map(|e| e.as_ref().trim_start_matches('.').to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+impl Default for ExtensionFilter {
+    fn default() -> Self {
+        ExtensionFilter::new(DEFAULT_SKIP_EXTENSIONS)
+    }
+}
+
+impl LinkFilter for ExtensionFilter {
+    fn accept(&self, url: &Url) -> bool {
+        let path = url.path().to_lowercase();
+        !self.extensions.iter().any(|ext| {
+            path.strip_suffix(ext.as_str())
+                .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+}
+
+/// Accepts only, or rejects, the links under a path prefix
+///
+/// The prefix matches whole path segments: `/changelog` matches `/changelog` and
+/// `/changelog/v1` but not `/changelogs`.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{LinkFilter, PathPrefixFilter};
+/// use url::Url;
+///
+/// let filter = PathPrefixFilter::exclude("/changelog");
+/// assert!(!filter.accept(&Url::parse("https://docs.example.com/changelog/v1").unwrap()));
+/// assert!(filter.accept(&Url::parse("https://docs.example.com/guide").unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPrefixFilter {
+    prefix: String,
+    include: bool,
+}
+
+impl PathPrefixFilter {
+    /// Accepts only the links under `prefix`
+    pub fn include(prefix: impl AsRef<str>) -> Self {
+        PathPrefixFilter {
+            prefix: normalize_path_prefix(prefix.as_ref()),
+            include: true,
+        }
+    }
+
+    /// Rejects the links under `prefix`
+    pub fn exclude(prefix: impl AsRef<str>) -> Self {
+        PathPrefixFilter {
+            prefix: normalize_path_prefix(prefix.as_ref()),
+            include: false,
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        path.strip_prefix(self.prefix.as_str()).is_some_and(|rest| {
+            rest.is_empty() || rest.starts_with('/') || self.prefix.ends_with('/')
+        })
+    }
+}
+
+impl LinkFilter for PathPrefixFilter {
+    fn accept(&self, url: &Url) -> bool {
+        self.matches(url.path()) == self.include
+    }
+}
+
+/// Adds the leading `/` and drops the trailing one, keeping `/` for the root
+fn normalize_path_prefix(prefix: &str) -> String {
+    format!("/{}", prefix.trim_matches('/'))
+}
+
+/// The filters of a crawl starting at `base`: the domain filter, the extension
+/// filter, then the configured ones
+fn crawl_link_filters(base: &Url, config: &CrawlConfig) -> Vec<Arc<dyn LinkFilter>> {
+    let mut filters: Vec<Arc<dyn LinkFilter>> = vec![
+        Arc::new(SameDomainFilter::for_crawl(base, config)),
+        Arc::new(ExtensionFilter::new(&config.skip_extensions)),
+    ];
+    filters.extend(config.link_filters.iter().cloned());
+    filters
+}
+
+/// Compiles the CSS selector of the followed links
+fn parse_link_selector(selector: &str) -> Result<Selector, GitBookError> {
+    Selector::parse(selector)
+        .map_err(|e| GitBookError::ParseError(format!("invalid CSS selector {}: {}", selector, e)))
+}
+
+/// Extracts the normalized documentation links of an HTML page
+///
+/// Links with a `#fragment` are left out unless `include_fragments` is set.
+fn extract_page_links(
+    base: &Url,
+    document: &Html,
+    link_selector: &Selector,
+    filters: &[Arc<dyn LinkFilter>],
+    include_fragments: bool,
+) -> Vec<String> {
+    let mut links = Vec::new();
+
+    for element in document.select(link_selector) {
+        if let Some(href) = element.value().attr("href") {
+            if let Ok(link_url) = base.join(href) {
+                let link_str = link_url.to_string();
+
+                if (include_fragments || !link_str.contains('#'))
+                    && filters.iter().all(|f| f.accept(&link_url))
+                {
+                    links.push(normalize_link(&link_url));
+                }
+            }
+        }
+    }
+
+    links
+}
+
+/// Extracts the title, description and canonical URL of a parsed HTML page
+///
+/// The title is the `<title>` element, the description the `description` meta tag
+/// (or `og:description`) and the canonical URL the `href` of `<link rel="canonical">`.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::extract_metadata;
+/// use scraper::Html;
+///
+/// let doc = Html::parse_document(
+///     r#"<title>Setup</title><link rel="canonical" href="https://docs.example.com/setup">"#,
+/// );
+/// let metadata = extract_metadata(&doc);
+/// assert_eq!(metadata.title.as_deref(), Some("Setup"));
+/// assert_eq!(metadata.canonical_url.as_deref(), Some("https://docs.example.com/setup"));
+/// assert_eq!(metadata.description, None);
+/// ```
+pub fn extract_metadata(doc: &Html) -> PageMetadata {
+    let first = |selector: &str, read: &dyn Fn(ElementRef) -> Option<String>| {
+        let selector = Selector::parse(selector).expect("valid selector");
+        doc.select(&selector)
+            .filter_map(read)
+            .map(|value| value.trim().to_string())
+            .find(|value| !value.is_empty())
+    };
+    let content = |el: ElementRef| el.value().attr("content").map(str::to_string);
+
+    PageMetadata {
+        title: first("title", &|el| Some(el.text().collect())),
+        description: first(r#"meta[name="description"]"#, &content)
+            .or_else(|| first(r#"meta[property="og:description"]"#, &content)),
+        canonical_url: first(r#"link[rel="canonical"]"#, &|el| {
+            el.value().attr("href").map(str::to_string)
+        }),
+    }
+}
+
+/// Checks whether the `<head>` of an HTML page asks robots not to index it
+///
+/// Looks for a `<meta name="robots">` whose `content` lists `noindex` (or `none`),
+/// ignoring the case.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::has_noindex_meta;
+///
+/// assert!(has_noindex_meta(
+///     r#"<head><meta name="Robots" content="noindex, follow"></head>"#
+/// ));
+/// assert!(!has_noindex_meta(r#"<meta name="robots" content="index">"#));
+/// ```
+pub fn has_noindex_meta(html: &str) -> bool {
+    let doc = Html::parse_document(html);
+    let selector = Selector::parse("head meta[name][content]").expect("valid selector");
+    doc.select(&selector)
+        .filter(|el| {
+            el.value()
+                .attr("name")
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case("robots"))
+        })
+        .filter_map(|el| el.value().attr("content"))
+        .flat_map(|content| content.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            directive.eq_ignore_ascii_case("noindex") || directive.eq_ignore_ascii_case("none")
+        })
+}
+
+/// Returns the form of a link used to compare it with the crawled links
+fn normalize_link(url: &Url) -> String {
+    let mut page = url.clone();
+    page.set_fragment(None);
+    let page = page.as_str().trim_end_matches('/');
+    match url.fragment().filter(|fragment| !fragment.is_empty()) {
+        Some(fragment) => format!("{}#{}", page, fragment),
+        None => page.to_string(),
+    }
+}
+
+/// Query parameters dropped by [`normalize_url`], along with every `utm_*` parameter
+///
+/// Only parameters that never select content are listed: `ref` or `source` may.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "_ga", "_gl", "igshid", "ref_src",
+];
+
+/// Returns the canonical form of a URL, under which its variants are crawled once
+///
+/// The trailing `/` of the path is dropped, the host is lowercased, the tracking
+/// parameters (`utm_source`, `fbclid`, `gclid`, ...) are removed and the other query
+/// parameters are sorted. The scheme and the fragment are kept; see
+/// [`normalize_url_with_base`] to also upgrade the `http` links of the crawled site.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::normalize_url;
+/// use url::Url;
+///
+/// let url = Url::parse("https://Docs.Example.com/guide/?b=2&utm_source=x&a=1#intro").unwrap();
+/// assert_eq!(
+///     normalize_url(&url).as_str(),
+///     "https://docs.example.com/guide?a=1&b=2#intro"
+/// );
+/// ```
+pub fn normalize_url(url: &Url) -> Url {
+    let mut normalized = url.clone();
+    if let Some(host) = url.host_str() {
+        let _ = normalized.set_host(Some(&host.to_lowercase()));
+    }
+
+    let path = url.path();
+    if path.len() > 1 && path.ends_with('/') {
+        normalized.set_path(path.trim_end_matches('/'));
+    }
+
+    let mut params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !name.starts_with("utm_") && !TRACKING_PARAMS.contains(&name.as_ref()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+    if params.is_empty() {
+        normalized.set_query(None);
+    } else {
+        normalized.query_pairs_mut().clear().extend_pairs(params);
+    }
+    normalized
+}
+
+/// Returns the [`normalize_url`] form of a link found while crawling `base`, with the
+/// scheme `http` turned into `https` when the link is on the host of `base`
+///
+/// The links to other hosts keep their scheme, as these may not serve HTTPS.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::normalize_url_with_base;
+/// use url::Url;
+///
+/// let base = Url::parse("https://docs.example.com").unwrap();
+/// let link = |url| normalize_url_with_base(&Url::parse(url).unwrap(), &base).to_string();
+/// assert_eq!(link("http://docs.example.com/guide/"), "https://docs.example.com/guide");
+/// assert_eq!(link("http://blog.example.com/post"), "http://blog.example.com/post");
+/// ```
+pub fn normalize_url_with_base(url: &Url, base: &Url) -> Url {
+    let mut normalized = normalize_url(url);
+    let same_host = match (url.host_str(), base.host_str()) {
+        (Some(host), Some(base_host)) => host.eq_ignore_ascii_case(base_host),
+        _ => false,
+    };
+    if same_host && normalized.scheme() == "http" {
+        let _ = normalized.set_scheme("https");
+    }
+    normalized
+}
+
+/// The key of a link in the sets of the crawler: its [`normalize_url_with_base`] form
+/// while crawling `base`, its [`normalize_url`] form otherwise
+pub(crate) fn url_key(link: &str, base: Option<&Url>) -> String {
+    match (Url::parse(link), base) {
+        (Ok(url), Some(base)) => normalize_url_with_base(&url, base).into(),
+        (Ok(url), None) => normalize_url(&url).into(),
+        (Err(_), _) => link.to_string(),
+    }
+}
+
+/// Returns the page of a link, without its `#fragment`
+fn strip_fragment(link: &str) -> &str {
+    link.split_once('#').map_or(link, |(page, _)| page)
+}
+
+/// The links of several links files combined by [`merge_links_files`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedLinks {
+    /// The distinct normalized links, sorted
+    pub links: Vec<String>,
+
+    /// The number of links read from all files, duplicates included
+    pub urls_found: usize,
+
+    /// The number of links removed as duplicates
+    pub duplicates: usize,
+}
+
+/// Combines the contents of several links files into one sorted list without duplicates
+///
+/// Comment lines are skipped and the links are normalized like the crawled
+/// links, so `https://docs.example.com/a/` and `https://docs.example.com/a`
+/// are the same page.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::merge_links_files;
+///
+/// let merged = merge_links_files(&[
+///     "# Source: https://docs.example.com\nhttps://docs.example.com/b\nhttps://docs.example.com/a/",
+///     "https://docs.example.com/a",
+/// ]);
+/// assert_eq!(merged.links, vec!["https://docs.example.com/a", "https://docs.example.com/b"]);
+/// assert_eq!(merged.urls_found, 3);
+/// assert_eq!(merged.duplicates, 1);
+/// ```
+pub fn merge_links_files(contents: &[&str]) -> MergedLinks {
+    let mut urls_found = 0;
+    let mut links = BTreeSet::new();
+    for content in contents {
+        for link in parse_links_file(content) {
+            urls_found += 1;
+            links.insert(match Url::parse(&link) {
+                Ok(url) => normalize_link(&url),
+                Err(_) => link.trim_end_matches('/').to_string(),
+            });
+        }
+    }
+
+    MergedLinks {
+        duplicates: urls_found - links.len(),
+        links: links.into_iter().collect(),
+        urls_found,
+    }
+}
+
+/// Parses the content of a links file, skipping blank lines and `#` comments
+///
+/// # Arguments
+///
+/// * `content` - The content of a file written by [`crawl_and_save`]
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::parse_links_file;
+///
+/// let content = "# Source: https://docs.example.com\nhttps://docs.example.com/intro\n\n";
+/// assert_eq!(parse_links_file(content), vec!["https://docs.example.com/intro"]);
+/// ```
+pub fn parse_links_file(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// An entry of a JSON links file
+#[derive(Serialize, Deserialize)]
+struct JsonLink {
+    url: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    depth: usize,
+}
+
+/// Parses the content of a JSON links file, an array of objects with a `url` field
+///
+/// # Arguments
+///
+/// * `content` - The content of a JSON file written by [`crawl_and_save_with_config`]
+///
+/// # Errors
+///
+/// Returns `GitBookError::ParseError` if the content is not such an array
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::parse_links_json;
+///
+/// let content = r#"[{"url": "https://docs.example.com/intro", "title": "Intro", "depth": 1}]"#;
+/// assert_eq!(parse_links_json(content).unwrap(), vec!["https://docs.example.com/intro"]);
+/// ```
+pub fn parse_links_json(content: &str) -> Result<Vec<String>, GitBookError> {
+    let links: Vec<JsonLink> =
+        serde_json::from_str(content).map_err(|e| GitBookError::ParseError(e.to_string()))?;
+    Ok(links.into_iter().map(|link| link.url).collect())
+}
+
+/// Returns the path of the JSON links file written next to the text links file
+///
+/// The extension of `output_file` is replaced with `.json`, or added if it has none.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::json_links_path;
+/// use std::path::Path;
+///
+/// assert_eq!(json_links_path(Path::new("links.txt")), Path::new("links.json"));
+/// assert_eq!(json_links_path(Path::new("out/links")), Path::new("out/links.json"));
+/// ```
+pub fn json_links_path(output_file: &Path) -> PathBuf {
+    output_file.with_extension("json")
+}
+
+/// Returns the comment block written at the top of a links file
+fn links_file_header(base_url: &str, count: usize, command_line: Option<&str>) -> String {
+    let mut header = format!(
+        "# gitbook2text v{}\n# Crawled: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        humantime::format_rfc3339_seconds(std::time::SystemTime::now()),
+    );
+    if let Some(command_line) = command_line {
+        header.push_str(&format!("# Command: {}\n", command_line));
+    }
+    header.push_str(&format!("# Source: {}\n# Pages: {}\n", base_url, count));
+    header
+}
+
+/// Extracts links from a GitBook and saves them to a file
+///
+/// # Arguments
+///
+/// * `base_url` - The base URL of the GitBook
+/// * `output_file` - The path to the output file (default: "links.txt")
+///
+/// The file lists one URL per line, sorted, after a `#` comment header
+/// (see [`CrawlConfig::links_header`]). With [`CrawlConfig::output_format`], the links
+/// are written to a JSON file instead or as well (see [`json_links_path`]).
+///
+/// Returns a `DownloadReport` with the number of links found.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::crawl_and_save;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let report = crawl_and_save("https://docs.example.com", "links.txt").await?;
+///     println!("{} page(s) found", report.pages_discovered);
+///     Ok(())
+/// }
+/// ```
+pub async fn crawl_and_save(
+    base_url: &str,
+    output_file: &str,
+) -> Result<DownloadReport, GitBookError> {
+    crawl_and_save_with_config(base_url, output_file, &CrawlConfig::default()).await
+}
+
+/// Extracts links from a GitBook and saves them to a file, following the given configuration
+///
+/// The file is written to `<output_file>.tmp` first and then renamed, so an
+/// interrupted run leaves the previous links file intact. With
+/// [`CrawlConfig::count_only`] nothing is written and only the report is returned.
+///
+/// The base URL is checked as in [`is_gitbook_with_config`]: with
+/// [`CrawlConfig::skip_http_detection`], a GitBook host needs no detection request.
+///
+/// # Arguments
+///
+/// * `base_url` - The base URL of the GitBook
+/// * `output_file` - The path to the output file
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns [`GitBookError::InvalidConfig`] with [`CrawlOutputFormat::Both`] when
+/// `output_file` already ends in `.json`, as the JSON file would overwrite it.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{crawl_and_save_with_config, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = CrawlConfig {
+///         min_confidence: 0.8,
+///         ..Default::default()
+///     };
+///     crawl_and_save_with_config("https://docs.example.com", "links.txt", &config).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn crawl_and_save_with_config(
+    base_url: &str,
+    output_file: &str,
+    config: &CrawlConfig,
+) -> Result<DownloadReport, GitBookError> {
+    if config.output_format == CrawlOutputFormat::Both
+        && json_links_path(Path::new(output_file)) == Path::new(output_file)
+    {
+        return Err(GitBookError::InvalidConfig(format!(
+            "{} would receive both the text and the JSON links",
+            output_file
+        )));
+    }
+
+    let start = Instant::now();
+    if !config.quiet {
+        println!("🔍 Checking that {} is a GitBook...", base_url);
+    }
+
+    let cache = config.detection_cache.as_deref();
+    let detection = if config.skip_http_detection && is_gitbook_url_heuristic(base_url) {
+        "from its URL".to_string()
+    } else {
+        match cache.and_then(|cache| is_gitbook_cached(base_url, cache)) {
+            Some(true) => "cached".to_string(),
+            Some(false) => {
+                return Err(GitBookError::NotAGitBook(format!("{} (cached)", base_url)));
+            }
+            None => {
+                let confidence = detect_gitbook_confidence_with_config(base_url, config).await?;
+                if let Some(cache) = cache {
+                    cache.insert(base_url, confidence >= config.min_confidence);
+                }
+                if confidence < config.min_confidence {
+                    return Err(GitBookError::NotAGitBook(format!(
+                        "{} (confidence {:.2})",
+                        base_url, confidence
+                    )));
+                }
+                format!("confidence {:.2}", confidence)
+            }
+        }
+    };
+
+    if !config.quiet {
+        println!("✅ GitBook detected ({}) !", detection);
+        println!("🕷️ Starting crawling...");
+    }
+
+    let (pages, domains_tripped, domains_limited) = crawl_pages(base_url, config).await?;
+    let links: Vec<String> = pages.iter().map(|page| page.url.clone()).collect();
+
+    if config.count_only {
+        return Ok(DownloadReport {
+            pages_discovered: links.len(),
+            domains_tripped,
+            domains_limited,
+            duration: start.elapsed(),
+            ..Default::default()
+        });
+    }
+
+    if config.output_format != CrawlOutputFormat::Json {
+        let mut content = String::new();
+        if config.links_header {
+            content.push_str(&links_file_header(
+                base_url,
+                links.len(),
+                config.command_line.as_deref(),
+            ));
+        }
+        content.push_str(&links.join("\n"));
+        write_atomic(Path::new(output_file), content).await?;
+
+        if !config.quiet {
+            println!("💾 {} saved links in {}", links.len(), output_file);
+        }
+    }
+
+    if config.output_format != CrawlOutputFormat::Text {
+        let json_file = json_links_path(Path::new(output_file));
+        let entries: Vec<JsonLink> = pages
+            .iter()
+            .map(|page| JsonLink {
+                url: page.url.clone(),
+                title: page.metadata.as_ref().and_then(|m| m.title.clone()),
+                depth: page.depth,
+            })
+            .collect();
+        let content = serde_json::to_string_pretty(&entries)
+            .map_err(|e| GitBookError::ParseError(e.to_string()))?;
+        write_atomic(&json_file, content).await?;
+
+        if !config.quiet {
+            println!("💾 {} saved links in {}", links.len(), json_file.display());
+        }
+    }
+
+    if let Some(path) = &config.manifest_path {
+        save_crawl_manifest(path, base_url, &pages)?;
+    }
+
+    Ok(DownloadReport {
+        pages_discovered: links.len(),
+        domains_tripped,
+        domains_limited,
+        duration: start.elapsed(),
+        ..Default::default()
+    })
+}
+
+/// Records the crawled pages in the manifest at `path`
+///
+/// The download state of the pages already in the manifest of the same site is kept;
+/// pages that are no longer linked are dropped. The metadata read from the HTML fills
+/// in the title of the pages not downloaded yet and replaces the description and
+/// canonical URL.
+fn save_crawl_manifest(
+    path: &Path,
+    base_url: &str,
+    pages: &[PageInfo],
+) -> Result<(), GitBookError> {
+    let previous = Manifest::load(path)
+        .ok()
+        .filter(|manifest| manifest.source_url == base_url);
+
+    let mut manifest = Manifest::new(base_url);
+    for page in pages {
+        let entry = match previous.as_ref().and_then(|m| m.page(&page.url)) {
+            Some(entry) => ManifestPage {
+                depth: page.depth,
+                ..entry.clone()
+            },
+            None => ManifestPage::new(page.url.clone(), page.depth),
+        };
+        let metadata = page.metadata.clone().unwrap_or_default();
+        manifest.upsert(ManifestPage {
+            title: entry.title.or(metadata.title),
+            description: metadata.description.or(entry.description),
+            canonical_url: metadata.canonical_url.or(entry.canonical_url),
+            ..entry
+        });
+    }
+
+    manifest.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LinkSorter;
+    use crate::test_server::{MockResponse, MockServer};
+    use futures::StreamExt;
+
+    /// Serves the given `(path, html)` pages on a local port and returns the base URL
+    async fn serve(pages: Vec<(&'static str, &'static str)>) -> String {
+        serve_with_status(pages.into_iter().map(|(p, b)| (p, 200, b)).collect()).await
+    }
+
+    /// Like [`serve`], with the status code of each page
+    async fn serve_with_status(pages: Vec<(&'static str, u16, &'static str)>) -> String {
+        let server = MockServer::start(move |request| {
+            match pages.iter().find(|(path, _, _)| *path == request.path) {
+                Some((_, status, body)) => {
+                    MockResponse::new(*status, *body).header("Content-Type", "text/html")
+                }
+                None => MockResponse::not_found(),
+            }
+        })
+        .await;
+        server.url
+    }
+
+    /// Serves the received requests back as the response body
+    async fn serve_echo() -> Url {
+        let server = MockServer::start(|request| MockResponse::ok(request.raw.clone())).await;
+        Url::parse(&server.url).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_is_gitbook() {
+        // This test requires an internet connection
+        // You can skip it with #[ignore] if necessary
+        let result = is_gitbook("https://docs.gitbook.com").await;
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_is_gitbook_url_heuristic() {
+        assert!(is_gitbook_url_heuristic("https://myproject.gitbook.io"));
+        assert!(is_gitbook_url_heuristic("https://docs.gitbook.com/intro"));
+        assert!(is_gitbook_url_heuristic("https://GitBook.example.org"));
+        assert!(!is_gitbook_url_heuristic(
+            "https://docs.example.com/gitbook.io"
+        ));
+        assert!(!is_gitbook_url_heuristic("not a url"));
+    }
+
+    #[tokio::test]
+    async fn test_is_gitbook_skips_http_for_gitbook_hosts() {
+        // Nothing listens on this port, so only the heuristic can succeed
+        let url = "http://docs.gitbook.localhost:9";
+        assert!(is_gitbook(url).await.unwrap());
+
+        let config = CrawlConfig {
+            skip_http_detection: false,
+            ..Default::default()
+        };
+        assert!(is_gitbook_with_config(url, &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_crawl_and_save_skips_http_for_gitbook_hosts() {
+        // Nothing listens on this port: the detection would fail, the crawl finds no page
+        let url = "http://docs.gitbook.localhost:9";
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("links.txt");
+        let config = CrawlConfig {
+            quiet: true,
+            ..Default::default()
+        };
+        let report = crawl_and_save_with_config(url, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_discovered, 0);
+
+        let config = CrawlConfig {
+            skip_http_detection: false,
+            ..config
+        };
+        assert!(
+            crawl_and_save_with_config(url, output.to_str().unwrap(), &config)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_is_gitbook_uses_detection_cache() {
+        let cache = Arc::new(CrawlCache::default());
+        let config = CrawlConfig {
+            detection_cache: Some(cache.clone()),
+            ..Default::default()
+        };
+
+        // Nothing listens on this port, so only the cache can answer
+        let unreachable = "http://127.0.0.1:9/docs";
+        assert!(is_gitbook_with_config(unreachable, &config).await.is_err());
+        cache.insert(unreachable, true);
+        assert!(is_gitbook_with_config(unreachable, &config).await.unwrap());
+
+        let base = serve(vec![("/", "<html><body>Plain site</body></html>")]).await;
+        assert_eq!(is_gitbook_cached(&base, &cache), None);
+        assert!(!is_gitbook_with_config(&base, &config).await.unwrap());
+        assert_eq!(is_gitbook_cached(&base, &cache), Some(false));
+        assert!(matches!(
+            crawl_and_save_with_config(&base, "unused.txt", &config).await,
+            Err(GitBookError::NotAGitBook(message)) if message.ends_with("(cached)")
+        ));
+    }
+
+    #[test]
+    fn test_extract_page_links() {
+        let base = Url::parse("https://docs.example.com").unwrap();
+        let html = r##"
+            <a href="/guide/">Guide</a>
+            <a href="https://docs.example.com/api">API</a>
+            <a href="/guide#install">Install</a>
+            <a href="/manual.pdf">Manual</a>
+            <a href="https://other.com/page">Other</a>
+        "##;
+        let config = CrawlConfig::default();
+        let selector = parse_link_selector(&config.link_selector).unwrap();
+        let links = extract_page_links(
+            &base,
+            &Html::parse_document(html),
+            &selector,
+            &crawl_link_filters(&base, &config),
+            false,
+        );
+        assert_eq!(
+            links,
+            vec![
+                "https://docs.example.com/guide",
+                "https://docs.example.com/api"
+            ]
+        );
+
+        let config = CrawlConfig {
+            allowed_domains: vec!["Other.com".to_string()],
+            ..Default::default()
+        };
+        let links = extract_page_links(
+            &base,
+            &Html::parse_document(html),
+            &selector,
+            &crawl_link_filters(&base, &config),
+            false,
+        );
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[2], "https://other.com/page");
+
+        let config = CrawlConfig {
+            follow_all_external: true,
+            ..Default::default()
+        };
+        let filter = SameDomainFilter::for_crawl(&base, &config);
+        assert!(filter.accept(&Url::parse("https://anything.org").unwrap()));
+    }
+
+    #[test]
+    fn test_extract_page_links_selector() {
+        let base = Url::parse("https://docs.example.com").unwrap();
+        let html = r#"
+            <nav><a class="sidebar-link" href="/guide">Guide</a></nav>
+            <footer><a href="/sponsor">Sponsor</a></footer>
+        "#;
+        let config = CrawlConfig::default();
+        let selector = parse_link_selector("nav a.sidebar-link").unwrap();
+        let links = extract_page_links(
+            &base,
+            &Html::parse_document(html),
+            &selector,
+            &crawl_link_filters(&base, &config),
+            false,
+        );
+        assert_eq!(links, vec!["https://docs.example.com/guide"]);
+    }
+
+    #[tokio::test]
+    async fn test_find_gitbooks() {
+        let base = serve(vec![
+            ("/", r#"<a href="/space">Space</a><a href="/blog">Blog</a>"#),
+            (
+                "/space",
+                r#"<script src="https://static.gitbook.com/app.js"></script><a href="/space/intro">Intro</a>"#,
+            ),
+            (
+                "/space/intro",
+                r#"<script src="https://static.gitbook.com/app.js"></script>"#,
+            ),
+            ("/blog", "<p>Not a GitBook</p>"),
+        ])
+        .await;
+
+        let expected = vec![format!("{}/space", base)];
+        assert_eq!(find_gitbooks(&base, 1).await.unwrap(), expected);
+        // The pages of a found GitBook are not reported as separate sites
+        assert_eq!(find_gitbooks(&base, 2).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_extract_gitbook_links_discovery_order() {
+        let base = serve(vec![
+            ("/", r#"<a href="/z">Z</a><a href="/m">M</a>"#),
+            ("/z", r#"<a href="/a">A</a>"#),
+            ("/m", "<p>M</p>"),
+            ("/a", "<p>A</p>"),
+        ])
+        .await;
+
+        let config = CrawlConfig {
+            sort_output: false,
+            ..Default::default()
+        };
+        let links = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                format!("{}/z", base),
+                format!("{}/m", base),
+                format!("{}/a", base)
+            ]
+        );
+
+        let links = extract_gitbook_links_with_config(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(links[0], format!("{}/a", base));
+    }
+
+    #[test]
+    fn test_parse_sitemap() {
+        let (is_index, locs) = parse_sitemap(
+            "<urlset><url><loc> https://docs.example.com/a?x=1&amp;y=2 </loc></url>\n\
+             <url><loc><![CDATA[https://docs.example.com/b]]></loc></url></urlset>",
+        );
+        assert!(!is_index);
+        assert_eq!(
+            locs,
+            [
+                "https://docs.example.com/a?x=1&y=2",
+                "https://docs.example.com/b"
+            ]
+        );
+
+        let (is_index, locs) = parse_sitemap(
+            r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>https://docs.example.com/pages.xml</loc></sitemap>
+            </sitemapindex>"#,
+        );
+        assert!(is_index);
+        assert_eq!(locs, ["https://docs.example.com/pages.xml"]);
+        assert_eq!(
+            robots_txt_sitemaps("User-agent: *\nSitemap: /map.xml\nsitemap:https://x.io/s.xml"),
+            ["/map.xml", "https://x.io/s.xml"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_crawl_sitemap_only() {
+        let base = serve(vec![
+            (
+                "/sitemap.xml",
+                "<sitemapindex><sitemap><loc>/guide.xml</loc></sitemap>\
+                 <sitemap><loc>/missing.xml</loc></sitemap>\
+                 <sitemap><loc>/api.xml</loc></sitemap></sitemapindex>",
+            ),
+            (
+                "/guide.xml",
+                "<urlset><url><loc>/guide/</loc></url><url><loc>/logo.png</loc></url>\
+                 <url><loc>https://elsewhere.example.com/page</loc></url></urlset>",
+            ),
+            (
+                "/api.xml",
+                "<urlset><url><loc>/api</loc></url><url><loc>/guide</loc></url></urlset>",
+            ),
+            // Never fetched: the HTML is not crawled
+            ("/", r#"<a href="/hidden">Hidden</a>"#),
+        ])
+        .await;
+
+        let pages = crawl_sitemap_only(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        let urls: Vec<&str> = pages.iter().map(|page| page.url.as_str()).collect();
+        assert_eq!(urls, [format!("{}/api", base), format!("{}/guide", base)]);
+        assert!(pages.iter().all(|page| page.depth == 1));
+
+        let config = CrawlConfig {
+            sitemap_only: true,
+            ..Default::default()
+        };
+        assert_eq!(crawl_pages(&base, &config).await.unwrap().0, pages);
+
+        let empty = serve(vec![("/", "<p>No sitemap</p>")]).await;
+        assert!(matches!(
+            crawl_sitemap_only(&empty, &CrawlConfig::default()).await,
+            Err(GitBookError::HttpError(404, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_extract_gitbook_links_link_sorter() {
+        let base = serve(vec![
+            ("/", r#"<a href="/zz">Z</a><a href="/m">M</a>"#),
+            ("/zz", r#"<a href="/a/b/c">C</a>"#),
+            ("/m", "<p>M</p>"),
+            ("/a/b/c", "<p>C</p>"),
+        ])
+        .await;
+        let links = |sorter: LinkSorter| {
+            let config = CrawlConfig {
+                link_sorter: Some(sorter),
+                ..Default::default()
+            };
+            let base = base.clone();
+            async move {
+                extract_gitbook_links_with_config(&base, &config)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(
+            links(Arc::new(crate::sorters::path_length_sort)).await,
+            vec![
+                format!("{}/m", base),
+                format!("{}/zz", base),
+                format!("{}/a/b/c", base)
+            ]
+        );
+        // The sorter wins over the default sort by URL
+        assert_eq!(
+            links(Arc::new(crate::sorters::discovery_order_sort)).await,
+            vec![
+                format!("{}/zz", base),
+                format!("{}/m", base),
+                format!("{}/a/b/c", base)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        let normalize = |url: &str| normalize_url(&Url::parse(url).unwrap()).to_string();
+        assert_eq!(
+            normalize("http://DOCS.example.com/guide/"),
+            "http://docs.example.com/guide"
+        );
+        assert_eq!(
+            normalize("https://docs.example.com/"),
+            "https://docs.example.com/"
+        );
+        assert_eq!(
+            normalize("https://docs.example.com/a?utm_source=x&gclid=y&fbclid=z"),
+            "https://docs.example.com/a"
+        );
+        // Parameters that may select content are kept
+        assert_eq!(
+            normalize("https://docs.example.com/a?source=api&ref=main"),
+            "https://docs.example.com/a?ref=main&source=api"
+        );
+        assert_eq!(
+            normalize("https://docs.example.com/a?b=2&utm_campaign=x&a=1#top"),
+            "https://docs.example.com/a?a=1&b=2#top"
+        );
+
+        let base = Url::parse("https://docs.example.com").unwrap();
+        let with_base = |url: &str| normalize_url_with_base(&Url::parse(url).unwrap(), &base);
+        assert_eq!(
+            with_base("http://DOCS.example.com/guide/").as_str(),
+            "https://docs.example.com/guide"
+        );
+        assert_eq!(
+            with_base("http://blog.example.com/post").as_str(),
+            "http://blog.example.com/post"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_gitbook_links_dedup_variants() {
+        let base = serve(vec![
+            (
+                "/",
+                r#"<a href="/page">P</a><a href="/page/">P</a><a href="/page?utm_source=x">P</a>
+                <a href="/q?b=2&a=1">Q</a><a href="/q?a=1&b=2">Q</a>"#,
+            ),
+            ("/page", "<p>Page</p>"),
+            ("/q?b=2&a=1", "<p>Q</p>"),
+        ])
+        .await;
+
+        let config = CrawlConfig {
+            sort_output: false,
+            ..Default::default()
+        };
+        let links = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+        // The first form found of each page is kept
+        assert_eq!(
+            links,
+            vec![format!("{}/page", base), format!("{}/q?b=2&a=1", base)]
+        );
+    }
+
+    #[test]
+    fn test_link_filters() {
+        let url = |u: &str| Url::parse(u).unwrap();
+        let filter = ExtensionFilter::default();
+        assert!(!filter.accept(&url("https://x.com/manual.PDF")));
+        assert!(!filter.accept(&url("https://x.com/logo.png?v=2")));
+        assert!(filter.accept(&url("https://x.com/png")));
+        assert!(!filter.accept(&url("https://x.com/dist/app.tar.gz")));
+        assert!(!filter.accept(&url("https://x.com/demo.webm")));
+
+        let filter = PathPrefixFilter::include("guide/");
+        assert!(filter.accept(&url("https://x.com/guide")));
+        assert!(filter.accept(&url("https://x.com/guide/setup")));
+        assert!(!filter.accept(&url("https://x.com/guides")));
+
+        let filter = SameDomainFilter::new(["Docs.Example.com"]);
+        assert!(filter.accept(&url("https://docs.example.com/a")));
+        assert!(!filter.accept(&url("https://example.com/a")));
+    }
+
+    #[tokio::test]
+    async fn test_page_limit_per_domain() {
+        let base = serve(vec![(
+            "/",
+            r#"<a href="/a">A</a><a href="/b">B</a><a href="/c">C</a><a href="/a">A</a>"#,
+        )])
+        .await;
+
+        let config = CrawlConfig {
+            page_limit_per_domain: HashMap::from([("*".to_string(), 2)]),
+            quiet: true,
+            ..Default::default()
+        };
+        let (pages, _, limited) = crawl_pages(&base, &config).await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(limited, vec!["127.0.0.1".to_string()]);
+
+        let config = CrawlConfig {
+            page_limit_per_domain: HashMap::from([
+                ("*".to_string(), 1),
+                ("127.0.0.1".to_string(), 10),
+            ]),
+            quiet: true,
+            ..Default::default()
+        };
+        let (pages, _, limited) = crawl_pages(&base, &config).await.unwrap();
+        assert_eq!(pages.len(), 3);
+        assert!(limited.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_include_fragments() {
+        let base = serve(vec![
+            (
+                "/",
+                r##"<a href="/page#section">Section</a><a href="/page#other">Other</a>"##,
+            ),
+            ("/page", r#"<a href="/page#section">Section</a>"#),
+            ("/page.md", "# Page"),
+        ])
+        .await;
+
+        let links = extract_gitbook_links_with_config(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        assert!(links.is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            include_fragments: true,
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let links = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                format!("{}/page#other", base),
+                format!("{}/page#section", base)
+            ]
+        );
+
+        let report = crate::download_pages(links, &config, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(report.pages_downloaded, 1);
+        assert!(report.pages_failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_skip_extensions() {
+        let base = serve(vec![(
+            "/",
+            r#"<a href="/guide">Guide</a><a href="/files/report.docx">Report</a><a href="/a.csv">CSV</a>"#,
+        )])
+        .await;
+
+        let links = extract_gitbook_links_with_config(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![format!("{}/a.csv", base), format!("{}/guide", base)]
+        );
+
+        let mut config = CrawlConfig::default();
+        config.skip_extensions.insert("csv".to_string());
+        config.skip_extensions.remove("docx");
+        let links = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                format!("{}/files/report.docx", base),
+                format!("{}/guide", base)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_prefix_filter_excludes_pages() {
+        let base = serve(vec![
+            (
+                "/",
+                r#"<a href="/guide">Guide</a><a href="/changelog">Changelog</a>"#,
+            ),
+            ("/guide", r#"<a href="/changelog/v1">v1</a>"#),
+            ("/changelog", r#"<a href="/hidden">Hidden</a>"#),
+            ("/changelog/v1", r#"<a href="/hidden">Hidden</a>"#),
+        ])
+        .await;
+
+        let mut config = CrawlConfig::default();
+        config
+            .link_filters
+            .push(Arc::new(PathPrefixFilter::exclude("/changelog")));
+        let links = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+        assert_eq!(links, vec![format!("{}/guide", base)]);
+    }
+
+    #[tokio::test]
+    async fn test_extract_gitbook_links_with_info_depth() {
+        let base = serve(vec![
+            ("/", r#"<a href="/a">A</a>"#),
+            ("/a", r#"<a href="/b">B</a><a href="/">Home</a>"#),
+            ("/b", r#"<a href="/c">C</a><a href="/a">A</a>"#),
+            ("/c", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let pages = extract_gitbook_links_with_info(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        let depths: Vec<(String, usize)> = pages.into_iter().map(|p| (p.url, p.depth)).collect();
+        assert!(depths.contains(&(format!("{}/a", base), 1)));
+        assert!(depths.contains(&(format!("{}/b", base), 2)));
+        assert!(depths.contains(&(format!("{}/c", base), 3)));
+
+        let config = CrawlConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let pages = extract_gitbook_links_with_info(&base, &config)
+            .await
+            .unwrap();
+        assert!(pages.iter().all(|page| page.depth <= 2));
+        assert!(!pages.iter().any(|page| page.url.ends_with("/c")));
+    }
+
+    #[tokio::test]
+    async fn test_extract_gitbook_links_parallel() {
+        let base = serve(vec![
+            (
+                "/",
+                r#"<a href="/a">A</a><a href="/b">B</a><a href="/c">C</a>"#,
+            ),
+            ("/a", r#"<a href="/a/1">1</a><a href="/b">B</a>"#),
+            ("/b", r#"<a href="/b/1">1</a><a href="/a/1">1</a>"#),
+            ("/c", "<title>C</title>"),
+            ("/a/1", r#"<a href="/">Home</a>"#),
+            ("/b/1", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let depths = |pages: Vec<PageInfo>| -> Vec<(String, usize)> {
+            pages.into_iter().map(|p| (p.url, p.depth)).collect()
+        };
+        let config = CrawlConfig {
+            concurrency: 4,
+            quiet: true,
+            ..Default::default()
+        };
+        let sequential = extract_gitbook_links_with_info(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        let parallel = extract_gitbook_links_parallel(&base, &config)
+            .await
+            .unwrap();
+        assert_eq!(parallel.len(), 6);
+        let c = parallel.iter().find(|p| p.url.ends_with("/c")).unwrap();
+        assert_eq!(c.metadata.as_ref().unwrap().title.as_deref(), Some("C"));
+        assert_eq!(depths(parallel), depths(sequential.clone()));
+
+        // crawl_pages explores by level above a concurrency of 1
+        let (pages, _, _) = crawl_pages(&base, &config).await.unwrap();
+        assert_eq!(depths(pages), depths(sequential));
+    }
+
+    #[tokio::test]
+    async fn test_extract_links_stream() {
+        let base = serve(vec![
+            ("/", r#"<a href="/a">A</a><a href="/b">B</a>"#),
+            ("/a", r#"<a href="/c">C</a>"#),
+            ("/b", r#"<a href="/a">A</a>"#),
+            ("/c", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let config = CrawlConfig::default();
+        let links: Vec<String> = extract_links_stream(&base, &config)
+            .map(|link| link.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(links.len(), 3);
+        assert_eq!(&links[..2], &[format!("{}/a", base), format!("{}/b", base)]);
+        assert!(links.contains(&format!("{}/c", base)));
+
+        let sorted = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            sorted,
+            vec![
+                format!("{}/a", base),
+                format!("{}/b", base),
+                format!("{}/c", base)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gitbook_yaml_restricts_crawl() {
+        let base = serve(vec![
+            ("/.gitbook.yaml", "root: ./docs/\n"),
+            (
+                "/",
+                r#"<a href="/blog">Blog</a><a href="/docs/intro">Intro</a>"#,
+            ),
+            ("/docs", r#"<a href="/docs/setup">Setup</a>"#),
+            ("/docs/intro", r#"<a href="/docsearch">Search</a>"#),
+            ("/docs/setup", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let links = extract_gitbook_links_with_config(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                format!("{}/docs/intro", base),
+                format!("{}/docs/setup", base)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_politeness_delay_per_domain() {
+        let config = CrawlConfig {
+            politeness_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let mut state = CrawlState::new("https://docs.example.com", &config).unwrap();
+        let docs = Url::parse("https://docs.example.com/a").unwrap();
+        let api = Url::parse("https://api.example.com/a").unwrap();
+
+        let start = Instant::now();
+        state.wait_for_domain(&docs).await;
+        state.wait_for_domain(&api).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        state.wait_for_domain(&docs).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_explore_levels_waits_in_the_fetches() {
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let received = requests.clone();
+        let server = MockServer::start(move |request| {
+            received
+                .lock()
+                .unwrap()
+                .push((request.path.clone(), Instant::now()));
+            match request.path.as_str() {
+                "/" => {
+                    MockResponse::ok(r#"<a href="/a">A</a><a href="/b">B</a><a href="/c">C</a>"#)
+                }
+                _ => MockResponse::ok("<p>Leaf</p>"),
+            }
+            .header("Content-Type", "text/html")
+        })
+        .await;
+
+        let config = CrawlConfig {
+            concurrency: 3,
+            politeness_delay: Duration::from_millis(200),
+            quiet: true,
+            ..Default::default()
+        };
+        let (pages, _, _) = crawl_pages(&server.url, &config).await.unwrap();
+        assert_eq!(pages.len(), 3);
+
+        // Each page is requested on its own slot, not all at once after the last wait
+        let times: Vec<Instant> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(path, _)| ["/a", "/b", "/c"].contains(&path.as_str()))
+            .map(|(_, time)| *time)
+            .collect();
+        assert_eq!(times.len(), 3);
+        assert!(times[2] - times[0] >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_reserve_fetch() {
+        let config = CrawlConfig {
+            politeness_delay: Duration::from_millis(100),
+            circuit_breaker_threshold: 1,
+            ..Default::default()
+        };
+        let mut state = CrawlState::new("https://docs.example.com", &config).unwrap();
+
+        let (domain, first) = state.reserve_fetch("https://docs.example.com/a");
+        assert_eq!(domain, "docs.example.com");
+        let (_, second) = state.reserve_fetch("https://docs.example.com/b");
+        assert_eq!(second - first, Duration::from_millis(100));
+
+        // An open circuit delays the probe to the end of its pause
+        state.breaker.record_failure("api.example.com");
+        let (_, probe) = state.reserve_fetch("https://api.example.com/a");
+        assert!(probe - first >= config.circuit_breaker_cooldown - Duration::from_millis(10));
+        let (_, next) = state.reserve_fetch("https://api.example.com/b");
+        assert_eq!(next - probe, Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_build_client_sends_cookies() {
+        let base = serve_echo().await;
+
+        let config = CrawlConfig {
+            cookies: vec![("auth_token".to_string(), "secret".to_string())],
+            ..Default::default()
+        };
+        let client = build_client(&config, [&base]).unwrap();
+        let request = client.get(base).send().await.unwrap().text().await.unwrap();
+        assert!(request.to_lowercase().contains("cookie: auth_token=secret"));
+    }
+
+    #[tokio::test]
+    async fn test_build_client_sends_extra_headers() {
+        let base = serve_echo().await;
+
+        let config = CrawlConfig {
+            extra_headers: vec![
+                ("CF-Access-Client-Id".to_string(), "id".to_string()),
+                ("X-API-Key".to_string(), "key".to_string()),
+            ],
+            bearer_token: Some("t0k3n".to_string()),
+            ..Default::default()
+        };
+        let client = build_client(&config, [&base]).unwrap();
+        let request = client.get(base).send().await.unwrap().text().await.unwrap();
+        let request = request.to_lowercase();
+        assert!(request.contains("cf-access-client-id: id"));
+        assert!(request.contains("x-api-key: key"));
+        assert!(request.contains("authorization: bearer t0k3n"));
+    }
+
+    #[test]
+    fn test_build_client_invalid_header() {
+        let base = Url::parse("https://docs.example.com").unwrap();
+        for header in [("Bad Name", "value"), ("X-Ok", "bad\nvalue")] {
+            let config = CrawlConfig {
+                extra_headers: vec![(header.0.to_string(), header.1.to_string())],
+                ..Default::default()
+            };
+            assert!(matches!(
+                build_client(&config, [&base]),
+                Err(GitBookError::InvalidHeader(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_scope_url() {
+        let base = Url::parse("https://docs.example.com/guide").unwrap();
+        assert_eq!(
+            scope_url(&base, "./docs/").as_deref(),
+            Some("https://docs.example.com/docs")
+        );
+        assert_eq!(scope_url(&base, "./"), None);
+        assert_eq!(scope_url(&base, "/"), None);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_and_save_report() {
+        let base = serve(vec![
+            (
+                "/",
+                r#"<script src="https://static.gitbook.com/app.js"></script><a href="/a">A</a>"#,
+            ),
+            ("/a", r#"<a href="/b">B</a>"#),
+            ("/b", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("links.txt");
+        let report = crawl_and_save(&base, output.to_str().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(report.pages_discovered, 2);
+        assert_eq!(report.pages_downloaded, 0);
+        assert!(report.pages_failed.is_empty());
+        let content = std::fs::read_to_string(&output).unwrap();
+        assert!(content.starts_with(&format!("# gitbook2text v{}\n", env!("CARGO_PKG_VERSION"))));
+        let crawled = content.lines().nth(1).unwrap();
+        let timestamp = crawled.strip_prefix("# Crawled: ").unwrap();
+        assert!(timestamp.ends_with('Z'));
+        assert!(humantime::parse_rfc3339(timestamp).is_ok());
+        assert!(!content.contains("# Command:"));
+        assert!(content.contains(&format!("# Source: {}\n# Pages: 2\n", base)));
+        assert_eq!(
+            parse_links_file(&content),
+            vec![format!("{}/a", base), format!("{}/b", base)]
+        );
+
+        let config = CrawlConfig {
+            command_line: Some("gitbook2text crawl URL".to_string()),
+            ..Default::default()
+        };
+        crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        let content = std::fs::read_to_string(&output).unwrap();
+        assert!(content.contains("\n# Command: gitbook2text crawl URL\n# Source: "));
+
+        let config = CrawlConfig {
+            count_only: true,
+            ..Default::default()
+        };
+        let counted = dir.path().join("counted.txt");
+        let report = crawl_and_save_with_config(&base, counted.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_discovered, 2);
+        assert!(!counted.exists());
+
+        let config = CrawlConfig {
+            links_header: false,
+            ..Default::default()
+        };
+        crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            format!("{0}/a\n{0}/b", base)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pre_visited() {
+        let base = serve(vec![
+            ("/", r#"<a href="/a">A</a><a href="/b">B</a>"#),
+            ("/a", r#"<a href="/c">C</a>"#),
+            ("/b", r#"<a href="/a">A</a>"#),
+            ("/c", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let config = CrawlConfig {
+            pre_visited: Some(Arc::new(move |url: &str| {
+                seen.lock().unwrap().push(url.to_string());
+                url.ends_with("/a")
+            })),
+            ..Default::default()
+        };
+        let links = extract_gitbook_links_with_config(&base, &config)
+            .await
+            .unwrap();
+
+        // `/a` is listed but not explored, so `/c` is never found
+        assert_eq!(links, vec![format!("{}/a", base), format!("{}/b", base)]);
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_links_files() {
+        let merged = merge_links_files(&[
+            "# gitbook2text v0.3.1\n# Source: https://a.com\nhttps://a.com/intro/\nhttps://a.com/setup\n",
+            "https://a.com/intro\n\nhttps://B.com/api/\nhttps://a.com/setup/",
+            "https://b.com/api",
+        ]);
+        assert_eq!(
+            merged.links,
+            vec![
+                "https://a.com/intro",
+                "https://a.com/setup",
+                "https://b.com/api"
+            ]
+        );
+        assert_eq!(merged.urls_found, 6);
+        assert_eq!(merged.duplicates, 3);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_circuit_breaker() {
+        let base = serve_with_status(vec![
+            (
+                "/",
+                200,
+                r#"<script src="https://static.gitbook.com/app.js"></script>
+                <a href="/a">A</a><a href="/b">B</a><a href="/c">C</a>"#,
+            ),
+            ("/a", 500, ""),
+            ("/b", 503, ""),
+            ("/c", 200, "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("links.txt");
+        let config = CrawlConfig {
+            circuit_breaker_threshold: 2,
+            circuit_breaker_cooldown: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let report = crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_discovered, 3);
+        assert_eq!(report.domains_tripped, vec!["127.0.0.1".to_string()]);
+
+        let config = CrawlConfig {
+            circuit_breaker_threshold: 0,
+            ..Default::default()
+        };
+        let report = crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert!(report.domains_tripped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_crawl_and_save_manifest() {
+        let base = serve(vec![
+            (
+                "/",
+                r#"<script src="https://static.gitbook.com/app.js"></script><a href="/a">A</a>"#,
+            ),
+            (
+                "/a",
+                r#"<title>Page A</title><meta name="description" content="About A">
+                <link rel="canonical" href="https://docs.example.com/a"><a href="/b">B</a>"#,
+            ),
+            ("/b", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("links.txt");
+        let manifest_path = dir.path().join("manifest.json");
+        let config = CrawlConfig {
+            manifest_path: Some(manifest_path.clone()),
+            ..Default::default()
+        };
+        crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+
+        let mut manifest = Manifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.source_url, base);
+        assert_eq!(manifest.pages.len(), 2);
+        assert_eq!(manifest.page(&format!("{}/b", base)).unwrap().depth, 2);
+        let page = manifest.page(&format!("{}/a", base)).unwrap();
+        assert_eq!(page.title.as_deref(), Some("Page A"));
+        assert_eq!(page.description.as_deref(), Some("About A"));
+        assert_eq!(
+            page.canonical_url.as_deref(),
+            Some("https://docs.example.com/a")
+        );
+
+        // The download state survives a new crawl
+        manifest.upsert(ManifestPage {
+            content_hash: Some("abc".to_string()),
+            ..ManifestPage::new(format!("{}/a", base), 1)
+        });
+        manifest.save(&manifest_path).unwrap();
+        crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        let manifest = Manifest::load(&manifest_path).unwrap();
+        let page = manifest.page(&format!("{}/a", base)).unwrap();
+        assert_eq!(page.content_hash.as_deref(), Some("abc"));
+        assert_eq!(page.description.as_deref(), Some("About A"));
+    }
+
+    #[tokio::test]
+    async fn test_crawl_and_save_json() {
+        let base = serve(vec![
+            (
+                "/",
+                r#"<script src="https://static.gitbook.com/app.js"></script><a href="/a">A</a>"#,
+            ),
+            ("/a", r#"<title>Page A</title><a href="/b">B</a>"#),
+            ("/b", "<p>Leaf</p>"),
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("links.txt");
+        let json = dir.path().join("links.json");
+        let config = CrawlConfig {
+            output_format: CrawlOutputFormat::Json,
+            quiet: true,
+            ..Default::default()
+        };
+        crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert!(!output.exists());
+
+        let content = std::fs::read_to_string(&json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            value[0],
+            serde_json::json!({"url": format!("{}/a", base), "title": "Page A", "depth": 1})
+        );
+        assert_eq!(value[1]["depth"], 2);
+        assert_eq!(
+            parse_links_json(&content).unwrap(),
+            vec![format!("{}/a", base), format!("{}/b", base)]
+        );
+        assert!(parse_links_json("https://docs.example.com/a").is_err());
+
+        let config = CrawlConfig {
+            output_format: CrawlOutputFormat::Both,
+            ..config
+        };
+        std::fs::remove_file(&json).unwrap();
+        crawl_and_save_with_config(&base, output.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        assert!(output.exists() && json.exists());
+
+        // The JSON file would overwrite a text file already named .json
+        assert!(matches!(
+            crawl_and_save_with_config(&base, json.to_str().unwrap(), &config).await,
+            Err(GitBookError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_extract_links_stream_invalid_url() {
+        let config = CrawlConfig::default();
+        let links: Vec<_> = extract_links_stream("not a url", &config).collect().await;
+        assert_eq!(links.len(), 1);
+        assert!(matches!(links[0], Err(GitBookError::InvalidUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_extract_links_stream_invalid_selector() {
+        let config = CrawlConfig {
+            link_selector: "a[".to_string(),
+            ..Default::default()
+        };
+        let links: Vec<_> = extract_links_stream("https://docs.example.com", &config)
+            .collect()
+            .await;
+        assert_eq!(links.len(), 1);
+        assert!(matches!(links[0], Err(GitBookError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_gitbook_confidence() {
+        let url = Url::parse("https://docs.example.com").unwrap();
+        assert_eq!(
+            gitbook_confidence(&url, "<html><body>Docs</body></html>", ""),
+            0.0
+        );
+
+        let mention = gitbook_confidence(&url, "<p>Powered by GitBook</p>", "");
+        assert!(mention > 0.0 && mention < DEFAULT_MIN_CONFIDENCE);
+
+        let script = r#"<script src="https://static.gitbook.com/app.js"></script>"#;
+        assert_eq!(gitbook_confidence(&url, script, ""), 1.0);
+
+        let hosted = Url::parse("https://acme.gitbook.io/docs").unwrap();
+        assert!(gitbook_confidence(&hosted, "<html></html>", "") >= DEFAULT_MIN_CONFIDENCE);
+
+        let robots = "User-agent: *\nDisallow: /admin\n\nuser-agent: GitBook\nAllow: /\n";
+        let score = gitbook_confidence(&url, "<p>Powered by GitBook</p>", robots);
+        assert!((score - mention - 0.3).abs() < 1e-6);
+        assert_eq!(
+            gitbook_confidence(&url, "<html></html>", "Disallow: /gitbook"),
+            0.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detect_gitbook_confidence_robots_txt() {
+        let base = serve(vec![
+            ("/", "<p>Powered by GitBook</p>"),
+            ("/robots.txt", "User-agent: Gitbook\nAllow: /\n"),
+        ])
+        .await;
+        let confidence = detect_gitbook_confidence(&base).await.unwrap();
+        assert!(confidence >= DEFAULT_MIN_CONFIDENCE);
+
+        let base = serve(vec![("/", "<p>Powered by GitBook</p>")]).await;
+        let confidence = detect_gitbook_confidence(&base).await.unwrap();
+        assert!(confidence < DEFAULT_MIN_CONFIDENCE);
     }
 }
@@ -0,0 +1,140 @@
+use crate::config::DedupStrategy;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// Computes the SHA-256 hash of a content
+pub fn content_hash(content: &str) -> [u8; 32] {
+    Sha256::digest(content.as_bytes()).into()
+}
+
+/// Computes the 64-bit SimHash of a text
+///
+/// Near-identical texts produce hashes with a small Hamming distance.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{hamming_distance, simhash};
+///
+/// let a = simhash("the quick brown fox jumps over the lazy dog");
+/// let b = simhash("the quick brown fox jumps over the lazy cat");
+/// let c = simhash("an entirely different sentence about documentation");
+/// assert!(hamming_distance(a, b) < hamming_distance(a, c));
+/// ```
+pub fn simhash(text: &str) -> u64 {
+    let mut weights = [0i64; 64];
+
+    for token in text.split_whitespace() {
+        let hash = fnv1a(&token.to_lowercase());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// Returns the number of differing bits between two hashes
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 64-bit FNV-1a hash, stable across platforms and Rust versions
+fn fnv1a(token: &str) -> u64 {
+    token.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Remembers the pages already saved to detect duplicates
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    strategy: DedupStrategy,
+    hashes: HashSet<[u8; 32]>,
+    simhashes: Vec<u64>,
+}
+
+impl Deduplicator {
+    /// Creates a deduplicator using the given strategy
+    pub fn new(strategy: DedupStrategy) -> Self {
+        Deduplicator {
+            strategy,
+            ..Default::default()
+        }
+    }
+
+    /// Checks whether a content duplicates a previously seen one, and remembers it otherwise
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::{DedupStrategy, Deduplicator};
+    ///
+    /// let mut dedup = Deduplicator::new(DedupStrategy::ExactHash);
+    /// assert!(!dedup.is_duplicate("# Page"));
+    /// assert!(dedup.is_duplicate("# Page"));
+    /// ```
+    pub fn is_duplicate(&mut self, content: &str) -> bool {
+        match self.strategy {
+            DedupStrategy::None => false,
+            DedupStrategy::ExactHash => !self.hashes.insert(content_hash(content)),
+            DedupStrategy::SimHash { threshold } => {
+                if !self.hashes.insert(content_hash(content)) {
+                    return true;
+                }
+
+                let hash = simhash(content);
+                if self
+                    .simhashes
+                    .iter()
+                    .any(|&seen| hamming_distance(seen, hash) <= threshold)
+                {
+                    return true;
+                }
+
+                self.simhashes.push(hash);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simhash_near_duplicates() {
+        let base = "Install the CLI with cargo install gitbook2text then run it on your documentation site to download every page as markdown and text";
+        let near = "Install the CLI with cargo install gitbook2text then run it on your documentation website to download every page as markdown and text";
+        let other = "The authentication endpoint returns a bearer token that must be sent in the Authorization header of each request";
+
+        assert_eq!(simhash(base), simhash(base));
+        assert!(hamming_distance(simhash(base), simhash(near)) <= 10);
+        assert!(hamming_distance(simhash(base), simhash(other)) > 10);
+    }
+
+    #[test]
+    fn test_deduplicator_strategies() {
+        let mut none = Deduplicator::new(DedupStrategy::None);
+        assert!(!none.is_duplicate("a"));
+        assert!(!none.is_duplicate("a"));
+
+        let mut exact = Deduplicator::new(DedupStrategy::ExactHash);
+        assert!(!exact.is_duplicate("a b c"));
+        assert!(!exact.is_duplicate("a b d"));
+        assert!(exact.is_duplicate("a b c"));
+
+        let mut sim = Deduplicator::new(DedupStrategy::SimHash { threshold: 64 });
+        assert!(!sim.is_duplicate("a b c"));
+        assert!(sim.is_duplicate("x y z"));
+    }
+}
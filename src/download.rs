@@ -0,0 +1,1528 @@
+use crate::chunker::{split_into_chunks, CharacterTokenizer};
+use crate::config::{CrawlConfig, DownloadConfig, OutputFormat, SanitizeConfig};
+use crate::crawler::{
+    add_cookies, build_client, build_client_with_jar, cookie_jar, has_noindex_meta, PageInfo,
+};
+use crate::dedup::{content_hash, Deduplicator};
+use crate::manifest::{hex_content_hash, Manifest, ManifestPage, ManifestPageStatus};
+use crate::page::{extract_page_metadata, group_pages_by_path_segment, GitBookPage};
+use crate::utils::{
+    combine_texts, compress, detect_language, download_page_with_client, download_raw_with_client,
+    extract_code_blocks, first_h1, markdown_to_asciidoc, markdown_to_text_marked,
+    markdown_to_text_with_config, output_path, rewrite_absolute_urls, rewrite_markdown_links,
+    save_code_blocks, save_markdown_compressed, save_markdown_with_config, slugify_with_separator,
+    strip_md_suffix, strip_navigation_boilerplate, txt_sanitize_with_context,
+    with_encoding_declaration, wrap_marked_text, wrap_text, write_output, SanitizeContext,
+};
+use crate::GitBookError;
+use futures::future;
+use futures::stream::{self, FuturesUnordered, Stream};
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
+use url::Url;
+
+/// Summary of a crawl or download run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DownloadReport {
+    /// Number of page links found by the crawler
+    pub pages_discovered: usize,
+    /// Number of pages saved to disk
+    pub pages_downloaded: usize,
+    /// Number of pages filtered out (language filter or deduplication)
+    pub pages_skipped: usize,
+    /// Number of pages not saved because their HTML asks not to index them
+    /// (see [`DownloadConfig::respect_noindex`])
+    pub pages_noindex: usize,
+    /// Number of pages not saved because `max_total_bytes` was reached
+    pub pages_over_limit: usize,
+    /// Number of pages saved again by `force_redownload` although their hash matched
+    /// the manifest
+    pub pages_forced: usize,
+    /// Pages that could not be downloaded or saved, with the error message
+    pub pages_failed: Vec<(String, String)>,
+    /// Domains paused by the circuit breaker after too many consecutive errors
+    pub domains_tripped: Vec<String>,
+    /// Domains with more pages than their [`CrawlConfig::page_limit_per_domain`]
+    pub domains_limited: Vec<String>,
+    /// Total size of the saved markdown and text content, before compression
+    pub total_bytes: u64,
+    /// Time spent on the run
+    pub duration: Duration,
+}
+
+/// A progress event of [`download_pages_with_events`]
+///
+/// Its [`Display`](fmt::Display) implementation gives the line [`download_pages`] prints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadEvent {
+    /// The download of `total` pages started
+    Started { total: usize },
+
+    /// The download of the page `index` (from 1) of `total` started
+    PageStarted {
+        url: String,
+        index: usize,
+        total: usize,
+    },
+
+    /// A page was saved, `bytes` being the size of its markdown and text content
+    Saved { url: String, bytes: u64 },
+
+    /// The pages of a chapter were saved to `path`
+    ChapterSaved {
+        path: PathBuf,
+        pages: usize,
+        bytes: u64,
+    },
+
+    /// A page was left out by the language filter, the deduplication, `skip_unchanged`,
+    /// `respect_noindex` or `max_total_bytes`
+    Skipped { url: String, reason: &'static str },
+
+    /// `max_total_bytes` was reached; no new page is downloaded
+    LimitReached,
+
+    /// A page could not be downloaded
+    DownloadFailed { url: String, error: String },
+
+    /// A page or a chapter could not be saved
+    SaveFailed { name: String, error: String },
+}
+
+impl DownloadEvent {
+    /// Returns `true` for the events reporting an error
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            DownloadEvent::DownloadFailed { .. } | DownloadEvent::SaveFailed { .. }
+        )
+    }
+}
+
+impl fmt::Display for DownloadEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadEvent::Started { total } => write!(f, "📥 Downloading {} page(s)...", total),
+            DownloadEvent::PageStarted { url, index, total } => {
+                write!(f, "⬇️ Downloading {}/{}: {}", index, total, url)
+            }
+            DownloadEvent::Saved { url, .. } => write!(f, "✅ Page saved: {}", url),
+            DownloadEvent::ChapterSaved { path, pages, .. } => write!(
+                f,
+                "✅ Chapter saved: {} ({} page(s))",
+                path.display(),
+                pages
+            ),
+            DownloadEvent::Skipped { url, reason } => {
+                write!(f, "⏭️ Page skipped ({}): {}", reason, url)
+            }
+            DownloadEvent::LimitReached => write!(f, "⚠️ Total size limit reached, stopping"),
+            DownloadEvent::DownloadFailed { error, .. } => write!(f, "❌ Error: {}", error),
+            DownloadEvent::SaveFailed { name, error } => {
+                write!(f, "❌ Error while saving {}: {}", name, error)
+            }
+        }
+    }
+}
+
+/// Where the download pipeline reports its progress: stdout and stderr, or a channel
+#[derive(Clone)]
+struct Events(Option<mpsc::UnboundedSender<DownloadEvent>>);
+
+impl Events {
+    fn emit(&self, event: DownloadEvent) {
+        match &self.0 {
+            Some(tx) => {
+                let _ = tx.send(event);
+            }
+            // One line per saved page, and one for the size limit, are enough on a terminal
+            None if matches!(
+                event,
+                DownloadEvent::PageStarted { .. }
+                    | DownloadEvent::Skipped {
+                        reason: "size limit",
+                        ..
+                    }
+            ) => {}
+            None if event.is_error() => eprintln!("{}", event),
+            None => println!("{}", event),
+        }
+    }
+}
+
+enum PageOutcome {
+    /// The page is queued for writing, with its title and text for `combined.txt`, and
+    /// whether `force_redownload` queued it although it is unchanged
+    Queued(Option<(String, String)>, bool),
+    Skipped(PageInfo, &'static str),
+    /// The page has the hash recorded in the manifest, with its title and text for
+    /// `combined.txt`
+    Unchanged(PageInfo, Option<(String, String)>),
+    /// The HTML of the page has a `noindex` robots meta tag
+    Noindex(PageInfo),
+    OverLimit,
+}
+
+struct WriteJob {
+    page: PageInfo,
+    markdown: String,
+    text: String,
+}
+
+/// Downloads pages and saves them as markdown and plain text in `config.output_dir`
+///
+/// The `#fragment` of the URLs is dropped, so the links to the sections of a page
+/// download it once, and the `.md` suffix is appended to the URLs that lack it.
+/// Pages that fail are recorded in the returned report rather than aborting the run.
+///
+/// With `split_by_depth`, the pages are saved as chapter files once they are
+/// all downloaded (see [`group_pages_by_path_segment`]).
+///
+/// With `title_from_h1`, pages with a `# Heading` are saved under its slug; when two
+/// pages share a slug, the later one gets a numeric suffix (`setup-2.md`).
+///
+/// With `manifest_path`, the saved pages are recorded in the manifest, keeping the
+/// other pages it lists. With `skip_unchanged` too, the pages whose markdown still has
+/// the hash recorded in the manifest are not written again and count as skipped;
+/// `force_redownload` saves them anyway and counts them in `pages_forced`.
+///
+/// Once `max_total_bytes` is reached, the pages already queued are still saved
+/// but no new page is downloaded or queued. The downloads in flight complete without
+/// being saved, so the saved content exceeds the limit by at most the page reaching it.
+///
+/// With `respect_noindex`, the HTML of each page is fetched first and the pages with a
+/// `noindex` robots meta tag are counted in `pages_noindex` instead of being saved; the
+/// manifest lists them with the `SKIPPED_NOINDEX` status.
+///
+/// With `combine`, the text of the queued pages is also saved in
+/// `combined.txt`, in the order of `urls`, each page after its `page_separator`.
+///
+/// # Arguments
+///
+/// * `urls` - The URLs of the pages to download
+/// * `config` - The crawl configuration, used to name the output files
+/// * `download_config` - The download pipeline configuration
+///
+/// # Errors
+///
+/// Returns an error if `language_confidence` is not between 0.0 and 1.0, if the output
+/// directories cannot be created or if the HTTP client cannot be built.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{download_pages, CrawlConfig, DownloadConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let urls = vec!["https://docs.example.com/intro".to_string()];
+///     let report =
+///         download_pages(urls, &CrawlConfig::default(), &DownloadConfig::default()).await?;
+///     println!("{} page(s) saved", report.pages_downloaded);
+///     Ok(())
+/// }
+/// ```
+pub async fn download_pages(
+    urls: impl IntoIterator<Item = String>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<DownloadReport, GitBookError> {
+    download_pages_inner(urls, config, download_config, Events(None)).await
+}
+
+/// Downloads pages like [`download_pages`], sending its progress on a channel instead of printing it
+///
+/// Nothing is printed: every line [`download_pages`] would print is sent as a
+/// [`DownloadEvent`], along with a [`DownloadEvent::PageStarted`] per page.
+/// Events sent after the receiver is dropped are discarded.
+///
+/// # Errors
+///
+/// Returns an error if `language_confidence` is not between 0.0 and 1.0, if the output
+/// directories cannot be created or if the HTTP client cannot be built.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{download_pages_with_events, CrawlConfig, DownloadConfig, DownloadEvent};
+/// use tokio::sync::mpsc;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (tx, mut rx) = mpsc::unbounded_channel();
+///     let urls = vec!["https://docs.example.com/intro".to_string()];
+///     let (config, download_config) = (CrawlConfig::default(), DownloadConfig::default());
+///     let download = download_pages_with_events(urls, &config, &download_config, tx);
+///     let printer = async {
+///         while let Some(event) = rx.recv().await {
+///             if let DownloadEvent::Saved { url, .. } = event {
+///                 println!("{}", url);
+///             }
+///         }
+///     };
+///     let (report, ()) = tokio::join!(download, printer);
+///     println!("{} page(s) saved", report?.pages_downloaded);
+///     Ok(())
+/// }
+/// ```
+pub async fn download_pages_with_events(
+    urls: impl IntoIterator<Item = String>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+    events: mpsc::UnboundedSender<DownloadEvent>,
+) -> Result<DownloadReport, GitBookError> {
+    download_pages_inner(urls, config, download_config, Events(Some(events))).await
+}
+
+async fn download_pages_inner(
+    urls: impl IntoIterator<Item = String>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+    events: Events,
+) -> Result<DownloadReport, GitBookError> {
+    let start = Instant::now();
+
+    // The links to the sections of a page download the page once
+    let mut seen = HashSet::new();
+    let urls: Vec<String> = urls
+        .into_iter()
+        .map(markdown_url)
+        .filter(|u| seen.insert(u.clone()))
+        .collect();
+
+    events.emit(DownloadEvent::Started { total: urls.len() });
+
+    let parsed: Vec<Url> = urls.iter().filter_map(|u| Url::parse(u).ok()).collect();
+    let client = build_client(config, &parsed)?;
+
+    let total = urls.len();
+    let urls = stream::iter(urls.into_iter().map(|url| (url, total)));
+    download_stream(urls, &client, config, download_config, events, start).await
+}
+
+/// Downloads pages like [`download_pages_with_events`] as their URLs arrive on a stream
+///
+/// Each page is downloaded as soon as its URL is received, and the run ends once the
+/// stream ends and the received pages are saved. The URLs already received are
+/// skipped. No [`DownloadEvent::Started`] is sent and the `total` of
+/// [`DownloadEvent::PageStarted`] is the number of pages received so far.
+///
+/// # Errors
+///
+/// Returns an error if `language_confidence` is not between 0.0 and 1.0, if the output
+/// directories cannot be created or if the HTTP client cannot be built.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use futures::stream;
+/// use gitbook2text::{download_pages_stream, CrawlConfig, DownloadConfig};
+/// use tokio::sync::mpsc;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let (tx, mut rx) = mpsc::unbounded_channel();
+///     let urls = stream::iter(vec!["https://docs.example.com/intro".to_string()]);
+///     let (config, download_config) = (CrawlConfig::default(), DownloadConfig::default());
+///     let download = download_pages_stream(urls, &config, &download_config, tx);
+///     let printer = async {
+///         while let Some(event) = rx.recv().await {
+///             eprintln!("{}", event);
+///         }
+///     };
+///     let (report, ()) = tokio::join!(download, printer);
+///     println!("{} page(s) saved", report?.pages_downloaded);
+///     Ok(())
+/// }
+/// ```
+pub async fn download_pages_stream(
+    urls: impl Stream<Item = String>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+    events: mpsc::UnboundedSender<DownloadEvent>,
+) -> Result<DownloadReport, GitBookError> {
+    let start = Instant::now();
+
+    // The cookies are added for the domain of each URL as it arrives
+    let jar = cookie_jar(config);
+    let client = build_client_with_jar(config, jar.clone())?;
+
+    let mut seen = HashSet::new();
+    let mut received = 0;
+    let urls = urls
+        .map(markdown_url)
+        .filter(move |u| future::ready(seen.insert(u.clone())))
+        .map(move |url| {
+            if let (Some(jar), Ok(parsed)) = (&jar, Url::parse(&url)) {
+                add_cookies(jar, config, &parsed);
+            }
+            received += 1;
+            (url, received)
+        });
+    let events = Events(Some(events));
+    download_stream(urls, &client, config, download_config, events, start).await
+}
+
+/// Returns the URL of the markdown of a page, without its `#fragment`
+fn markdown_url(mut url: String) -> String {
+    url.truncate(url.find('#').unwrap_or(url.len()));
+    if strip_md_suffix(&url).len() == url.len() {
+        url.push_str(".md");
+    }
+    url
+}
+
+/// Downloads and saves the pages of a stream of `(url, total)` pairs
+async fn download_stream(
+    urls: impl Stream<Item = (String, usize)>,
+    client: &reqwest::Client,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+    events: Events,
+    start: Instant,
+) -> Result<DownloadReport, GitBookError> {
+    if !(0.0..=1.0).contains(&download_config.language_confidence) {
+        return Err(GitBookError::InvalidConfig(format!(
+            "language_confidence must be between 0.0 and 1.0, got {}",
+            download_config.language_confidence
+        )));
+    }
+
+    tokio::fs::create_dir_all(config.output_dir.join("md")).await?;
+    tokio::fs::create_dir_all(config.output_dir.join(download_config.format.extension())).await?;
+
+    let (tx, rx) = mpsc::channel::<WriteJob>(download_config.parallel_writes * 2);
+    let writer = tokio::spawn(write_pages(
+        rx,
+        config.clone(),
+        download_config.clone(),
+        events.clone(),
+    ));
+
+    let dedup = Mutex::new(Deduplicator::new(download_config.dedup));
+    let dedup = &dedup;
+
+    let known_hashes = match &config.manifest_path {
+        Some(path) if download_config.skip_unchanged || download_config.force_redownload => {
+            known_hashes(path)
+        }
+        _ => HashMap::new(),
+    };
+    let known_hashes = &known_hashes;
+
+    let queued_bytes = AtomicU64::new(0);
+    let queued_bytes = &queued_bytes;
+
+    let mut urls = pin!(urls.enumerate().fuse());
+    let mut futures = FuturesUnordered::new();
+    let mut combined = Vec::new();
+    let mut noindex = Vec::new();
+    let mut report = DownloadReport::default();
+    let events = &events;
+
+    let parallel_downloads = download_config.parallel_downloads.max(1);
+
+    loop {
+        tokio::select! {
+            // The next URL is only read once a download slot is free
+            Some((i, (url, total))) = urls.next(), if futures.len() < parallel_downloads => {
+                combined.push(None);
+                let tx = tx.clone();
+                futures.push(async move {
+                    events.emit(DownloadEvent::PageStarted {
+                        url: url.clone(),
+                        index: i + 1,
+                        total,
+                    });
+                    let outcome =
+                        process_page(
+                            client,
+                            &url,
+                            download_config,
+                            dedup,
+                            known_hashes,
+                            queued_bytes,
+                            &tx,
+                        )
+                        .await;
+                    (i, url, outcome)
+                });
+            }
+            Some((i, url, outcome)) = futures.next(), if !futures.is_empty() => {
+                match outcome {
+                    Ok(PageOutcome::Queued(page, forced)) => {
+                        combined[i] = page.map(|(title, text)| (url, title, text));
+                        report.pages_forced += usize::from(forced);
+                    }
+                    Ok(PageOutcome::OverLimit) => {
+                        if report.pages_over_limit == 0 {
+                            events.emit(DownloadEvent::LimitReached);
+                        }
+                        report.pages_over_limit += 1;
+                        events.emit(DownloadEvent::Skipped {
+                            url,
+                            reason: "size limit",
+                        });
+                    }
+                    Ok(PageOutcome::Skipped(page, reason)) => {
+                        report.pages_skipped += 1;
+                        events.emit(DownloadEvent::Skipped {
+                            url: page.url,
+                            reason,
+                        });
+                    }
+                    Ok(PageOutcome::Unchanged(page, text)) => {
+                        combined[i] = text.map(|(title, text)| (url, title, text));
+                        report.pages_skipped += 1;
+                        events.emit(DownloadEvent::Skipped {
+                            url: page.url,
+                            reason: "unchanged",
+                        });
+                    }
+                    Ok(PageOutcome::Noindex(page)) => {
+                        report.pages_noindex += 1;
+                        events.emit(DownloadEvent::Skipped {
+                            url: page.url.clone(),
+                            reason: "noindex",
+                        });
+                        let url = strip_md_suffix(&page.url);
+                        noindex.push(ManifestPage {
+                            status: Some(ManifestPageStatus::SkippedNoindex),
+                            ..ManifestPage::new(url, page.depth)
+                        });
+                    }
+                    Err(e) => {
+                        events.emit(DownloadEvent::DownloadFailed {
+                            url: url.clone(),
+                            error: e.clone(),
+                        });
+                        report.pages_failed.push((url, e));
+                    }
+                }
+            }
+            else => break,
+        }
+    }
+    drop(tx);
+
+    let (written, mut saved) = writer
+        .await
+        .map_err(|e| GitBookError::IoError(std::io::Error::other(e)))?;
+    if download_config.rewrite_links {
+        rewrite_saved_links(&saved, config).await?;
+    }
+    if let Some(path) = &config.manifest_path {
+        saved.extend(noindex);
+        save_download_manifest(path, saved)?;
+    }
+    if download_config.combine {
+        let pages: Vec<(&str, &str, &str)> = combined
+            .iter()
+            .flatten()
+            .map(|(url, title, text)| {
+                let url = strip_md_suffix(url);
+                (url, title.as_str(), text.as_str())
+            })
+            .collect();
+        let content = combine_texts(&pages, &download_config.page_separator);
+        tokio::fs::write(config.output_dir.join("combined.txt"), content).await?;
+    }
+    report.pages_downloaded = written.pages_downloaded;
+    report.total_bytes = written.total_bytes;
+    report.pages_failed.extend(written.pages_failed);
+    report.duration = start.elapsed();
+
+    Ok(report)
+}
+
+/// Rewrites the absolute links of a page to `base_url_override`, if set
+pub(crate) fn override_base_url(md: String, url: &str, download_config: &DownloadConfig) -> String {
+    match &download_config.base_url_override {
+        Some(new_base) => match Url::parse(url).and_then(|u| u.join("/")) {
+            Ok(old_base) => rewrite_absolute_urls(&md, old_base.as_str(), new_base),
+            Err(_) => md,
+        },
+        None => md,
+    }
+}
+
+/// Downloads and converts one page, then queues it for writing
+async fn process_page(
+    client: &reqwest::Client,
+    url: &str,
+    download_config: &DownloadConfig,
+    dedup: &Mutex<Deduplicator>,
+    known_hashes: &HashMap<String, [u8; 32]>,
+    queued_bytes: &AtomicU64,
+    tx: &mpsc::Sender<WriteJob>,
+) -> Result<PageOutcome, String> {
+    let limit = download_config.max_total_bytes.unwrap_or(u64::MAX);
+    if queued_bytes.load(Ordering::SeqCst) >= limit {
+        return Ok(PageOutcome::OverLimit);
+    }
+
+    // A page whose HTML cannot be read is downloaded: its markdown may still be served.
+    // The raw body is read, as the decoded HTML of a page drops its `<head>`
+    if download_config.respect_noindex {
+        let page_url = strip_md_suffix(url);
+        if let Ok(html) =
+            download_raw_with_client(client, page_url, download_config.per_request_timeout).await
+        {
+            if has_noindex_meta(&html) {
+                return Ok(PageOutcome::Noindex(PageInfo::new(url.to_string())));
+            }
+        }
+    }
+
+    let md_content = download_page_with_client(client, url, download_config.per_request_timeout)
+        .await
+        .map_err(|e| e.to_string())?;
+    let md_content = override_base_url(md_content, url, download_config);
+
+    // The code blocks are marked so that wrapping leaves them untouched
+    let mark_code = download_config.wrap > 0
+        && !download_config.sanitize.join_lines
+        && download_config.renderer.is_none();
+    let mut text_content = match &download_config.renderer {
+        Some(renderer) => renderer.render(&md_content),
+        None if mark_code => markdown_to_text_marked(&md_content, &download_config.text),
+        None => markdown_to_text_with_config(&md_content, &download_config.text),
+    };
+    if download_config.strip_nav {
+        text_content = strip_navigation_boilerplate(&text_content);
+    }
+    // The chunks are split on the paragraph breaks, and their lines joined once split
+    let keeps_lines = download_config.chunk_size.is_some()
+        || download_config
+            .renderer
+            .as_ref()
+            .is_some_and(|renderer| renderer.keeps_lines());
+    let sanitize = SanitizeConfig {
+        join_lines: download_config.sanitize.join_lines && !keeps_lines,
+        ..download_config.sanitize.clone()
+    };
+    let text_cleaned = txt_sanitize_with_context(
+        &text_content,
+        &sanitize,
+        &SanitizeContext {
+            base_url: Some(url.to_string()),
+        },
+    );
+    let text_cleaned = if mark_code {
+        wrap_marked_text(&text_cleaned, download_config.wrap)
+    } else {
+        wrap_text(&text_cleaned, download_config.wrap)
+    };
+
+    let mut page = PageInfo::new(url.to_string());
+    page.detected_language = detect_language(&text_cleaned, download_config.language_confidence);
+
+    if let (Some(wanted), Some(detected)) = (download_config.language, page.detected_language) {
+        if wanted != detected {
+            return Ok(PageOutcome::Skipped(page, "language"));
+        }
+    }
+
+    if dedup.lock().unwrap().is_duplicate(&md_content) {
+        return Ok(PageOutcome::Skipped(page, "duplicate"));
+    }
+
+    let combined = download_config.combine.then(|| {
+        let title = extract_page_metadata(&md_content, None).title;
+        (title.unwrap_or_default(), text_cleaned.clone())
+    });
+
+    let page_url = strip_md_suffix(url);
+    let unchanged = known_hashes.get(page_url) == Some(&content_hash(&md_content));
+    if unchanged && !download_config.force_redownload {
+        return Ok(PageOutcome::Unchanged(page, combined));
+    }
+
+    let text = match download_config.format {
+        OutputFormat::AsciiDoc => markdown_to_asciidoc(&md_content),
+        OutputFormat::Text => text_cleaned,
+    };
+
+    let size = (md_content.len() + text.len()) as u64;
+    if queued_bytes.fetch_add(size, Ordering::SeqCst) >= limit {
+        return Ok(PageOutcome::OverLimit);
+    }
+
+    let job = WriteJob {
+        page,
+        markdown: md_content,
+        text,
+    };
+    tx.send(job)
+        .await
+        .map_err(|_| "the writer task stopped".to_string())?;
+
+    Ok(PageOutcome::Queued(combined, unchanged))
+}
+
+/// Writes the downloaded pages received on the channel, up to `parallel_writes` at once
+///
+/// Returns a partial report with the saved pages, their size and the write failures,
+/// along with the manifest entries of the saved pages
+async fn write_pages(
+    rx: mpsc::Receiver<WriteJob>,
+    config: CrawlConfig,
+    download_config: DownloadConfig,
+    events: Events,
+) -> (DownloadReport, Vec<ManifestPage>) {
+    let report = Mutex::new(DownloadReport::default());
+    let saved_pages = Mutex::new(Vec::new());
+
+    let jobs = stream::unfold(
+        rx,
+        |mut rx| async move { rx.recv().await.map(|job| (job, rx)) },
+    );
+
+    if let Some(depth) = config.split_by_depth {
+        let jobs: Vec<WriteJob> = jobs.collect().await;
+        return write_chapters(jobs, depth, &config, &download_config, &events).await;
+    }
+
+    let used_names = Mutex::new(HashSet::new());
+    let (config, download_config, shared, saved, used_names, events) = (
+        &config,
+        &download_config,
+        &report,
+        &saved_pages,
+        &used_names,
+        &events,
+    );
+    jobs.for_each_concurrent(download_config.parallel_writes, |job| async move {
+        let name = config
+            .title_from_h1
+            .then(|| title_file_name(&job.markdown, config.slug_separator, used_names))
+            .flatten();
+        let result = match name {
+            Some(name) => write_titled_page(&name, &job, config, download_config).await,
+            None => write_page(&job, config, download_config).await,
+        };
+        let mut report = shared.lock().unwrap();
+        match result {
+            Ok(path) => {
+                let bytes = (job.markdown.len() + job.text.len()) as u64;
+                report.pages_downloaded += 1;
+                report.total_bytes += bytes;
+                events.emit(DownloadEvent::Saved {
+                    url: job.page.url.clone(),
+                    bytes,
+                });
+                let page = manifest_page(&job.page.url, &job.markdown, job.page.depth, path);
+                saved.lock().unwrap().push(page);
+            }
+            Err(e) => {
+                events.emit(DownloadEvent::SaveFailed {
+                    name: job.page.url.clone(),
+                    error: e.to_string(),
+                });
+                report.pages_failed.push((job.page.url, e.to_string()));
+            }
+        }
+    })
+    .await;
+
+    (
+        report.into_inner().unwrap(),
+        saved_pages.into_inner().unwrap(),
+    )
+}
+
+/// Saves a page and returns the path of its markdown file
+async fn write_page(
+    job: &WriteJob,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<PathBuf, GitBookError> {
+    let url = &job.page.url;
+
+    let ext = download_config.format.extension();
+    let text_dir = config.output_dir.join(ext);
+
+    let md_path = match download_config.compression_level {
+        Some(level) => {
+            save_markdown_compressed(url, &job.markdown, level, config).await?;
+            output_path(url, &config.output_dir.join("md"), "md.zst", config)
+        }
+        None => {
+            save_markdown_with_config(url, &job.markdown, config).await?;
+            output_path(url, &config.output_dir.join("md"), "md", config)
+        }
+    };
+    let text_path = |extension: &str| output_path(url, &text_dir, extension, config);
+    write_text(&job.text, ext, text_path, config, download_config).await?;
+
+    if let Some(dir) = &download_config.extract_code_dir {
+        save_code_blocks(url, &extract_code_blocks(&job.markdown), dir).await?;
+    }
+
+    Ok(md_path)
+}
+
+/// Saves the converted text of a page to `path_for(extension)`, compressed when
+/// `compression_level` is set
+///
+/// With `chunk_size`, the text is split and each chunk is saved with a numbered
+/// extension (`001.txt`, `002.txt`, ...). The `encoding_declaration` heads every
+/// text file, after the chunks are split and joined.
+async fn write_text(
+    text: &str,
+    ext: &str,
+    path_for: impl Fn(&str) -> PathBuf,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), GitBookError> {
+    let files = match download_config.chunk_size {
+        Some(max_tokens) => split_into_chunks(text, max_tokens, &CharacterTokenizer::default())
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let chunk = if download_config.sanitize.join_lines {
+                    chunk.split_whitespace().collect::<Vec<_>>().join(" ")
+                } else {
+                    chunk
+                };
+                (format!("{:03}.{}", i + 1, ext), chunk)
+            })
+            .collect(),
+        None => vec![(ext.to_string(), text.to_string())],
+    };
+
+    for (extension, mut content) in files {
+        if let (OutputFormat::Text, Some(declaration)) = (
+            download_config.format,
+            &download_config.encoding_declaration,
+        ) {
+            content = with_encoding_declaration(&content, declaration);
+        }
+        match download_config.compression_level {
+            Some(level) => {
+                let path = path_for(&format!("{}.zst", extension));
+                write_output(&path, compress(&content, level)?, config).await?;
+            }
+            None => write_output(&path_for(&extension), &content, config).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Builds the manifest entry of a page saved to `path`
+fn manifest_page(url: &str, markdown: &str, depth: usize, path: PathBuf) -> ManifestPage {
+    let url = strip_md_suffix(url);
+    let metadata = extract_page_metadata(markdown, None);
+    ManifestPage {
+        title: metadata.title,
+        description: metadata.description,
+        content_hash: Some(hex_content_hash(markdown)),
+        downloaded_at: Some(SystemTime::now()),
+        file_path: Some(path),
+        ..ManifestPage::new(url, depth)
+    }
+}
+
+/// Rewrites the links of the saved markdown files as relative paths
+///
+/// The pages of the manifest, if any, are link targets along with the saved pages.
+async fn rewrite_saved_links(
+    saved: &[ManifestPage],
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let mut pages: HashMap<String, PathBuf> = config
+        .manifest_path
+        .as_deref()
+        .and_then(|path| Manifest::load(path).ok())
+        .map(|manifest| {
+            manifest
+                .pages
+                .into_iter()
+                .filter_map(|page| Some((page.url, page.file_path?)))
+                .collect()
+        })
+        .unwrap_or_default();
+    pages.extend(
+        saved
+            .iter()
+            .filter_map(|page| Some((page.url.clone(), page.file_path.clone()?))),
+    );
+
+    let mut rewritten = HashSet::new();
+    for page in saved {
+        let Some(path) = page
+            .file_path
+            .as_ref()
+            .filter(|p| p.extension() == Some("md".as_ref()))
+        else {
+            continue;
+        };
+        if !rewritten.insert(path) {
+            continue;
+        }
+        let md = tokio::fs::read_to_string(path).await?;
+        tokio::fs::write(path, rewrite_markdown_links(&md, &page.url, &pages)).await?;
+    }
+    Ok(())
+}
+
+/// Returns the content hashes recorded in the manifest at `path` for the pages whose
+/// file still exists, by URL; empty if there is no manifest
+fn known_hashes(path: &Path) -> HashMap<String, [u8; 32]> {
+    let Ok(manifest) = Manifest::load(path) else {
+        return HashMap::new();
+    };
+    manifest
+        .pages
+        .iter()
+        .filter(|page| page.file_path.as_deref().is_some_and(Path::is_file))
+        .filter_map(|page| Some((page.url.clone(), page.content_sha256()?)))
+        .collect()
+}
+
+/// Records the saved pages in the manifest at `path`, keeping their crawl depth
+fn save_download_manifest(path: &Path, saved: Vec<ManifestPage>) -> Result<(), GitBookError> {
+    let mut manifest = match Manifest::load(path) {
+        Ok(manifest) => manifest,
+        Err(_) => {
+            let source = saved
+                .first()
+                .and_then(|page| Url::parse(&page.url).ok())
+                .map(|url| url.origin().ascii_serialization())
+                .unwrap_or_default();
+            Manifest::new(source)
+        }
+    };
+
+    for mut page in saved {
+        if let Some(previous) = manifest.page(&page.url) {
+            page.depth = previous.depth;
+            page.description = page.description.or(previous.description.clone());
+            page.canonical_url = previous.canonical_url.clone();
+        }
+        manifest.upsert(page);
+    }
+
+    manifest.save(path)
+}
+
+/// Reserves a file name derived from the first `# Heading` of a page
+///
+/// Returns `None` when the page has no heading with alphanumeric characters.
+fn title_file_name(
+    markdown: &str,
+    sep: char,
+    used_names: &Mutex<HashSet<String>>,
+) -> Option<String> {
+    let slug = slugify_with_separator(&first_h1(markdown)?, sep);
+    if slug.is_empty() {
+        return None;
+    }
+
+    let mut used_names = used_names.lock().unwrap();
+    let mut name = slug.clone();
+    let mut suffix = 2;
+    while !used_names.insert(name.clone()) {
+        name = format!("{}{}{}", slug, sep, suffix);
+        suffix += 1;
+    }
+
+    Some(name)
+}
+
+/// Saves a page under `<output_dir>/md/<name>.md` and `<output_dir>/txt/<name>.txt`
+/// (`adoc/<name>.adoc` for AsciiDoc) and returns the path of its markdown file
+async fn write_titled_page(
+    name: &str,
+    job: &WriteJob,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<PathBuf, GitBookError> {
+    let md_path = config.output_dir.join("md").join(name);
+    let ext = download_config.format.extension();
+    let txt_path = config.output_dir.join(ext).join(name);
+
+    let md_path = match download_config.compression_level {
+        Some(level) => {
+            let md_path = md_path.with_added_extension("md.zst");
+            write_output(&md_path, compress(&job.markdown, level)?, config).await?;
+            md_path
+        }
+        None => {
+            let md_path = md_path.with_added_extension("md");
+            write_output(&md_path, &job.markdown, config).await?;
+            md_path
+        }
+    };
+    // The extension is appended, as the name may contain dots (`--slug-sep .`)
+    let text_path = |extension: &str| txt_path.with_added_extension(extension);
+    write_text(&job.text, ext, text_path, config, download_config).await?;
+
+    if let Some(dir) = &download_config.extract_code_dir {
+        save_code_blocks(&job.page.url, &extract_code_blocks(&job.markdown), dir).await?;
+    }
+
+    Ok(md_path)
+}
+
+/// Writes the downloaded pages as one markdown and one text file per chapter in the output directory
+///
+/// Each page becomes a section headed by its title, in URL order.
+/// Pages starting with a heading keep it as the section heading.
+async fn write_chapters(
+    jobs: Vec<WriteJob>,
+    depth: usize,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+    events: &Events,
+) -> (DownloadReport, Vec<ManifestPage>) {
+    let mut report = DownloadReport::default();
+    let mut saved = Vec::new();
+
+    let mut pages: Vec<GitBookPage> = jobs
+        .into_iter()
+        .map(|job| {
+            let mut page = GitBookPage::parse(&job.page.url, None, &job.markdown);
+            page.plain_text = job.text;
+            page
+        })
+        .collect();
+    pages.sort_by(|a, b| a.url.cmp(&b.url));
+
+    let mut chapters: Vec<(String, Vec<&GitBookPage>)> = group_pages_by_path_segment(&pages, depth)
+        .into_iter()
+        .collect();
+    chapters.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, pages) in chapters {
+        let mut markdown = String::new();
+        let mut text = String::new();
+        for page in &pages {
+            // Pages starting with their own heading already open their section
+            if !page.markdown.trim_start().starts_with('#') {
+                let title = page.metadata.title.as_deref().unwrap_or(&page.url);
+                markdown.push_str(&format!("# {}\n\n", title));
+                text.push_str(&format!("{}\n\n", title));
+            }
+            markdown.push_str(&format!("{}\n\n", page.markdown.trim()));
+            text.push_str(&format!("{}\n\n", page.plain_text.trim()));
+        }
+        text = match (
+            download_config.format,
+            &download_config.encoding_declaration,
+        ) {
+            (OutputFormat::AsciiDoc, _) => markdown_to_asciidoc(&markdown),
+            (OutputFormat::Text, Some(declaration)) => {
+                with_encoding_declaration(&text, declaration)
+            }
+            (OutputFormat::Text, None) => text,
+        };
+
+        let base = config.output_dir.join(&name);
+        let result = write_chapter(&base, &markdown, &text, &pages, download_config).await;
+        match result {
+            Ok(path) => {
+                let bytes = (markdown.len() + text.len()) as u64;
+                report.pages_downloaded += pages.len();
+                report.total_bytes += bytes;
+                events.emit(DownloadEvent::ChapterSaved {
+                    path: path.clone(),
+                    pages: pages.len(),
+                    bytes,
+                });
+                for page in &pages {
+                    saved.push(manifest_page(&page.url, &page.markdown, 0, path.clone()));
+                }
+            }
+            Err(e) => {
+                events.emit(DownloadEvent::SaveFailed {
+                    name: format!("chapter {}", name),
+                    error: e.to_string(),
+                });
+                for page in pages {
+                    report.pages_failed.push((page.url.clone(), e.to_string()));
+                }
+            }
+        }
+    }
+
+    (report, saved)
+}
+
+/// Saves a chapter under `<base>.md` and `<base>.txt` (`.md.zst` and `.txt.zst` when
+/// compressed) and returns the path of its markdown file
+async fn write_chapter(
+    base: &Path,
+    markdown: &str,
+    text: &str,
+    pages: &[&GitBookPage],
+    download_config: &DownloadConfig,
+) -> Result<PathBuf, GitBookError> {
+    let ext = download_config.format.extension();
+    let md_path = match download_config.compression_level {
+        Some(level) => {
+            let md_path = base.with_added_extension("md.zst");
+            tokio::fs::write(&md_path, compress(markdown.trim_end(), level)?).await?;
+            let text_path = base.with_added_extension(format!("{}.zst", ext));
+            tokio::fs::write(text_path, compress(text.trim_end(), level)?).await?;
+            md_path
+        }
+        None => {
+            let md_path = base.with_added_extension("md");
+            tokio::fs::write(&md_path, markdown.trim_end()).await?;
+            tokio::fs::write(base.with_added_extension(ext), text.trim_end()).await?;
+            md_path
+        }
+    };
+
+    if let Some(dir) = &download_config.extract_code_dir {
+        for page in pages {
+            save_code_blocks(&page.url, &page.code_blocks, dir).await?;
+        }
+    }
+
+    Ok(md_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::LlmChunkRenderer;
+    use crate::test_server::{MockResponse, MockServer};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_markdown_url() {
+        let url = |u: &str| markdown_url(u.to_string());
+        assert_eq!(url("https://x.com/guide#setup"), "https://x.com/guide.md");
+        assert_eq!(url("https://x.com/guide.md"), "https://x.com/guide.md");
+        assert_eq!(url("https://x.com/GUIDE.MD"), "https://x.com/GUIDE.MD");
+    }
+
+    /// Serves `# A` at `/a.md` on a local port and returns the base URL
+    async fn serve_page_a() -> String {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/a.md" => MockResponse::ok("# A"),
+            "/a" => MockResponse::ok(
+                r#"<html><head><meta name="robots" content="noindex"></head><body><p>A</p></body></html>"#,
+            )
+            .header("Content-Type", "text/html; charset=utf-8"),
+            _ => MockResponse::not_found(),
+        })
+        .await;
+        server.url
+    }
+
+    /// Downloads a page whose markdown is `md` and returns its text files, by file name
+    async fn download_texts(md: &'static str, download_config: &DownloadConfig) -> Vec<String> {
+        let server = MockServer::pages(vec![("/page.md", md)]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let report = download_pages(
+            vec![format!("{}/page", server.url)],
+            &config,
+            download_config,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.pages_downloaded, 1);
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir.path().join("txt"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| std::fs::read_to_string(path).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_invalid_language_confidence() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        for confidence in [1.5, -0.1, f64::NAN] {
+            let download_config = DownloadConfig {
+                language_confidence: confidence,
+                ..Default::default()
+            };
+            assert!(matches!(
+                download_pages(Vec::new(), &config, &download_config).await,
+                Err(GitBookError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_title_from_h1() {
+        let server = MockServer::pages(vec![("/page.md", "# v1.2 Release\n\nNotes.")]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            title_from_h1: true,
+            slug_separator: '.',
+            ..Default::default()
+        };
+        let report = download_pages(
+            vec![format!("{}/page", server.url)],
+            &config,
+            &DownloadConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.pages_downloaded, 1);
+
+        // The dots of the name are kept before the extension
+        assert!(dir.path().join("md/v1.2.release.md").is_file());
+        assert!(dir.path().join("txt/v1.2.release.txt").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_chapters() {
+        let server = MockServer::pages(vec![
+            ("/guide/a.md", "# A\n\nFirst."),
+            ("/guide/b.md", "# B\n\nSecond."),
+        ])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            split_by_depth: Some(1),
+            ..Default::default()
+        };
+        let download_config = DownloadConfig {
+            encoding_declaration: Some(crate::DEFAULT_ENCODING_DECLARATION.to_string()),
+            compression_level: Some(3),
+            ..Default::default()
+        };
+        let urls = ["/guide/a", "/guide/b"].map(|path| format!("{}{}", server.url, path));
+        let report = download_pages(urls.to_vec(), &config, &download_config)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_downloaded, 2);
+
+        let read = |name: &str| {
+            let file = std::fs::File::open(dir.path().join(name)).unwrap();
+            String::from_utf8(zstd::stream::decode_all(file).unwrap()).unwrap()
+        };
+        assert_eq!(read("guide.md.zst"), "# A\n\nFirst.\n\n# B\n\nSecond.");
+        // The declaration heads the chapter once
+        let text = read("guide.txt.zst");
+        assert_eq!(
+            text.lines().next(),
+            Some(crate::DEFAULT_ENCODING_DECLARATION)
+        );
+        assert_eq!(text.matches(crate::DEFAULT_ENCODING_DECLARATION).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_lists() {
+        let md = "Steps:\n\n3. Install\n4. Configure\n   - `config.yml`\n   - the \"env\"\n\nDone";
+        let texts = download_texts(md, &DownloadConfig::default()).await;
+        assert_eq!(
+            texts,
+            ["Steps:\n\n3. Install\n4. Configure\n  • config.yml\n  • the env\n\nDone"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_wrap() {
+        let md = "Run the installer to get started.\n\n```sh\n./install.sh --prefix /usr/local --verbose\n```\n\nThen restart.";
+        let download_config = DownloadConfig {
+            wrap: 20,
+            ..Default::default()
+        };
+        let texts = download_texts(md, &download_config).await;
+        assert_eq!(
+            texts,
+            ["Run the installer to\nget started.\n\n./install.sh --prefix /usr/local --verbose\n\nThen restart."]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_chunk_renderer() {
+        let download_config = DownloadConfig {
+            renderer: Some(Arc::new(LlmChunkRenderer::new(3))),
+            sanitize: SanitizeConfig {
+                join_lines: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let texts = download_texts("One two.\n\nThree four.\n\nFive", &download_config).await;
+        assert_eq!(texts, ["One two.\n\n---\n\nThree four.\n\nFive"]);
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_chunks() {
+        let md = "# Install\n\nRun the installer.\n\n# Configure\n\nEdit the file.";
+        let mut download_config = DownloadConfig {
+            chunk_size: Some(8),
+            ..Default::default()
+        };
+        let texts = download_texts(md, &download_config).await;
+        assert_eq!(
+            texts,
+            [
+                "Install\n\nRun the installer.",
+                "Configure\n\nEdit the file."
+            ]
+        );
+
+        download_config.sanitize.join_lines = true;
+        let texts = download_texts(md, &download_config).await;
+        assert_eq!(
+            texts,
+            ["Install Run the installer.", "Configure Edit the file."]
+        );
+
+        // Every chunk starts with the declaration, on its own line
+        download_config.encoding_declaration = Some(crate::DEFAULT_ENCODING_DECLARATION.into());
+        let texts = download_texts(md, &download_config).await;
+        assert_eq!(
+            texts,
+            [
+                "# -*- coding: utf-8 -*-\nInstall Run the installer.",
+                "# -*- coding: utf-8 -*-\nConfigure Edit the file."
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_parallel_downloads() {
+        let server =
+            MockServer::start(|_| MockResponse::not_found().delay(Duration::from_millis(50))).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let download_config = DownloadConfig {
+            parallel_downloads: 2,
+            ..Default::default()
+        };
+        let urls = stream::iter((0..6).map(|i| format!("{}/{}", server.url, i)));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let report = download_pages_stream(urls, &config, &download_config, tx)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_failed.len(), 6);
+
+        let (mut running, mut max_running) = (0, 0);
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                DownloadEvent::PageStarted { .. } => running += 1,
+                DownloadEvent::DownloadFailed { .. } => running -= 1,
+                _ => {}
+            }
+            max_running = max_running.max(running);
+        }
+        assert_eq!(max_running, 2);
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_max_total_bytes() {
+        let server = MockServer::start(|_| MockResponse::ok("# Page")).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let download_config = DownloadConfig {
+            max_total_bytes: Some(1),
+            parallel_downloads: 1,
+            ..Default::default()
+        };
+        let urls: Vec<String> = (0..3).map(|i| format!("{}/{}", server.url, i)).collect();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let report = download_pages_with_events(urls, &config, &download_config, tx)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_downloaded, 1);
+        assert_eq!(report.pages_over_limit, 2);
+
+        // The pages downloaded in parallel when the limit is reached are not saved
+        let urls: Vec<String> = (3..9).map(|i| format!("{}/{}", server.url, i)).collect();
+        let parallel = DownloadConfig {
+            parallel_downloads: 8,
+            ..download_config.clone()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let parallel_config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let parallel_report = download_pages(urls, &parallel_config, &parallel)
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                parallel_report.pages_downloaded,
+                parallel_report.pages_over_limit
+            ),
+            (1, 5)
+        );
+
+        // Each page over the limit is reported, so that the progress reaches the total
+        let events: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        let over_limit = events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    DownloadEvent::Skipped {
+                        reason: "size limit",
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(over_limit, 2);
+        assert!(events.contains(&DownloadEvent::LimitReached));
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_stream() {
+        let base = serve_page_a().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let (urls_tx, urls_rx) = mpsc::unbounded_channel();
+        let urls = stream::unfold(urls_rx, |mut rx| async move {
+            rx.recv().await.map(|url| (url, rx))
+        });
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let download = tokio::spawn(async move {
+            download_pages_stream(urls, &config, &DownloadConfig::default(), tx).await
+        });
+
+        // Each page is downloaded as soon as its URL arrives
+        urls_tx.send(format!("{}/a", base)).unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            let saved = matches!(event, DownloadEvent::Saved { .. });
+            events.push(event);
+            if saved {
+                break;
+            }
+        }
+        urls_tx.send(format!("{}/a#intro", base)).unwrap();
+        urls_tx.send(format!("{}/gone", base)).unwrap();
+        drop(urls_tx);
+
+        let report = download.await.unwrap().unwrap();
+        events.extend(std::iter::from_fn(|| rx.try_recv().ok()));
+        assert_eq!(report.pages_downloaded, 1);
+        assert_eq!(report.pages_failed.len(), 1);
+        assert_eq!(
+            events[0],
+            DownloadEvent::PageStarted {
+                url: format!("{}/a.md", base),
+                index: 1,
+                total: 1
+            }
+        );
+        assert!(events.contains(&DownloadEvent::PageStarted {
+            url: format!("{}/gone.md", base),
+            index: 2,
+            total: 2
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_skip_unchanged() {
+        let base = serve_page_a().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            manifest_path: Some(dir.path().join("manifest.json")),
+            ..Default::default()
+        };
+        let download_config = DownloadConfig {
+            skip_unchanged: true,
+            ..Default::default()
+        };
+        let urls = || vec![format!("{}/a", base)];
+
+        let report = download_pages(urls(), &config, &download_config)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_downloaded, 1);
+
+        let report = download_pages(urls(), &config, &download_config)
+            .await
+            .unwrap();
+        assert_eq!((report.pages_downloaded, report.pages_skipped), (0, 1));
+
+        // Without the saved file the page is written again
+        let manifest = Manifest::load(&dir.path().join("manifest.json")).unwrap();
+        std::fs::remove_file(manifest.pages[0].file_path.as_ref().unwrap()).unwrap();
+        let report = download_pages(urls(), &config, &download_config)
+            .await
+            .unwrap();
+        assert_eq!(report.pages_downloaded, 1);
+
+        // `force_redownload` writes the unchanged page again
+        let forced = DownloadConfig {
+            force_redownload: true,
+            ..download_config
+        };
+        let report = download_pages(urls(), &config, &forced).await.unwrap();
+        assert_eq!(
+            (
+                report.pages_downloaded,
+                report.pages_skipped,
+                report.pages_forced
+            ),
+            (1, 0, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_pages_respect_noindex() {
+        let base = serve_page_a().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            manifest_path: Some(dir.path().join("manifest.json")),
+            ..Default::default()
+        };
+        let urls = || vec![format!("{}/a", base)];
+
+        let report = download_pages(urls(), &config, &DownloadConfig::default())
+            .await
+            .unwrap();
+        assert_eq!((report.pages_downloaded, report.pages_noindex), (1, 0));
+
+        let download_config = DownloadConfig {
+            respect_noindex: true,
+            ..Default::default()
+        };
+        let report = download_pages(urls(), &config, &download_config)
+            .await
+            .unwrap();
+        assert_eq!((report.pages_downloaded, report.pages_noindex), (0, 1));
+
+        let manifest = Manifest::load(&dir.path().join("manifest.json")).unwrap();
+        let page = manifest.page(&format!("{}/a", base)).unwrap();
+        assert_eq!(page.status, Some(ManifestPageStatus::SkippedNoindex));
+        assert_eq!(page.file_path, None);
+    }
+}
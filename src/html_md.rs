@@ -0,0 +1,357 @@
+use crate::config::HtmlToMdConfig;
+use scraper::{ElementRef, Html, Node, Selector};
+use url::Url;
+
+/// Converts an HTML page to markdown
+///
+/// Only the main content is converted (`main`, then `article`, then `body`).
+/// Headings, paragraphs, emphasis, inline code, code blocks, lists, quotes,
+/// tables, rules, links and images are rendered; scripts and styles are dropped
+/// and the text of other elements is kept.
+///
+/// # Arguments
+///
+/// * `html` - The HTML page or fragment
+/// * `config` - Which details are kept and how relative URLs are resolved
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{html_to_markdown, HtmlToMdConfig};
+///
+/// let html = r#"<main><h1>Setup</h1><p>Read <a href="/guide">the <b>guide</b></a>.</p></main>"#;
+/// let config = HtmlToMdConfig {
+///     base_url: Some("https://docs.example.com/setup".to_string()),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     html_to_markdown(html, &config),
+///     "# Setup\n\nRead [the **guide**](https://docs.example.com/guide)."
+/// );
+/// ```
+pub fn html_to_markdown(html: &str, config: &HtmlToMdConfig) -> String {
+    let document = Html::parse_document(html);
+    let root = ["main", "article", "body"]
+        .iter()
+        .filter_map(|tag| Selector::parse(tag).ok())
+        .find_map(|selector| document.select(&selector).next())
+        .unwrap_or_else(|| document.root_element());
+
+    let converter = Converter {
+        base: config.base_url.as_deref().and_then(|u| Url::parse(u).ok()),
+        config,
+    };
+    converter.blocks(root).join("\n\n")
+}
+
+struct Converter<'a> {
+    base: Option<Url>,
+    config: &'a HtmlToMdConfig,
+}
+
+impl Converter<'_> {
+    /// Renders the children of an element as markdown blocks
+    ///
+    /// Runs of text and inline elements between the block elements become paragraphs.
+    fn blocks(&self, el: ElementRef) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut paragraph = String::new();
+
+        for child in el.children() {
+            match ElementRef::wrap(child) {
+                Some(child) if is_block(child.value().name()) => {
+                    push_paragraph(&mut blocks, &mut paragraph);
+                    blocks.extend(self.block(child));
+                }
+                Some(child) => paragraph.push_str(&self.inline(child)),
+                None => {
+                    if let Node::Text(text) = child.value() {
+                        paragraph.push_str(&collapse_whitespace(text));
+                    }
+                }
+            }
+        }
+        push_paragraph(&mut blocks, &mut paragraph);
+        blocks
+    }
+
+    /// Renders a block element, which may produce several blocks
+    fn block(&self, el: ElementRef) -> Vec<String> {
+        let name = el.value().name();
+        let block = match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                format!("{} {}", "#".repeat(level), self.inline_children(el).trim())
+            }
+            "p" => self.inline_children(el).trim().to_string(),
+            "pre" => {
+                let language = el
+                    .descendants()
+                    .filter_map(ElementRef::wrap)
+                    .filter_map(|e| e.value().attr("class"))
+                    .flat_map(str::split_whitespace)
+                    .find_map(|class| class.strip_prefix("language-"))
+                    .unwrap_or_default();
+                let code: String = el.text().collect();
+                format!("```{}\n{}\n```", language, code.trim_end_matches('\n'))
+            }
+            "ul" | "ol" => self.list(el, name == "ol"),
+            "blockquote" => self
+                .blocks(el)
+                .join("\n\n")
+                .lines()
+                .map(|line| format!("> {}", line).trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "hr" => "---".to_string(),
+            "table" => self.table(el),
+            "script" | "style" | "noscript" | "template" | "head" => String::new(),
+            _ => return self.blocks(el),
+        };
+        if block.trim().is_empty() {
+            Vec::new()
+        } else {
+            vec![block]
+        }
+    }
+
+    /// Renders a list, indenting the blocks of each item under its marker
+    fn list(&self, el: ElementRef, ordered: bool) -> String {
+        let start = el
+            .value()
+            .attr("start")
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(1);
+
+        el.children()
+            .filter_map(ElementRef::wrap)
+            .filter(|item| item.value().name() == "li")
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = if ordered {
+                    format!("{}. ", start + i)
+                } else {
+                    "- ".to_string()
+                };
+                let indent = " ".repeat(marker.len());
+                self.blocks(item)
+                    .join("\n")
+                    .lines()
+                    .enumerate()
+                    .map(|(j, line)| match j {
+                        0 => format!("{}{}", marker, line),
+                        _ if line.is_empty() => String::new(),
+                        _ => format!("{}{}", indent, line),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Renders a table as a GFM table, the first row being the header
+    fn table(&self, el: ElementRef) -> String {
+        let row_selector = Selector::parse("tr").expect("valid selector");
+        let rows: Vec<Vec<String>> = el
+            .select(&row_selector)
+            .map(|row| {
+                row.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                    .map(|cell| self.inline_children(cell).trim().replace('|', "\\|"))
+                    .collect()
+            })
+            .filter(|cells: &Vec<String>| !cells.is_empty())
+            .collect();
+        let Some(columns) = rows.iter().map(Vec::len).max() else {
+            return String::new();
+        };
+
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(columns, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    /// Renders the children of an element as inline markdown
+    fn inline_children(&self, el: ElementRef) -> String {
+        let mut markdown = String::new();
+        for child in el.children() {
+            match ElementRef::wrap(child) {
+                Some(child) => markdown.push_str(&self.inline(child)),
+                None => {
+                    if let Node::Text(text) = child.value() {
+                        markdown.push_str(&collapse_whitespace(text));
+                    }
+                }
+            }
+        }
+        markdown
+    }
+
+    /// Renders an inline element
+    fn inline(&self, el: ElementRef) -> String {
+        let wrap = |marker: &str| {
+            let inner = self.inline_children(el);
+            if inner.trim().is_empty() {
+                inner
+            } else {
+                format!("{}{}{}", marker, inner.trim(), marker)
+            }
+        };
+
+        match el.value().name() {
+            "strong" | "b" => wrap("**"),
+            "em" | "i" => wrap("_"),
+            "del" | "s" => wrap("~~"),
+            "code" => format!("`{}`", el.text().collect::<String>()),
+            "br" => "\n".to_string(),
+            "a" => {
+                let text = self.inline_children(el);
+                match el.value().attr("href") {
+                    Some(href) if self.config.include_links && !href.starts_with('#') => {
+                        format!("[{}]({})", text.trim(), self.resolve(href))
+                    }
+                    _ => text,
+                }
+            }
+            "img" => match el.value().attr("src") {
+                Some(src) if self.config.include_images => format!(
+                    "![{}]({})",
+                    el.value().attr("alt").unwrap_or_default(),
+                    self.resolve(src)
+                ),
+                _ => String::new(),
+            },
+            "script" | "style" | "noscript" | "template" => String::new(),
+            _ => self.inline_children(el),
+        }
+    }
+
+    /// Resolves a URL against the base URL, if any
+    fn resolve(&self, url: &str) -> String {
+        match self.base.as_ref().and_then(|base| base.join(url).ok()) {
+            Some(resolved) => resolved.to_string(),
+            None => url.to_string(),
+        }
+    }
+}
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "address"
+            | "article"
+            | "aside"
+            | "blockquote"
+            | "body"
+            | "details"
+            | "div"
+            | "dl"
+            | "figure"
+            | "footer"
+            | "form"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "head"
+            | "header"
+            | "hr"
+            | "li"
+            | "main"
+            | "nav"
+            | "noscript"
+            | "ol"
+            | "p"
+            | "pre"
+            | "script"
+            | "section"
+            | "style"
+            | "table"
+            | "template"
+            | "ul"
+    )
+}
+
+/// Replaces each run of whitespace with a single space
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !space {
+                collapsed.push(' ');
+            }
+            space = true;
+        } else {
+            collapsed.push(c);
+            space = false;
+        }
+    }
+    collapsed
+}
+
+/// Adds the pending inline content as a paragraph, if it is not blank
+fn push_paragraph(blocks: &mut Vec<String>, paragraph: &mut String) {
+    let text = paragraph.trim();
+    if !text.is_empty() {
+        blocks.push(text.to_string());
+    }
+    paragraph.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"<html><head><title>T</title></head><body>
+            <nav>Menu</nav>
+            <main>
+              <h2>Install</h2>
+              <p>Run <code>cargo   install</code> or
+                 see <a href="https://x.com/docs">the <em>docs</em></a>.<br>Done</p>
+              <ol start="3"><li>First</li><li><p>Second</p><ul><li>Nested</li></ul></li></ol>
+              <pre><code class="language-rust">fn main() {
+    println!("hi");
+}</code></pre>
+              <blockquote><p>Careful</p></blockquote>
+              <img src="/logo.png" alt="Logo">
+              <table><tr><th>Name</th><th>Type</th></tr><tr><td>id</td><td>a|b</td></tr></table>
+              <hr><script>var x = 1;</script>
+            </main></body></html>"#;
+
+        let markdown = html_to_markdown(html, &HtmlToMdConfig::default());
+        assert_eq!(
+            markdown,
+            "## Install\n\n\
+             Run `cargo   install` or see [the _docs_](https://x.com/docs).\nDone\n\n\
+             3. First\n4. Second\n   - Nested\n\n\
+             ```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+             > Careful\n\n\
+             ![Logo](/logo.png)\n\n\
+             | Name | Type |\n| --- | --- |\n| id | a\\|b |\n\n\
+             ---"
+        );
+
+        let config = HtmlToMdConfig {
+            base_url: Some("https://docs.example.com/a/b".to_string()),
+            include_images: false,
+            include_links: false,
+        };
+        let markdown = html_to_markdown(
+            r#"<p>See <a href="c">this</a> <img src="d.png" alt="D"></p>"#,
+            &config,
+        );
+        assert_eq!(markdown, "See this");
+    }
+}
@@ -0,0 +1,250 @@
+use crate::search::{collect_files, read_output_file};
+use crate::stats::{english_stop_words, words};
+use crate::GitBookError;
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// A place where a word of the index appears
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// The markdown file, relative to the indexed directory
+    pub filename: String,
+
+    /// The level of the heading containing the word (1 to 6), `0` for the text of a section
+    pub heading_level: u8,
+
+    /// The heading of the section, or the file name before the first heading
+    pub context: String,
+}
+
+/// A page matching a [`DocumentIndex::search`] query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    /// The markdown file, relative to the indexed directory
+    pub filename: String,
+
+    /// The sum of the weights of the matches: 6 for a `#` heading down to 1 for a
+    /// `######` heading, and 1 for the text of a section
+    pub score: u32,
+
+    /// The headings of the matching sections, in order of appearance
+    pub headings: Vec<String>,
+}
+
+/// An inverted index of the headings and words of the downloaded markdown pages
+///
+/// Words are compared in lowercase and the English stop words are left out.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::DocumentIndex;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// std::fs::write(dir.path().join("auth.md"), "# Authentication\n\nSend a token.").unwrap();
+/// std::fs::write(dir.path().join("faq.md"), "# FAQ\n\n## Tokens\n\nSee authentication.").unwrap();
+///
+/// let index = DocumentIndex::build(dir.path()).unwrap();
+/// let results = index.search("authentication");
+/// assert_eq!(results[0].filename, "auth.md");
+/// assert_eq!(results[1].headings, vec!["Tokens"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentIndex {
+    /// The places where each word appears
+    pub terms: HashMap<String, Vec<IndexEntry>>,
+}
+
+impl DocumentIndex {
+    /// Indexes all `.md` files of a directory
+    ///
+    /// The directory is scanned recursively and Zstd-compressed `.md.zst` files
+    /// are decompressed transparently.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or one of its files cannot be read
+    pub fn build(dir: &Path) -> Result<DocumentIndex, GitBookError> {
+        let mut files = Vec::new();
+        collect_files(dir, &[".md", ".md.zst"], &mut files)?;
+        files.sort();
+
+        let stop_words = english_stop_words();
+        let mut index = DocumentIndex::default();
+        for path in files {
+            let content = read_output_file(&path)?;
+            let filename = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            index.add_page(&filename, &content, &stop_words);
+        }
+        Ok(index)
+    }
+
+    /// Indexes the headings and the text of a markdown page
+    fn add_page(&mut self, filename: &str, md: &str, stop_words: &HashSet<String>) {
+        let mut context = filename.to_string();
+        let mut heading: Option<(u8, String)> = None;
+        let mut seen = HashSet::new();
+
+        for event in Parser::new(md) {
+            match event {
+                Event::Start(Tag::Heading { level, .. }) => {
+                    heading = Some((heading_level(level), String::new()));
+                }
+                Event::End(TagEnd::Heading(_)) => {
+                    if let Some((level, text)) = heading.take() {
+                        context = text.trim().to_string();
+                        self.add_words(filename, level, &context, &context, stop_words, &mut seen);
+                    }
+                }
+                Event::Text(text) | Event::Code(text) => match &mut heading {
+                    Some((_, heading_text)) => heading_text.push_str(&text),
+                    None => self.add_words(filename, 0, &context, &text, stop_words, &mut seen),
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Adds an entry per new word of `text`, once per section and level
+    fn add_words(
+        &mut self,
+        filename: &str,
+        heading_level: u8,
+        context: &str,
+        text: &str,
+        stop_words: &HashSet<String>,
+        seen: &mut HashSet<(String, u8, String)>,
+    ) {
+        for word in words(text).filter(|word| !stop_words.contains(word)) {
+            if !seen.insert((word.clone(), heading_level, context.to_string())) {
+                continue;
+            }
+            self.terms.entry(word).or_default().push(IndexEntry {
+                filename: filename.to_string(),
+                heading_level,
+                context: context.to_string(),
+            });
+        }
+    }
+
+    /// Returns the pages containing the words of the query, the most relevant first
+    ///
+    /// Ties are broken by file name.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let mut results: HashMap<&str, SearchResult> = HashMap::new();
+        for word in words(query) {
+            for entry in self.terms.get(&word).into_iter().flatten() {
+                let result = results
+                    .entry(&entry.filename)
+                    .or_insert_with(|| SearchResult {
+                        filename: entry.filename.clone(),
+                        score: 0,
+                        headings: Vec::new(),
+                    });
+                result.score += match entry.heading_level {
+                    0 => 1,
+                    level => 7 - u32::from(level),
+                };
+                if entry.context != entry.filename && !result.headings.contains(&entry.context) {
+                    result.headings.push(entry.context.clone());
+                }
+            }
+        }
+
+        let mut results: Vec<SearchResult> = results.into_values().collect();
+        results.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.filename.cmp(&b.filename))
+        });
+        results
+    }
+
+    /// Writes the index as JSON, creating the parent directories
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be created or if the file write fails
+    pub fn save(&self, path: &Path) -> Result<(), GitBookError> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json =
+            serde_json::to_string(self).map_err(|e| GitBookError::ParseError(e.to_string()))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Reads an index written by [`DocumentIndex::save`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid index
+    pub fn load(path: &Path) -> Result<Self, GitBookError> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json)
+            .map_err(|e| GitBookError::ParseError(format!("{}: {}", path.display(), e)))
+    }
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_document_index_search() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("md")).unwrap();
+        fs::write(
+            dir.path().join("md/setup.md"),
+            "# Installation\n\nRun the installer.\n\n## Configuration\n\nEdit the config.",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("md/faq.md"),
+            "# FAQ\n\nInstallation fails? Check the configuration.\n\n### Configuration errors",
+        )
+        .unwrap();
+        fs::write(dir.path().join("notes.txt"), "# Configuration").unwrap();
+
+        let index = DocumentIndex::build(dir.path()).unwrap();
+        assert!(!index.terms.contains_key("the"));
+
+        let results = index.search("Configuration");
+        assert_eq!(results.len(), 2);
+        // A `###` heading (4) and the text of a section (1) weigh as much as a `##` heading
+        assert_eq!(results[0].filename, "md/faq.md");
+        assert_eq!(results[0].score, 5);
+        assert_eq!(results[0].headings, vec!["FAQ", "Configuration errors"]);
+        assert_eq!(results[1].filename, "md/setup.md");
+        assert_eq!(results[1].score, 5);
+        assert_eq!(results[1].headings, vec!["Configuration"]);
+
+        let results = index.search("installation configuration");
+        assert_eq!(results[0].filename, "md/setup.md");
+        assert_eq!(results[0].score, 11);
+        assert!(index.search("missing").is_empty());
+
+        let path = dir.path().join("index.json");
+        index.save(&path).unwrap();
+        assert_eq!(DocumentIndex::load(&path).unwrap(), index);
+    }
+}
@@ -0,0 +1,410 @@
+//! Decompression of the gzip, zlib and raw DEFLATE bodies (RFC 1950, 1951 and 1952)
+
+/// Maximum number of bits of a Huffman code
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order in which the lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads the bits of a DEFLATE stream, least significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or("unexpected end of data")?;
+            self.buffer |= u32::from(byte) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops the bits left in the current byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or("unexpected end of data")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; MAX_BITS + 1],
+    /// The symbols, ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err("over-subscribed Huffman code".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; usize::from(offsets[MAX_BITS + 1])];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, returning the data and the number of bytes read
+///
+/// Fails once the data would exceed `limit` bytes.
+pub(crate) fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut reader = BitReader::new(data);
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = reader.bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err("invalid stored block length".to_string());
+                }
+                check_limit(out.len() + usize::from(len), limit)?;
+                out.extend_from_slice(reader.bytes(usize::from(len))?);
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                inflate_block(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            break;
+        }
+    }
+
+    Ok((out, reader.pos))
+}
+
+/// Fails if `size` bytes of output exceed `limit`
+fn check_limit(size: usize, limit: usize) -> Result<(), String> {
+    if size > limit {
+        return Err(format!("decompressed data larger than {} bytes", limit));
+    }
+    Ok(())
+}
+
+/// Reads the literal/length and distance codes of a dynamic block
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("too many length or distance codes".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_lengths.decode(reader)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *i
+                    .checked_sub(1)
+                    .and_then(|p| lengths.get(p))
+                    .ok_or("repeated length without a previous length")?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err("too many code lengths".to_string());
+        }
+        lengths[i..i + repeat].fill(length);
+        i += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err("missing end-of-block code".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+/// Decodes the symbols of a compressed block until its end-of-block code
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = usize::from(literals.decode(reader)?);
+        if symbol < 256 {
+            check_limit(out.len() + 1, limit)?;
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let index = symbol - 257;
+        let (Some(&base), Some(&extra)) = (LENGTH_BASE.get(index), LENGTH_EXTRA.get(index)) else {
+            return Err("invalid length code".to_string());
+        };
+        let length = usize::from(base) + reader.bits(u32::from(extra))? as usize;
+
+        let index = usize::from(distances.decode(reader)?);
+        let (Some(&base), Some(&extra)) = (DIST_BASE.get(index), DIST_EXTRA.get(index)) else {
+            return Err("invalid distance code".to_string());
+        };
+        let distance = usize::from(base) + reader.bits(u32::from(extra))? as usize;
+        if distance > out.len() {
+            return Err("distance too far back".to_string());
+        }
+        check_limit(out.len() + length, limit)?;
+
+        let start = out.len() - distance;
+        for i in 0..length {
+            out.push(out[start + i]);
+        }
+    }
+}
+
+/// Decompresses a gzip member, checking its CRC-32 and size, up to `limit` bytes
+pub(crate) fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 18 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+        return Err("not a gzip stream".to_string());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+
+    if flags & 0x04 != 0 {
+        let extra = data.get(pos..pos + 2).ok_or("unexpected end of data")?;
+        pos += 2 + usize::from(u16::from_le_bytes([extra[0], extra[1]]));
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let end = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or("unexpected end of data")?;
+            pos += end + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+
+    let (out, read) = inflate(data.get(pos..).ok_or("unexpected end of data")?, limit)?;
+    let trailer = data
+        .get(pos + read..pos + read + 8)
+        .ok_or("missing gzip trailer")?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err("gzip checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+/// Decompresses a zlib stream, or a raw DEFLATE stream as some servers send for `deflate`,
+/// up to `limit` bytes
+pub(crate) fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let zlib_header = data.len() >= 2
+        && data[0] & 0x0f == 8
+        && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0;
+    if !zlib_header {
+        return Ok(inflate(data, limit)?.0);
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("preset dictionaries are not supported".to_string());
+    }
+
+    let (out, read) = inflate(&data[2..], limit)?;
+    let checksum = data
+        .get(2 + read..2 + read + 4)
+        .ok_or("missing zlib checksum")?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"# Title\n\nHello, hello, hello!\n";
+
+    #[test]
+    fn test_inflate_blocks() {
+        let stored = b"\x01\x1e\x00\xe1\xff\x23\x20\x54\x69\x74\x6c\x65\x0a\x0a\x48\x65\x6c\x6c\x6f\x2c\x20\x68\x65\x6c\x6c\x6f\x2c\x20\x68\x65\x6c\x6c\x6f\x21\x0a";
+        assert_eq!(
+            inflate(stored, usize::MAX).unwrap(),
+            (TEXT.to_vec(), stored.len())
+        );
+
+        let fixed = b"\x78\xda\x53\x56\x08\xc9\x2c\xc9\x49\xe5\xe2\xf2\x48\xcd\xc9\xc9\xd7\x51\xc8\x40\xa2\x14\xb9\x00\x8d\x12\x09\x39";
+        assert_eq!(zlib_decompress(fixed, usize::MAX).unwrap(), TEXT);
+        assert_eq!(
+            zlib_decompress(&fixed[2..fixed.len() - 4], usize::MAX).unwrap(),
+            TEXT
+        );
+
+        let mut corrupt = fixed.to_vec();
+        corrupt[fixed.len() - 1] ^= 1;
+        assert!(zlib_decompress(&corrupt, usize::MAX).is_err());
+        assert!(inflate(&fixed[2..10], usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_gunzip() {
+        let gzip = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x53\x56\x08\xc9\x2c\xc9\x49\xe5\xe2\xf2\x48\xcd\xc9\xc9\xd7\x51\xc8\x40\xa2\x14\xb9\x00\xe7\xb9\x10\x40\x1e\x00\x00\x00";
+        assert_eq!(gunzip(gzip, usize::MAX).unwrap(), TEXT);
+
+        // A dynamic Huffman block
+        let dynamic = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x65\xce\xb1\x0d\x80\x20\x14\x45\xd1\x9e\x29\xfe\x02\x24\x3e\x45\x44\xd7\x31\x14\x84\x50\x98\xc8\xfe\x16\x24\x16\x97\xf2\x74\xc7\x5b\x79\x73\xb3\xe5\xb2\xa7\x97\xbb\x3a\x3f\x2c\x78\x85\x37\x38\xc0\x3b\x1c\xe1\x03\x4e\xf0\xc9\xcf\x14\xe4\x50\x2c\x8a\x47\x31\x29\x2e\xc5\xa6\xfe\xe7\x07\x7b\xe2\xfb\xc7\x28\x01\x00\x00";
+        let expected: String = (0..18).map(|i| format!("- item {}: quick\n", i)).collect();
+        assert_eq!(gunzip(dynamic, usize::MAX).unwrap(), expected.as_bytes());
+
+        assert!(gunzip(TEXT, usize::MAX).is_err());
+        assert!(gunzip(&gzip[..gzip.len() - 4], usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let stored = b"\x01\x1e\x00\xe1\xff\x23\x20\x54\x69\x74\x6c\x65\x0a\x0a\x48\x65\x6c\x6c\x6f\x2c\x20\x68\x65\x6c\x6c\x6f\x2c\x20\x68\x65\x6c\x6c\x6f\x21\x0a";
+        assert!(inflate(stored, TEXT.len()).is_ok());
+        assert!(inflate(stored, TEXT.len() - 1).is_err());
+
+        // Literals, then a match repeating them
+        let fixed = b"\x78\xda\x53\x56\x08\xc9\x2c\xc9\x49\xe5\xe2\xf2\x48\xcd\xc9\xc9\xd7\x51\xc8\x40\xa2\x14\xb9\x00\x8d\x12\x09\x39";
+        for limit in [5, 20, TEXT.len() - 1] {
+            assert!(zlib_decompress(fixed, limit).is_err());
+        }
+        assert_eq!(zlib_decompress(fixed, TEXT.len()).unwrap(), TEXT);
+    }
+}
@@ -0,0 +1,327 @@
+use serde_json::Value;
+
+/// A page in the JSON document format of GitBook's rendering API
+///
+/// The document is a tree of `block`, `inline` and `text` nodes. Pages served
+/// with a ready-made `markdown` field keep it as is.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::GitBookJsonPage;
+/// use serde_json::json;
+///
+/// let page = GitBookJsonPage::from_json(json!({
+///     "title": "Intro",
+///     "document": {"object": "document", "nodes": [
+///         {"object": "block", "type": "heading-1", "nodes": [
+///             {"object": "text", "leaves": [{"text": "Intro"}]}
+///         ]},
+///         {"object": "block", "type": "paragraph", "nodes": [
+///             {"object": "text", "leaves": [
+///                 {"text": "Read "},
+///                 {"text": "this", "marks": [{"type": "bold"}]}
+///             ]}
+///         ]}
+///     ]}
+/// }));
+/// assert_eq!(page.title.as_deref(), Some("Intro"));
+/// assert_eq!(page.to_markdown(), "# Intro\n\nRead **this**");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitBookJsonPage {
+    /// The title of the page
+    pub title: Option<String>,
+
+    /// The short description of the page
+    pub description: Option<String>,
+
+    /// The markdown of the page, when the API provides it directly
+    pub markdown: Option<String>,
+
+    /// The document tree of the page, `Value::Null` if absent
+    pub document: Value,
+}
+
+impl GitBookJsonPage {
+    /// Reads a page from the JSON returned by [`download_page_json`](crate::download_page_json)
+    ///
+    /// The page fields may be at the top level or under a `page` object.
+    pub fn from_json(value: Value) -> Self {
+        let page = match value.get("page") {
+            Some(page) if page.is_object() => page,
+            _ => &value,
+        };
+        let string = |key: &str| page.get(key).and_then(Value::as_str).map(str::to_string);
+
+        GitBookJsonPage {
+            title: string("title"),
+            description: string("description"),
+            markdown: string("markdown"),
+            document: page.get("document").cloned().unwrap_or(Value::Null),
+        }
+    }
+
+    /// Converts the document tree to markdown
+    ///
+    /// Headings, paragraphs, lists, quotes, hints, code blocks, images, dividers,
+    /// links and the bold, italic, code and strikethrough marks are rendered;
+    /// the text of other nodes is kept as plain paragraphs.
+    pub fn to_markdown(&self) -> String {
+        if let Some(markdown) = &self.markdown {
+            return markdown.clone();
+        }
+        blocks_markdown(children(&self.document)).join("\n\n")
+    }
+}
+
+/// Returns the child nodes of a node
+fn children(node: &Value) -> &[Value] {
+    node.get("nodes")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn node_type(node: &Value) -> &str {
+    node.get("type").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn is_block(node: &Value) -> bool {
+    node.get("object").and_then(Value::as_str) == Some("block")
+}
+
+/// Reads a string under `data`, e.g. `data_str(node, &["ref", "url"])` for `data.ref.url`
+fn data_str<'a>(node: &'a Value, path: &[&str]) -> Option<&'a str> {
+    path.iter()
+        .try_fold(node.get("data")?, |value, key| value.get(key))?
+        .as_str()
+}
+
+/// Renders a sequence of block nodes, one markdown block each
+fn blocks_markdown(nodes: &[Value]) -> Vec<String> {
+    nodes
+        .iter()
+        .filter_map(block_markdown)
+        .filter(|block| !block.trim().is_empty())
+        .collect()
+}
+
+fn block_markdown(node: &Value) -> Option<String> {
+    if !is_block(node) {
+        let text = inline_markdown(std::slice::from_ref(node));
+        return (!text.trim().is_empty()).then(|| text.trim().to_string());
+    }
+
+    let kind = node_type(node);
+    let markdown = match kind {
+        _ if kind.starts_with("heading-") => {
+            let level = kind["heading-".len()..]
+                .parse::<usize>()
+                .unwrap_or(1)
+                .clamp(1, 6);
+            format!(
+                "{} {}",
+                "#".repeat(level),
+                inline_markdown(children(node)).trim()
+            )
+        }
+        "list-unordered" | "list-ordered" => list_markdown(node, kind == "list-ordered"),
+        "blockquote" | "hint" => blocks_markdown(children(node))
+            .join("\n\n")
+            .lines()
+            .map(|line| format!("> {}", line).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "code" => {
+            let syntax = data_str(node, &["syntax"]).unwrap_or_default();
+            let lines: Vec<String> = children(node).iter().map(plain_text).collect();
+            format!("```{}\n{}\n```", syntax, lines.join("\n"))
+        }
+        "divider" => "---".to_string(),
+        "image" => {
+            let src = data_str(node, &["src"]).or_else(|| data_str(node, &["ref", "url"]))?;
+            let alt = data_str(node, &["alt"])
+                .or_else(|| data_str(node, &["caption"]))
+                .unwrap_or_default();
+            format!("![{}]({})", alt, src)
+        }
+        _ if children(node).iter().any(is_block) => blocks_markdown(children(node)).join("\n\n"),
+        _ => inline_markdown(children(node)).trim().to_string(),
+    };
+    Some(markdown)
+}
+
+/// Renders a list, indenting the blocks of each item under its marker
+fn list_markdown(node: &Value, ordered: bool) -> String {
+    children(node)
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let marker = if ordered {
+                format!("{}. ", i + 1)
+            } else {
+                "- ".to_string()
+            };
+            let indent = " ".repeat(marker.len());
+            let content = if children(item).iter().any(is_block) {
+                blocks_markdown(children(item)).join("\n")
+            } else {
+                inline_markdown(children(item)).trim().to_string()
+            };
+            content
+                .lines()
+                .enumerate()
+                .map(|(j, line)| match j {
+                    0 => format!("{}{}", marker, line),
+                    _ if line.is_empty() => String::new(),
+                    _ => format!("{}{}", indent, line),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders inline nodes: text leaves with their marks and links
+fn inline_markdown(nodes: &[Value]) -> String {
+    let mut markdown = String::new();
+    for node in nodes {
+        match node.get("object").and_then(Value::as_str) {
+            Some("text") => {
+                for leaf in leaves(node) {
+                    markdown.push_str(&leaf_markdown(leaf));
+                }
+            }
+            _ if node_type(node) == "link" => {
+                let text = inline_markdown(children(node));
+                match data_str(node, &["ref", "url"]).or_else(|| data_str(node, &["url"])) {
+                    Some(url) => markdown.push_str(&format!("[{}]({})", text, url)),
+                    None => markdown.push_str(&text),
+                }
+            }
+            _ => markdown.push_str(&inline_markdown(children(node))),
+        }
+    }
+    markdown
+}
+
+/// Returns the leaves of a text node; a text node without leaves is its own leaf
+fn leaves(node: &Value) -> &[Value] {
+    match node.get("leaves").and_then(Value::as_array) {
+        Some(leaves) => leaves,
+        None => std::slice::from_ref(node),
+    }
+}
+
+/// Renders a text leaf, wrapping it in the markers of its marks
+fn leaf_markdown(leaf: &Value) -> String {
+    let text = leaf.get("text").and_then(Value::as_str).unwrap_or_default();
+    if text.trim().is_empty() {
+        return text.to_string();
+    }
+
+    let marks: Vec<&str> = leaf
+        .get("marks")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|mark| mark.get("type").and_then(Value::as_str))
+        .collect();
+    if marks.contains(&"code") {
+        return format!("`{}`", text);
+    }
+
+    // Markers go around the trimmed text, `** bold**` is not bold in markdown
+    let start = text.len() - text.trim_start().len();
+    let end = text.trim_end().len();
+    let mut inner = text[start..end].to_string();
+    for (mark, marker) in [("italic", "_"), ("bold", "**"), ("strikethrough", "~~")] {
+        if marks.contains(&mark) {
+            inner = format!("{}{}{}", marker, inner, marker);
+        }
+    }
+    format!("{}{}{}", &text[..start], inner, &text[end..])
+}
+
+/// Returns the text of all the leaves under a node, without formatting
+fn plain_text(node: &Value) -> String {
+    let mut text = String::new();
+    if node.get("object").and_then(Value::as_str) == Some("text") {
+        for leaf in leaves(node) {
+            text.push_str(leaf.get("text").and_then(Value::as_str).unwrap_or_default());
+        }
+    }
+    for child in children(node) {
+        text.push_str(&plain_text(child));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text(s: &str) -> Value {
+        json!({"object": "text", "leaves": [{"text": s}]})
+    }
+
+    fn block(kind: &str, nodes: Vec<Value>) -> Value {
+        json!({"object": "block", "type": kind, "nodes": nodes})
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let document = json!({"object": "document", "nodes": [
+            block("heading-2", vec![text("Setup")]),
+            {"object": "block", "type": "paragraph", "nodes": [
+                {"object": "text", "leaves": [
+                    {"text": "Run "},
+                    {"text": "cargo build", "marks": [{"type": "code"}]},
+                    {"text": " then see "}
+                ]},
+                {"object": "inline", "type": "link", "data": {"ref": {"url": "https://x.com/a"}},
+                 "nodes": [text("the guide")]}
+            ]},
+            block("list-ordered", vec![
+                block("list-item", vec![block("paragraph", vec![text("One")])]),
+                block("list-item", vec![
+                    block("paragraph", vec![text("Two")]),
+                    block("list-unordered", vec![
+                        block("list-item", vec![block("paragraph", vec![text("Nested")])])
+                    ])
+                ])
+            ]),
+            {"object": "block", "type": "code", "data": {"syntax": "bash"}, "nodes": [
+                block("code-line", vec![text("cd docs")]),
+                block("code-line", vec![text("make")])
+            ]},
+            block("hint", vec![block("paragraph", vec![text("Careful")])]),
+            block("divider", vec![]),
+            {"object": "block", "type": "image", "data": {"src": "/img.png", "caption": "Logo"}}
+        ]});
+
+        let page =
+            GitBookJsonPage::from_json(json!({"page": {"title": "Setup", "document": document}}));
+        assert_eq!(page.title.as_deref(), Some("Setup"));
+        assert_eq!(
+            page.to_markdown(),
+            "## Setup\n\n\
+             Run `cargo build` then see [the guide](https://x.com/a)\n\n\
+             1. One\n2. Two\n   - Nested\n\n\
+             ```bash\ncd docs\nmake\n```\n\n\
+             > Careful\n\n\
+             ---\n\n\
+             ![Logo](/img.png)"
+        );
+    }
+
+    #[test]
+    fn test_to_markdown_prefers_markdown_field() {
+        let page = GitBookJsonPage::from_json(json!({"markdown": "# Ready", "document": {}}));
+        assert_eq!(page.to_markdown(), "# Ready");
+        assert_eq!(GitBookJsonPage::from_json(json!({})).to_markdown(), "");
+    }
+}
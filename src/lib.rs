@@ -37,30 +37,149 @@
 //! }
 //! ```
 
+mod cache;
+mod chain;
+mod chunker;
+mod circuit_breaker;
+mod config;
 mod crawler;
+mod dedup;
+mod download;
+mod html_md;
+mod index;
+mod inflate;
+mod json_page;
+mod lint;
+mod manifest;
+mod page;
+mod relevance;
+mod render;
+mod search;
+mod site;
+pub mod sorters;
+mod source;
+mod stats;
+#[cfg(test)]
+mod test_server;
 mod utils;
+mod verify;
+
+pub use config::{
+    config_json_schema, parse_gitbook_yaml, ColorMode, CrawlConfig, CrawlOutputFormat,
+    DedupStrategy, DownloadConfig, GitBookConfig, HtmlToMdConfig, LinkSorter, OrderedListStyle,
+    OutputFormat, PreVisited, SanitizeConfig, TextConfig, UnicodeForm, COOKIE_ENV_VAR,
+    GITHUB_TOKEN_ENV_VAR, TOKEN_ENV_VAR,
+};
 
 pub use utils::{
-    download_page, markdown_to_text, save_markdown, save_text, txt_sanitize, url_to_filename,
+    combine_texts, decode_response, detect_content_type, detect_language, download_page,
+    download_page_checked, download_page_json, download_page_streaming, download_page_verified,
+    extract_code_blocks, markdown_to_asciidoc, markdown_to_text, markdown_to_text_with_config,
+    normalize_unicode, read_markdown_hash, rewrite_absolute_urls, rewrite_markdown_links,
+    save_asciidoc, save_code_blocks, save_markdown, save_markdown_compressed,
+    save_markdown_streaming, save_markdown_with_config, save_markdown_with_hash, save_text,
+    save_text_compressed, save_text_streaming, save_text_with_config, save_text_with_hash,
+    save_text_wrapped, slugify, slugify_with_separator, strip_navigation_boilerplate,
+    strip_navigation_boilerplate_with_patterns, txt_sanitize, txt_sanitize_with_config,
+    txt_sanitize_with_context, url_to_filename, url_to_filename_strip_scheme,
+    url_to_filename_windows_safe, url_to_filename_with_max, url_to_path, with_encoding_declaration,
+    wrap_text, write_text_colored, CodeBlock, ContentType, SanitizeContext,
+    DEFAULT_ENCODING_DECLARATION, DEFAULT_NAVIGATION_PATTERNS, DEFAULT_PAGE_SEPARATOR,
+};
+
+pub use cache::{CrawlCache, DEFAULT_CACHE_TTL};
+pub use chain::{build_page_chain, PageChain};
+pub use chunker::{split_into_chunks, CharacterTokenizer, Tokenize};
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerState};
+pub use json_page::GitBookJsonPage;
+pub use manifest::{Manifest, ManifestPage, ManifestPageStatus, DEFAULT_MANIFEST_PATH};
+
+pub use dedup::{content_hash, hamming_distance, simhash, Deduplicator};
+
+pub use download::{
+    download_pages, download_pages_stream, download_pages_with_events, DownloadEvent,
+    DownloadReport,
+};
+
+pub use html_md::html_to_markdown;
+
+pub use index::{DocumentIndex, IndexEntry, SearchResult};
+
+pub use lint::{lint_files, lint_markdown, LintIssue, Severity};
+
+pub use page::{
+    extract_links_from_markdown, extract_page_metadata, group_pages_by_path_segment, save_page,
+    GitBookPage, GitBookPageReader, GitBookPageWriter, PageMetadata,
 };
 
-pub use crawler::{crawl_and_save, extract_gitbook_links, is_gitbook};
+pub use relevance::{score_relevance, select_top_pages, PageScore};
+
+pub use render::{HtmlStripRenderer, LlmChunkRenderer, PulldownRenderer, TextRenderer};
+
+pub use site::GitBookSite;
+
+pub use source::Source;
+
+pub use search::{search_files, search_files_regex, SearchHit};
+
+pub use stats::{
+    compute_stats, compute_word_frequencies, english_stop_words, top_words, word_histogram,
+    PageStats, TextStats, WordCount, ENGLISH_STOP_WORDS,
+};
+
+pub use crawler::{
+    crawl_and_save, crawl_and_save_with_config, crawl_pages, crawl_sitemap_only,
+    detect_gitbook_confidence, detect_gitbook_confidence_with_config, extract_gitbook_links,
+    extract_gitbook_links_parallel, extract_gitbook_links_with_config,
+    extract_gitbook_links_with_info, extract_links_stream, extract_metadata, find_gitbooks,
+    find_gitbooks_with_config, has_noindex_meta, is_gitbook, is_gitbook_cached,
+    is_gitbook_url_heuristic, is_gitbook_with_config, json_links_path, merge_links_files,
+    normalize_url, normalize_url_with_base, parse_links_file, parse_links_json, ExtensionFilter,
+    LinkFilter, MergedLinks, PageInfo, PathPrefixFilter, SameDomainFilter, DEFAULT_MIN_CONFIDENCE,
+    DEFAULT_SKIP_EXTENSIONS,
+};
+
+pub use verify::{verify_pages, PageStatus, PageVerification};
+
+pub use whatlang::Lang;
 
 #[derive(Debug)]
 pub enum GitBookError {
     NetworkError(reqwest::Error),
+    HttpError(u16, String),
     IoError(std::io::Error),
     InvalidUrl(String),
     NotAGitBook(String),
+    InvalidGitBookYaml(String),
+    ParseError(String),
+    InvalidHeader(String),
+    UnsupportedEncoding(String, String),
+    ChecksumMismatch { expected: String, got: String },
+    InvalidConfig(String),
 }
 
 impl std::fmt::Display for GitBookError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             GitBookError::NetworkError(e) => write!(f, "Network error: {}", e),
+            GitBookError::HttpError(status, url) => write!(f, "HTTP error {} for {}", status, url),
             GitBookError::IoError(e) => write!(f, "I/O Error: {}", e),
             GitBookError::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
             GitBookError::NotAGitBook(url) => write!(f, "{} is not a GitBook", url),
+            GitBookError::InvalidGitBookYaml(e) => write!(f, "Invalid .gitbook.yaml: {}", e),
+            GitBookError::ParseError(e) => write!(f, "Parse error: {}", e),
+            GitBookError::InvalidHeader(e) => write!(f, "Invalid HTTP header: {}", e),
+            GitBookError::UnsupportedEncoding(encoding, url) => {
+                write!(f, "Unsupported content encoding {} for {}", encoding, url)
+            }
+            GitBookError::ChecksumMismatch { expected, got } => {
+                write!(
+                    f,
+                    "Checksum mismatch: expected SHA-256 {}, got {}",
+                    expected, got
+                )
+            }
+            GitBookError::InvalidConfig(e) => write!(f, "Invalid configuration: {}", e),
         }
     }
 }
@@ -0,0 +1,259 @@
+use crate::search::{collect_files, read_output_file};
+use crate::GitBookError;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The severity of a lint issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A suspicious construct that does not break the document
+    Warn,
+
+    /// A malformed document
+    Error,
+}
+
+/// A problem found in a markdown document
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    /// How serious the issue is
+    pub severity: Severity,
+
+    /// The line of the issue, starting at 1
+    pub line: usize,
+
+    /// A description of the issue
+    pub message: String,
+}
+
+/// Checks a markdown document for common issues
+///
+/// The following issues are reported:
+///
+/// * a code fence that is never closed (error)
+/// * an image with a relative URL pointing to a missing file (warning)
+/// * a heading with the same text as a previous heading (warning)
+///
+/// # Arguments
+///
+/// * `md` - The markdown content to check
+/// * `base_path` - The directory against which relative image URLs are resolved
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{lint_markdown, Severity};
+/// use std::path::Path;
+///
+/// let issues = lint_markdown("# Title\n\n```rust\nfn main() {}\n", Path::new("."));
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].severity, Severity::Error);
+/// assert_eq!(issues[0].line, 3);
+/// ```
+pub fn lint_markdown(md: &str, base_path: &Path) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    if let Some(line) = unclosed_fence(md) {
+        issues.push(LintIssue {
+            severity: Severity::Error,
+            line,
+            message: "Unclosed code fence".to_string(),
+        });
+    }
+
+    let line_of = |offset: usize| md[..offset].matches('\n').count() + 1;
+    let mut headings: HashMap<String, usize> = HashMap::new();
+    let mut heading: Option<(String, usize)> = None;
+
+    for (event, range) in Parser::new(md).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { .. }) => heading = Some((String::new(), range.start)),
+            Event::Text(t) | Event::Code(t) => {
+                if let Some((text, _)) = &mut heading {
+                    text.push_str(&t);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                let Some((text, start)) = heading.take() else {
+                    continue;
+                };
+                let line = line_of(start);
+                let key = text.trim().to_lowercase();
+                if key.is_empty() {
+                    continue;
+                }
+                match headings.get(&key) {
+                    Some(first) => issues.push(LintIssue {
+                        severity: Severity::Warn,
+                        line,
+                        message: format!(
+                            "Duplicate heading \"{}\" (first on line {})",
+                            text.trim(),
+                            first
+                        ),
+                    }),
+                    None => {
+                        headings.insert(key, line);
+                    }
+                }
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                if let Some(path) = local_image_path(&dest_url) {
+                    if !base_path.join(path).exists() {
+                        issues.push(LintIssue {
+                            severity: Severity::Warn,
+                            line: line_of(range.start),
+                            message: format!("Broken image reference: {}", dest_url),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+/// Lints all saved `.md` files of a directory
+///
+/// The directory is scanned recursively and files are visited in path order.
+/// Relative image URLs are resolved against the directory of each file, and
+/// Zstd-compressed `.md.zst` files are decompressed transparently. Only the
+/// files with at least one issue are returned.
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::lint_files;
+/// use std::path::Path;
+///
+/// for (path, issues) in lint_files(Path::new("data/md")).unwrap() {
+///     println!("{}: {} issue(s)", path.display(), issues.len());
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the directory or one of its files cannot be read
+pub fn lint_files(dir: &Path) -> Result<Vec<(PathBuf, Vec<LintIssue>)>, GitBookError> {
+    let mut files = Vec::new();
+    collect_files(dir, &[".md", ".md.zst"], &mut files)?;
+    files.sort();
+
+    let mut results = Vec::new();
+    for path in files {
+        let content = read_output_file(&path)?;
+        let base_path = path.parent().unwrap_or(dir);
+        let issues = lint_markdown(&content, base_path);
+        if !issues.is_empty() {
+            results.push((path, issues));
+        }
+    }
+
+    Ok(results)
+}
+
+/// Returns the line of the first code fence that is still open at the end of the document
+fn unclosed_fence(md: &str) -> Option<usize> {
+    let mut open: Option<(char, usize, usize)> = None;
+
+    for (i, line) in md.lines().enumerate() {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > 3 {
+            continue;
+        }
+        let trimmed = &line[indent..];
+        let Some(marker) = trimmed.chars().next().filter(|c| *c == '`' || *c == '~') else {
+            continue;
+        };
+        let len = trimmed.len() - trimmed.trim_start_matches(marker).len();
+        if len < 3 {
+            continue;
+        }
+
+        match open {
+            Some((open_marker, open_len, _)) => {
+                if marker == open_marker && len >= open_len && trimmed[len..].trim().is_empty() {
+                    open = None;
+                }
+            }
+            None => {
+                // A backtick fence cannot have backticks in its info string
+                if marker == '`' && trimmed[len..].contains('`') {
+                    continue;
+                }
+                open = Some((marker, len, i + 1));
+            }
+        }
+    }
+
+    open.map(|(_, _, line)| line)
+}
+
+/// Returns the local file targeted by an image URL, `None` for remote or inline images
+fn local_image_path(url: &str) -> Option<&str> {
+    if url.is_empty() || url.starts_with("//") || url.starts_with('#') || Url::parse(url).is_ok() {
+        return None;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    Some(path.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_lint_markdown_unclosed_fence() {
+        let base = Path::new(".");
+        assert!(lint_markdown("```rust\nfn main() {}\n```\n", base).is_empty());
+        assert!(lint_markdown("````\n```\n````\n", base).is_empty());
+
+        let issues = lint_markdown("Text\n\n~~~\ncode\n```\n", base);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].line, 3);
+    }
+
+    #[test]
+    fn test_lint_markdown_images() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("logo.png"), b"png").unwrap();
+
+        let md =
+            "![ok](logo.png)\n![remote](https://example.com/a.png)\n![missing](img/a.png?w=1)\n";
+        let issues = lint_markdown(md, dir.path());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warn);
+        assert_eq!(issues[0].line, 3);
+        assert!(issues[0].message.contains("img/a.png"));
+    }
+
+    #[test]
+    fn test_lint_markdown_duplicate_headings() {
+        let md = "# Install\n\n## Usage\n\n## `install`\n\n## Usage\n";
+        let issues = lint_markdown(md, Path::new("."));
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].line, 5);
+        assert_eq!(issues[1].line, 7);
+        assert!(issues[1].message.contains("first on line 3"));
+    }
+
+    #[test]
+    fn test_lint_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("guide")).unwrap();
+        fs::write(dir.path().join("a.md"), "# Fine\n").unwrap();
+        fs::write(dir.path().join("guide/b.md"), "```\nopen").unwrap();
+        fs::write(dir.path().join("c.txt"), "```\nopen").unwrap();
+
+        let results = lint_files(dir.path()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, dir.path().join("guide/b.md"));
+    }
+}
@@ -1,16 +1,42 @@
-use clap::{Parser, Subcommand};
-use futures::stream::FuturesUnordered;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures::stream::{self, FuturesUnordered};
 use futures::StreamExt;
-use gitbook2text::{crawl_and_save, extract_gitbook_links, is_gitbook};
-use gitbook2text::{download_page, markdown_to_text, save_markdown, save_text, txt_sanitize};
-use std::collections::HashSet;
+use gitbook2text::{
+    compute_stats, config_json_schema, english_stop_words, lint_files, search_files_regex,
+    word_histogram, write_text_colored, ColorMode, DocumentIndex, SearchHit, Severity,
+};
+use gitbook2text::{
+    crawl_and_save_with_config, crawl_pages, detect_gitbook_confidence_with_config, download_pages,
+    download_pages_stream, download_pages_with_events, extract_gitbook_links_with_config,
+    find_gitbooks_with_config, is_gitbook_url_heuristic, merge_links_files, parse_links_file,
+    parse_links_json, slugify, verify_pages, CrawlConfig, CrawlOutputFormat, DedupStrategy,
+    DownloadConfig, DownloadEvent, DownloadReport, GitBookError, Lang, Manifest, OutputFormat,
+    PageStatus, Source, UnicodeForm, COOKIE_ENV_VAR, DEFAULT_ENCODING_DECLARATION,
+    DEFAULT_MANIFEST_PATH, DEFAULT_MIN_CONFIDENCE, GITHUB_TOKEN_ENV_VAR, TOKEN_ENV_VAR,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+use tokio::sync::{mpsc, Semaphore};
+use url::Url;
+
+#[cfg(feature = "tui")]
+mod tui;
 
 #[derive(Parser)]
 #[command(name = "gitbook2text")]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Print the JSON Schema of the configuration file format and exit
+    #[arg(long)]
+    output_json_schema: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -23,28 +49,879 @@ enum Commands {
 
         #[arg(short, long, default_value = "links.txt")]
         output: String,
+
+        /// Write the bare URL list, without the comment header
+        #[arg(long)]
+        no_header: bool,
+
+        /// Write the links as a JSON array of {"url", "title", "depth"} objects, to the
+        /// output file with a .json extension
+        #[arg(long)]
+        output_json: bool,
+
+        /// Only print the number of pages found, without writing any file
+        #[arg(long)]
+        count: bool,
+
+        #[command(flatten)]
+        crawl: CrawlOptions,
+
+        #[command(flatten)]
+        http: HttpOptions,
+
+        #[command(flatten)]
+        manifest: ManifestOptions,
     },
 
     Download {
-        #[arg(short, long, default_value = "links.txt")]
-        input: String,
+        /// Page URLs to download, merged with the URLs of the input file
+        #[arg(value_name = "URLS")]
+        urls: Vec<String>,
+
+        /// File listing the URLs to download (defaults to links.txt when no URL is given)
+        #[arg(short, long)]
+        input: Option<String>,
+
+        /// Format of the input file; by default JSON for a .json file, text otherwise
+        #[arg(long, value_enum, value_name = "FORMAT")]
+        input_format: Option<InputFormatArg>,
+
+        /// Print the progress as one JSON object per line instead of the human-readable lines
+        #[arg(long)]
+        json_output: bool,
+
+        /// Read the URLs from stdin, one per line, downloading each page as soon as it
+        /// arrives, until the end of the input or an empty line; the progress goes to
+        /// stderr and the JSON lines of --json-output to stdout
+        #[arg(long, conflicts_with_all = ["urls", "input"])]
+        stdin_links: bool,
+
+        /// Show the download progress in a terminal UI instead of one line per page
+        #[cfg(feature = "tui")]
+        #[arg(long)]
+        tui: bool,
+
+        #[command(flatten)]
+        download: DownloadOptions,
+
+        #[command(flatten)]
+        http: HttpOptions,
+
+        #[command(flatten)]
+        manifest: ManifestOptions,
     },
 
     All {
+        /// GitBooks to crawl and download; with several URLs, each one is saved
+        /// under <OUTPUT_DIR>/<hostname>
+        #[arg(value_name = "URL", required = true)]
+        urls: Vec<String>,
+
+        /// Number of GitBooks crawled and downloaded at the same time
+        #[arg(long, value_name = "N", default_value_t = 2, value_parser = parse_non_zero)]
+        concurrency: usize,
+
+        /// Directory receiving the downloaded files
+        #[arg(long, default_value = "data")]
+        output_dir: PathBuf,
+
+        /// Print the progress as one JSON object per line instead of the human-readable lines
+        #[arg(long)]
+        json_output: bool,
+
+        /// Show the download progress in a terminal UI instead of one line per page
+        #[cfg(feature = "tui")]
+        #[arg(long)]
+        tui: bool,
+
+        #[command(flatten)]
+        crawl: CrawlOptions,
+
+        #[command(flatten)]
+        download: DownloadOptions,
+
+        #[command(flatten)]
+        http: HttpOptions,
+
+        #[command(flatten)]
+        manifest: ManifestOptions,
+    },
+
+    /// Poll a GitBook and download the pages added since the previous run
+    Watch {
         #[arg(value_name = "URL")]
         url: String,
+
+        /// Number of seconds between two crawls
+        #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+        interval: u64,
+
+        /// Shell command executed after each batch of new or changed pages
+        #[arg(long, value_name = "COMMAND")]
+        on_change: Option<String>,
+
+        #[command(flatten)]
+        crawl: CrawlOptions,
+
+        #[command(flatten)]
+        download: DownloadOptions,
+
+        #[command(flatten)]
+        http: HttpOptions,
+
+        #[command(flatten)]
+        manifest: ManifestOptions,
+    },
+
+    /// Download the pages of the manifest again and report the ones that changed or were deleted
+    Verify {
+        /// Directory containing the downloaded files and their manifest.json
+        #[arg(long, default_value = "data")]
+        output_dir: PathBuf,
+
+        /// Save the changed pages again and update the manifest
+        #[arg(long)]
+        update_changed: bool,
+
+        #[command(flatten)]
+        download: DownloadOptions,
+
+        #[command(flatten)]
+        http: HttpOptions,
+    },
+
+    /// Print the GitBook sites linked from a portal page, one per line
+    Discover {
+        #[arg(value_name = "URL")]
+        url: String,
+
+        /// Maximum number of links followed from the portal
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        depth: usize,
+
+        #[command(flatten)]
+        http: HttpOptions,
+    },
+
+    /// Search the downloaded text files
+    Search {
+        #[arg(value_name = "QUERY")]
+        query: String,
+
+        /// Directory containing the downloaded files
+        #[arg(long, default_value = "data")]
+        output_dir: String,
+
+        /// Number of lines of context to show around each match
+        #[arg(short = 'C', long, default_value_t = 0)]
+        context: usize,
+
+        /// Interpret the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+
+        /// Color the matches and the [INFO], [WARNING] and [DANGER] hint labels
+        #[arg(long, value_enum, value_name = "MODE", default_value_t = ColorArg::Auto)]
+        color: ColorArg,
+    },
+
+    /// Index the headings and words of the downloaded markdown files in index.json
+    Index {
+        /// Directory containing the downloaded files
+        #[arg(long, default_value = "data")]
+        output_dir: String,
+    },
+
+    /// Check the downloaded markdown files for common issues
+    Lint {
+        /// Directory containing the downloaded files
+        #[arg(long, default_value = "data")]
+        output_dir: String,
+    },
+
+    /// Combine several links files into one, without duplicates
+    Merge {
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<String>,
+
+        #[arg(short, long, default_value = "links.txt")]
+        output: String,
+    },
+
+    /// Show the most frequent words of the downloaded text files and write them to stats.json
+    Stats {
+        /// Directory containing the downloaded files
+        #[arg(long, default_value = "data")]
+        output_dir: String,
+
+        /// Number of words to show
+        #[arg(long, value_name = "N", default_value_t = 20, value_parser = parse_non_zero)]
+        top: usize,
     },
 }
 
+#[derive(Args)]
+struct CrawlOptions {
+    /// Minimum GitBook detection confidence required to crawl a site
+    #[arg(long, value_name = "0.0-1.0", default_value_t = DEFAULT_MIN_CONFIDENCE)]
+    min_confidence: f32,
+
+    /// Minimum delay in milliseconds between two requests to the same domain
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    politeness_delay: u64,
+
+    /// Number of pages of the same depth fetched at once
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = parse_non_zero)]
+    crawl_concurrency: usize,
+
+    /// List the pages from the sitemap.xml of the site only, without crawling its HTML
+    #[arg(long)]
+    sitemap_only: bool,
+
+    /// Pause a domain after this number of consecutive failed requests (0 disables)
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    breaker_threshold: u32,
+
+    /// Number of seconds a domain stays paused after too many failed requests
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    breaker_cooldown: u64,
+
+    /// Also follow links to this domain (repeatable)
+    #[arg(long, value_name = "DOMAIN")]
+    follow_external: Vec<String>,
+
+    /// Maximum number of pages listed from a domain, as DOMAIN=N; "*" sets the limit of the
+    /// other domains (repeatable)
+    #[arg(long = "domain-limit", value_name = "DOMAIN=N", value_parser = parse_domain_limit)]
+    domain_limits: Vec<(String, usize)>,
+
+    /// Follow links to every domain. This can crawl a large part of the web and requires --yes
+    #[arg(long, requires = "yes")]
+    follow_all_external: bool,
+
+    /// Confirm --follow-all-external
+    #[arg(long)]
+    yes: bool,
+
+    /// Maximum number of links followed from the base URL
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Keep the links in breadth-first discovery order instead of sorting them
+    #[arg(long)]
+    no_sort: bool,
+
+    /// CSS selector of the links to follow (e.g. "nav a.sidebar-link")
+    #[arg(long, value_name = "CSS", default_value = "a")]
+    selector: String,
+
+    /// Also list the links with a #fragment, for GitBooks routing their pages through it
+    #[arg(long)]
+    include_anchors: bool,
+}
+
+impl CrawlOptions {
+    fn apply(&self, config: &mut CrawlConfig) {
+        config.min_confidence = self.min_confidence;
+        config.politeness_delay = Duration::from_millis(self.politeness_delay);
+        config.concurrency = self.crawl_concurrency;
+        config.sitemap_only = self.sitemap_only;
+        config.circuit_breaker_threshold = self.breaker_threshold;
+        config.circuit_breaker_cooldown = Duration::from_secs(self.breaker_cooldown);
+        config.allowed_domains = self.follow_external.clone();
+        config.follow_all_external = self.follow_all_external;
+        config.page_limit_per_domain = self.domain_limits.iter().cloned().collect();
+        config.link_selector = self.selector.clone();
+        config.include_fragments = self.include_anchors;
+        config.max_depth = self.max_depth;
+        config.sort_output = !self.no_sort;
+    }
+}
+
+#[derive(Args)]
+struct HttpOptions {
+    /// Cookie sent with every request, as name=value (repeatable). The environment variable
+    /// takes several cookies separated by ";" and is ignored when --cookie is given
+    #[arg(
+        long = "cookie",
+        value_name = "NAME=VALUE",
+        value_parser = parse_cookie,
+        env = COOKIE_ENV_VAR,
+        hide_env_values = true,
+        value_delimiter = ';'
+    )]
+    cookies: Vec<(String, String)>,
+
+    /// Bearer token sent with every request in the Authorization header. Prefer the
+    /// environment variable, which stays out of the shell history and the process list
+    #[arg(long, value_name = "TOKEN", env = TOKEN_ENV_VAR, hide_env_values = true)]
+    token: Option<String>,
+
+    /// Token sent to the GitHub API and raw.githubusercontent.com when the URL is a
+    /// GitHub repository, instead of --token
+    #[arg(long, value_name = "TOKEN", env = GITHUB_TOKEN_ENV_VAR, hide_env_values = true)]
+    github_token: Option<String>,
+
+    /// Header sent with every request, as "Name: Value" (repeatable)
+    #[arg(long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Only use HTTP/1.1, for servers that misbehave with HTTP/2
+    #[arg(long)]
+    no_http2: bool,
+}
+
+impl HttpOptions {
+    fn apply(&self, config: &mut CrawlConfig) {
+        config.cookies = self.cookies.clone();
+        config.bearer_token = self.token.clone();
+        config.github_token = self.github_token.clone();
+        config.extra_headers = self.headers.clone();
+        config.prefer_http2 = !self.no_http2;
+    }
+}
+
+#[derive(Args)]
+struct ManifestOptions {
+    /// File recording the crawled and downloaded pages
+    #[arg(long, value_name = "PATH", default_value = DEFAULT_MANIFEST_PATH)]
+    manifest: PathBuf,
+
+    /// Do not write the manifest
+    #[arg(long, conflicts_with = "manifest")]
+    no_manifest: bool,
+}
+
+impl ManifestOptions {
+    fn apply(&self, config: &mut CrawlConfig) {
+        config.manifest_path = (!self.no_manifest).then(|| self.manifest.clone());
+    }
+}
+
+#[derive(Args)]
+struct DownloadOptions {
+    /// Mirror the URL path as a directory tree in the output directories
+    #[arg(long)]
+    preserve_path_structure: bool,
+
+    /// Drop the URL scheme from output file names
+    #[arg(long)]
+    strip_scheme: bool,
+
+    /// Shorten the output file names longer than this number of characters with a hash
+    #[arg(long, value_name = "CHARS", default_value_t = 200, value_parser = clap::value_parser!(u64).range(16..=255))]
+    max_filename_length: u64,
+
+    /// Name output files after the first `# Heading` of each page
+    #[arg(long)]
+    title_from_h1: bool,
+
+    /// Word separator of the file names with --title-from-h1
+    #[arg(
+        long,
+        value_name = "CHAR",
+        default_value_t = '-',
+        requires = "title_from_h1"
+    )]
+    slug_sep: char,
+
+    /// Save one file per chapter (data/api.md, data/guide.md) instead of one file per page
+    #[arg(long)]
+    chapter_split: bool,
+
+    /// Number of URL path segments forming a chapter with --chapter-split
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "chapter_split", value_parser = parse_non_zero)]
+    split_depth: usize,
+
+    /// Only save pages detected in this language (ISO 639-3 code, e.g. eng, fra)
+    #[arg(long, value_name = "ISO-CODE", value_parser = parse_language)]
+    language: Option<Lang>,
+
+    /// Minimum detection confidence for a page to be filtered out by --language
+    #[arg(long, value_name = "0.0-1.0", default_value_t = 0.9, value_parser = parse_fraction)]
+    language_confidence: f64,
+
+    /// Compress the saved files with Zstd (.md.zst and .txt.zst)
+    #[arg(long)]
+    compress: bool,
+
+    /// Zstd compression level used with --compress
+    #[arg(long, value_name = "1-22", default_value_t = 3, value_parser = clap::value_parser!(i32).range(1..=22))]
+    compress_level: i32,
+
+    /// Save the code blocks of each page as separate files in this directory
+    #[arg(long, value_name = "DIR")]
+    extract_code: Option<PathBuf>,
+
+    /// Maximum number of files written concurrently
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = parse_non_zero)]
+    parallel_writes: usize,
+
+    /// Maximum number of pages downloaded concurrently
+    #[arg(long, value_name = "N", default_value_t = 8, value_parser = parse_non_zero)]
+    parallel_downloads: usize,
+
+    /// Remove navigation boilerplate from the plain text output
+    #[arg(long)]
+    strip_nav: bool,
+
+    /// Keep the GitBook {% ... %} tags verbatim in the plain text output
+    #[arg(long)]
+    keep_gitbook_tags: bool,
+
+    /// Remove the dashes from the plain text output, as 0.3 did
+    #[arg(long, overrides_with = "no_sanitize_dashes")]
+    sanitize_dashes: bool,
+
+    /// Keep the dashes in the plain text output (default)
+    #[arg(long, overrides_with = "sanitize_dashes")]
+    no_sanitize_dashes: bool,
+
+    /// Join the lines of the plain text output into one, as 0.3 did
+    #[arg(long)]
+    join_lines: bool,
+
+    /// Format of the converted file saved next to the markdown of each page
+    #[arg(long, value_enum, default_value_t = FormatArg::Txt)]
+    format: FormatArg,
+
+    /// Skip pages whose content duplicates an already saved page
+    #[arg(long, value_enum, default_value_t = DedupArg::None)]
+    dedup: DedupArg,
+
+    /// Maximum SimHash Hamming distance between two near-identical pages
+    #[arg(long, value_name = "BITS", default_value_t = 3)]
+    dedup_threshold: u32,
+
+    /// Hard-wrap the plain text at this number of columns (0 disables wrapping)
+    #[arg(long, value_name = "COLUMNS", default_value_t = 0)]
+    wrap: usize,
+
+    /// Abort the download of a page after this number of seconds
+    #[arg(long, value_name = "SECONDS", value_parser = parse_non_zero)]
+    request_timeout: Option<usize>,
+
+    /// Write an encoding declaration as the first line of every text file
+    #[arg(
+        long,
+        alias = "output-encoding-declaration",
+        value_name = "LINE",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_ENCODING_DECLARATION
+    )]
+    encoding_declaration: Option<String>,
+
+    /// Stop downloading once the saved content reaches this number of bytes; the saved
+    /// content exceeds it by at most the page reaching it
+    #[arg(long, value_name = "BYTES")]
+    max_total_size: Option<u64>,
+
+    /// Also save the text of all pages in <OUTPUT_DIR>/combined.txt
+    #[arg(long)]
+    combine: bool,
+
+    /// Separator written before each page of combined.txt; {url}, {title}, {index} and
+    /// {total} are replaced, and \n, \t and \f are unescaped
+    #[arg(long, value_name = "TEMPLATE", requires = "combine", value_parser = parse_escapes)]
+    page_separator: Option<String>,
+
+    /// Replace the site origin in the absolute URLs of the saved markdown with this base
+    #[arg(long, value_name = "NEW_BASE")]
+    base_url_override: Option<String>,
+
+    /// Rewrite the links between the saved pages as relative paths to their markdown files
+    #[arg(long)]
+    rewrite_links: bool,
+
+    /// Do not write again the pages whose markdown has the hash recorded in the manifest
+    #[arg(long)]
+    skip_unchanged: bool,
+
+    /// Download and write again every page, even the ones whose markdown has the hash
+    /// recorded in the manifest
+    #[arg(long, conflicts_with = "skip_unchanged")]
+    force_download: bool,
+
+    /// Skip the pages whose HTML has a <meta name="robots" content="noindex">
+    #[arg(long)]
+    respect_noindex: bool,
+
+    /// Drop the images from the plain text output, alt text included
+    #[arg(long)]
+    strip_images: bool,
+
+    /// Keep the text of the HTML found in the markdown (<details>, <kbd>, ...) in the plain
+    /// text output, without its tags
+    #[arg(long)]
+    keep_inline_html: bool,
+
+    /// Split the text file of each page into numbered files of at most this many
+    /// tokens (about 4 characters each)
+    #[arg(long, value_name = "TOKENS", value_parser = parse_non_zero)]
+    chunk_size: Option<usize>,
+
+    /// Apply this Unicode normalization form to the plain text
+    #[arg(long, value_enum, value_name = "FORM")]
+    normalize_unicode: Option<UnicodeFormArg>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    /// Plain text in <OUTPUT_DIR>/txt
+    Txt,
+    /// AsciiDoc in <OUTPUT_DIR>/adoc
+    Adoc,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormatArg {
+    /// One URL per line, as written by `crawl`
+    Text,
+    /// The JSON array written by `crawl --output-json`
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorArg {
+    /// Color when stdout is a terminal
+    Auto,
+    /// Always color, e.g. when piping to `less -R`
+    Always,
+    /// Never color
+    Never,
+}
+
+impl From<ColorArg> for ColorMode {
+    fn from(arg: ColorArg) -> Self {
+        match arg {
+            ColorArg::Auto => ColorMode::Auto,
+            ColorArg::Always => ColorMode::Always,
+            ColorArg::Never => ColorMode::Never,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DedupArg {
+    None,
+    Exact,
+    Simhash,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum UnicodeFormArg {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+impl DownloadOptions {
+    fn apply(&self, config: &mut CrawlConfig, download_config: &mut DownloadConfig) {
+        config.preserve_path_structure = self.preserve_path_structure;
+        config.strip_scheme = self.strip_scheme;
+        config.max_filename_length = self.max_filename_length as usize;
+        config.split_by_depth = self.chapter_split.then_some(self.split_depth);
+        config.title_from_h1 = self.title_from_h1;
+        config.slug_separator = self.slug_sep;
+        download_config.language = self.language;
+        download_config.language_confidence = self.language_confidence;
+        download_config.compression_level = self.compress.then_some(self.compress_level);
+        download_config.extract_code_dir = self.extract_code.clone();
+        download_config.parallel_writes = self.parallel_writes;
+        download_config.parallel_downloads = self.parallel_downloads;
+        download_config.strip_nav = self.strip_nav;
+        download_config.sanitize.remove_gitbook_tags = !self.keep_gitbook_tags;
+        download_config.sanitize.remove_dashes = self.sanitize_dashes;
+        download_config.sanitize.join_lines = self.join_lines;
+        download_config.wrap = self.wrap;
+        download_config.per_request_timeout = self
+            .request_timeout
+            .map(|secs| Duration::from_secs(secs as u64));
+        download_config.encoding_declaration = self.encoding_declaration.clone();
+        download_config.max_total_bytes = self.max_total_size;
+        download_config.base_url_override = self.base_url_override.clone();
+        download_config.rewrite_links = self.rewrite_links;
+        download_config.skip_unchanged = self.skip_unchanged;
+        download_config.force_redownload = self.force_download;
+        download_config.respect_noindex = self.respect_noindex;
+        download_config.combine = self.combine;
+        if let Some(separator) = &self.page_separator {
+            download_config.page_separator = separator.clone();
+        }
+        download_config.format = match self.format {
+            FormatArg::Txt => OutputFormat::Text,
+            FormatArg::Adoc => OutputFormat::AsciiDoc,
+        };
+        download_config.text.strip_images = self.strip_images;
+        download_config.text.strip_inline_html = !self.keep_inline_html;
+        download_config.chunk_size = self.chunk_size;
+        download_config.text.unicode_normalization =
+            self.normalize_unicode.map(|form| match form {
+                UnicodeFormArg::Nfc => UnicodeForm::Nfc,
+                UnicodeFormArg::Nfd => UnicodeForm::Nfd,
+                UnicodeFormArg::Nfkc => UnicodeForm::Nfkc,
+                UnicodeFormArg::Nfkd => UnicodeForm::Nfkd,
+            });
+        download_config.dedup = match self.dedup {
+            DedupArg::None => DedupStrategy::None,
+            DedupArg::Exact => DedupStrategy::ExactHash,
+            DedupArg::Simhash => DedupStrategy::SimHash {
+                threshold: self.dedup_threshold,
+            },
+        };
+    }
+}
+
+fn parse_non_zero(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("the value must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(n),
+        Ok(_) => Err("the value must be between 0.0 and 1.0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Replaces the `\n`, `\t`, `\f` and `\\` escapes typed on the command line
+fn parse_escapes(value: &str) -> Result<String, String> {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('f') => unescaped.push('\x0c'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    Ok(unescaped)
+}
+
+fn parse_cookie(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected name=value, got {}", value)),
+    }
+}
+
+fn parse_domain_limit(value: &str) -> Result<(String, usize), String> {
+    match value.split_once('=') {
+        Some((domain, limit)) if !domain.trim().is_empty() => {
+            let limit = limit.trim().parse::<usize>().map_err(|e| e.to_string())?;
+            Ok((domain.trim().to_lowercase(), limit))
+        }
+        _ => Err(format!("expected domain=number, got {}", value)),
+    }
+}
+
+/// The options whose value is hidden in the links file header
+const SECRET_OPTIONS: [&str; 3] = ["--cookie", "--token", "--github-token"];
+
+/// Rebuilds the command line for the links file header, hiding cookie, token and header values
+fn command_line(args: impl IntoIterator<Item = String>) -> String {
+    let mut parts = Vec::new();
+    let mut hide_next = false;
+    for arg in args {
+        let secret = SECRET_OPTIONS.iter().find(|option| {
+            arg.strip_prefix(**option)
+                .is_some_and(|v| v.starts_with('='))
+        });
+        let part = if hide_next {
+            "<redacted>".to_string()
+        } else if let Some(option) = secret {
+            format!("{}=<redacted>", option)
+        } else if let Some(header) = arg.strip_prefix("--header=") {
+            let name = header.split(':').next().unwrap_or_default();
+            format!("'--header={}: <redacted>'", name.trim())
+        } else if arg.chars().any(char::is_whitespace) || arg.is_empty() {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        } else {
+            arg.clone()
+        };
+        hide_next = arg == "--header" || SECRET_OPTIONS.contains(&arg.as_str());
+        parts.push(part);
+    }
+    parts.join(" ")
+}
+
+fn parse_header(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected Name: Value, got {}", value)),
+    }
+}
+
+fn parse_language(code: &str) -> Result<Lang, String> {
+    Lang::from_code(code.to_lowercase())
+        .ok_or_else(|| format!("unknown ISO 639-3 language code: {}", code))
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
+    if cli.output_json_schema {
+        println!("{}", config_json_schema());
+        return;
+    }
+
+    let mut config = CrawlConfig {
+        manifest_path: Some(PathBuf::from(DEFAULT_MANIFEST_PATH)),
+        ..Default::default()
+    };
+    let mut download_config = DownloadConfig::default();
+
     let result = match cli.command {
-        Some(Commands::Crawl { url, output }) => crawl_command(&url, &output).await,
-        Some(Commands::Download { input }) => download_command(&input).await,
-        Some(Commands::All { url }) => all_command(&url).await,
-        None => download_command("links.txt").await,
+        Some(Commands::Crawl {
+            url,
+            output,
+            no_header,
+            output_json,
+            count,
+            crawl,
+            http,
+            manifest,
+        }) => {
+            manifest.apply(&mut config);
+            crawl.apply(&mut config);
+            http.apply(&mut config);
+            config.links_header = !no_header;
+            if output_json {
+                config.output_format = CrawlOutputFormat::Json;
+            }
+            config.count_only = count;
+            config.command_line = Some(command_line(std::env::args()));
+            crawl_command(&url, &output, &config).await
+        }
+        Some(Commands::Download {
+            urls,
+            input,
+            input_format,
+            json_output,
+            stdin_links,
+            download,
+            http,
+            manifest,
+            #[cfg(feature = "tui")]
+            tui,
+        }) => {
+            #[cfg(not(feature = "tui"))]
+            let tui = false;
+            manifest.apply(&mut config);
+            download.apply(&mut config, &mut download_config);
+            http.apply(&mut config);
+            config.quiet = json_output;
+            if stdin_links {
+                if tui {
+                    eprintln!("⚠️ --tui is ignored with --stdin-links");
+                }
+                stdin_links_command(&config, &download_config).await
+            } else {
+                let progress = ProgressMode::new(tui, json_output);
+                let input = input.map(|path| {
+                    let json = match input_format {
+                        Some(format) => matches!(format, InputFormatArg::Json),
+                        None => path.ends_with(".json"),
+                    };
+                    (path, json)
+                });
+                download_command(&urls, input, progress, &config, &download_config).await
+            }
+        }
+        Some(Commands::All {
+            urls,
+            concurrency,
+            output_dir,
+            json_output,
+            crawl,
+            download,
+            http,
+            manifest,
+            #[cfg(feature = "tui")]
+            tui,
+        }) => {
+            #[cfg(not(feature = "tui"))]
+            let tui = false;
+            manifest.apply(&mut config);
+            crawl.apply(&mut config);
+            download.apply(&mut config, &mut download_config);
+            http.apply(&mut config);
+            config.output_dir = output_dir;
+            config.quiet = json_output;
+            let progress = ProgressMode::new(tui, json_output);
+            all_command(&urls, concurrency, progress, &config, &download_config).await
+        }
+        Some(Commands::Watch {
+            url,
+            interval,
+            on_change,
+            crawl,
+            download,
+            http,
+            manifest,
+        }) => {
+            manifest.apply(&mut config);
+            crawl.apply(&mut config);
+            download.apply(&mut config, &mut download_config);
+            http.apply(&mut config);
+            let interval = Duration::from_secs(interval);
+            watch_command(
+                &url,
+                interval,
+                on_change.as_deref(),
+                &config,
+                &download_config,
+            )
+            .await
+        }
+        Some(Commands::Verify {
+            output_dir,
+            update_changed,
+            download,
+            http,
+        }) => {
+            download.apply(&mut config, &mut download_config);
+            http.apply(&mut config);
+            config.manifest_path = Some(output_dir.join("manifest.json"));
+            config.output_dir = output_dir;
+            verify_command(update_changed, &config, &download_config).await
+        }
+        Some(Commands::Discover { url, depth, http }) => {
+            http.apply(&mut config);
+            discover_command(&url, depth, &config).await
+        }
+        Some(Commands::Search {
+            query,
+            output_dir,
+            context,
+            regex,
+            color,
+        }) => search_command(&query, &output_dir, context, regex, color.into()),
+        Some(Commands::Index { output_dir }) => index_command(&output_dir),
+        Some(Commands::Lint { output_dir }) => lint_command(&output_dir),
+        Some(Commands::Merge { files, output }) => merge_command(&files, &output),
+        Some(Commands::Stats { output_dir, top }) => stats_command(&output_dir, top),
+        None => download_command(&[], None, ProgressMode::Lines, &config, &download_config).await,
     };
 
     if let Err(e) = result {
@@ -53,114 +930,1033 @@ async fn main() {
     }
 }
 
-async fn crawl_command(url: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn crawl_command(
+    url: &str,
+    output: &str,
+    config: &CrawlConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
     println!("🕷️ Crawl Mode");
-    crawl_and_save(url, output).await?;
+    let report = crawl_and_save_with_config(url, output, config).await?;
+    if config.count_only {
+        println!("{} pages found", report.pages_discovered);
+        return Ok(());
+    }
+    println!(
+        "⏱️ {} page(s) found in {:.1}s",
+        report.pages_discovered,
+        report.duration.as_secs_f64()
+    );
+    print_tripped_domains(&report.domains_tripped);
+    print_limited_domains(&report.domains_limited);
     Ok(())
 }
 
-async fn download_command(input: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📥 Download Mode");
+/// Compares the pages of the manifest with the live site, saving the changed ones
+/// again with `update_changed`
+async fn verify_command(
+    update_changed: bool,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest_path = config.manifest_path.as_deref().unwrap_or(Path::new(""));
+    let manifest = Manifest::load(manifest_path)
+        .map_err(|e| format!("cannot read {}: {}", manifest_path.display(), e))?;
+    println!("🔎 Verifying {} page(s)...", manifest.pages.len());
 
-    let content = fs::read_to_string(input).map_err(|e| {
-        format!(
-            "Can't read file {} : {}. You can use 'gitbook2text crawl <URL>' to generate the file.",
-            input, e
-        )
-    })?;
+    let results = verify_pages(&manifest, config, download_config).await?;
+    let mut changed = Vec::new();
+    let (mut unchanged, mut deleted, mut failed) = (0, 0, 0);
+    for result in &results {
+        match &result.status {
+            PageStatus::Unchanged => {
+                unchanged += 1;
+                println!("UNCHANGED {}", result.url);
+            }
+            PageStatus::Changed { diff } => {
+                changed.push(result.url.clone());
+                println!("CHANGED   {}", result.url);
+                if !diff.is_empty() {
+                    println!("{}", textwrap::indent(diff, "    "));
+                }
+            }
+            PageStatus::Deleted => {
+                deleted += 1;
+                println!("DELETED   {}", result.url);
+            }
+            PageStatus::Failed { error } => {
+                failed += 1;
+                eprintln!("⚠️ {}: {}", result.url, error);
+            }
+        }
+    }
+
+    println!("\n📊 Summary:");
+    println!("  ✅ Unchanged: {}", unchanged);
+    println!("  ✏️ Changed: {}", changed.len());
+    println!("  🗑️ Deleted: {}", deleted);
+    if failed > 0 {
+        println!("  ❌ Errors: {}", failed);
+    }
+
+    if update_changed && !changed.is_empty() {
+        println!("\n🔄 Updating the changed pages");
+        let report = download_pages(changed, config, download_config).await?;
+        print_report(&report, ProgressMode::Lines, None);
+    }
+    Ok(())
+}
+
+/// Prints the GitBook sites linked from `url`, with nothing else on stdout so the output can be piped
+async fn discover_command(
+    url: &str,
+    depth: usize,
+    config: &CrawlConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for gitbook in find_gitbooks_with_config(url, depth, config).await? {
+        println!("{}", gitbook);
+    }
+    Ok(())
+}
+
+/// How the progress of a download is shown
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProgressMode {
+    /// One human-readable line per page and a summary
+    Lines,
+    /// The terminal UI of `--tui`
+    Tui,
+    /// One JSON object per line, for scripts (`--json-output`)
+    Json,
+}
+
+impl ProgressMode {
+    fn new(tui: bool, json_output: bool) -> Self {
+        if json_output {
+            ProgressMode::Json
+        } else if tui {
+            ProgressMode::Tui
+        } else {
+            ProgressMode::Lines
+        }
+    }
 
-    let urls: HashSet<String> = content
-        .lines()
-        .map(|l| l.trim())
-        .filter(|l| !l.is_empty())
-        .map(|l| l.to_string())
+    /// Whether the human-readable messages are printed
+    fn is_human(self) -> bool {
+        self != ProgressMode::Json
+    }
+}
+
+/// Downloads the given URLs and those of the input file, `(path, is_json)`
+async fn download_command(
+    args_urls: &[String],
+    input: Option<(String, bool)>,
+    progress: ProgressMode,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if progress.is_human() {
+        println!("📥 Download Mode");
+    }
+
+    let mut urls: HashSet<String> = args_urls
+        .iter()
+        .map(|u| u.trim())
+        .filter(|u| !u.is_empty())
+        .map(|u| u.to_string())
         .collect();
 
-    if urls.is_empty() {
-        return Err(format!("No URL found in {}", input).into());
+    let input = match input {
+        Some(input) => Some(input),
+        None if urls.is_empty() => Some(("links.txt".to_string(), false)),
+        None => None,
+    };
+
+    if let Some((input, json)) = input {
+        let content = fs::read_to_string(&input).map_err(|e| {
+            format!(
+                "Can't read file {} : {}. You can use 'gitbook2text crawl <URL>' to generate the file.",
+                input, e
+            )
+        })?;
+
+        if json {
+            urls.extend(parse_links_json(&content).map_err(|e| format!("{}: {}", input, e))?);
+        } else {
+            urls.extend(parse_links_file(&content));
+        }
+
+        if urls.is_empty() {
+            return Err(format!("No URL found in {}", input).into());
+        }
     }
 
-    download_pages(urls).await
+    let report = run_download(
+        urls.into_iter().collect(),
+        progress,
+        None,
+        config,
+        download_config,
+    )
+    .await?;
+    print_report(&report, progress, None);
+    Ok(())
 }
 
-async fn all_command(url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🚀 Full Mode: Crawl + Download");
+/// Downloads the pages whose URLs are read from stdin, each one as soon as it arrives
+///
+/// The human-readable progress goes to stderr, leaving stdout to the JSON lines.
+async fn stdin_links_command(
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    eprintln!("📥 Download Mode: reading URLs from stdin, an empty line stops");
 
-    println!("\n📍 Step 1: Crawling");
-    println!("🔍 Vérification que {} est un GitBook...", url);
+    // The blocking reads of stdin run on their own thread
+    let (lines_tx, lines_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            let url = line.trim().to_string();
+            if url.is_empty() || lines_tx.send(url).is_err() {
+                break;
+            }
+        }
+    });
+    let urls = stream::unfold(lines_rx, |mut rx| async move {
+        rx.recv().await.map(|url| (url, rx))
+    });
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let download = download_pages_stream(urls, config, download_config, tx);
+    let printer = async {
+        while let Some(event) = rx.recv().await {
+            eprintln!("{}", event);
+            if let Some(line) = json_event(&event, None) {
+                line.print();
+            }
+        }
+    };
+    let (report, ()) = tokio::join!(download, printer);
+    print_report(&report?, ProgressMode::Json, None);
+    Ok(())
+}
 
-    if !is_gitbook(url).await? {
-        return Err(format!("⚠️ Checking that {} is a GitBook...", url).into());
+async fn all_command(
+    urls: &[String],
+    concurrency: usize,
+    progress: ProgressMode,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if progress.is_human() {
+        println!("🚀 Full Mode: Crawl + Download");
     }
 
-    println!("✅ GitBook detected !");
-    println!("🕷️ Extracting links...");
+    if let [url] = urls {
+        let report = crawl_and_download(url, progress, config, download_config).await?;
+        print_report(&report, progress, Some(url));
+        return Ok(());
+    }
+
+    let progress = match progress {
+        ProgressMode::Tui => {
+            eprintln!("⚠️ --tui is ignored with several URLs");
+            ProgressMode::Lines
+        }
+        progress => progress,
+    };
 
-    let links = extract_gitbook_links(url).await?;
+    let semaphore = Semaphore::new(concurrency);
+    let semaphore = &semaphore;
+    let mut futures = FuturesUnordered::new();
+    for (i, (url, config)) in urls.iter().zip(site_configs(urls, config)?).enumerate() {
+        futures.push(async move {
+            let _permit = semaphore.acquire().await;
+            let result = crawl_and_download(url, progress, &config, download_config).await;
+            (i, result.map_err(|e| e.to_string()))
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(result) = futures.next().await {
+        results.push(result);
+    }
+    results.sort_by_key(|(i, _)| *i);
+
+    let mut failed = 0;
+    for (i, result) in results {
+        if progress.is_human() {
+            println!("\n🌐 {}", urls[i]);
+        }
+        match result {
+            Ok(report) => print_report(&report, progress, Some(&urls[i])),
+            Err(e) => {
+                failed += 1;
+                eprintln!("❌ Error: {}", e);
+            }
+        }
+    }
 
-    println!("✅ {} page(s) found", links.len());
+    if failed > 0 {
+        return Err(format!("{} of {} GitBook(s) failed", failed, urls.len()).into());
+    }
+    Ok(())
+}
 
-    println!("\n📍 Step 2: Downloading");
-    download_pages(links.into_iter().collect()).await
+/// Returns the configuration saving the GitBook at `url` under `<output_dir>/<site>`,
+/// see [`site_dir_name`]
+///
+/// The manifest, if any, is written to the same directory.
+fn site_config(url: &str, config: &CrawlConfig) -> Result<CrawlConfig, GitBookError> {
+    let name = Url::parse(url)
+        .ok()
+        .and_then(|u| site_dir_name(&u))
+        .ok_or_else(|| GitBookError::InvalidUrl(url.to_string()))?;
+    let output_dir = config.output_dir.join(name);
+    let manifest_path = config
+        .manifest_path
+        .as_ref()
+        .map(|path| output_dir.join(path.file_name().unwrap_or("manifest.json".as_ref())));
+    Ok(CrawlConfig {
+        output_dir,
+        manifest_path,
+        ..config.clone()
+    })
 }
 
-async fn download_pages(mut urls: HashSet<String>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📥 Downloading {} page(s)...", urls.len());
+/// Returns the directory name of the GitBook at `url`: its host, followed by its port
+/// and the slugs of its path segments if any, e.g. `docs.example.com_v2` for
+/// `https://docs.example.com/v2/`
+fn site_dir_name(url: &Url) -> Option<String> {
+    let mut name = url.host_str()?.to_string();
+    if let Some(port) = url.port() {
+        name.push_str(&format!("_{}", port));
+    }
+    for segment in url.path_segments().into_iter().flatten() {
+        let slug = slugify(segment);
+        if !slug.is_empty() {
+            name.push('_');
+            name.push_str(&slug);
+        }
+    }
+    Some(name)
+}
 
-    let new_urls_with_md = urls
-        .drain()
-        .map(|mut u| {
-            if !u.ends_with(".md") {
-                u.push_str(".md");
+/// Returns the configuration of each GitBook with [`site_config`], failing if two of
+/// them would be saved in the same directory
+fn site_configs(urls: &[String], config: &CrawlConfig) -> Result<Vec<CrawlConfig>, GitBookError> {
+    let mut dirs: HashMap<PathBuf, &String> = HashMap::new();
+    urls.iter()
+        .map(|url| {
+            let site = site_config(url, config)?;
+            if let Some(other) = dirs.insert(site.output_dir.clone(), url) {
+                return Err(GitBookError::InvalidUrl(format!(
+                    "{} and {} would both be saved in {}",
+                    other,
+                    url,
+                    site.output_dir.display()
+                )));
             }
-            u
+            Ok(site)
         })
-        .collect();
+        .collect()
+}
 
-    urls = new_urls_with_md;
+/// Checks that `url` is a GitBook site and returns how it was detected
+///
+/// With `skip_http_detection`, a URL matching [`is_gitbook_url_heuristic`] is
+/// accepted without any request; otherwise its confidence must reach `min_confidence`.
+async fn check_gitbook(
+    url: &str,
+    config: &CrawlConfig,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if config.skip_http_detection && is_gitbook_url_heuristic(url) {
+        return Ok("from its URL".to_string());
+    }
+    let confidence = detect_gitbook_confidence_with_config(url, config).await?;
+    if confidence < config.min_confidence {
+        return Err(format!(
+            "⚠️ {} does not seem to be a GitBook site (confidence {:.2})",
+            url, confidence
+        )
+        .into());
+    }
+    Ok(format!("confidence {:.2}", confidence))
+}
 
-    fs::create_dir_all("data/md")?;
-    fs::create_dir_all("data/txt")?;
+/// Checks that `url` is a GitBook, then crawls it and downloads all of its pages
+///
+/// A GitHub repository is not crawled: its `.md` files are listed with the GitHub API
+/// and downloaded from raw.githubusercontent.com.
+async fn crawl_and_download(
+    url: &str,
+    progress: ProgressMode,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<DownloadReport, Box<dyn std::error::Error>> {
+    let source = Source::from_url(url);
+    if let Source::GitHub { owner, repo, .. } = &source {
+        if progress.is_human() {
+            println!("\n📍 Step 1: Listing");
+            println!("🐙 Lecture du dépôt GitHub {}/{}...", owner, repo);
+        }
+        let links = source.markdown_urls(config).await?;
+        if progress.is_human() {
+            println!("✅ {} markdown file(s) found", links.len());
+            println!("\n📍 Step 2: Downloading");
+        }
+        let github_config = source.request_config(config);
+        let mut report = run_download(
+            links.clone(),
+            progress,
+            Some(url),
+            &github_config,
+            download_config,
+        )
+        .await?;
+        report.pages_discovered = links.len();
+        return Ok(report);
+    }
 
-    let mut futures = FuturesUnordered::new();
+    if progress.is_human() {
+        println!("\n📍 Step 1: Crawling");
+        println!("🔍 Vérification que {} est un GitBook...", url);
+    }
 
-    for url in urls {
-        let url_clone = url.clone();
-        futures.push(async move {
-            let md_content = download_page(&url_clone).await?;
-            save_markdown(&url_clone, &md_content).await?;
+    let detection = check_gitbook(url, config).await?;
 
-            let text_content = markdown_to_text(&md_content);
-            let text_cleaned = txt_sanitize(&text_content);
-            save_text(&url_clone, &text_cleaned).await?;
+    if progress.is_human() {
+        println!("✅ GitBook detected ({}) !", detection);
+        println!("🕷️ Extracting links...");
+    }
 
-            Ok::<String, Box<dyn std::error::Error>>(url_clone)
-        });
+    let (pages, domains_tripped, domains_limited) = crawl_pages(url, config).await?;
+    let links: Vec<String> = pages.into_iter().map(|page| page.url).collect();
+
+    if progress.is_human() {
+        println!("✅ {} page(s) found", links.len());
+        println!("\n📍 Step 2: Downloading");
     }
+    let mut report =
+        run_download(links.clone(), progress, Some(url), config, download_config).await?;
+    report.pages_discovered = links.len();
+    report.domains_tripped = domains_tripped;
+    report.domains_limited = domains_limited;
+    Ok(report)
+}
 
-    let mut success_count = 0;
-    let mut error_count = 0;
+/// Downloads the pages, showing the progress as `progress` requests; the JSON lines
+/// name the GitBook `site` if set
+async fn run_download(
+    urls: Vec<String>,
+    progress: ProgressMode,
+    site: Option<&str>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<DownloadReport, Box<dyn std::error::Error>> {
+    #[cfg(feature = "tui")]
+    if progress == ProgressMode::Tui {
+        return tui::download_pages(urls, config, download_config).await;
+    }
+    if progress == ProgressMode::Json {
+        return Ok(json_download(urls, site, config, download_config).await?);
+    }
+    Ok(download_pages(urls, config, download_config).await?)
+}
 
-    while let Some(result) = futures.next().await {
-        match result {
-            Ok(url) => {
-                success_count += 1;
-                println!("✅ Page saved: {}", url);
+/// Downloads the pages, printing each progress event as a line of JSON
+async fn json_download(
+    urls: Vec<String>,
+    site: Option<&str>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<DownloadReport, GitBookError> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let download = download_pages_with_events(urls, config, download_config, tx);
+    let printer = async {
+        while let Some(event) = rx.recv().await {
+            if let Some(line) = json_event(&event, site) {
+                line.print();
             }
-            Err(e) => {
-                error_count += 1;
-                eprintln!("❌ Error: {:?}", e);
+        }
+    };
+    let (report, ()) = tokio::join!(download, printer);
+    report
+}
+
+/// A line of `--json-output`: an event, followed in `all` by the URL of its GitBook
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    #[serde(flatten)]
+    event: JsonEvent<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    site: Option<&'a str>,
+}
+
+/// An event of `--json-output`, serialized with its `event` name first
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonEvent<'a> {
+    PageStart {
+        url: &'a str,
+        index: usize,
+        total: usize,
+    },
+    PageDone {
+        url: &'a str,
+        bytes: u64,
+    },
+    ChapterDone {
+        path: &'a Path,
+        pages: usize,
+        bytes: u64,
+    },
+    PageSkipped {
+        url: &'a str,
+        reason: &'a str,
+    },
+    LimitReached,
+    PageError {
+        url: &'a str,
+        error: &'a str,
+    },
+    Summary {
+        success: usize,
+        errors: usize,
+        skipped: usize,
+    },
+}
+
+impl JsonLine<'_> {
+    fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("❌ Error: {}", e),
+        }
+    }
+}
+
+/// The JSON line of a download event of the GitBook `site`, `None` for the events
+/// without one
+fn json_event<'a>(event: &'a DownloadEvent, site: Option<&'a str>) -> Option<JsonLine<'a>> {
+    let event = match event {
+        DownloadEvent::Started { .. } => return None,
+        DownloadEvent::PageStarted { url, index, total } => JsonEvent::PageStart {
+            url,
+            index: *index,
+            total: *total,
+        },
+        DownloadEvent::Saved { url, bytes } => JsonEvent::PageDone { url, bytes: *bytes },
+        DownloadEvent::ChapterSaved { path, pages, bytes } => JsonEvent::ChapterDone {
+            path,
+            pages: *pages,
+            bytes: *bytes,
+        },
+        DownloadEvent::Skipped { url, reason } => JsonEvent::PageSkipped { url, reason },
+        DownloadEvent::LimitReached => JsonEvent::LimitReached,
+        DownloadEvent::DownloadFailed { url, error } => JsonEvent::PageError { url, error },
+        DownloadEvent::SaveFailed { name, error } => JsonEvent::PageError { url: name, error },
+    };
+    Some(JsonLine { event, site })
+}
+
+async fn watch_command(
+    url: &str,
+    interval: Duration,
+    on_change: Option<&str>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "👀 Watch Mode: every {}s (Ctrl-C to stop)",
+        interval.as_secs()
+    );
+
+    check_gitbook(url, config).await?;
+
+    let Some(manifest_path) = config.manifest_path.as_deref() else {
+        return Err("⚠️ watch compares the pages with the manifest, remove --no-manifest".into());
+    };
+
+    loop {
+        let iteration = watch_iteration(url, manifest_path, on_change, config, download_config);
+
+        tokio::select! {
+            result = iteration => if let Err(e) = result {
+                eprintln!("❌ Error: {}", e);
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    println!("\n👋 Watch stopped");
+    Ok(())
+}
+
+/// Crawls the GitBook once and downloads the new and changed pages
+///
+/// The pages are compared with the manifest left by the previous runs: the pages
+/// whose markdown still has the recorded hash are not written again, and the pages
+/// no longer linked are dropped from the manifest.
+async fn watch_iteration(
+    url: &str,
+    manifest_path: &Path,
+    on_change: Option<&str>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let site = url.trim_end_matches('/');
+    let previous: HashMap<String, Option<String>> = Manifest::load(manifest_path)
+        .map(|manifest| manifest.pages)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|page| page.url.starts_with(site))
+        .map(|page| (page.url, page.content_hash))
+        .collect();
+
+    let mut current = extract_gitbook_links_with_config(url, config).await?;
+    current.sort();
+    current.dedup();
+    let linked: HashSet<&String> = current.iter().collect();
+
+    let download_config = DownloadConfig {
+        skip_unchanged: true,
+        ..download_config.clone()
+    };
+    let report = download_pages(current.clone(), config, &download_config).await?;
+
+    let mut manifest = Manifest::load(manifest_path).unwrap_or_else(|_| Manifest::new(site));
+    let mut removed: Vec<String> = previous
+        .keys()
+        .filter(|page| !linked.contains(page))
+        .cloned()
+        .collect();
+    removed.sort();
+    manifest.pages.retain(|page| !removed.contains(&page.url));
+    manifest.save(manifest_path)?;
+
+    let mut changes = 0;
+    for link in &current {
+        let hash = manifest
+            .page(link)
+            .and_then(|page| page.content_hash.as_ref());
+        match previous.get(link) {
+            Some(Some(old)) if hash.is_some_and(|hash| hash != old) => {
+                println!("✏️ Changed: {}", link);
+                changes += 1;
+            }
+            None | Some(None) if hash.is_some() => {
+                println!("➕ New: {}", link);
+                changes += 1;
             }
+            _ => {}
         }
     }
+    for link in &removed {
+        println!("➖ Removed: {}", link);
+    }
 
-    println!("\n📊 Summary:");
-    println!("  ✅ Success: {}", success_count);
-    println!("  ❌ Errors: {}", error_count);
+    if changes == 0 && report.pages_failed.is_empty() {
+        println!("💤 No change");
+        return Ok(());
+    }
+    print_report(&report, ProgressMode::Lines, None);
+    if changes == 0 {
+        return Ok(());
+    }
 
-    if error_count > 0 {
-        println!("\n⚠️  {} page(s) could not be downloaded", error_count);
+    if let Some(command) = on_change {
+        println!("⚙️ Running: {}", command);
+        let status = shell_command(command).status().await?;
+        if !status.success() {
+            eprintln!("⚠️ The on-change command exited with {}", status);
+        }
     }
 
     Ok(())
 }
+
+fn shell_command(command: &str) -> tokio::process::Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+fn search_command(
+    query: &str,
+    output_dir: &str,
+    context: usize,
+    regex: bool,
+    color: ColorMode,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let re = if regex {
+        Regex::new(query)?
+    } else {
+        Regex::new(&regex::escape(query))?
+    };
+
+    let hits = search_files_regex(Path::new(output_dir), &re, context)?;
+
+    let mut stdout = StandardStream::stdout(color.color_choice());
+
+    for (i, hit) in hits.iter().enumerate() {
+        if context > 0 && i > 0 {
+            writeln!(stdout, "--")?;
+        }
+        print_search_hit(&mut stdout, hit, &re)?;
+    }
+
+    if hits.is_empty() {
+        eprintln!("No match found for {}", query);
+    }
+
+    Ok(())
+}
+
+fn index_command(output_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🗂️ Index Mode");
+
+    let index = DocumentIndex::build(Path::new(output_dir))?;
+    let index_path = Path::new(output_dir).join("index.json");
+    index.save(&index_path)?;
+
+    println!(
+        "💾 {} word(s) indexed in {}",
+        index.terms.len(),
+        index_path.display()
+    );
+    Ok(())
+}
+
+fn lint_command(output_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🔎 Lint Mode");
+
+    let results = lint_files(Path::new(output_dir))?;
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for (path, issues) in &results {
+        for issue in issues {
+            let label = match issue.severity {
+                Severity::Error => {
+                    errors += 1;
+                    "error"
+                }
+                Severity::Warn => {
+                    warnings += 1;
+                    "warning"
+                }
+            };
+            println!(
+                "{}:{}: {}: {}",
+                path.display(),
+                issue.line,
+                label,
+                issue.message
+            );
+        }
+    }
+
+    println!(
+        "\n📊 {} error(s), {} warning(s) in {} file(s)",
+        errors,
+        warnings,
+        results.len()
+    );
+
+    if errors > 0 {
+        return Err(format!("{} lint error(s) found", errors).into());
+    }
+    Ok(())
+}
+
+fn merge_command(files: &[String], output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = files
+        .iter()
+        .map(|file| fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let contents: Vec<&str> = contents.iter().map(String::as_str).collect();
+
+    let merged = merge_links_files(&contents);
+    fs::write(output, merged.links.join("\n"))?;
+
+    println!(
+        "🔗 Merged {} files, {} URLs found, {} duplicates removed",
+        files.len(),
+        merged.urls_found,
+        merged.duplicates
+    );
+    println!("💾 {} link(s) saved in {}", merged.links.len(), output);
+    Ok(())
+}
+
+fn stats_command(output_dir: &str, top: usize) -> Result<(), Box<dyn std::error::Error>> {
+    println!("📊 Stats Mode");
+
+    let stats = compute_stats(Path::new(output_dir), top, &english_stop_words())?;
+    let json_path = Path::new(output_dir).join("stats.json");
+    fs::write(&json_path, serde_json::to_string_pretty(&stats)?)?;
+
+    println!(
+        "📄 {} word(s) in {} page(s)\n",
+        stats.word_count,
+        stats.pages.len()
+    );
+    println!("{}", word_histogram(&stats.top_words, 40));
+    println!("\n💾 Statistics saved in {}", json_path.display());
+    Ok(())
+}
+
+fn print_search_hit(
+    stdout: &mut StandardStream,
+    hit: &SearchHit,
+    re: &Regex,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = hit.path.display().to_string();
+    let first_line = hit.line_number - hit.context_before.len();
+
+    for (i, line) in hit.context_before.iter().enumerate() {
+        print_search_prefix(stdout, &path, first_line + i, '-')?;
+        write_text_colored(stdout, line)?;
+        writeln!(stdout)?;
+    }
+
+    print_search_prefix(stdout, &path, hit.line_number, ':')?;
+    let mut last = 0;
+    for m in re.find_iter(&hit.line) {
+        write_text_colored(stdout, &hit.line[last..m.start()])?;
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+        write!(stdout, "{}", m.as_str())?;
+        stdout.reset()?;
+        last = m.end();
+    }
+    write_text_colored(stdout, &hit.line[last..])?;
+    writeln!(stdout)?;
+
+    for (i, line) in hit.context_after.iter().enumerate() {
+        print_search_prefix(stdout, &path, hit.line_number + 1 + i, '-')?;
+        write_text_colored(stdout, line)?;
+        writeln!(stdout)?;
+    }
+
+    Ok(())
+}
+
+fn print_search_prefix(
+    stdout: &mut StandardStream,
+    path: &str,
+    line_number: usize,
+    sep: char,
+) -> Result<(), Box<dyn std::error::Error>> {
+    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+    write!(stdout, "{}", path)?;
+    stdout.reset()?;
+    write!(stdout, "{}", sep)?;
+    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+    write!(stdout, "{}", line_number)?;
+    stdout.reset()?;
+    write!(stdout, "{}", sep)?;
+    Ok(())
+}
+
+fn print_report(report: &DownloadReport, progress: ProgressMode, site: Option<&str>) {
+    if !progress.is_human() {
+        let event = JsonEvent::Summary {
+            success: report.pages_downloaded,
+            errors: report.pages_failed.len(),
+            skipped: report.pages_skipped,
+        };
+        JsonLine { event, site }.print();
+        return;
+    }
+
+    println!("\n📊 Summary:");
+    println!("  ✅ Success: {}", report.pages_downloaded);
+    if report.pages_skipped > 0 {
+        println!("  ⏭️ Skipped: {}", report.pages_skipped);
+    }
+    if report.pages_noindex > 0 {
+        println!("  🚫 Noindex: {}", report.pages_noindex);
+    }
+    if report.pages_over_limit > 0 {
+        println!("  🛑 Over size limit: {}", report.pages_over_limit);
+    }
+    if report.pages_forced > 0 {
+        println!("  🔁 Forced re-download: {}", report.pages_forced);
+    }
+    println!("  ❌ Errors: {}", report.pages_failed.len());
+    println!(
+        "  💾 {} byte(s) in {:.1}s",
+        report.total_bytes,
+        report.duration.as_secs_f64()
+    );
+
+    if !report.pages_failed.is_empty() {
+        println!(
+            "\n⚠️  {} page(s) could not be downloaded:",
+            report.pages_failed.len()
+        );
+        for (url, error) in &report.pages_failed {
+            println!("  - {}: {}", url, error);
+        }
+    }
+    print_tripped_domains(&report.domains_tripped);
+    print_limited_domains(&report.domains_limited);
+}
+
+/// Warns about the domains whose pages were cut by `--domain-limit`
+fn print_limited_domains(domains: &[String]) {
+    if !domains.is_empty() {
+        println!();
+    }
+    for domain in domains {
+        println!("⚠️ Domain limit reached for {}", domain);
+    }
+}
+
+/// Lists the domains paused by the circuit breaker during the crawl
+fn print_tripped_domains(domains: &[String]) {
+    if !domains.is_empty() {
+        println!(
+            "\n⏸️  Circuit breaker tripped for {} domain(s):",
+            domains.len()
+        );
+        for domain in domains {
+            println!("  - {}", domain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site_configs() {
+        let config = CrawlConfig {
+            output_dir: PathBuf::from("data"),
+            ..Default::default()
+        };
+        let urls = [
+            "https://docs.example.com",
+            "https://docs.example.com/v2/",
+            "https://docs.example.com/API_Guide",
+            "http://localhost:3000/",
+        ]
+        .map(String::from);
+        let dirs: Vec<PathBuf> = site_configs(&urls, &config)
+            .unwrap()
+            .into_iter()
+            .map(|site| site.output_dir)
+            .collect();
+        assert_eq!(
+            dirs,
+            [
+                "data/docs.example.com",
+                "data/docs.example.com_v2",
+                "data/docs.example.com_api-guide",
+                "data/localhost_3000",
+            ]
+            .map(PathBuf::from)
+        );
+
+        let urls = [
+            "https://docs.example.com/v2",
+            "https://docs.example.com/v2/",
+        ]
+        .map(String::from);
+        assert!(site_configs(&urls, &config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_gitbook() {
+        // Nothing listens on this port, so only the heuristic can succeed
+        let url = "http://docs.gitbook.localhost:9";
+        let config = CrawlConfig::default();
+        assert_eq!(check_gitbook(url, &config).await.unwrap(), "from its URL");
+
+        let config = CrawlConfig {
+            skip_http_detection: false,
+            ..config
+        };
+        assert!(check_gitbook(url, &config).await.is_err());
+    }
+
+    #[test]
+    fn test_command_line() {
+        let line = |args: &[&str]| command_line(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            line(&[
+                "gitbook2text",
+                "crawl",
+                "--github-token",
+                "ghp_secret",
+                "https://x.com"
+            ]),
+            "gitbook2text crawl --github-token <redacted> https://x.com"
+        );
+        assert_eq!(
+            line(&["--github-token=ghp_secret", "--token=abc", "--cookie=a=b"]),
+            "--github-token=<redacted> --token=<redacted> --cookie=<redacted>"
+        );
+        assert_eq!(
+            line(&["--header", "X-Key: abc", "--header=X-Key: abc", "a b"]),
+            "--header <redacted> '--header=X-Key: <redacted>' 'a b'"
+        );
+        assert_eq!(line(&["--tokens=5"]), "--tokens=5");
+    }
+
+    #[test]
+    fn test_parse_fraction() {
+        assert_eq!(parse_fraction("0.75"), Ok(0.75));
+        assert_eq!(parse_fraction("1"), Ok(1.0));
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("-0.1").is_err());
+        assert!(parse_fraction("NaN").is_err());
+        assert!(parse_fraction("high").is_err());
+    }
+
+    #[test]
+    fn test_json_event() {
+        let json = |event: &DownloadEvent, site| {
+            json_event(event, site).map(|line| serde_json::to_string(&line).unwrap())
+        };
+
+        let started = DownloadEvent::PageStarted {
+            url: "https://x.com/a.md".to_string(),
+            index: 1,
+            total: 2,
+        };
+        assert_eq!(
+            json(&started, None).unwrap(),
+            r#"{"event":"page_start","url":"https://x.com/a.md","index":1,"total":2}"#
+        );
+
+        let failed = DownloadEvent::SaveFailed {
+            name: "a.md".to_string(),
+            error: "disk full".to_string(),
+        };
+        assert_eq!(
+            json(&failed, Some("https://x.com")).unwrap(),
+            r#"{"event":"page_error","url":"a.md","error":"disk full","site":"https://x.com"}"#
+        );
+        assert_eq!(
+            json(&DownloadEvent::LimitReached, None).unwrap(),
+            r#"{"event":"limit_reached"}"#
+        );
+        assert_eq!(json(&DownloadEvent::Started { total: 2 }, None), None);
+    }
+}
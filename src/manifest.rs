@@ -0,0 +1,266 @@
+use crate::GitBookError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Default location of the manifest written by the CLI
+pub const DEFAULT_MANIFEST_PATH: &str = "data/manifest.json";
+
+/// The persistent state of a crawl: the pages found and what was saved for each
+///
+/// Dates are stored as RFC 3339 strings in UTC.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{Manifest, ManifestPage};
+///
+/// let mut manifest = Manifest::new("https://docs.example.com");
+/// manifest.upsert(ManifestPage::new("https://docs.example.com/intro", 1));
+/// assert_eq!(manifest.page("https://docs.example.com/intro").unwrap().depth, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Manifest {
+    /// The gitbook2text version that wrote the manifest
+    pub version: String,
+
+    /// The base URL of the crawled GitBook
+    pub source_url: String,
+
+    /// When the site was last crawled
+    #[serde(with = "rfc3339")]
+    pub crawled_at: SystemTime,
+
+    /// The pages of the site, sorted by URL
+    pub pages: Vec<ManifestPage>,
+}
+
+/// A page recorded in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestPage {
+    /// The URL of the page, without the `.md` suffix
+    pub url: String,
+
+    /// The title of the page, from its HTML once crawled and from its markdown once downloaded
+    pub title: Option<String>,
+
+    /// The description of the page, from the same sources as the title
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// The canonical URL declared by the HTML of the page
+    #[serde(default)]
+    pub canonical_url: Option<String>,
+
+    /// The number of links followed from the base URL to discover the page
+    pub depth: usize,
+
+    /// The SHA-256 of the downloaded markdown, in hexadecimal
+    pub content_hash: Option<String>,
+
+    /// When the page was last saved
+    #[serde(with = "rfc3339_option")]
+    pub downloaded_at: Option<SystemTime>,
+
+    /// The markdown file the page was saved to
+    pub file_path: Option<PathBuf>,
+
+    /// Why the page was not saved, `None` for the crawled and saved pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ManifestPageStatus>,
+}
+
+/// Why a page listed in a [`Manifest`] was not saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum ManifestPageStatus {
+    /// The HTML of the page has a `<meta name="robots" content="noindex">` and
+    /// [`DownloadConfig::respect_noindex`](crate::DownloadConfig::respect_noindex) was set
+    SkippedNoindex,
+}
+
+impl ManifestPage {
+    /// Creates an entry for a page that has not been downloaded yet
+    pub fn new(url: impl Into<String>, depth: usize) -> Self {
+        ManifestPage {
+            url: url.into(),
+            title: None,
+            description: None,
+            canonical_url: None,
+            depth,
+            content_hash: None,
+            downloaded_at: None,
+            file_path: None,
+            status: None,
+        }
+    }
+
+    /// Returns `content_hash` as bytes, `None` if missing or not a SHA-256
+    pub fn content_sha256(&self) -> Option<[u8; 32]> {
+        parse_sha256(self.content_hash.as_deref()?)
+    }
+}
+
+impl Manifest {
+    /// Creates an empty manifest for a site crawled now
+    pub fn new(source_url: impl Into<String>) -> Self {
+        Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            source_url: source_url.into(),
+            crawled_at: SystemTime::now(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Returns the entry of a page
+    pub fn page(&self, url: &str) -> Option<&ManifestPage> {
+        self.pages.iter().find(|page| page.url == url)
+    }
+
+    /// Adds a page, replacing the entry with the same URL if any
+    pub fn upsert(&mut self, page: ManifestPage) {
+        match self.pages.binary_search_by(|p| p.url.cmp(&page.url)) {
+            Ok(i) => self.pages[i] = page,
+            Err(i) => self.pages.insert(i, page),
+        }
+    }
+
+    /// Writes the manifest as pretty-printed JSON, creating the parent directories
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be created or if the file write fails
+    pub fn save(&self, path: &Path) -> Result<(), GitBookError> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| GitBookError::ParseError(e.to_string()))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Reads a manifest written by [`Manifest::save`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid manifest
+    pub fn load(path: &Path) -> Result<Self, GitBookError> {
+        let json = std::fs::read_to_string(path)?;
+        let mut manifest: Manifest = serde_json::from_str(&json)
+            .map_err(|e| GitBookError::ParseError(format!("{}: {}", path.display(), e)))?;
+        manifest.pages.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(manifest)
+    }
+}
+
+/// Formats the SHA-256 of a content as lowercase hexadecimal
+pub(crate) fn hex_content_hash(content: &str) -> String {
+    crate::dedup::content_hash(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Parses a SHA-256 written in hexadecimal
+pub(crate) fn parse_sha256(digest: &str) -> Option<[u8; 32]> {
+    if digest.len() != 64 {
+        return None;
+    }
+    let bytes: Option<Vec<u8>> = (0..digest.len())
+        .step_by(2)
+        .map(|i| {
+            digest
+                .get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect();
+    bytes?.try_into().ok()
+}
+
+mod rfc3339 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&humantime::format_rfc3339_seconds(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        humantime::parse_rfc3339(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+mod rfc3339_option {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => super::rfc3339::serialize(time, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| humantime::parse_rfc3339(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_manifest_save_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data/manifest.json");
+
+        let mut manifest = Manifest::new("https://docs.example.com");
+        manifest.crawled_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        manifest.upsert(ManifestPage::new("https://docs.example.com/b", 2));
+        manifest.upsert(ManifestPage {
+            title: Some("A".to_string()),
+            content_hash: Some(hex_content_hash("# A")),
+            downloaded_at: Some(manifest.crawled_at),
+            file_path: Some(PathBuf::from("data/md/a.md")),
+            ..ManifestPage::new("https://docs.example.com/a", 1)
+        });
+        manifest.save(&path).unwrap();
+
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(json.contains("\"crawled_at\": \"2023-11-14T22:13:20Z\""));
+
+        let loaded = Manifest::load(&path).unwrap();
+        assert_eq!(loaded, manifest);
+        assert_eq!(loaded.pages[0].url, "https://docs.example.com/a");
+        assert_eq!(
+            loaded.pages[0].content_sha256(),
+            Some(crate::dedup::content_hash("# A"))
+        );
+        assert_eq!(loaded.pages[0].content_hash.as_ref().unwrap().len(), 64);
+        assert_eq!(loaded.pages[1].downloaded_at, None);
+    }
+
+    #[test]
+    fn test_manifest_load_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        std::fs::write(&path, "{}").unwrap();
+        assert!(matches!(
+            Manifest::load(&path),
+            Err(GitBookError::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,592 @@
+use crate::config::{CrawlConfig, SanitizeConfig};
+use crate::crawler::extract_metadata;
+use crate::utils::{
+    extract_code_blocks, markdown_to_text, save_markdown_with_config, save_text_with_config,
+    txt_sanitize_with_context, CodeBlock, SanitizeContext,
+};
+use crate::GitBookError;
+use pulldown_cmark::{Event, LinkType, Parser, Tag, TagEnd};
+use scraper::Html;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use url::Url;
+
+/// Descriptive information about a page
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageMetadata {
+    /// The title of the page
+    pub title: Option<String>,
+
+    /// The short description of the page
+    pub description: Option<String>,
+
+    /// The canonical URL declared by the HTML of the page
+    pub canonical_url: Option<String>,
+}
+
+/// A downloaded GitBook page with everything derived from its content
+///
+/// [`GitBookPage::reader`] reads the UTF-8 bytes of its plain text through
+/// [`AsyncRead`], so it can be passed to `tokio::io::copy` or wrapped by async encoders.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct GitBookPage {
+    /// The URL of the page
+    pub url: String,
+
+    /// The HTML of the page, when it was downloaded
+    pub html: Option<String>,
+
+    /// The markdown content of the page
+    pub markdown: String,
+
+    /// The sanitized plain text of the page
+    pub plain_text: String,
+
+    /// The title and description of the page
+    pub metadata: PageMetadata,
+
+    /// The absolute URLs of the links of the page, in order of appearance
+    pub links: Vec<String>,
+
+    /// The code blocks of the page
+    pub code_blocks: Vec<CodeBlock>,
+
+    /// The number of words of the plain text
+    pub word_count: usize,
+}
+
+impl GitBookPage {
+    /// Builds a page from its URL, its optional HTML and its markdown content
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the page, used to resolve relative links
+    /// * `html` - The HTML of the page, used as a metadata fallback
+    /// * `markdown` - The markdown content of the page
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::GitBookPage;
+    ///
+    /// let md = "# Install\n\nSee [the guide](guide.md).\n\n```sh\ncargo install gitbook2text\n```";
+    /// let page = GitBookPage::parse("https://docs.example.com/intro", None, md);
+    /// assert_eq!(page.metadata.title.as_deref(), Some("Install"));
+    /// assert_eq!(page.links, vec!["https://docs.example.com/guide.md"]);
+    /// assert_eq!(page.code_blocks.len(), 1);
+    /// ```
+    pub fn parse(url: &str, html: Option<&str>, markdown: &str) -> GitBookPage {
+        let context = SanitizeContext {
+            base_url: Some(url.to_string()),
+        };
+        let plain_text = txt_sanitize_with_context(
+            &markdown_to_text(markdown),
+            &SanitizeConfig::default(),
+            &context,
+        );
+        let word_count = plain_text.split_whitespace().count();
+
+        GitBookPage {
+            url: url.to_string(),
+            html: html.map(str::to_string),
+            markdown: markdown.to_string(),
+            metadata: extract_page_metadata(markdown, html),
+            links: extract_links_from_markdown(markdown, url),
+            code_blocks: extract_code_blocks(markdown),
+            plain_text,
+            word_count,
+        }
+    }
+
+    /// Returns a reader yielding the UTF-8 bytes of the plain text of the page
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::GitBookPage;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> std::io::Result<()> {
+    ///     let page = GitBookPage::parse("https://docs.example.com/intro", None, "# Intro");
+    ///     let mut text = Vec::new();
+    ///     tokio::io::copy(&mut page.reader(), &mut text).await?;
+    ///     assert_eq!(text, b"Intro");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn reader(&self) -> GitBookPageReader<'_> {
+        GitBookPageReader {
+            remaining: self.plain_text.as_bytes(),
+        }
+    }
+}
+
+/// Reads the plain text of a [`GitBookPage`] through [`AsyncRead`], see [`GitBookPage::reader`]
+#[derive(Debug, Clone)]
+pub struct GitBookPageReader<'a> {
+    /// The bytes of the plain text not read yet
+    remaining: &'a [u8],
+}
+
+impl AsyncRead for GitBookPageReader<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let n = this.remaining.len().min(buf.remaining());
+        buf.put_slice(&this.remaining[..n]);
+        this.remaining = &this.remaining[n..];
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Builds a [`GitBookPage`] from markdown written to it asynchronously
+///
+/// The written bytes are accumulated, then parsed as markdown with
+/// [`GitBookPage::parse`] on each flush (and on shutdown).
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::GitBookPageWriter;
+/// use tokio::io::AsyncWriteExt;
+///
+/// #[tokio::main]
+/// async fn main() -> std::io::Result<()> {
+///     let mut writer = GitBookPageWriter::new("https://docs.example.com/intro");
+///     writer.write_all(b"# Intro\n\nHello").await?;
+///     writer.flush().await?;
+///     assert_eq!(writer.page().unwrap().metadata.title.as_deref(), Some("Intro"));
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GitBookPageWriter {
+    url: String,
+    html: Option<String>,
+    buffer: Vec<u8>,
+    page: Option<GitBookPage>,
+}
+
+impl GitBookPageWriter {
+    /// Creates a writer for the page at `url`
+    pub fn new(url: impl Into<String>) -> Self {
+        GitBookPageWriter {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the HTML of the page, used as a metadata fallback
+    pub fn with_html(mut self, html: impl Into<String>) -> Self {
+        self.html = Some(html.into());
+        self
+    }
+
+    /// Returns the page parsed at the last flush
+    pub fn page(&self) -> Option<&GitBookPage> {
+        self.page.as_ref()
+    }
+
+    /// Consumes the writer and returns the page parsed at the last flush
+    pub fn into_page(self) -> Option<GitBookPage> {
+        self.page
+    }
+}
+
+impl AsyncWrite for GitBookPageWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().buffer.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let markdown = std::str::from_utf8(&this.buffer)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        this.page = Some(GitBookPage::parse(
+            &this.url,
+            this.html.as_deref(),
+            markdown,
+        ));
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[derive(Deserialize)]
+struct FrontMatter {
+    title: Option<String>,
+    description: Option<String>,
+}
+
+/// Extracts the title, description and canonical URL of a page
+///
+/// The YAML front matter of the markdown (`title:` and `description:`) is used first,
+/// then the first heading for the title, and finally the metadata of the HTML when it
+/// is given (see [`extract_metadata`]), which is also the only source of the canonical URL.
+///
+/// # Arguments
+///
+/// * `markdown` - The markdown content of the page
+/// * `html` - The HTML of the page, if available
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::extract_page_metadata;
+///
+/// let md = "---\ndescription: Getting started\n---\n\n# Quickstart\n";
+/// let metadata = extract_page_metadata(md, None);
+/// assert_eq!(metadata.title.as_deref(), Some("Quickstart"));
+/// assert_eq!(metadata.description.as_deref(), Some("Getting started"));
+/// ```
+pub fn extract_page_metadata(markdown: &str, html: Option<&str>) -> PageMetadata {
+    let (front_matter, body) = split_front_matter(markdown);
+    let mut metadata = PageMetadata::default();
+
+    if let Some(front_matter) =
+        front_matter.and_then(|yaml| serde_yaml::from_str::<FrontMatter>(yaml).ok())
+    {
+        metadata.title = front_matter.title;
+        metadata.description = front_matter.description;
+    }
+
+    if metadata.title.is_none() {
+        metadata.title = first_heading(body);
+    }
+
+    if let Some(html) = html {
+        let from_html = extract_metadata(&Html::parse_document(html));
+        metadata.title = metadata.title.or(from_html.title);
+        metadata.description = metadata.description.or(from_html.description);
+        metadata.canonical_url = from_html.canonical_url;
+    }
+
+    metadata
+}
+
+/// Extracts the link targets of a markdown document as absolute URLs
+///
+/// Relative links are resolved against `base_url`. Anchors within the page,
+/// e-mail links and duplicates are left out.
+///
+/// # Arguments
+///
+/// * `markdown` - The markdown content
+/// * `base_url` - The URL of the page
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::extract_links_from_markdown;
+///
+/// let md = "[Setup](../setup.md) [Top](#top) <https://example.org>";
+/// let links = extract_links_from_markdown(md, "https://docs.example.com/guide/intro");
+/// assert_eq!(links, vec!["https://docs.example.com/setup.md", "https://example.org/"]);
+/// ```
+pub fn extract_links_from_markdown(markdown: &str, base_url: &str) -> Vec<String> {
+    let base = Url::parse(base_url).ok();
+    let mut seen = HashSet::new();
+    let mut links = Vec::new();
+
+    for event in Parser::new(markdown) {
+        let Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            ..
+        }) = event
+        else {
+            continue;
+        };
+        if link_type == LinkType::Email || dest_url.is_empty() || dest_url.starts_with('#') {
+            continue;
+        }
+
+        let resolved = match &base {
+            Some(base) => base.join(&dest_url),
+            None => Url::parse(&dest_url),
+        };
+        let Ok(url) = resolved else {
+            continue;
+        };
+        if url.scheme() == "mailto" {
+            continue;
+        }
+
+        let url = url.to_string();
+        if seen.insert(url.clone()) {
+            links.push(url);
+        }
+    }
+
+    links
+}
+
+/// Saves a page as markdown in `md/` and as plain text in `txt/` under `config.output_dir`
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{save_page, CrawlConfig, GitBookPage};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let page = GitBookPage::parse("https://example.com/page", None, "# Titre\nContenu");
+///     save_page(&page, &CrawlConfig::default()).await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or if a file write fails
+#[must_use = "the page may not have been saved"]
+pub async fn save_page(page: &GitBookPage, config: &CrawlConfig) -> Result<(), GitBookError> {
+    save_markdown_with_config(&page.url, &page.markdown, config).await?;
+    save_text_with_config(&page.url, &page.plain_text, config).await
+}
+
+/// Groups pages by the first `depth` segments of their URL path
+///
+/// The key is made of the segments joined with `_`, without the `.md` suffix
+/// (e.g. `api` for `/api/users.md` at depth 1, `api_users` at depth 2).
+/// Pages whose path is the site root are grouped under `index`.
+///
+/// # Arguments
+///
+/// * `pages` - The pages to group
+/// * `depth` - The number of path segments forming a group, at least 1
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{group_pages_by_path_segment, GitBookPage};
+///
+/// let pages = vec![
+///     GitBookPage::parse("https://docs.example.com/api/users", None, "# Users"),
+///     GitBookPage::parse("https://docs.example.com/api/teams", None, "# Teams"),
+///     GitBookPage::parse("https://docs.example.com/guide/intro", None, "# Intro"),
+/// ];
+/// let groups = group_pages_by_path_segment(&pages, 1);
+/// assert_eq!(groups["api"].len(), 2);
+/// assert_eq!(groups["guide"].len(), 1);
+/// ```
+pub fn group_pages_by_path_segment(
+    pages: &[GitBookPage],
+    depth: usize,
+) -> HashMap<String, Vec<&GitBookPage>> {
+    let mut groups: HashMap<String, Vec<&GitBookPage>> = HashMap::new();
+    for page in pages {
+        groups
+            .entry(path_segment_key(&page.url, depth.max(1)))
+            .or_default()
+            .push(page);
+    }
+    groups
+}
+
+/// Returns the group key of a URL, see [`group_pages_by_path_segment`]
+fn path_segment_key(url: &str, depth: usize) -> String {
+    let segments: Vec<String> = Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments().map(|segments| {
+                segments
+                    .filter(|segment| !segment.is_empty())
+                    .take(depth)
+                    .map(|segment| segment.strip_suffix(".md").unwrap_or(segment))
+                    .map(|segment| {
+                        segment
+                            .chars()
+                            .map(|c| {
+                                if c.is_alphanumeric() || c == '-' || c == '.' {
+                                    c
+                                } else {
+                                    '_'
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+
+    if segments.is_empty() {
+        "index".to_string()
+    } else {
+        segments.join("_")
+    }
+}
+
+/// Splits a `---` delimited YAML front matter from the rest of a markdown document
+fn split_front_matter(markdown: &str) -> (Option<&str>, &str) {
+    let Some(rest) = markdown
+        .strip_prefix("---\n")
+        .or_else(|| markdown.strip_prefix("---\r\n"))
+    else {
+        return (None, markdown);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+
+    (None, markdown)
+}
+
+/// Returns the text of the first heading of a markdown document
+fn first_heading(markdown: &str) -> Option<String> {
+    let mut heading: Option<String> = None;
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => heading = Some(String::new()),
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(heading) = &mut heading {
+                    heading.push_str(&t);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(title) = heading.take().map(|h| h.trim().to_string()) {
+                    if !title.is_empty() {
+                        return Some(title);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_page_async_read_write() {
+        let page = GitBookPage::parse(
+            "https://docs.example.com/a",
+            None,
+            "# Title\n\nSome **bold** text",
+        );
+
+        let mut reader = page.reader();
+        let mut text = Vec::new();
+        tokio::io::copy(&mut reader, &mut text).await.unwrap();
+        assert_eq!(text, page.plain_text.as_bytes());
+        // A reader reads the plain text once, a new reader starts over
+        let mut again = Vec::new();
+        tokio::io::copy(&mut reader, &mut again).await.unwrap();
+        assert!(again.is_empty());
+        tokio::io::copy(&mut page.reader(), &mut again)
+            .await
+            .unwrap();
+        assert_eq!(again, text);
+
+        let mut writer = GitBookPageWriter::new("https://docs.example.com/b");
+        tokio::io::copy(&mut page.markdown.as_bytes(), &mut writer)
+            .await
+            .unwrap();
+        writer.shutdown().await.unwrap();
+        let copy = writer.into_page().unwrap();
+        assert_eq!(copy.markdown, page.markdown);
+        assert_eq!(copy.metadata.title.as_deref(), Some("Title"));
+
+        let mut writer = GitBookPageWriter::new("https://docs.example.com/c");
+        writer.write_all(&[0xff, 0xfe]).await.unwrap();
+        let err = writer.flush().await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_extract_page_metadata() {
+        let md = "---\ntitle: Setup\ndescription: How to install\n---\n\n# Install\n";
+        let metadata = extract_page_metadata(md, None);
+        assert_eq!(metadata.title.as_deref(), Some("Setup"));
+        assert_eq!(metadata.description.as_deref(), Some("How to install"));
+
+        let html = r#"<html><head><title>Docs - Intro</title>
+            <meta name="description" content="The introduction"></head></html>"#;
+        let metadata = extract_page_metadata("No heading here", Some(html));
+        assert_eq!(metadata.title.as_deref(), Some("Docs - Intro"));
+        assert_eq!(metadata.description.as_deref(), Some("The introduction"));
+
+        assert_eq!(extract_page_metadata("text", None), PageMetadata::default());
+    }
+
+    #[test]
+    fn test_extract_links_from_markdown() {
+        let md = "[A](a.md) [Again](a.md) [Mail](mailto:me@example.com) <me@example.com>\n\n\
+                  [Abs](https://other.example.com/x) [Anchor](#part)";
+        let links = extract_links_from_markdown(md, "https://docs.example.com/guide/");
+        assert_eq!(
+            links,
+            vec![
+                "https://docs.example.com/guide/a.md",
+                "https://other.example.com/x"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_pages_by_path_segment() {
+        let pages: Vec<GitBookPage> = [
+            "https://docs.example.com/api/users/list.md",
+            "https://docs.example.com/api/users.md",
+            "https://docs.example.com/api/teams",
+            "https://docs.example.com/intro.md",
+            "https://docs.example.com/",
+        ]
+        .iter()
+        .map(|url| GitBookPage::parse(url, None, ""))
+        .collect();
+
+        let groups = group_pages_by_path_segment(&pages, 1);
+        let mut keys: Vec<&String> = groups.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["api", "index", "intro"]);
+        assert_eq!(groups["api"].len(), 3);
+
+        let groups = group_pages_by_path_segment(&pages, 2);
+        assert_eq!(groups["api_users"].len(), 2);
+        assert_eq!(groups["api_teams"].len(), 1);
+        assert_eq!(groups["intro"].len(), 1);
+    }
+
+    #[test]
+    fn test_gitbook_page_parse() {
+        let md = "# Title\n\nSome words [here](/next).\n\n```rust\nfn main() {}\n```\n";
+        let html = "<title>Ignored</title>";
+        let page = GitBookPage::parse("https://docs.example.com/intro", Some(html), md);
+
+        assert_eq!(page.html.as_deref(), Some(html));
+        assert_eq!(page.metadata.title.as_deref(), Some("Title"));
+        assert_eq!(page.links, vec!["https://docs.example.com/next"]);
+        assert_eq!(page.code_blocks.len(), 1);
+        assert_eq!(page.plain_text, "Title\n\nSome words here.\n\nfn main() {}");
+        assert_eq!(page.word_count, 7);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::chunker::{CharacterTokenizer, Tokenize};
+use crate::page::GitBookPage;
+use crate::stats::{english_stop_words, words};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use std::collections::HashSet;
+use url::Url;
+
+/// Number of words at the start of the plain text counted by [`PageScore::top_density`]
+const TOP_WORDS: usize = 100;
+
+/// Weight of a query term found in a heading, relative to one found in the text
+const HEADING_WEIGHT: f32 = 3.0;
+
+/// The relevance of a page to a query, as computed by [`score_relevance`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub struct PageScore {
+    /// The overall score: the sum of `term_frequency`, 3 times `heading_matches` and
+    /// `top_density`, divided by `1 + depth / 2`; `0.0` when no query term appears
+    pub score: f32,
+
+    /// Occurrences of the query terms per 100 words of the plain text
+    pub term_frequency: f32,
+
+    /// Occurrences of the query terms in the headings of the markdown
+    pub heading_matches: usize,
+
+    /// Occurrences of the query terms per 100 words among the first 100 words
+    pub top_density: f32,
+
+    /// The number of segments of the URL path, `0` for the root of the site
+    pub depth: usize,
+}
+
+/// Scores how relevant a page is to a query
+///
+/// The query is split into lowercase words and the English stop words are left out.
+/// The score grows with the frequency of the query terms in the plain text, their
+/// occurrences in the headings (weighted 3×) and their density in the first 100 words,
+/// and shrinks with the depth of the page in the URL tree.
+///
+/// # Arguments
+///
+/// * `page` - The page to score
+/// * `query` - The words to look for
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{score_relevance, GitBookPage};
+///
+/// let auth = GitBookPage::parse(
+///     "https://docs.example.com/auth",
+///     None,
+///     "# Authentication\n\nSend the token in a header.",
+/// );
+/// let faq = GitBookPage::parse("https://docs.example.com/help/faq", None, "# FAQ\n\nAsk us.");
+/// assert!(score_relevance(&auth, "token authentication").score > 0.0);
+/// assert_eq!(score_relevance(&faq, "token authentication").score, 0.0);
+/// ```
+pub fn score_relevance(page: &GitBookPage, query: &str) -> PageScore {
+    let stop_words = english_stop_words();
+    let terms: HashSet<String> = words(query)
+        .filter(|word| !stop_words.contains(word))
+        .collect();
+    let depth = Url::parse(&page.url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .map(|segments| segments.filter(|s| !s.is_empty()).count())
+        })
+        .unwrap_or_default();
+
+    let text: Vec<String> = words(&page.plain_text).collect();
+    let matches = |words: &[String]| words.iter().filter(|w| terms.contains(*w)).count();
+    let per_hundred_words = |words: &[String]| match words.len() {
+        0 => 0.0,
+        n => matches(words) as f32 * 100.0 / n as f32,
+    };
+
+    let term_frequency = per_hundred_words(&text);
+    let top_density = per_hundred_words(&text[..text.len().min(TOP_WORDS)]);
+    let heading_words: Vec<String> = headings(&page.markdown)
+        .iter()
+        .flat_map(|heading| words(heading))
+        .collect();
+    let heading_matches = matches(&heading_words);
+
+    let sum = term_frequency + HEADING_WEIGHT * heading_matches as f32 + top_density;
+    PageScore {
+        score: sum / (1.0 + depth as f32 / 2.0),
+        term_frequency,
+        heading_matches,
+        top_density,
+        depth,
+    }
+}
+
+/// Selects the pages most relevant to a query whose plain text fits in a token budget
+///
+/// The pages are taken by decreasing [`score_relevance`], skipping the ones that no
+/// longer fit in the budget; pages without any query term are never selected. Tokens
+/// are counted with the default [`CharacterTokenizer`].
+///
+/// # Arguments
+///
+/// * `pages` - The candidate pages
+/// * `query` - The words to look for
+/// * `max_tokens` - The maximum number of tokens of the selected pages together
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{select_top_pages, GitBookPage};
+///
+/// let pages = vec![
+///     GitBookPage::parse("https://docs.example.com/faq", None, "# FAQ\n\nA token expires."),
+///     GitBookPage::parse("https://docs.example.com/tokens", None, "# Token\n\nCreate a token."),
+///     GitBookPage::parse("https://docs.example.com/about", None, "# About\n\nWho we are."),
+/// ];
+/// let selected = select_top_pages(&pages, "token", 1000);
+/// assert_eq!(selected.len(), 2);
+/// assert_eq!(selected[0].url, "https://docs.example.com/tokens");
+/// ```
+pub fn select_top_pages<'a>(
+    pages: &'a [GitBookPage],
+    query: &str,
+    max_tokens: usize,
+) -> Vec<&'a GitBookPage> {
+    let mut scored: Vec<(f32, &GitBookPage)> = pages
+        .iter()
+        .map(|page| (score_relevance(page, query).score, page))
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let tokenizer = CharacterTokenizer::default();
+    let mut budget = max_tokens;
+    let mut selected = Vec::new();
+    for (_, page) in scored {
+        let tokens = tokenizer.count_tokens(&page.plain_text);
+        if tokens <= budget {
+            budget -= tokens;
+            selected.push(page);
+        }
+    }
+    selected
+}
+
+/// Returns the text of the headings of a markdown document
+fn headings(md: &str) -> Vec<String> {
+    let mut headings = Vec::new();
+    let mut current: Option<String> = None;
+    for event in Parser::new(md) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => current = Some(String::new()),
+            Event::End(TagEnd::Heading(_)) => headings.extend(current.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut current {
+                    heading.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_relevance() {
+        let page = |url: &str, md: &str| GitBookPage::parse(url, None, md);
+        let root = page(
+            "https://docs.example.com/webhooks",
+            "# Webhooks\n\nWebhooks notify your server.",
+        );
+        let nested = page(
+            "https://docs.example.com/api/v2/webhooks",
+            "# Webhooks\n\nWebhooks notify your server.",
+        );
+
+        let score = score_relevance(&root, "the webhooks");
+        assert_eq!(score.heading_matches, 1);
+        assert_eq!(score.depth, 1);
+        assert!(score.term_frequency > 0.0);
+        assert_eq!(score.top_density, score.term_frequency);
+        assert!(score.score > score_relevance(&nested, "webhooks").score);
+
+        let mention = page(
+            "https://docs.example.com/events",
+            "# Events\n\nEvents are sent to webhooks.",
+        );
+        assert!(score.score > score_relevance(&mention, "webhooks").score);
+        assert_eq!(score_relevance(&root, "the").score, 0.0);
+    }
+
+    #[test]
+    fn test_select_top_pages() {
+        let long = format!("# Webhooks\n\n{}", "Webhooks retry. ".repeat(50));
+        let pages = vec![
+            GitBookPage::parse("https://docs.example.com/a", None, &long),
+            GitBookPage::parse("https://docs.example.com/b", None, "# B\n\nUse webhooks."),
+            GitBookPage::parse("https://docs.example.com/c", None, "# C\n\nNothing."),
+        ];
+        let urls = |selected: Vec<&GitBookPage>| -> Vec<String> {
+            selected.iter().map(|p| p.url.clone()).collect()
+        };
+
+        assert_eq!(
+            urls(select_top_pages(&pages, "webhooks", 10_000)),
+            vec!["https://docs.example.com/a", "https://docs.example.com/b"]
+        );
+        // The first page does not fit, the next one does
+        assert_eq!(
+            urls(select_top_pages(&pages, "webhooks", 10)),
+            vec!["https://docs.example.com/b"]
+        );
+        assert!(select_top_pages(&pages, "webhooks", 0).is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config::TextConfig;
+use crate::utils::markdown_to_text_with_config;
+use pulldown_cmark::{html, Parser};
+use scraper::Html;
+use std::fmt;
+use std::sync::Arc;
+
+/// Converts the markdown of a page to the text saved in its `.txt` file
+///
+/// The download pipeline uses [`DownloadConfig::renderer`](crate::DownloadConfig::renderer)
+/// when set, and a [`PulldownRenderer`] built from
+/// [`DownloadConfig::text`](crate::DownloadConfig::text) otherwise.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{DownloadConfig, TextRenderer};
+/// use std::sync::Arc;
+///
+/// struct Uppercase;
+///
+/// impl TextRenderer for Uppercase {
+///     fn render(&self, md: &str) -> String {
+///         md.to_uppercase()
+///     }
+/// }
+///
+/// let config = DownloadConfig {
+///     renderer: Some(Arc::new(Uppercase)),
+///     ..Default::default()
+/// };
+/// ```
+pub trait TextRenderer: Send + Sync {
+    /// Returns the text of the markdown `md`
+    fn render(&self, md: &str) -> String;
+
+    /// Whether the sanitization keeps the lines of the text even with
+    /// [`SanitizeConfig::join_lines`](crate::SanitizeConfig::join_lines), for a text
+    /// whose structure is in its lines, e.g. chunk separators
+    fn keeps_lines(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for dyn TextRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TextRenderer")
+    }
+}
+
+/// Renders the text of the markdown events, like [`markdown_to_text_with_config`]
+#[derive(Debug, Clone, Default)]
+pub struct PulldownRenderer {
+    /// The text conversion configuration
+    pub config: TextConfig,
+}
+
+impl PulldownRenderer {
+    /// Creates a renderer following `config`
+    pub fn new(config: TextConfig) -> Self {
+        PulldownRenderer { config }
+    }
+}
+
+impl TextRenderer for PulldownRenderer {
+    fn render(&self, md: &str) -> String {
+        markdown_to_text_with_config(md, &self.config)
+    }
+}
+
+/// Renders the markdown to HTML and keeps the text of the HTML nodes
+///
+/// Unlike [`PulldownRenderer`], the raw HTML embedded in the markdown is
+/// parsed, so only its text is kept.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{HtmlStripRenderer, TextRenderer};
+///
+/// let text = HtmlStripRenderer.render("# Title\n\n<div class=\"hint\">Be <b>careful</b></div>");
+/// assert_eq!(text, "Title\nBe careful");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtmlStripRenderer;
+
+impl TextRenderer for HtmlStripRenderer {
+    fn render(&self, md: &str) -> String {
+        let mut rendered = String::new();
+        html::push_html(&mut rendered, Parser::new(md));
+
+        let text: String = Html::parse_fragment(&rendered)
+            .root_element()
+            .text()
+            .collect();
+        text.lines()
+            .map(str::trim_end)
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Splits the text of another renderer into chunks of at most `max_tokens` tokens,
+/// ready to be embedded or sent to a language model
+///
+/// Tokens are approximated by whitespace-separated words. Paragraphs are kept
+/// together when they fit in a chunk, and longer paragraphs are split between words.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{LlmChunkRenderer, TextRenderer};
+///
+/// let renderer = LlmChunkRenderer::new(4);
+/// assert_eq!(
+///     renderer.render("One two.\n\nThree four.\n\nFive"),
+///     "One two.\n\nThree four.\n\n---\n\nFive"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct LlmChunkRenderer {
+    /// Maximum number of tokens of a chunk
+    pub max_tokens: usize,
+
+    /// Written between two chunks
+    pub separator: String,
+
+    /// Renders the text before it is split
+    pub inner: Arc<dyn TextRenderer>,
+}
+
+impl LlmChunkRenderer {
+    /// Splits the text of a default [`PulldownRenderer`] into chunks of `max_tokens` tokens
+    pub fn new(max_tokens: usize) -> Self {
+        LlmChunkRenderer {
+            max_tokens,
+            separator: "\n\n---\n\n".to_string(),
+            inner: Arc::new(PulldownRenderer::default()),
+        }
+    }
+
+    /// Returns the chunks of a text
+    pub fn chunks(&self, text: &str) -> Vec<String> {
+        let max_tokens = self.max_tokens.max(1);
+        let mut chunks = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        let mut tokens = 0;
+
+        for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+            let count = paragraph.split_whitespace().count();
+            if tokens + count > max_tokens && !current.is_empty() {
+                chunks.push(current.join("\n\n"));
+                current.clear();
+                tokens = 0;
+            }
+
+            if count <= max_tokens {
+                current.push(paragraph.to_string());
+                tokens += count;
+                continue;
+            }
+
+            // A paragraph longer than a chunk is split between words
+            let words: Vec<&str> = paragraph.split_whitespace().collect();
+            let mut parts = words.chunks(max_tokens).map(|part| part.join(" "));
+            let last = parts.next_back();
+            chunks.extend(parts);
+            if let Some(last) = last {
+                tokens = last.split_whitespace().count();
+                current.push(last);
+            }
+        }
+
+        if !current.is_empty() {
+            chunks.push(current.join("\n\n"));
+        }
+        chunks
+    }
+}
+
+impl TextRenderer for LlmChunkRenderer {
+    fn render(&self, md: &str) -> String {
+        self.chunks(&self.inner.render(md)).join(&self.separator)
+    }
+
+    fn keeps_lines(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pulldown_renderer_matches_markdown_to_text() {
+        let md = "# Title\n\nSee [the docs](https://example.com) and `code`.";
+        assert_eq!(
+            PulldownRenderer::default().render(md),
+            crate::markdown_to_text(md)
+        );
+    }
+
+    #[test]
+    fn test_llm_chunk_renderer_splits_long_paragraphs() {
+        let renderer = LlmChunkRenderer::new(3);
+        assert_eq!(
+            renderer.chunks("a b\n\nc d e f g h i\n\nj"),
+            vec!["a b", "c d e", "f g h", "i\n\nj"]
+        );
+        assert!(renderer.chunks("").is_empty());
+    }
+}
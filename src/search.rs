@@ -0,0 +1,174 @@
+use crate::GitBookError;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A line matching a search query in a downloaded text file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// The file containing the match
+    pub path: PathBuf,
+
+    /// The line number of the match, starting at 1
+    pub line_number: usize,
+
+    /// The matching line
+    pub line: String,
+
+    /// The lines preceding the match
+    pub context_before: Vec<String>,
+
+    /// The lines following the match
+    pub context_after: Vec<String>,
+}
+
+/// Searches all `.txt` files of a directory for a literal query
+///
+/// The directory is scanned recursively and files are visited in path order.
+/// Zstd-compressed `.txt.zst` files are decompressed transparently.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the downloaded files
+/// * `query` - The text to look for
+/// * `context` - The number of lines of context to keep around each match
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::search_files;
+/// use std::path::Path;
+///
+/// let hits = search_files(Path::new("data/txt"), "install", 1).unwrap();
+/// for hit in hits {
+///     println!("{}:{}: {}", hit.path.display(), hit.line_number, hit.line);
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the directory or one of its files cannot be read
+pub fn search_files(
+    dir: &Path,
+    query: &str,
+    context: usize,
+) -> Result<Vec<SearchHit>, GitBookError> {
+    let re = Regex::new(&regex::escape(query)).expect("escaped query is a valid regex");
+    search_files_regex(dir, &re, context)
+}
+
+/// Searches all `.txt` files of a directory for lines matching a regex
+///
+/// See [`search_files`] for the details.
+///
+/// # Errors
+///
+/// Returns an error if the directory or one of its files cannot be read
+pub fn search_files_regex(
+    dir: &Path,
+    re: &Regex,
+    context: usize,
+) -> Result<Vec<SearchHit>, GitBookError> {
+    let mut files = Vec::new();
+    collect_files(dir, &[".txt", ".txt.zst"], &mut files)?;
+    files.sort();
+
+    let mut hits = Vec::new();
+    for path in files {
+        let content = read_output_file(&path)?;
+        let lines: Vec<&str> = content.lines().collect();
+
+        for (i, line) in lines.iter().enumerate() {
+            if re.is_match(line) {
+                let start = i.saturating_sub(context);
+                let end = (i + 1 + context).min(lines.len());
+                hits.push(SearchHit {
+                    path: path.clone(),
+                    line_number: i + 1,
+                    line: line.to_string(),
+                    context_before: lines[start..i].iter().map(|l| l.to_string()).collect(),
+                    context_after: lines[i + 1..end].iter().map(|l| l.to_string()).collect(),
+                });
+            }
+        }
+    }
+
+    Ok(hits)
+}
+
+/// Recursively collects the files whose name ends with one of the given suffixes
+pub(crate) fn collect_files(
+    dir: &Path,
+    suffixes: &[&str],
+    files: &mut Vec<PathBuf>,
+) -> Result<(), GitBookError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, suffixes, files)?;
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| suffixes.iter().any(|suffix| name.ends_with(suffix)))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a saved file, decompressing it first if it is a `.zst` file
+pub(crate) fn read_output_file(path: &Path) -> Result<String, GitBookError> {
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        let bytes = zstd::stream::decode_all(fs::File::open(path)?)?;
+        String::from_utf8(bytes).map_err(|e| {
+            GitBookError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })
+    } else {
+        Ok(fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("guide")).unwrap();
+        fs::write(dir.path().join("a.txt"), "one\ntwo install\nthree").unwrap();
+        fs::write(dir.path().join("guide/b.txt"), "install first").unwrap();
+        fs::write(dir.path().join("c.md"), "install").unwrap();
+
+        let hits = search_files(dir.path(), "install", 1).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].path, dir.path().join("a.txt"));
+        assert_eq!(hits[0].line_number, 2);
+        assert_eq!(hits[0].context_before, vec!["one"]);
+        assert_eq!(hits[0].context_after, vec!["three"]);
+        assert_eq!(hits[1].path, dir.path().join("guide/b.txt"));
+    }
+
+    #[test]
+    fn test_search_files_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let compressed = zstd::stream::encode_all("install here".as_bytes(), 3).unwrap();
+        fs::write(dir.path().join("a.txt.zst"), compressed).unwrap();
+
+        let hits = search_files(dir.path(), "install", 0).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].line, "install here");
+    }
+
+    #[test]
+    fn test_search_files_regex() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "GET /users\nPOST /users\nnote").unwrap();
+
+        let re = Regex::new(r"^(GET|POST) ").unwrap();
+        let hits = search_files_regex(dir.path(), &re, 0).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits[1].context_before.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::config::CrawlConfig;
+use crate::crawler::{build_client, extract_links_stream};
+use crate::page::{extract_links_from_markdown, GitBookPage};
+use crate::utils::download_page_with_client;
+use crate::GitBookError;
+use futures::StreamExt;
+use std::collections::HashMap;
+use url::Url;
+
+/// The pages of a GitBook site, with their discovery and `SUMMARY.md` orders
+#[derive(Debug, Clone, Default)]
+pub struct GitBookSite {
+    /// The base URL of the site
+    pub base_url: String,
+
+    /// The pages, by URL
+    pages: HashMap<String, GitBookPage>,
+
+    /// The page URLs in the order they were discovered by the crawler
+    discovery_order: Vec<String>,
+
+    /// The page URLs in the order of the table of contents, if the site has a `SUMMARY.md`
+    summary_order: Option<Vec<String>>,
+}
+
+impl GitBookSite {
+    /// Creates an empty site
+    pub fn new(base_url: impl Into<String>) -> Self {
+        GitBookSite {
+            base_url: base_url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Crawls a GitBook site and downloads the markdown of its pages and its `SUMMARY.md`
+    ///
+    /// Pages whose markdown cannot be found (non-2xx response) are left out.
+    ///
+    /// # Exemples
+    ///
+    /// ```no_run
+    /// use gitbook2text::{CrawlConfig, GitBookSite};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let site = GitBookSite::fetch("https://docs.example.com", &CrawlConfig::default()).await?;
+    ///     for page in site.pages_in_order() {
+    ///         println!("{}", page.url);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base URL is invalid, if the HTTP client cannot be built
+    /// or if a page download fails for another reason than its status code
+    pub async fn fetch(base_url: &str, config: &CrawlConfig) -> Result<Self, GitBookError> {
+        let base =
+            Url::parse(base_url).map_err(|_| GitBookError::InvalidUrl(base_url.to_string()))?;
+        let client = build_client(config, [&base])?;
+        let mut site = GitBookSite::new(base_url);
+
+        let links: Vec<Result<String, GitBookError>> =
+            extract_links_stream(base_url, config).collect().await;
+        for link in links {
+            let url = match link {
+                Ok(url) => url,
+                Err(e) => {
+                    eprintln!("⚠️ {}", e);
+                    continue;
+                }
+            };
+            let markdown_url = format!("{}.md", url.trim_end_matches(".md"));
+            match download_page_with_client(&client, &markdown_url, None).await {
+                Ok(markdown) => site.add_page(GitBookPage::parse(&url, None, &markdown)),
+                Err(GitBookError::HttpError(..)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Ok(summary_url) = base.join("/SUMMARY.md") {
+            if let Ok(summary) =
+                download_page_with_client(&client, summary_url.as_str(), None).await
+            {
+                site.set_summary(&summary);
+            }
+        }
+
+        Ok(site)
+    }
+
+    /// Adds a page, after the pages already added in discovery order
+    ///
+    /// A page with the same URL as an existing page replaces it.
+    pub fn add_page(&mut self, page: GitBookPage) {
+        if !self.pages.contains_key(&page.url) {
+            self.discovery_order.push(page.url.clone());
+        }
+        self.pages.insert(page.url.clone(), page);
+    }
+
+    /// Sets the book order from the content of the `SUMMARY.md` file of the site
+    ///
+    /// The links of the table of contents are resolved against the base URL.
+    pub fn set_summary(&mut self, summary: &str) {
+        let base = format!("{}/", self.base_url.trim_end_matches('/'));
+        self.summary_order = Some(extract_links_from_markdown(summary, &base));
+    }
+
+    /// Returns the page with the given URL
+    pub fn page(&self, url: &str) -> Option<&GitBookPage> {
+        self.pages.get(url)
+    }
+
+    /// Returns the number of pages of the site
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns `true` if the site has no page
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Returns the pages in book order
+    ///
+    /// The pages listed in `SUMMARY.md` come first, in the order of the table of
+    /// contents, followed by the other pages in discovery order. Without a
+    /// `SUMMARY.md`, all pages are returned in discovery order.
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::{GitBookPage, GitBookSite};
+    ///
+    /// let mut site = GitBookSite::new("https://docs.example.com");
+    /// site.add_page(GitBookPage::parse("https://docs.example.com/setup", None, "# Setup"));
+    /// site.add_page(GitBookPage::parse("https://docs.example.com", None, "# Intro"));
+    /// site.set_summary("* [Intro](README.md)\n* [Setup](setup.md)\n");
+    ///
+    /// let titles: Vec<_> = site
+    ///     .pages_in_order()
+    ///     .iter()
+    ///     .filter_map(|page| page.metadata.title.as_deref())
+    ///     .collect();
+    /// assert_eq!(titles, ["Intro", "Setup"]);
+    /// ```
+    pub fn pages_in_order(&self) -> Vec<&GitBookPage> {
+        let by_key: HashMap<String, &str> = self
+            .discovery_order
+            .iter()
+            .map(|url| (page_key(url), url.as_str()))
+            .collect();
+
+        let mut ordered: Vec<&str> = Vec::new();
+        for entry in self.summary_order.iter().flatten() {
+            if let Some(url) = by_key.get(&page_key(entry)) {
+                if !ordered.contains(url) {
+                    ordered.push(url);
+                }
+            }
+        }
+        for url in &self.discovery_order {
+            if !ordered.contains(&url.as_str()) {
+                ordered.push(url);
+            }
+        }
+
+        ordered
+            .into_iter()
+            .filter_map(|url| self.pages.get(url))
+            .collect()
+    }
+}
+
+/// Normalizes a page URL or a `SUMMARY.md` link so that both designate the same page
+///
+/// `guide/README.md`, `guide/` and `guide` all map to `guide`.
+fn page_key(url: &str) -> String {
+    let url = url.split('#').next().unwrap_or(url);
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".md").unwrap_or(url);
+    let url = match url.rsplit_once('/') {
+        Some((parent, last)) if last.eq_ignore_ascii_case("readme") => parent,
+        _ => url,
+    };
+    url.trim_end_matches('/').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::MockServer;
+
+    #[test]
+    fn test_page_key() {
+        assert_eq!(
+            page_key("https://x.com/guide/README.md"),
+            "https://x.com/guide"
+        );
+        assert_eq!(page_key("https://x.com/guide/"), "https://x.com/guide");
+        assert_eq!(
+            page_key("https://x.com/guide/setup.md#install"),
+            "https://x.com/guide/setup"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pages_in_order_follows_summary() {
+        let base = MockServer::pages(vec![
+            (
+                "/",
+                r#"<script src="https://static.gitbook.com/app.js"></script><a href="/c">C</a><a href="/b">B</a><a href="/a">A</a>"#,
+            ),
+            ("/a", "<p>A</p>"),
+            ("/b", "<p>B</p>"),
+            ("/c", "<p>C</p>"),
+            ("/a.md", "# Alpha"),
+            ("/b.md", "# Beta"),
+            ("/c.md", "# Gamma"),
+            ("/SUMMARY.md", "# Table of contents\n\n* [Beta](b.md)\n* [Alpha](a.md)\n"),
+        ])
+        .await
+        .url;
+
+        let site = GitBookSite::fetch(&base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(site.len(), 3);
+
+        let titles: Vec<&str> = site
+            .pages_in_order()
+            .iter()
+            .filter_map(|page| page.metadata.title.as_deref())
+            .collect();
+        assert_eq!(titles, ["Beta", "Alpha", "Gamma"]);
+    }
+
+    #[test]
+    fn test_pages_in_order_without_summary() {
+        let mut site = GitBookSite::new("https://docs.example.com");
+        site.add_page(GitBookPage::parse(
+            "https://docs.example.com/b",
+            None,
+            "# B",
+        ));
+        site.add_page(GitBookPage::parse(
+            "https://docs.example.com/a",
+            None,
+            "# A",
+        ));
+
+        let urls: Vec<&str> = site
+            .pages_in_order()
+            .iter()
+            .map(|page| page.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            ["https://docs.example.com/b", "https://docs.example.com/a"]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Orderings of the crawled links, to use as [`CrawlConfig::link_sorter`](crate::CrawlConfig::link_sorter)
+//!
+//! # Exemples
+//!
+//! ```
+//! use gitbook2text::{sorters, CrawlConfig};
+//! use std::sync::Arc;
+//!
+//! let config = CrawlConfig {
+//!     link_sorter: Some(Arc::new(sorters::depth_first_sort)),
+//!     ..Default::default()
+//! };
+//! assert!(config.link_sorter.is_some());
+//! ```
+
+use std::cmp::Ordering;
+use url::Url;
+
+/// Sorts the links by URL, like the default output
+pub fn lexicographic_sort(a: &str, b: &str) -> Ordering {
+    a.cmp(b)
+}
+
+/// Keeps the links in their breadth-first discovery order
+///
+/// Every pair compares equal and the sort is stable, so the order of the crawl is kept
+/// even when `sort_output` is enabled.
+pub fn discovery_order_sort(_: &str, _: &str) -> Ordering {
+    Ordering::Equal
+}
+
+/// Sorts the links like a table of contents: each page is followed by the pages under it
+///
+/// The URLs are compared host first, then path segment by path segment, so
+/// `/guide/intro` comes right after `/guide` and before `/guide-v2`.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::sorters::depth_first_sort;
+///
+/// let mut links = vec![
+///     "https://docs.example.com/guide-v2",
+///     "https://docs.example.com/guide/intro",
+///     "https://docs.example.com/guide",
+/// ];
+/// links.sort_by(|a, b| depth_first_sort(a, b));
+/// assert_eq!(
+///     links,
+///     [
+///         "https://docs.example.com/guide",
+///         "https://docs.example.com/guide/intro",
+///         "https://docs.example.com/guide-v2",
+///     ]
+/// );
+/// ```
+pub fn depth_first_sort(a: &str, b: &str) -> Ordering {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(url_a), Ok(url_b)) => url_a
+            .host_str()
+            .cmp(&url_b.host_str())
+            .then_with(|| path_segments(&url_a).cmp(path_segments(&url_b)))
+            .then_with(|| a.cmp(b)),
+        _ => a.cmp(b),
+    }
+}
+
+/// Sorts the links by the length of their path, shortest first, then by URL
+///
+/// The pages closest to the root of the site come first.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::sorters::path_length_sort;
+///
+/// let mut links = vec![
+///     "https://docs.example.com/api/reference",
+///     "https://docs.example.com/faq",
+///     "https://docs.example.com/",
+/// ];
+/// links.sort_by(|a, b| path_length_sort(a, b));
+/// assert_eq!(
+///     links,
+///     [
+///         "https://docs.example.com/",
+///         "https://docs.example.com/faq",
+///         "https://docs.example.com/api/reference",
+///     ]
+/// );
+/// ```
+pub fn path_length_sort(a: &str, b: &str) -> Ordering {
+    let path_len = |link: &str| Url::parse(link).map_or(link.len(), |url| url.path().len());
+    path_len(a).cmp(&path_len(b)).then_with(|| a.cmp(b))
+}
+
+/// The non-empty segments of the path of a URL
+fn path_segments(url: &Url) -> impl Iterator<Item = &str> {
+    url.path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+}
@@ -0,0 +1,497 @@
+use crate::config::CrawlConfig;
+use crate::crawler::{build_client, extract_gitbook_links_with_config};
+use crate::page::GitBookPage;
+use crate::site::GitBookSite;
+use crate::utils::{download_page_with_client, strip_md_suffix};
+use crate::GitBookError;
+use serde::Deserialize;
+use url::Url;
+
+/// Base URL of the GitHub REST API
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Base URL of the raw content of the GitHub repositories
+const GITHUB_RAW_URL: &str = "https://raw.githubusercontent.com";
+
+/// Where the markdown of a documentation comes from
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::Source;
+///
+/// assert_eq!(
+///     Source::from_url("https://github.com/acme/docs/tree/main/guide"),
+///     Source::GitHub {
+///         owner: "acme".to_string(),
+///         repo: "docs".to_string(),
+///         branch: "main".to_string(),
+///         path: "guide".to_string(),
+///     }
+/// );
+/// assert!(matches!(
+///     Source::from_url("https://docs.example.com"),
+///     Source::GitBookSite { .. }
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A published GitBook, crawled from its base URL
+    GitBookSite { base_url: String },
+
+    /// The `.md` files under `path` in a GitHub repository, read through the GitHub API
+    /// and the raw content of the repository; `path` is empty for the whole repository
+    GitHub {
+        owner: String,
+        repo: String,
+        branch: String,
+        path: String,
+    },
+}
+
+/// The part of a GitHub tree response read by [`Source::markdown_urls`]
+#[derive(Deserialize)]
+struct GitHubTree {
+    tree: Vec<GitHubTreeEntry>,
+    #[serde(default)]
+    truncated: bool,
+}
+
+#[derive(Deserialize)]
+struct GitHubTreeEntry {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl Source {
+    /// Returns the configuration of the requests to the source
+    ///
+    /// For a GitHub repository, `github_token` replaces the bearer token, and the
+    /// cookies and headers meant for the GitBook are not sent; a GitBook site uses
+    /// `config` as it is.
+    pub fn request_config(&self, config: &CrawlConfig) -> CrawlConfig {
+        match self {
+            Source::GitBookSite { .. } => config.clone(),
+            Source::GitHub { .. } => github_config(config),
+        }
+    }
+
+    /// Returns the source of a URL
+    ///
+    /// `https://github.com/{owner}/{repo}`, optionally followed by `/tree/{branch}/{path}`,
+    /// and `https://raw.githubusercontent.com/{owner}/{repo}/{branch}/{path}` are GitHub
+    /// repositories; without a branch, the default branch (`HEAD`) is read. Any other
+    /// URL is a GitBook site.
+    ///
+    /// The first segment after the repository is taken as the branch. As in the GitHub
+    /// URLs, a branch name with `/` (`feature/new-api`) is only told apart from the path
+    /// when the files are listed: the path segments are moved to the branch until the
+    /// GitHub API knows it.
+    pub fn from_url(url: &str) -> Source {
+        let github = Url::parse(url).ok().and_then(|parsed| {
+            let segments: Vec<&str> = parsed
+                .path_segments()?
+                .filter(|segment| !segment.is_empty())
+                .collect();
+            let (owner, repo, rest) = match (parsed.host_str()?, segments.as_slice()) {
+                ("github.com" | "www.github.com", [owner, repo, rest @ ..]) => match rest {
+                    [] => (owner, repo, ["HEAD"].as_slice()),
+                    ["tree" | "blob", rest @ ..] if !rest.is_empty() => (owner, repo, rest),
+                    _ => return None,
+                },
+                ("raw.githubusercontent.com", [owner, repo, rest @ ..]) if !rest.is_empty() => {
+                    (owner, repo, rest)
+                }
+                _ => return None,
+            };
+            Some(Source::GitHub {
+                owner: owner.to_string(),
+                repo: repo.trim_end_matches(".git").to_string(),
+                branch: rest[0].to_string(),
+                path: rest[1..].join("/"),
+            })
+        });
+        github.unwrap_or_else(|| Source::GitBookSite {
+            base_url: url.to_string(),
+        })
+    }
+
+    /// Lists the URLs of the markdown of the pages
+    ///
+    /// A GitBook site is crawled and `.md` is appended to its page URLs. For a GitHub
+    /// repository, the files are listed with the GitHub API and the raw URLs of the
+    /// `.md` files under `path` are returned, `SUMMARY.md` included, sorted by path.
+    /// The GitHub requests send `github_token` if set.
+    ///
+    /// # Exemples
+    ///
+    /// ```no_run
+    /// use gitbook2text::{download_pages, CrawlConfig, DownloadConfig, Source};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let config = CrawlConfig::from_env();
+    ///     let source = Source::from_url("https://github.com/acme/docs/tree/main/docs");
+    ///     let urls = source.markdown_urls(&config).await?;
+    ///     download_pages(urls, &config, &DownloadConfig::default()).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is invalid, if the HTTP client cannot be built or if
+    /// the GitHub API answers with an error status
+    pub async fn markdown_urls(&self, config: &CrawlConfig) -> Result<Vec<String>, GitBookError> {
+        self.markdown_urls_from(GITHUB_API_URL, GITHUB_RAW_URL, config)
+            .await
+    }
+
+    async fn markdown_urls_from(
+        &self,
+        api_url: &str,
+        raw_url: &str,
+        config: &CrawlConfig,
+    ) -> Result<Vec<String>, GitBookError> {
+        let (owner, repo, branch, path) = match self {
+            Source::GitBookSite { base_url } => {
+                let links = extract_gitbook_links_with_config(base_url, config).await?;
+                return Ok(links
+                    .into_iter()
+                    .map(|link| format!("{}.md", link.trim_end_matches(".md")))
+                    .collect());
+            }
+            Source::GitHub {
+                owner,
+                repo,
+                branch,
+                path,
+            } => (owner, repo, branch, path),
+        };
+
+        let (_, urls) =
+            github_markdown(owner, repo, branch, path, api_url, raw_url, config).await?;
+        Ok(urls)
+    }
+
+    /// Downloads the pages of the source
+    ///
+    /// A GitBook site is read with [`GitBookSite::fetch`]. The `.md` files of a GitHub
+    /// repository become the pages, named after their raw URL without `.md`, and the
+    /// `SUMMARY.md` at the root of `path`, if any, sets the book order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files cannot be listed (see [`Source::markdown_urls`])
+    /// or if a download fails for another reason than its status code
+    pub async fn fetch(&self, config: &CrawlConfig) -> Result<GitBookSite, GitBookError> {
+        self.fetch_from(GITHUB_API_URL, GITHUB_RAW_URL, config)
+            .await
+    }
+
+    async fn fetch_from(
+        &self,
+        api_url: &str,
+        raw_url: &str,
+        config: &CrawlConfig,
+    ) -> Result<GitBookSite, GitBookError> {
+        let (owner, repo, branch, path) = match self {
+            Source::GitBookSite { base_url } => return GitBookSite::fetch(base_url, config).await,
+            Source::GitHub {
+                owner,
+                repo,
+                branch,
+                path,
+            } => (owner, repo, branch, path),
+        };
+
+        let (base, urls) =
+            github_markdown(owner, repo, branch, path, api_url, raw_url, config).await?;
+        let client = build_client(&github_config(config), [])?;
+        let mut site = GitBookSite::new(base.trim_end_matches('/'));
+
+        for url in urls {
+            let markdown = match download_page_with_client(&client, &url, None).await {
+                Ok(markdown) => markdown,
+                Err(GitBookError::HttpError(..)) => continue,
+                Err(e) => return Err(e),
+            };
+            let is_summary = url
+                .strip_prefix(&base)
+                .is_some_and(|file| file.eq_ignore_ascii_case("SUMMARY.md"));
+            if is_summary {
+                site.set_summary(&markdown);
+            } else {
+                let page_url = strip_md_suffix(&url);
+                site.add_page(GitBookPage::parse(page_url, None, &markdown));
+            }
+        }
+        Ok(site)
+    }
+}
+
+/// Lists the `.md` files under `path` in a GitHub repository
+///
+/// Returns the raw URL of the folder, ending with `/`, and the raw URLs of the files.
+/// While the tree of `branch` is not found, the first segment of `path` is moved to
+/// the branch name, as the branch names may contain `/`.
+async fn github_markdown(
+    owner: &str,
+    repo: &str,
+    branch: &str,
+    path: &str,
+    api_url: &str,
+    raw_url: &str,
+    config: &CrawlConfig,
+) -> Result<(String, Vec<String>), GitBookError> {
+    let api = Url::parse(api_url).map_err(|_| GitBookError::InvalidUrl(api_url.to_string()))?;
+    let client = build_client(&github_config(config), [&api])?;
+
+    let mut branch = branch.to_string();
+    let mut path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let tree = loop {
+        let mut tree_url = api.clone();
+        tree_url
+            .path_segments_mut()
+            .map_err(|_| GitBookError::InvalidUrl(api_url.to_string()))?
+            .pop_if_empty()
+            .extend(["repos", owner, repo, "git", "trees", &branch]);
+        tree_url.set_query(Some("recursive=1"));
+
+        let response = client
+            .get(tree_url.clone())
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND && !path.is_empty() {
+            branch = format!("{}/{}", branch, path.remove(0));
+            continue;
+        }
+        if !status.is_success() {
+            return Err(GitBookError::HttpError(
+                status.as_u16(),
+                tree_url.to_string(),
+            ));
+        }
+        let tree: GitHubTree = serde_json::from_str(&response.text().await?)
+            .map_err(|e| GitBookError::ParseError(format!("{}: {}", tree_url, e)))?;
+        break tree;
+    };
+    if tree.truncated {
+        eprintln!(
+            "⚠️ {}/{}: the repository is too large, some files are not listed",
+            owner, repo
+        );
+    }
+
+    let prefix = path.join("/");
+    let mut files: Vec<String> = tree
+        .tree
+        .into_iter()
+        .filter(|entry| entry.kind == "blob")
+        .map(|entry| entry.path)
+        .filter(|file| file.to_lowercase().ends_with(".md"))
+        .filter(|file| {
+            prefix.is_empty()
+                || file
+                    .strip_prefix(&prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .collect();
+    files.sort();
+
+    let raw = Url::parse(raw_url).map_err(|_| GitBookError::InvalidUrl(raw_url.to_string()))?;
+    let urls = files
+        .iter()
+        .map(|file| {
+            let mut url = raw.clone();
+            url.path_segments_mut()
+                .map_err(|_| GitBookError::InvalidUrl(raw_url.to_string()))?
+                .pop_if_empty()
+                .extend([owner, repo])
+                .extend(branch.split('/'))
+                .extend(file.split('/'));
+            Ok(url.to_string())
+        })
+        .collect::<Result<_, GitBookError>>()?;
+
+    let mut base = format!(
+        "{}/{}/{}/{}/",
+        raw_url.trim_end_matches('/'),
+        owner,
+        repo,
+        branch
+    );
+    if !prefix.is_empty() {
+        base = format!("{}{}/", base, prefix);
+    }
+    Ok((base, urls))
+}
+
+/// The configuration of the GitHub requests: `github_token` replaces the bearer token,
+/// and the cookies and headers of the GitBook are not sent
+fn github_config(config: &CrawlConfig) -> CrawlConfig {
+    CrawlConfig {
+        bearer_token: config.github_token.clone(),
+        cookies: Vec::new(),
+        extra_headers: Vec::new(),
+        ..config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::MockServer;
+
+    #[test]
+    fn test_source_from_url() {
+        let github = |owner: &str, repo: &str, branch: &str, path: &str| Source::GitHub {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            branch: branch.to_string(),
+            path: path.to_string(),
+        };
+        assert_eq!(
+            Source::from_url("https://github.com/acme/docs.git"),
+            github("acme", "docs", "HEAD", "")
+        );
+        assert_eq!(
+            Source::from_url("https://raw.githubusercontent.com/acme/docs/v2/a/b/"),
+            github("acme", "docs", "v2", "a/b")
+        );
+        assert_eq!(
+            Source::from_url("https://github.com/acme/docs/issues"),
+            Source::GitBookSite {
+                base_url: "https://github.com/acme/docs/issues".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_repository() {
+        let server = MockServer::pages(vec![
+            (
+                "/repos/acme/docs/git/trees/main?recursive=1",
+                r#"{"tree": [
+                    {"path": "README.md", "type": "blob"},
+                    {"path": "docs", "type": "tree"},
+                    {"path": "docs/SUMMARY.md", "type": "blob"},
+                    {"path": "docs/setup.md", "type": "blob"},
+                    {"path": "docs/intro.md", "type": "blob"},
+                    {"path": "docs/logo.png", "type": "blob"},
+                    {"path": "docs-old/setup.md", "type": "blob"}
+                ], "truncated": false}"#,
+            ),
+            (
+                "/acme/docs/main/docs/SUMMARY.md",
+                "* [Intro](intro.md)\n* [Setup](setup.md)\n",
+            ),
+            ("/acme/docs/main/docs/setup.md", "# Setup"),
+            ("/acme/docs/main/docs/intro.md", "# Intro"),
+        ])
+        .await;
+        let base = &server.url;
+        let source = Source::GitHub {
+            owner: "acme".to_string(),
+            repo: "docs".to_string(),
+            branch: "main".to_string(),
+            path: "docs".to_string(),
+        };
+        let config = CrawlConfig {
+            github_token: Some("ghp_secret".to_string()),
+            ..Default::default()
+        };
+
+        let urls = source
+            .markdown_urls_from(base, base, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            urls,
+            [
+                format!("{}/acme/docs/main/docs/SUMMARY.md", base),
+                format!("{}/acme/docs/main/docs/intro.md", base),
+                format!("{}/acme/docs/main/docs/setup.md", base),
+            ]
+        );
+        assert!(server.requests()[0]
+            .to_lowercase()
+            .contains("authorization: bearer ghp_secret"));
+
+        let site = source.fetch_from(base, base, &config).await.unwrap();
+        let titles: Vec<&str> = site
+            .pages_in_order()
+            .iter()
+            .filter_map(|page| page.metadata.title.as_deref())
+            .collect();
+        assert_eq!(titles, ["Intro", "Setup"]);
+
+        let missing = Source::GitHub {
+            owner: "acme".to_string(),
+            repo: "gone".to_string(),
+            branch: "main".to_string(),
+            path: String::new(),
+        };
+        assert!(matches!(
+            missing.markdown_urls_from(base, base, &config).await,
+            Err(GitBookError::HttpError(404, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_branch_with_slash() {
+        let server = MockServer::pages(vec![
+            (
+                "/repos/acme/docs/git/trees/feature%2Fnew-api?recursive=1",
+                r#"{"tree": [
+                    {"path": "docs/intro.md", "type": "blob"},
+                    {"path": "docs/GUIDE.MD", "type": "blob"}
+                ]}"#,
+            ),
+            ("/acme/docs/feature/new-api/docs/GUIDE.MD", "# Guide"),
+            ("/acme/docs/feature/new-api/docs/intro.md", "# Intro"),
+        ])
+        .await;
+        let base = &server.url;
+        let source = Source::from_url("https://github.com/acme/docs/tree/feature/new-api/docs");
+
+        let site = source
+            .fetch_from(base, base, &CrawlConfig::default())
+            .await
+            .unwrap();
+        let urls: Vec<&str> = site
+            .pages_in_order()
+            .iter()
+            .map(|page| page.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                format!("{}/acme/docs/feature/new-api/docs/GUIDE", base),
+                format!("{}/acme/docs/feature/new-api/docs/intro", base),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_config() {
+        let config = CrawlConfig {
+            bearer_token: Some("gitbook-token".to_string()),
+            github_token: Some("ghp_secret".to_string()),
+            cookies: vec![("session".to_string(), "abc".to_string())],
+            extra_headers: vec![("X-Team".to_string(), "docs".to_string())],
+            ..Default::default()
+        };
+
+        let github = Source::from_url("https://github.com/acme/docs").request_config(&config);
+        assert_eq!(github.bearer_token.as_deref(), Some("ghp_secret"));
+        assert!(github.cookies.is_empty() && github.extra_headers.is_empty());
+
+        let gitbook = Source::from_url("https://docs.example.com").request_config(&config);
+        assert_eq!(gitbook.bearer_token.as_deref(), Some("gitbook-token"));
+        assert_eq!(gitbook.extra_headers, config.extra_headers);
+    }
+}
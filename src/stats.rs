@@ -0,0 +1,245 @@
+use crate::search::{collect_files, read_output_file};
+use crate::GitBookError;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Common English words left out of the word frequencies
+#[rustfmt::skip]
+pub const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "also", "am", "an", "and", "any",
+    "are", "as", "at", "be", "because", "been", "before", "being", "below", "between", "both",
+    "but", "by", "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few",
+    "for", "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers",
+    "herself", "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "its",
+    "itself", "just", "me", "more", "most", "must", "my", "myself", "no", "nor", "not", "now",
+    "of", "off", "on", "once", "only", "or", "other", "our", "ours", "ourselves", "out", "over",
+    "own", "same", "she", "should", "so", "some", "such", "than", "that", "the", "their",
+    "theirs", "them", "themselves", "then", "there", "these", "they", "this", "those",
+    "through", "to", "too", "under", "until", "up", "use", "used", "using", "very", "was", "we",
+    "were", "what", "when", "where", "which", "while", "who", "whom", "why", "will", "with",
+    "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+/// A word and its number of occurrences
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WordCount {
+    /// The word, lowercased
+    pub word: String,
+
+    /// The number of occurrences
+    pub count: usize,
+}
+
+/// The word statistics of one text file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageStats {
+    /// The text file
+    pub path: PathBuf,
+
+    /// The number of words of the page, stop words included
+    pub word_count: usize,
+
+    /// The most frequent words of the page, stop words excluded
+    pub top_words: Vec<WordCount>,
+}
+
+/// The word statistics of a directory of text files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextStats {
+    /// The number of words of all pages, stop words included
+    pub word_count: usize,
+
+    /// The most frequent words of all pages, stop words excluded
+    pub top_words: Vec<WordCount>,
+
+    /// The statistics of each page, in path order
+    pub pages: Vec<PageStats>,
+}
+
+/// Returns [`ENGLISH_STOP_WORDS`] as a set
+pub fn english_stop_words() -> HashSet<String> {
+    ENGLISH_STOP_WORDS.iter().map(|w| w.to_string()).collect()
+}
+
+/// Counts the occurrences of each word in the given texts
+///
+/// Words are sequences of letters, digits and inner apostrophes, compared in
+/// lowercase. Stop words and tokens without any letter are left out.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{compute_word_frequencies, english_stop_words};
+///
+/// let freqs = compute_word_frequencies(
+///     &["The API returns JSON.", "Call the API twice"],
+///     &english_stop_words(),
+/// );
+/// assert_eq!(freqs["api"], 2);
+/// assert!(!freqs.contains_key("the"));
+/// ```
+pub fn compute_word_frequencies(
+    texts: &[&str],
+    stop_words: &HashSet<String>,
+) -> HashMap<String, usize> {
+    let mut freqs = HashMap::new();
+    for text in texts {
+        for word in words(text) {
+            if !stop_words.contains(&word) {
+                *freqs.entry(word).or_insert(0) += 1;
+            }
+        }
+    }
+    freqs
+}
+
+/// Returns the `n` most frequent words, ties broken alphabetically
+pub fn top_words(freqs: &HashMap<String, usize>, n: usize) -> Vec<WordCount> {
+    let mut words: Vec<WordCount> = freqs
+        .iter()
+        .map(|(word, count)| WordCount {
+            word: word.clone(),
+            count: *count,
+        })
+        .collect();
+    words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+    words.truncate(n);
+    words
+}
+
+/// Computes the word statistics of all saved `.txt` files of a directory
+///
+/// The directory is scanned recursively and Zstd-compressed `.txt.zst` files
+/// are decompressed transparently.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the downloaded files
+/// * `top` - The number of words kept in each top list
+/// * `stop_words` - The words left out of the top lists
+///
+/// # Errors
+///
+/// Returns an error if the directory or one of its files cannot be read
+pub fn compute_stats(
+    dir: &Path,
+    top: usize,
+    stop_words: &HashSet<String>,
+) -> Result<TextStats, GitBookError> {
+    let mut files = Vec::new();
+    collect_files(dir, &[".txt", ".txt.zst"], &mut files)?;
+    files.sort();
+
+    let mut total = HashMap::new();
+    let mut word_count = 0;
+    let mut pages = Vec::new();
+    for path in files {
+        let content = read_output_file(&path)?;
+        let page_count = words(&content).count();
+        let freqs = compute_word_frequencies(&[&content], stop_words);
+        for (word, count) in &freqs {
+            *total.entry(word.clone()).or_insert(0) += count;
+        }
+        word_count += page_count;
+        pages.push(PageStats {
+            path,
+            word_count: page_count,
+            top_words: top_words(&freqs, top),
+        });
+    }
+
+    Ok(TextStats {
+        word_count,
+        top_words: top_words(&total, top),
+        pages,
+    })
+}
+
+/// Draws a horizontal bar chart of word counts, the longest bar being `width` characters
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{word_histogram, WordCount};
+///
+/// let words = vec![
+///     WordCount { word: "api".to_string(), count: 4 },
+///     WordCount { word: "token".to_string(), count: 2 },
+/// ];
+/// assert_eq!(word_histogram(&words, 4), "api   ████ 4\ntoken ██ 2");
+/// ```
+pub fn word_histogram(words: &[WordCount], width: usize) -> String {
+    let max = words.iter().map(|w| w.count).max().unwrap_or(0).max(1);
+    let label_width = words
+        .iter()
+        .map(|w| w.word.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    words
+        .iter()
+        .map(|w| {
+            let bar = (w.count * width).div_ceil(max);
+            let padding = label_width - w.word.chars().count();
+            format!(
+                "{}{} {} {}",
+                w.word,
+                " ".repeat(padding),
+                "█".repeat(bar),
+                w.count
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Splits a text into lowercase words containing at least one letter
+pub(crate) fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '’')
+        .map(|token| token.trim_matches(|c| c == '\'' || c == '’'))
+        .filter(|token| token.chars().any(char::is_alphabetic))
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_compute_word_frequencies() {
+        let stop_words = english_stop_words();
+        let freqs = compute_word_frequencies(
+            &["Rust's API: the 'token' API, 2 tokens.", "API keys and 42"],
+            &stop_words,
+        );
+        assert_eq!(freqs["api"], 3);
+        assert_eq!(freqs["rust's"], 1);
+        assert_eq!(freqs["token"], 1);
+        assert!(!freqs.contains_key("the"));
+        assert!(!freqs.contains_key("and"));
+        assert!(!freqs.contains_key("42"));
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("txt")).unwrap();
+        fs::write(dir.path().join("txt/a.txt"), "token token api").unwrap();
+        fs::write(dir.path().join("txt/b.txt"), "the api api api").unwrap();
+        fs::write(dir.path().join("a.md"), "token token token").unwrap();
+
+        let stats = compute_stats(dir.path(), 1, &english_stop_words()).unwrap();
+        assert_eq!(stats.word_count, 7);
+        assert_eq!(
+            stats.top_words,
+            vec![WordCount {
+                word: "api".to_string(),
+                count: 4
+            }]
+        );
+        assert_eq!(stats.pages.len(), 2);
+        assert_eq!(stats.pages[0].top_words[0].word, "token");
+    }
+}
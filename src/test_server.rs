@@ -0,0 +1,137 @@
+//! A local HTTP server for the tests, answering each request with a handler
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A request received by a [`MockServer`]
+pub(crate) struct MockRequest {
+    /// The path of the request line, with its query
+    pub path: String,
+
+    /// The request as received, request line and headers included
+    pub raw: String,
+}
+
+impl MockRequest {
+    /// Returns the value of a header, compared without case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.raw.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then_some(value.trim())
+        })
+    }
+}
+
+/// The answer of a [`MockServer`] to a request
+pub(crate) struct MockResponse {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+    delay: Duration,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        MockResponse {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        MockResponse::new(200, body)
+    }
+
+    pub fn not_found() -> Self {
+        MockResponse::new(404, "")
+    }
+
+    /// Adds a header to the response
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Waits before answering
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {} Status\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+/// A server on a local port, started by [`MockServer::start`]
+pub(crate) struct MockServer {
+    /// The base URL of the server, `http://127.0.0.1:port`
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Starts a server answering each request with `handler`, one task per connection
+    pub async fn start(
+        handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        let handler = Arc::new(handler);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let handler = handler.clone();
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 8192];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let raw = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let path = raw.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    received.lock().unwrap().push(raw.clone());
+
+                    let response = handler(&MockRequest { path, raw });
+                    tokio::time::sleep(response.delay).await;
+                    let _ = socket.write_all(&response.to_bytes()).await;
+                });
+            }
+        });
+
+        MockServer { url, requests }
+    }
+
+    /// Starts a server answering the given paths with a fixed body and 404 otherwise
+    pub async fn pages(pages: Vec<(&'static str, &'static str)>) -> Self {
+        MockServer::start(move |request| {
+            match pages.iter().find(|(path, _)| *path == request.path) {
+                Some((_, body)) => MockResponse::ok(*body),
+                None => MockResponse::not_found(),
+            }
+        })
+        .await
+    }
+
+    /// Returns the requests received so far, as received
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
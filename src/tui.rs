@@ -0,0 +1,185 @@
+use gitbook2text::{
+    download_pages_with_events, CrawlConfig, DownloadConfig, DownloadEvent, DownloadReport,
+};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Number of events kept in the scrolling log
+const LOG_LINES: usize = 20;
+
+/// Width of the progress bar, in characters
+const BAR_WIDTH: usize = 40;
+
+/// The state shown by the terminal UI, updated from the download events
+struct Progress {
+    total: usize,
+    done: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    limit_reached: bool,
+    log: VecDeque<String>,
+    start: Instant,
+}
+
+impl Progress {
+    fn new() -> Self {
+        Progress {
+            total: 0,
+            done: 0,
+            skipped: 0,
+            failed: 0,
+            bytes: 0,
+            limit_reached: false,
+            log: VecDeque::with_capacity(LOG_LINES),
+            start: Instant::now(),
+        }
+    }
+
+    fn apply(&mut self, event: DownloadEvent) {
+        match &event {
+            DownloadEvent::Started { total } => self.total = *total,
+            DownloadEvent::PageStarted { .. } => return,
+            DownloadEvent::Saved { bytes, .. } => {
+                self.done += 1;
+                self.bytes += bytes;
+            }
+            DownloadEvent::ChapterSaved { pages, bytes, .. } => {
+                self.done += pages;
+                self.bytes += bytes;
+            }
+            DownloadEvent::Skipped { .. } => {
+                self.done += 1;
+                self.skipped += 1;
+            }
+            DownloadEvent::LimitReached => self.limit_reached = true,
+            DownloadEvent::DownloadFailed { .. } | DownloadEvent::SaveFailed { .. } => {
+                self.done += 1;
+                self.failed += 1;
+            }
+        }
+
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(event.to_string());
+    }
+
+    /// Pages processed per second since the start
+    fn rate(&self) -> f64 {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.done as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time left at the current rate
+    fn eta(&self) -> Option<Duration> {
+        let rate = self.rate();
+        let left = self.total.saturating_sub(self.done);
+        (rate > 0.0).then(|| Duration::from_secs((left as f64 / rate).ceil() as u64))
+    }
+
+    fn render(&self) -> String {
+        let ratio = if self.total > 0 {
+            (self.done as f64 / self.total as f64).min(1.0)
+        } else {
+            0.0
+        };
+        let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+        let eta = match self.eta() {
+            Some(eta) => humantime::format_duration(eta).to_string(),
+            None => "-".to_string(),
+        };
+
+        let mut screen = format!(
+            "📥 Downloading {}/{} page(s)\n[{}{}] {:.0}%\n",
+            self.done,
+            self.total,
+            "█".repeat(filled),
+            "░".repeat(BAR_WIDTH - filled),
+            ratio * 100.0
+        );
+        screen.push_str(&format!(
+            "⚡ {:.1} page(s)/s  ⏳ ETA {}  ⏭️ {} skipped  ❌ {} failed  💾 {} byte(s)\n",
+            self.rate(),
+            eta,
+            self.skipped,
+            self.failed,
+            self.bytes
+        ));
+        if self.limit_reached {
+            screen.push_str("⚠️ Total size limit reached, stopping\n");
+        }
+        screen.push('\n');
+        for line in &self.log {
+            screen.push_str(line);
+            screen.push('\n');
+        }
+        screen
+    }
+}
+
+/// Switches to the alternate screen, and back when dropped
+struct Screen;
+
+impl Screen {
+    fn enter() -> Self {
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = std::io::stdout().flush();
+        Screen
+    }
+
+    fn draw(&self, progress: &Progress) {
+        print!("\x1b[H\x1b[2J{}", progress.render());
+        let _ = std::io::stdout().flush();
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Downloads pages like [`gitbook2text::download_pages`], showing the progress in a terminal UI
+///
+/// Ctrl-C restores the terminal and stops the download.
+pub async fn download_pages(
+    urls: Vec<String>,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<DownloadReport, Box<dyn std::error::Error>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let download = download_pages_with_events(urls, config, download_config, tx);
+    tokio::pin!(download);
+
+    let mut progress = Progress::new();
+    let mut ticker = tokio::time::interval(Duration::from_millis(200));
+    let screen = Screen::enter();
+
+    let result = loop {
+        tokio::select! {
+            result = &mut download => break result,
+            Some(event) = rx.recv() => progress.apply(event),
+            _ = ticker.tick() => screen.draw(&progress),
+            _ = tokio::signal::ctrl_c() => {
+                drop(screen);
+                return Err("Interrupted".into());
+            }
+        }
+    };
+
+    while let Ok(event) = rx.try_recv() {
+        progress.apply(event);
+    }
+    screen.draw(&progress);
+    drop(screen);
+
+    Ok(result?)
+}
@@ -1,6 +1,64 @@
-use pulldown_cmark::{Event, Parser};
+use crate::config::{CrawlConfig, SanitizeConfig, TextConfig, UnicodeForm};
+use crate::manifest::parse_sha256;
+use crate::GitBookError;
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Parser, Tag, TagEnd,
+};
 use regex::Regex;
+use scraper::{Html, Node, Selector};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+use termcolor::{Color, ColorSpec, WriteColor};
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use url::Url;
+use whatlang::Lang;
+
+// The regexes of `txt_sanitize_with_context` and `write_text_colored`, compiled on first use
+
+static RE_CONTENT_REF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?s)\{%\s*content-ref\s+url\s*=\s*"([^"]*)"\s*%\}(.*?)\{%\s*endcontent-ref\s*%\}"#,
+    )
+    .expect("static regex is valid")
+});
+static RE_HINT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)\{%\s*hint\s+style\s*=\s*["']?(\w+)["']?\s*%\}(.*?)\{%\s*endhint\s*%\}"#)
+        .expect("static regex is valid")
+});
+static RE_CODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{%\s*code[^}]*title\s*=\s*"([^"]+)"[^}]*%}(.*?)\{%\s*endcode\s*%\}"#)
+        .expect("static regex is valid")
+});
+static RE_CODE_NO_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{%\s*code[^}]*%}(.*?)\{%\s*endcode\s*%\}"#).expect("static regex is valid")
+});
+static RE_API: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)\{%\s*(?:swagger|openapi)\s(.*?)%\}"#).expect("static regex is valid")
+});
+static RE_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(\w+)\s*=\s*"([^"]*)""#).expect("static regex is valid"));
+static RE_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{%\s*[^}]*title\s*=\s*"([^"]+)"[^}]*%\}"#).expect("static regex is valid")
+});
+static RE_GENERIC: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\{%\s*[^}]*%\}"#).expect("static regex is valid"));
+static RE_DASH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\[See: [^\]]*\]|["|-]"#).expect("static regex is valid"));
+static RE_SPACE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s+").expect("static regex is valid"));
+static RE_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[(INFO|SUCCESS|WARNING|DANGER)\]").expect("static regex is valid")
+});
+
+// The tags and comments removed from the HTML kept by `markdown_to_text_with_config`
+static RE_HTML_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|</?[A-Za-z][^>]*>").expect("static regex is valid")
+});
 
 /// Download the content of a page from a URL
 ///
@@ -23,201 +81,3496 @@ use tokio::fs;
 ///
 /// # Errors
 ///
-/// Returns an error if the HTTP request fails or if the response cannot be read
-pub async fn download_page(url: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let resp = reqwest::get(url).await?;
-    let text = resp.text().await?;
+/// Returns an error if the HTTP request fails or if the response cannot be read.
+/// A non-2xx response is reported as `GitBookError::HttpError` with its status code.
+#[must_use = "the downloaded page is lost when the result is not used"]
+pub async fn download_page(url: &str) -> Result<String, GitBookError> {
+    let mut buffer = Vec::new();
+    download_page_streaming(url, &mut buffer).await?;
+    let text = String::from_utf8(buffer).map_err(|e| {
+        GitBookError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    })?;
     Ok(text)
 }
 
-/// Save the markdown content to a file
+/// Download the content of a page and check it against a pinned SHA-256
 ///
-/// The file will be created in the `data/md/` directory with a name based on the URL
+/// The expected hash usually comes from the `.sha256` sidecar written by
+/// [`save_markdown_with_hash`], read back with [`read_markdown_hash`].
 ///
 /// # Arguments
 ///
-/// * `url` - The source URL (used to generate the file name)
-/// * `content` - The markdown content to save
+/// * `url` - The URL of the page to download
+/// * `expected_sha256` - The SHA-256 the content must have
 ///
 /// # Exemples
 ///
 /// ```no_run
-/// use gitbook2text::save_markdown;
+/// use gitbook2text::{download_page_verified, read_markdown_hash, CrawlConfig};
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     save_markdown("https://example.com/page", "# Titre\nContenu").await?;
+///     let url = "https://example.com/page.md";
+///     if let Some(hash) = read_markdown_hash(url, &CrawlConfig::default()).await? {
+///         let content = download_page_verified(url, &hash).await?;
+///         println!("Contenu vérifié: {} octets", content.len());
+///     }
 ///     Ok(())
 /// }
 /// ```
 ///
 /// # Errors
 ///
-/// Returns an error if the file write fails
-pub async fn save_markdown(url: &str, content: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let filename = url_to_filename(url) + ".md";
-    fs::write(format!("data/md/{}", filename), content).await?;
-    Ok(())
+/// Returns `GitBookError::ChecksumMismatch` if the content does not have the
+/// expected hash, and an error if the download fails or the content is not UTF-8
+#[must_use = "the downloaded page is lost when the result is not used"]
+pub async fn download_page_verified(
+    url: &str,
+    expected_sha256: &[u8; 32],
+) -> Result<String, GitBookError> {
+    let mut buffer = Vec::new();
+    download_page_streaming(url, &mut buffer).await?;
+    let text = String::from_utf8(buffer)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    let got = crate::dedup::content_hash(&text);
+    if &got != expected_sha256 {
+        return Err(GitBookError::ChecksumMismatch {
+            expected: hex(expected_sha256),
+            got: hex(&got),
+        });
+    }
+    Ok(text)
 }
 
-/// Converts a URL into a safe filename
+/// Download the content of a page and compare it with a known-good SHA-256
 ///
-/// Replaces the characters `/` and `:` with underscores
+/// Unlike [`download_page_verified`], a different hash is not an error: the page is
+/// returned with its hash and whether it changed, so the caller can skip writing
+/// the pages that did not. Without an expected hash the page counts as changed.
+/// The expected hash usually comes from the `content_hash` of a
+/// [`ManifestPage::content_sha256`](crate::ManifestPage::content_sha256).
 ///
 /// # Arguments
 ///
-/// * `url` - The URL to convert
+/// * `url` - The URL of the page to download
+/// * `expected_hash` - The SHA-256 of the last known content, if any
 ///
 /// # Exemples
 ///
-/// ```
-/// use gitbook2text::url_to_filename;
+/// ```no_run
+/// use gitbook2text::{download_page_checked, save_markdown, Manifest};
+/// use std::path::Path;
 ///
-/// let filename = url_to_filename("https://example.com/path/to/page");
-/// assert_eq!(filename, "https___example.com_path_to_page");
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let manifest = Manifest::load(Path::new("data/manifest.json"))?;
+///     for page in &manifest.pages {
+///         let url = format!("{}.md", page.url);
+///         let (content, _, changed) = download_page_checked(&url, page.content_sha256()).await?;
+///         if changed {
+///             save_markdown(&url, &content).await?;
+///         }
+///     }
+///     Ok(())
+/// }
 /// ```
-pub fn url_to_filename(url: &str) -> String {
-    url.replace(['/', ':'], "_")
+///
+/// # Errors
+///
+/// Returns an error if the download fails or if the content is not UTF-8
+#[must_use = "the downloaded page is lost when the result is not used"]
+pub async fn download_page_checked(
+    url: &str,
+    expected_hash: Option<[u8; 32]>,
+) -> Result<(String, [u8; 32], bool), GitBookError> {
+    let content = download_page(url).await?;
+    let hash = crate::dedup::content_hash(&content);
+    let changed = expected_hash != Some(hash);
+    Ok((content, hash, changed))
 }
 
-/// Converts markdown to plain text
+/// Download the content of a page, writing the body to `writer` as it is received
 ///
-/// Extracts text from markdown events, ignoring formatting
+/// The page is never held in memory as a whole, which suits large pages. The
+/// exception is a compressed body (`Content-Encoding: gzip`, `deflate` or `zstd`):
+/// it is downloaded and decompressed in memory, up to 64 MiB, before being written.
 ///
 /// # Arguments
 ///
-/// * `md` - The markdown content to convert
+/// * `url` - The URL of the page to download
+/// * `writer` - The destination of the response body
 ///
 /// # Exemples
 ///
-/// ```
-/// use gitbook2text::markdown_to_text;
+/// ```no_run
+/// use gitbook2text::download_page_streaming;
 ///
-/// let markdown = "# Titre\n\nParagraphe avec **gras**";
-/// let text = markdown_to_text(markdown);
-/// assert!(text.contains("Titre"));
-/// assert!(text.contains("gras"));
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let file = tokio::fs::File::create("page.md").await?;
+///     let size = download_page_streaming("https://example.com/page.md", file).await?;
+///     println!("{} octets écrits", size);
+///     Ok(())
+/// }
 /// ```
-pub fn markdown_to_text(md: &str) -> String {
-    let parser = Parser::new(md);
-    let mut text = String::new();
+///
+/// # Errors
+///
+/// Returns an error if the HTTP request fails, if the response is not a 2xx
+/// (`GitBookError::HttpError`), if a compressed body is invalid or decompresses to
+/// more than 64 MiB (`GitBookError::ParseError`) or if writing to `writer` fails
+#[must_use = "the download may have failed"]
+pub async fn download_page_streaming(
+    url: &str,
+    mut writer: impl AsyncWrite + Unpin,
+) -> Result<u64, GitBookError> {
+    let mut resp = check_status(reqwest::get(url).await?, url)?;
 
-    for event in parser {
-        match event {
-            Event::Text(t) => text.push_str(&t),
-            Event::Code(t) => text.push_str(&t),
-            Event::SoftBreak | Event::HardBreak => text.push('\n'),
-            _ => {}
-        }
+    // A compressed body is decompressed as a whole before it is written
+    let encodings = content_encodings(&resp);
+    if !encodings.is_empty() {
+        let body = decompress(&resp.bytes().await?, &encodings, url, MAX_DECOMPRESSED_SIZE)?;
+        writer.write_all(&body).await?;
+        writer.flush().await?;
+        return Ok(body.len() as u64);
+    }
+
+    let mut total = 0;
+    while let Some(chunk) = resp.chunk().await? {
+        writer.write_all(&chunk).await?;
+        total += chunk.len() as u64;
     }
+    writer.flush().await?;
 
-    text
+    Ok(total)
 }
 
-/// Cleans and sanitizes the text by removing special GitBook tags
+/// Download a page from GitBook's rendering API as JSON
 ///
-/// Removes `{% code %}`, `{% endcode %}`, and other special GitBook tags,
-/// normalizes spaces, and removes dashes and quotation marks.
+/// The request is sent with `Accept: application/json`; the result can be
+/// converted to markdown with [`GitBookJsonPage`](crate::GitBookJsonPage).
 ///
 /// # Arguments
 ///
-/// * `txt` - The text to clean
+/// * `url` - The URL of the page, e.g. `https://docs.example.com/~gitbook/pdf?page=intro`
 ///
 /// # Exemples
 ///
-/// ```
-/// use gitbook2text::txt_sanitize;
+/// ```no_run
+/// use gitbook2text::{download_page_json, GitBookJsonPage};
 ///
-/// let dirty = r#"{% code title="example.rs" %}fn main() {}{% endcode %}"#;
-/// let clean = txt_sanitize(dirty);
-/// assert!(clean.contains("example.rs"));
-/// assert!(clean.contains("fn main()"));
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let json = download_page_json("https://docs.example.com/intro").await?;
+///     println!("{}", GitBookJsonPage::from_json(json).to_markdown());
+///     Ok(())
+/// }
 /// ```
-pub fn txt_sanitize(txt: &str) -> String {
-    let mut result = String::from(txt);
+///
+/// # Errors
+///
+/// Returns `GitBookError::HttpError` for a non-2xx response and
+/// `GitBookError::ParseError` if the body is not valid JSON.
+#[must_use = "the downloaded page is lost when the result is not used"]
+pub async fn download_page_json(url: &str) -> Result<serde_json::Value, GitBookError> {
+    let resp = reqwest::Client::new()
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await?;
+    let body = response_text(check_status(resp, url)?).await?;
+    serde_json::from_str(&body).map_err(|e| GitBookError::ParseError(format!("{}: {}", url, e)))
+}
 
-    let re_code =
-        Regex::new(r#"\{%\s*code[^}]*title\s*=\s*"([^"]+)"[^}]*%}(.*?)\{%\s*endcode\s*%\}"#)
-            .unwrap();
-    result = re_code
-        .replace_all(&result, |caps: &regex::Captures| {
-            format!("{} {}", &caps[1], &caps[2])
-        })
-        .to_string();
+/// Download the content of a page with an already configured client,
+/// giving up after `timeout` if set
+pub(crate) async fn download_page_with_client(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<String, GitBookError> {
+    decode_response(send_checked(client, url, timeout).await?).await
+}
 
-    let re_code_no_title = Regex::new(r#"\{%\s*code[^}]*%}(.*?)\{%\s*endcode\s*%\}"#).unwrap();
-    result = re_code_no_title
-        .replace_all(&result, |caps: &regex::Captures| caps[1].to_string())
-        .to_string();
+/// Download the body of a page as-is, whatever its `Content-Type`, with an already
+/// configured client, giving up after `timeout` if set
+pub(crate) async fn download_raw_with_client(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<String, GitBookError> {
+    response_text(send_checked(client, url, timeout).await?).await
+}
 
-    let re_title = Regex::new(r#"\{%\s*[^}]*title\s*=\s*"([^"]+)"[^}]*%\}"#).unwrap();
-    result = re_title.replace_all(&result, "$1").to_string();
+/// The `Accept-Encoding` of the page downloads, the encodings [`decompress`] supports
+const ACCEPT_ENCODING: &str = "gzip, deflate, zstd";
 
-    let re_generic = Regex::new(r#"\{%\s*[^}]*%\}"#).unwrap();
-    result = re_generic.replace_all(&result, "").to_string();
+/// Sends a GET request accepting the compressed encodings, and checks its response
+/// with [`check_status`]
+async fn send_checked(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<reqwest::Response, GitBookError> {
+    let mut request = client
+        .get(url)
+        .header(reqwest::header::ACCEPT_ENCODING, ACCEPT_ENCODING);
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    check_status(request.send().await?, url)
+}
+
+/// Turns a non-2xx response into `GitBookError::HttpError`, and a body compressed
+/// with an encoding [`decompress`] cannot decode into `GitBookError::UnsupportedEncoding`
+///
+/// Some servers compress (`br`, `gzip`, `deflate`) even when the request does not
+/// advertise it; saving such a body would produce garbage. `gzip`, `deflate` and
+/// `zstd` bodies are decompressed when read; `br` has no decoder in the crate.
+fn check_status(resp: reqwest::Response, url: &str) -> Result<reqwest::Response, GitBookError> {
+    let resp = resp.error_for_status().map_err(|e| match e.status() {
+        Some(status) => GitBookError::HttpError(status.as_u16(), url.to_string()),
+        None => GitBookError::NetworkError(e),
+    })?;
 
-    let re_dash = Regex::new(r#"["|-]"#).unwrap();
-    result = re_dash.replace_all(&result, "").to_string();
-    let re_space = Regex::new(r"\s+").unwrap();
-    result = re_space.replace_all(&result, " ").to_string();
+    let unsupported = content_encodings(&resp)
+        .into_iter()
+        .find(|encoding| !matches!(encoding.as_str(), "gzip" | "x-gzip" | "deflate" | "zstd"));
+    if let Some(encoding) = unsupported {
+        return Err(GitBookError::UnsupportedEncoding(encoding, url.to_string()));
+    }
 
-    result.trim().to_string()
+    Ok(resp)
 }
 
-/// Saves the text content to a file
+/// Returns the encodings of the `Content-Encoding` header of a response, in the
+/// order they were applied, without `identity`
+fn content_encodings(resp: &reqwest::Response) -> Vec<String> {
+    resp.headers()
+        .get_all(reqwest::header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|encoding| encoding.trim().to_lowercase())
+        .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+        .collect()
+}
+
+/// The maximum size of a decompressed page body, so that a small compressed
+/// body cannot expand to gigabytes in memory
+const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Undoes the `encodings` of a body, the last applied first
 ///
-/// The file will be created in the `data/txt/` directory with a name based on the URL
+/// Each step fails with `GitBookError::ParseError` once its output exceeds `limit` bytes.
+fn decompress(
+    body: &[u8],
+    encodings: &[String],
+    url: &str,
+    limit: usize,
+) -> Result<Vec<u8>, GitBookError> {
+    let mut body = body.to_vec();
+    for encoding in encodings.iter().rev() {
+        body = match encoding.as_str() {
+            "gzip" | "x-gzip" => crate::inflate::gunzip(&body, limit),
+            "deflate" => crate::inflate::zlib_decompress(&body, limit),
+            "zstd" => zstd_decompress(&body, limit),
+            _ => {
+                return Err(GitBookError::UnsupportedEncoding(
+                    encoding.clone(),
+                    url.to_string(),
+                ))
+            }
+        }
+        .map_err(|e| {
+            GitBookError::ParseError(format!("{}: invalid {} body: {}", url, encoding, e))
+        })?;
+    }
+    Ok(body)
+}
+
+/// Decompresses a Zstd body, up to `limit` bytes
+fn zstd_decompress(body: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let decoder = zstd::stream::Decoder::new(body).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| e.to_string())?;
+    if out.len() > limit {
+        return Err(format!("decompressed data larger than {} bytes", limit));
+    }
+    Ok(out)
+}
+
+/// Reads the body of a response as text, decompressing it if needed
+///
+/// An uncompressed body is decoded following the `charset` of its `Content-Type`,
+/// a decompressed one as UTF-8.
+async fn response_text(resp: reqwest::Response) -> Result<String, GitBookError> {
+    let encodings = content_encodings(&resp);
+    if encodings.is_empty() {
+        return Ok(resp.text().await?);
+    }
+
+    let url = resp.url().to_string();
+    let body = decompress(
+        &resp.bytes().await?,
+        &encodings,
+        &url,
+        MAX_DECOMPRESSED_SIZE,
+    )?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// The format of a page, from the `Content-Type` of its response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentType {
+    /// An HTML page (`text/html`, `application/xhtml+xml`)
+    Html,
+
+    /// Raw markdown, served for the `.md` variant of a page
+    /// (`text/markdown`, `text/x-markdown`, `text/plain`)
+    Markdown,
+
+    /// A page in the GitBook JSON document format (`application/json`, `*+json`)
+    Json,
+
+    /// Any other media type, or an empty string when the header is missing
+    Other(String),
+}
+
+/// Returns the format of a response from its `Content-Type` header
+///
+/// Parameters such as `charset` are ignored and the media type is case-insensitive.
+pub fn detect_content_type(resp: &reqwest::Response) -> ContentType {
+    let header = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    content_type_from_header(header)
+}
+
+fn content_type_from_header(header: &str) -> ContentType {
+    let media_type = header
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    match media_type.as_str() {
+        "text/html" | "application/xhtml+xml" => ContentType::Html,
+        "text/markdown" | "text/x-markdown" | "text/plain" => ContentType::Markdown,
+        "application/json" => ContentType::Json,
+        _ if media_type.ends_with("+json") => ContentType::Json,
+        _ => ContentType::Other(media_type),
+    }
+}
+
+/// Reads the body of a response and converts it to text according to its `Content-Type`
+///
+/// A `gzip`, `deflate` or `zstd` body is decompressed first.
+///
+/// * HTML: the text of the main content (`main`, then `article`, then `body`),
+///   without scripts and styles, one block per line
+/// * Markdown and other types: the body as-is
+/// * JSON: the text of the GitBook document (the `markdown` field when present,
+///   otherwise the text of the `document` nodes, one block per paragraph)
+///
+/// # Errors
+///
+/// Returns an error if the body cannot be read or if a JSON body is not valid JSON
+pub async fn decode_response(resp: reqwest::Response) -> Result<String, GitBookError> {
+    let content_type = detect_content_type(&resp);
+    let body = response_text(resp).await?;
+
+    match content_type {
+        ContentType::Html => Ok(html_main_text(&body)),
+        ContentType::Json => gitbook_json_text(&body),
+        ContentType::Markdown | ContentType::Other(_) => Ok(body),
+    }
+}
+
+/// Extracts the text of the main content of an HTML page
+fn html_main_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let root = ["main", "article", "body"]
+        .iter()
+        .filter_map(|tag| Selector::parse(tag).ok())
+        .find_map(|selector| document.select(&selector).next());
+    let Some(root) = root else {
+        return String::new();
+    };
+
+    let mut text = String::new();
+    for node in root.descendants() {
+        match node.value() {
+            Node::Text(t) => {
+                let hidden = node.ancestors().any(|ancestor| {
+                    ancestor.value().as_element().is_some_and(|el| {
+                        matches!(el.name(), "script" | "style" | "noscript" | "template")
+                    })
+                });
+                if !hidden {
+                    text.push_str(t);
+                }
+            }
+            Node::Element(el)
+                if matches!(
+                    el.name(),
+                    "p" | "div"
+                        | "br"
+                        | "li"
+                        | "tr"
+                        | "pre"
+                        | "h1"
+                        | "h2"
+                        | "h3"
+                        | "h4"
+                        | "h5"
+                        | "h6"
+                ) =>
+            {
+                text.push('\n');
+            }
+            _ => {}
+        }
+    }
+
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Extracts the text of a page in the GitBook JSON document format
+fn gitbook_json_text(json: &str) -> Result<String, GitBookError> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| GitBookError::ParseError(e.to_string()))?;
+
+    if let Some(markdown) = value.get("markdown").and_then(|m| m.as_str()) {
+        return Ok(markdown.to_string());
+    }
+
+    let mut blocks = Vec::new();
+    if let Some(document) = value.get("document") {
+        collect_json_blocks(document, &mut blocks);
+    }
+    Ok(blocks.join("\n\n"))
+}
+
+/// Appends the text of each block of a GitBook document node to `blocks`
+///
+/// A block whose children are all inline content becomes one entry; blocks
+/// containing other blocks are visited recursively.
+fn collect_json_blocks(node: &serde_json::Value, blocks: &mut Vec<String>) {
+    let children = node
+        .get("nodes")
+        .and_then(|n| n.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let has_blocks = children
+        .iter()
+        .any(|child| child.get("object").and_then(|o| o.as_str()) == Some("block"));
+    if has_blocks {
+        for child in children {
+            collect_json_blocks(child, blocks);
+        }
+        return;
+    }
+
+    let mut text = String::new();
+    json_inline_text(node, &mut text);
+    if !text.trim().is_empty() {
+        blocks.push(text.trim().to_string());
+    }
+}
+
+/// Appends the text of the leaves under a GitBook document node to `text`
+fn json_inline_text(node: &serde_json::Value, text: &mut String) {
+    if let Some(leaves) = node.get("leaves").and_then(|l| l.as_array()) {
+        for leaf in leaves {
+            if let Some(t) = leaf.get("text").and_then(|t| t.as_str()) {
+                text.push_str(t);
+            }
+        }
+    }
+    for child in node
+        .get("nodes")
+        .and_then(|n| n.as_array())
+        .into_iter()
+        .flatten()
+    {
+        json_inline_text(child, text);
+    }
+}
+
+/// Save the markdown content to a file
+///
+/// The file will be created in the `data/md/` directory with a name based on the URL
 ///
 /// # Arguments
 ///
 /// * `url` - The source URL (used to generate the file name)
-/// * `content` - The text content to save
+/// * `content` - The markdown content to save
 ///
 /// # Exemples
 ///
 /// ```no_run
-/// use gitbook2text::save_text;
+/// use gitbook2text::save_markdown;
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     save_text("https://example.com/page", "Contenu texte").await?;
+///     save_markdown("https://example.com/page", "# Titre\nContenu").await?;
 ///     Ok(())
 /// }
 /// ```
 ///
 /// # Errors
 ///
-/// Returns an error if writing the file fails
-pub async fn save_text(url: &str, content: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let filename = url_to_filename(url) + ".txt";
-    fs::write(format!("data/txt/{}", filename), content).await?;
-    Ok(())
+/// Returns an error if the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_markdown(url: &str, content: &str) -> Result<(), GitBookError> {
+    save_markdown_with_config(url, content, &CrawlConfig::default()).await
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Save the markdown content to a file, following the given configuration
+///
+/// With `preserve_path_structure` enabled, the file is written to a directory
+/// tree mirroring the URL path (e.g. `data/md/guide/intro.md`), and the
+/// intermediate directories are created as needed.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The markdown content to save
+/// * `config` - The crawl configuration
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{save_markdown_with_config, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = CrawlConfig {
+///         preserve_path_structure: true,
+///         ..Default::default()
+///     };
+///     save_markdown_with_config("https://example.com/guide/intro", "# Intro", &config).await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or if the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_markdown_with_config(
+    url: &str,
+    content: &str,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, &config.output_dir.join("md"), "md", config);
+    write_output(&path, content, config).await
+}
 
-    #[test]
-    fn test_url_to_filename() {
-        let url = "https://example.com/path/to/page";
-        let filename = url_to_filename(url);
-        assert_eq!(filename, "https___example.com_path_to_page");
-    }
+/// Downloads a page straight into its markdown file
+///
+/// Works like [`download_page`] followed by [`save_markdown_with_config`], without
+/// holding the page in memory. Returns the number of bytes written. The page is
+/// written to a `.tmp` file renamed once the download succeeds, so that a failed
+/// download leaves no empty or partial file.
+///
+/// # Arguments
+///
+/// * `url` - The URL of the page, also used to generate the file name
+/// * `config` - The crawl configuration
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{save_markdown_streaming, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     save_markdown_streaming("https://example.com/page.md", &CrawlConfig::default()).await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the download fails or if the file cannot be written
+#[must_use = "the file may not have been saved"]
+pub async fn save_markdown_streaming(url: &str, config: &CrawlConfig) -> Result<u64, GitBookError> {
+    let path = output_path(url, &config.output_dir.join("md"), "md", config);
+    create_parent_dir(&path, config).await?;
+    write_atomic_with(&path, async |file| download_page_streaming(url, file).await).await
+}
 
-    #[test]
-    fn test_markdown_to_text() {
-        let md = "# Title\n\nSome **bold** text";
-        let text = markdown_to_text(md);
-        assert!(text.contains("Title"));
-        assert!(text.contains("bold"));
-    }
+/// Save the markdown content to a Zstd-compressed file
+///
+/// Works like [`save_markdown_with_config`] but writes a `.md.zst` file
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The markdown content to save
+/// * `level` - The Zstd compression level (1 to 22)
+/// * `config` - The crawl configuration
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{save_markdown_compressed, CrawlConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     save_markdown_compressed("https://example.com/page", "# Titre", 3, &CrawlConfig::default())
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the compression or the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_markdown_compressed(
+    url: &str,
+    content: &str,
+    level: i32,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, &config.output_dir.join("md"), "md.zst", config);
+    write_output(&path, &compress(content, level)?, config).await
+}
 
-    #[test]
-    fn test_txt_sanitize() {
-        let input = r#"{% code title="test.rs" %}fn main(){}{% endcode %}"#;
-        let output = txt_sanitize(input);
-        assert!(output.contains("test.rs"));
-        assert!(output.contains("fn main(){}"));
+/// Save the markdown content to a file, along with a `.sha256` sidecar file
+///
+/// Works like [`save_markdown_with_config`]; the sidecar (e.g. `page.md.sha256`)
+/// holds the SHA-256 of the content in the `sha256sum` format. On re-download it
+/// is read by [`read_markdown_hash`] to verify the page with [`download_page_verified`].
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The markdown content to save
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or if a file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_markdown_with_hash(
+    url: &str,
+    content: &str,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, &config.output_dir.join("md"), "md", config);
+    write_output(&path, content, config).await?;
+    write_hash_sidecar(&path, content).await
+}
+
+/// Reads the SHA-256 saved by [`save_markdown_with_hash`] for a URL
+///
+/// Returns `None` if the page has no `.sha256` sidecar file.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if the sidecar file cannot be read or does not hold a SHA-256
+pub async fn read_markdown_hash(
+    url: &str,
+    config: &CrawlConfig,
+) -> Result<Option<[u8; 32]>, GitBookError> {
+    let path = hash_sidecar_path(&output_path(
+        url,
+        &config.output_dir.join("md"),
+        "md",
+        config,
+    ));
+    let sidecar = match fs::read_to_string(&path).await {
+        Ok(sidecar) => sidecar,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let digest = sidecar.split_whitespace().next().unwrap_or_default();
+    match parse_sha256(digest) {
+        Some(hash) => Ok(Some(hash)),
+        None => Err(GitBookError::ParseError(format!(
+            "{}: not a SHA-256",
+            path.display()
+        ))),
+    }
+}
+
+/// Returns the path of the `.sha256` sidecar of an output file
+fn hash_sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    PathBuf::from(sidecar)
+}
+
+/// Writes the `.sha256` sidecar of an output file, in the `sha256sum` format
+async fn write_hash_sidecar(path: &Path, content: &str) -> Result<(), GitBookError> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let line = format!("{}  {}\n", hex(&crate::dedup::content_hash(content)), name);
+    fs::write(hash_sidecar_path(path), line).await?;
+    Ok(())
+}
+
+/// Formats bytes as lowercase hexadecimal
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Converts a URL into a safe filename
+///
+/// Replaces the characters `/` and `:` with underscores
+///
+/// # Arguments
+///
+/// * `url` - The URL to convert
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::url_to_filename;
+///
+/// let filename = url_to_filename("https://example.com/path/to/page");
+/// assert_eq!(filename, "https___example.com_path_to_page");
+/// ```
+#[must_use = "the file name is only returned, not created"]
+pub fn url_to_filename(url: &str) -> String {
+    url_to_filename_with_max(url, 255)
+}
+
+/// Converts a URL into a safe filename of at most `max_len` characters
+///
+/// Works like [`url_to_filename`]; a longer name is shortened to its first 100
+/// characters followed by `_` and the first 8 hexadecimal digits of the SHA-256
+/// of the URL, so two long URLs sharing a prefix keep distinct names. The prefix
+/// is cut further when `max_len` is below 109.
+///
+/// # Arguments
+///
+/// * `url` - The URL to convert
+/// * `max_len` - The maximum length of the filename, in characters
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::url_to_filename_with_max;
+///
+/// assert_eq!(url_to_filename_with_max("https://x.com/a", 200), "https___x.com_a");
+///
+/// let long = format!("https://x.com/{}", "a".repeat(300));
+/// let filename = url_to_filename_with_max(&long, 200);
+/// assert_eq!(filename.len(), 109);
+/// assert!(filename.starts_with("https___x.com_aaa"));
+/// ```
+#[must_use = "the file name is only returned, not created"]
+pub fn url_to_filename_with_max(url: &str, max_len: usize) -> String {
+    shorten_filename(url.replace(['/', ':'], "_"), url, max_len)
+}
+
+/// Shortens a filename derived from `url` to `max_len` characters, see [`url_to_filename_with_max`]
+fn shorten_filename(name: String, url: &str, max_len: usize) -> String {
+    if name.chars().count() <= max_len {
+        return name;
+    }
+    let hash = crate::dedup::content_hash(url);
+    let prefix: String = name
+        .chars()
+        .take(max_len.saturating_sub(9).min(100))
+        .collect();
+    format!("{}_{}", prefix, hex(&hash[..4]))
+}
+
+/// Maximum length, in characters, of the file names produced by [`url_to_filename_windows_safe`]
+const WINDOWS_FILENAME_MAX_CHARS: usize = 200;
+
+/// Converts a URL into a filename that is valid on Windows
+///
+/// Replaces the characters reserved by Windows (`< > : " / \ | ? *`) and
+/// control characters with underscores, then truncates the result to 200
+/// characters to stay well under `MAX_PATH`. Output file names go through
+/// this function automatically on Windows.
+///
+/// # Arguments
+///
+/// * `url` - The URL to convert
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::url_to_filename_windows_safe;
+///
+/// let filename = url_to_filename_windows_safe("https://example.com/search?q=a|b");
+/// assert_eq!(filename, "https___example.com_search_q=a_b");
+/// ```
+#[must_use = "the file name is only returned, not created"]
+pub fn url_to_filename_windows_safe(url: &str) -> String {
+    url.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(WINDOWS_FILENAME_MAX_CHARS)
+        .collect()
+}
+
+/// Makes a file name valid on the current platform
+#[cfg(target_os = "windows")]
+fn platform_filename(name: String) -> String {
+    url_to_filename_windows_safe(&name)
+}
+
+/// Makes a file name valid on the current platform
+#[cfg(not(target_os = "windows"))]
+fn platform_filename(name: String) -> String {
+    name
+}
+
+/// Converts a URL into a safe filename without its scheme
+///
+/// Removes the `scheme://` prefix, then replaces the characters `/` and `:`
+/// with the given separator
+///
+/// # Arguments
+///
+/// * `url` - The URL to convert
+/// * `sep` - The character used in place of `/` and `:`
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::url_to_filename_strip_scheme;
+///
+/// let filename = url_to_filename_strip_scheme("https://example.com/docs/page", '_');
+/// assert_eq!(filename, "example.com_docs_page");
+/// ```
+#[must_use = "the file name is only returned, not created"]
+pub fn url_to_filename_strip_scheme(url: &str, sep: char) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    without_scheme.replace(['/', ':'], &sep.to_string())
+}
+
+/// Converts a heading into a file name
+///
+/// The text is lowercased, runs of whitespace, `-` and `_` become a single
+/// separator and all other non-alphanumeric characters are removed.
+///
+/// # Arguments
+///
+/// * `s` - The text to convert
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::slugify;
+///
+/// assert_eq!(slugify("Getting Started: API Keys!"), "getting-started-api-keys");
+/// ```
+pub fn slugify(s: &str) -> String {
+    slugify_with_separator(s, '-')
+}
+
+/// Converts a heading into a file name, with the given word separator
+///
+/// Works like [`slugify`] with `sep` in place of `-`.
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::slugify_with_separator;
+///
+/// assert_eq!(slugify_with_separator("Rate  limits", '_'), "rate_limits");
+/// ```
+pub fn slugify_with_separator(s: &str, sep: char) -> String {
+    let mut slug = String::new();
+    let mut pending_sep = false;
+
+    for c in s.chars() {
+        if c.is_alphanumeric() {
+            if pending_sep && !slug.is_empty() {
+                slug.push(sep);
+            }
+            pending_sep = false;
+            slug.extend(c.to_lowercase());
+        } else if c.is_whitespace() || c == '-' || c == '_' || c == sep {
+            pending_sep = true;
+        }
+    }
+
+    slug
+}
+
+/// Returns the text of the first level 1 heading of a markdown document
+pub(crate) fn first_h1(md: &str) -> Option<String> {
+    let mut title: Option<String> = None;
+
+    for event in Parser::new(md) {
+        match event {
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) => title = Some(String::new()),
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(title) = &mut title {
+                    title.push_str(&t);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) if title.is_some() => return title,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Converts a URL into a path mirroring its directory structure
+///
+/// The scheme and host are dropped and each path segment becomes a directory
+/// under `base`. The root of the site maps to `base/index`. Empty, `.` and
+/// `..` segments are ignored so the result always stays inside `base`.
+///
+/// # Arguments
+///
+/// * `url` - The URL to convert
+/// * `base` - The directory under which the path is built
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::url_to_path;
+/// use std::path::Path;
+///
+/// let path = url_to_path("https://docs.example.com/guide/intro", Path::new("base"));
+/// assert_eq!(path, Path::new("base/guide/intro"));
+/// ```
+pub fn url_to_path(url: &str, base: &Path) -> PathBuf {
+    let segments: Vec<String> = match Url::parse(url) {
+        Ok(parsed) => parsed
+            .path_segments()
+            .map(|segments| {
+                segments
+                    .filter(|s| !s.is_empty() && *s != "." && *s != "..")
+                    .map(|s| platform_filename(url_to_filename(s)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Err(_) => vec![platform_filename(url_to_filename(url))],
+    };
+
+    let mut path = base.to_path_buf();
+    if segments.is_empty() {
+        path.push("index");
+    } else {
+        path.extend(segments);
+    }
+    path
+}
+
+/// Returns the URL without its `.md` suffix, matched in any case (`README.MD`)
+pub(crate) fn strip_md_suffix(url: &str) -> &str {
+    match url.len().checked_sub(3) {
+        Some(i) if url.is_char_boundary(i) && url[i..].eq_ignore_ascii_case(".md") => &url[..i],
+        _ => url,
+    }
+}
+
+/// Builds the output path of a page for the given directory and extension
+pub(crate) fn output_path(url: &str, dir: &Path, extension: &str, config: &CrawlConfig) -> PathBuf {
+    if config.preserve_path_structure {
+        let url = strip_md_suffix(url);
+        let mut path = url_to_path(url, dir).into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    } else if config.strip_scheme {
+        let name = url_to_filename_strip_scheme(url, '_');
+        let name = platform_filename(shorten_filename(name, url, config.max_filename_length));
+        dir.join(format!("{}.{}", name, extension))
+    } else {
+        let name = platform_filename(url_to_filename_with_max(url, config.max_filename_length));
+        dir.join(format!("{}.{}", name, extension))
+    }
+}
+
+/// Creates the parent directories of an output file when the path structure is preserved
+async fn create_parent_dir(path: &Path, config: &CrawlConfig) -> Result<(), GitBookError> {
+    if config.preserve_path_structure {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces a file with `content` without ever leaving it partially written
+///
+/// See [`write_atomic_from`].
+pub(crate) async fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> std::io::Result<()> {
+    write_atomic_from(path, content.as_ref()).await
+}
+
+/// Copies `reader` to `<path>.tmp`, then renames it to `path`
+///
+/// When the rename fails (e.g. across devices), the temporary file is copied
+/// over `path` and deleted. On error the temporary file is removed and `path`
+/// keeps its previous content.
+pub(crate) async fn write_atomic_from(
+    path: &Path,
+    mut reader: impl AsyncRead + Unpin,
+) -> std::io::Result<()> {
+    write_atomic_with(path, async |file| tokio::io::copy(&mut reader, file).await).await?;
+    Ok(())
+}
+
+/// Writes `<path>.tmp` with `write`, then renames it to `path` like [`write_atomic_from`]
+///
+/// Returns the number of bytes `write` reports.
+async fn write_atomic_with<E: From<std::io::Error>>(
+    path: &Path,
+    write: impl AsyncFnOnce(&mut fs::File) -> Result<u64, E>,
+) -> Result<u64, E> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let result = async {
+        let mut file = fs::File::create(&tmp_path).await?;
+        let size = write(&mut file).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        if fs::rename(&tmp_path, path).await.is_err() {
+            fs::copy(&tmp_path, path).await?;
+            fs::remove_file(&tmp_path).await?;
+        }
+        Ok(size)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Compresses a text with Zstd
+pub(crate) fn compress(content: &str, level: i32) -> std::io::Result<Vec<u8>> {
+    let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), level)?;
+    encoder.write_all(content.as_bytes())?;
+    encoder.finish()
+}
+
+/// Writes an output file, creating its parent directories when the path structure is preserved
+pub(crate) async fn write_output(
+    path: &Path,
+    content: impl AsRef<[u8]>,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    if config.preserve_path_structure {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+    }
+    fs::write(path, content).await?;
+    Ok(())
+}
+
+/// Converts markdown to plain text
+///
+/// Extracts text from markdown events, ignoring formatting.
+/// Uses the default [`TextConfig`], which separates blocks with a blank line.
+///
+/// # Arguments
+///
+/// * `md` - The markdown content to convert
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::markdown_to_text;
+///
+/// let markdown = "# Titre\n\nParagraphe avec **gras**";
+/// let text = markdown_to_text(markdown);
+/// assert!(text.contains("Titre"));
+/// assert!(text.contains("gras"));
+/// ```
+#[must_use = "the input is left unchanged, the text is returned"]
+pub fn markdown_to_text(md: &str) -> String {
+    markdown_to_text_with_config(md, &TextConfig::default())
+}
+
+/// Converts markdown to plain text, following the given configuration
+///
+/// # Arguments
+///
+/// * `md` - The markdown content to convert
+/// * `config` - The text conversion configuration
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{markdown_to_text_with_config, TextConfig};
+///
+/// let text = markdown_to_text_with_config("# Titre\n\nParagraphe", &TextConfig::default());
+/// assert_eq!(text, "Titre\n\nParagraphe");
+///
+/// let config = TextConfig {
+///     preserve_urls: true,
+///     ..Default::default()
+/// };
+/// let text = markdown_to_text_with_config("See [the docs](https://example.com)", &config);
+/// assert_eq!(text, "See the docs (https://example.com)");
+/// ```
+#[must_use = "the input is left unchanged, the text is returned"]
+pub fn markdown_to_text_with_config(md: &str, config: &TextConfig) -> String {
+    convert_markdown_to_text(md, config, false)
+}
+
+/// Starts and ends each code block of the text returned by [`markdown_to_text_marked`],
+/// on a line of its own
+const CODE_MARK: &str = "\u{1}";
+
+/// Converts markdown to plain text like [`markdown_to_text_with_config`], with each code
+/// block between two [`CODE_MARK`] lines for [`wrap_marked_text`]
+pub(crate) fn markdown_to_text_marked(md: &str, config: &TextConfig) -> String {
+    convert_markdown_to_text(md, config, true)
+}
+
+fn convert_markdown_to_text(md: &str, config: &TextConfig, mark_code: bool) -> String {
+    let parser = Parser::new(md);
+    let mut text = String::new();
+    let mut links: Vec<(String, usize)> = Vec::new();
+    // The next number of each open list, `None` for unordered lists
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    // Number of open images whose alt text is dropped
+    let mut stripped_images = 0;
+
+    for event in parser {
+        if mark_code
+            && matches!(
+                event,
+                Event::Start(Tag::CodeBlock(_)) | Event::End(TagEnd::CodeBlock)
+            )
+        {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(CODE_MARK);
+            text.push('\n');
+        }
+
+        match event {
+            Event::Start(Tag::Image { .. }) if config.strip_images => stripped_images += 1,
+            Event::End(TagEnd::Image) if config.strip_images => stripped_images -= 1,
+            Event::Text(_) | Event::Code(_) if stripped_images > 0 => {}
+            Event::Start(Tag::List(start)) => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                lists.push(start);
+            }
+            Event::Start(Tag::Item) => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                let depth = lists.len().saturating_sub(1);
+                text.push_str(&" ".repeat(depth * config.indent_width));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        text.push_str(&config.ordered_list_prefix.prefix(*n));
+                        *n += 1;
+                    }
+                    _ => text.push_str(&config.list_bullet),
+                }
+            }
+            Event::End(TagEnd::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() && config.blank_lines_between_blocks {
+                    let trimmed_len = text.trim_end_matches('\n').len();
+                    text.truncate(trimmed_len);
+                    text.push_str("\n\n");
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) if config.preserve_urls => {
+                // Auto-links and e-mail links already render their target as text
+                let url = match link_type {
+                    LinkType::Autolink | LinkType::Email => String::new(),
+                    _ => dest_url.to_string(),
+                };
+                links.push((url, text.len()));
+            }
+            Event::End(TagEnd::Link) if config.preserve_urls => {
+                if let Some((url, start)) = links.pop() {
+                    let label = text[start..].trim();
+                    if !url.is_empty() && label != url {
+                        text.push_str(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::Text(t) => text.push_str(&t),
+            Event::Code(t) => text.push_str(&t),
+            Event::InlineHtml(t) if !config.strip_inline_html => {
+                text.push_str(&RE_HTML_TAG.replace_all(&t, ""));
+            }
+            // The lines of an HTML block, left out when only tags remain
+            Event::Html(t) if !config.strip_inline_html => {
+                let line = RE_HTML_TAG.replace_all(&t, "");
+                if !line.trim().is_empty() {
+                    text.push_str(&line);
+                }
+            }
+            Event::End(TagEnd::HtmlBlock)
+                if !config.strip_inline_html
+                    && config.blank_lines_between_blocks
+                    && !text.trim().is_empty() =>
+            {
+                let trimmed_len = text.trim_end_matches('\n').len();
+                text.truncate(trimmed_len);
+                text.push_str("\n\n");
+            }
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock)
+                if config.blank_lines_between_blocks =>
+            {
+                let trimmed_len = text.trim_end_matches('\n').len();
+                text.truncate(trimmed_len);
+                text.push_str("\n\n");
+            }
+            _ => {}
+        }
+    }
+
+    if config.blank_lines_between_blocks {
+        let trimmed_len = text.trim_end_matches('\n').len();
+        text.truncate(trimmed_len);
+    }
+
+    match config.unicode_normalization {
+        Some(form) => normalize_unicode(&text, form),
+        None => text,
+    }
+}
+
+/// Applies a Unicode normalization form to a text
+///
+/// # Arguments
+///
+/// * `text` - The text to normalize
+/// * `form` - The normalization form
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{normalize_unicode, UnicodeForm};
+///
+/// let decomposed = "Cafe\u{301}";
+/// assert_eq!(normalize_unicode(decomposed, UnicodeForm::Nfc), "Caf\u{e9}");
+/// ```
+pub fn normalize_unicode(text: &str, form: UnicodeForm) -> String {
+    let normalized = match form {
+        UnicodeForm::Nfc => ComposingNormalizerBorrowed::new_nfc().normalize(text),
+        UnicodeForm::Nfd => DecomposingNormalizerBorrowed::new_nfd().normalize(text),
+        UnicodeForm::Nfkc => ComposingNormalizerBorrowed::new_nfkc().normalize(text),
+        UnicodeForm::Nfkd => DecomposingNormalizerBorrowed::new_nfkd().normalize(text),
+    };
+    normalized.into_owned()
+}
+
+/// Converts markdown to AsciiDoc
+///
+/// Headings become `=` titles (`# Title` → `= Title`), bold `**bold**`, italic
+/// `__italic__`, code spans `` `code` `` and code blocks `[source,<lang>]` listings
+/// delimited by `----`. Bold and italic use the unconstrained AsciiDoc marks, which
+/// also apply inside a word. Tables become `|===` blocks, their header row followed
+/// by a blank line. Links, images, lists, quotes and rules are mapped to their
+/// AsciiDoc equivalents.
+///
+/// # Arguments
+///
+/// * `md` - The markdown content to convert
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::markdown_to_asciidoc;
+///
+/// let adoc = markdown_to_asciidoc("# Setup\n\nRun **now**:\n\n```sh\nmake\n```");
+/// assert_eq!(adoc, "= Setup\n\nRun **now**:\n\n[source,sh]\n----\nmake\n----");
+/// ```
+pub fn markdown_to_asciidoc(md: &str) -> String {
+    let mut out = String::new();
+    // Ordered flag of each open list
+    let mut lists: Vec<bool> = Vec::new();
+    // Destination and start of the label of each open link or image
+    let mut targets: Vec<(String, usize)> = Vec::new();
+    let mut in_table = false;
+
+    for event in Parser::new_ext(md, pulldown_cmark::Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                out.push_str(&"=".repeat(level as usize));
+                out.push(' ');
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                start_line(&mut out);
+                if let CodeBlockKind::Fenced(info) = kind {
+                    let lang = info.split_whitespace().next().unwrap_or_default();
+                    if !lang.is_empty() {
+                        out.push_str(&format!("[source,{}]\n", lang));
+                    }
+                }
+                out.push_str("----\n");
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                start_line(&mut out);
+                out.push_str("----");
+                end_block(&mut out, &lists);
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                start_line(&mut out);
+                out.push_str("____\n");
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                let trimmed_len = out.trim_end_matches('\n').len();
+                out.truncate(trimmed_len);
+                out.push_str("\n____");
+                end_block(&mut out, &lists);
+            }
+            Event::Start(Tag::List(start)) => {
+                start_line(&mut out);
+                lists.push(start.is_some());
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                end_block(&mut out, &lists);
+            }
+            Event::Start(Tag::Item) => {
+                start_line(&mut out);
+                let marker = if lists.last() == Some(&true) {
+                    "."
+                } else {
+                    "*"
+                };
+                out.push_str(&marker.repeat(lists.len()));
+                out.push(' ');
+            }
+            Event::End(TagEnd::Item) => start_line(&mut out),
+            Event::Start(Tag::Strong) | Event::End(TagEnd::Strong) => out.push_str("**"),
+            Event::Start(Tag::Emphasis) | Event::End(TagEnd::Emphasis) => out.push_str("__"),
+            Event::Start(Tag::Table(alignments)) => {
+                start_line(&mut out);
+                if alignments.iter().any(|a| *a != Alignment::None) {
+                    let cols: Vec<&str> = alignments
+                        .iter()
+                        .map(|a| match a {
+                            Alignment::Center => "^",
+                            Alignment::Right => ">",
+                            Alignment::Left | Alignment::None => "<",
+                        })
+                        .collect();
+                    out.push_str(&format!("[cols=\"{}\"]\n", cols.join(",")));
+                }
+                out.push_str("|===\n");
+                in_table = true;
+            }
+            Event::End(TagEnd::Table) => {
+                out.push_str("|===");
+                in_table = false;
+                end_block(&mut out, &lists);
+            }
+            Event::Start(Tag::TableCell) => {
+                if !out.ends_with('\n') {
+                    out.push(' ');
+                }
+                out.push_str("| ");
+            }
+            Event::End(TagEnd::TableCell) => {
+                let trimmed_len = out.trim_end_matches(' ').len();
+                out.truncate(trimmed_len);
+            }
+            Event::End(TagEnd::TableHead) => out.push_str("\n\n"),
+            Event::End(TagEnd::TableRow) => out.push('\n'),
+            Event::Text(t) if in_table => out.push_str(&t.replace('|', "\\|")),
+            Event::Start(Tag::Link { dest_url, .. })
+            | Event::Start(Tag::Image { dest_url, .. }) => {
+                targets.push((dest_url.to_string(), out.len()));
+            }
+            Event::End(end @ (TagEnd::Link | TagEnd::Image)) => {
+                if let Some((url, start)) = targets.pop() {
+                    let label = out.split_off(start).replace(']', "\\]");
+                    if end == TagEnd::Image {
+                        out.push_str(&format!("image:{}[{}]", url, label));
+                    } else if label == url {
+                        out.push_str(&url);
+                    } else if url.contains("://") || url.starts_with("mailto:") {
+                        out.push_str(&format!("{}[{}]", url, label));
+                    } else {
+                        out.push_str(&format!("link:{}[{}]", url, label));
+                    }
+                }
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_)) => end_block(&mut out, &lists),
+            Event::Text(t) | Event::Html(t) | Event::InlineHtml(t) => out.push_str(&t),
+            Event::Code(t) => out.push_str(&format!("`{}`", t)),
+            Event::SoftBreak => out.push('\n'),
+            Event::HardBreak => out.push_str(" +\n"),
+            Event::Rule => {
+                start_line(&mut out);
+                out.push_str("'''");
+                end_block(&mut out, &lists);
+            }
+            _ => {}
+        }
+    }
+
+    out.trim_end().to_string()
+}
+
+/// Starts a new line unless the output already ends with one
+fn start_line(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Ends a block: with a blank line at the top level, a line break inside a list
+fn end_block(out: &mut String, lists: &[bool]) {
+    let trimmed_len = out.trim_end_matches('\n').len();
+    out.truncate(trimmed_len);
+    out.push_str(if lists.is_empty() { "\n\n" } else { "\n" });
+}
+
+/// Saves AsciiDoc content as `<page>.adoc` in `dir`
+///
+/// The directory is created if needed.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The AsciiDoc content to save
+/// * `dir` - The directory in which the file is written
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{markdown_to_asciidoc, save_asciidoc};
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let adoc = markdown_to_asciidoc("# Titre\nContenu");
+///     save_asciidoc("https://example.com/page", &adoc, Path::new("data/adoc")).await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or if the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_asciidoc(url: &str, content: &str, dir: &Path) -> Result<(), GitBookError> {
+    fs::create_dir_all(dir).await?;
+    let page = url_to_filename(strip_md_suffix(url));
+    fs::write(dir.join(format!("{}.adoc", page)), content).await?;
+    Ok(())
+}
+
+/// A code block extracted from a markdown document
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// The language identifier of a fenced block, `None` for indented blocks
+    pub language: Option<String>,
+
+    /// The code of the block
+    pub content: String,
+
+    /// The line of the markdown document on which the block starts, starting at 1
+    pub line_start: usize,
+}
+
+impl CodeBlock {
+    /// Returns the file extension matching the language of the block
+    ///
+    /// # Exemples
+    ///
+    /// ```
+    /// use gitbook2text::extract_code_blocks;
+    ///
+    /// let blocks = extract_code_blocks("```rust\nfn main() {}\n```");
+    /// assert_eq!(blocks[0].extension(), "rs");
+    /// ```
+    pub fn extension(&self) -> String {
+        let language = match &self.language {
+            Some(language) => language.to_lowercase(),
+            None => return "txt".to_string(),
+        };
+
+        let extension = match language.as_str() {
+            "rust" => "rs",
+            "python" | "py" => "py",
+            "javascript" | "js" | "jsx" => "js",
+            "typescript" | "ts" | "tsx" => "ts",
+            "bash" | "sh" | "shell" | "zsh" | "console" => "sh",
+            "powershell" | "ps1" => "ps1",
+            "ruby" | "rb" => "rb",
+            "csharp" | "c#" | "cs" => "cs",
+            "cpp" | "c++" => "cpp",
+            "kotlin" | "kt" => "kt",
+            "yaml" | "yml" => "yml",
+            "markdown" | "md" => "md",
+            "text" | "plaintext" | "" => "txt",
+            other if other.chars().all(|c| c.is_ascii_alphanumeric()) => other,
+            _ => "txt",
+        };
+
+        extension.to_string()
+    }
+}
+
+/// Extracts the code blocks of a markdown document
+///
+/// Fenced code blocks keep the first word of their info string as language,
+/// indented code blocks have no language.
+///
+/// # Arguments
+///
+/// * `md` - The markdown content to analyze
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::extract_code_blocks;
+///
+/// let md = "# Example\n\n```python\nprint('hello')\n```\n";
+/// let blocks = extract_code_blocks(md);
+/// assert_eq!(blocks.len(), 1);
+/// assert_eq!(blocks[0].language.as_deref(), Some("python"));
+/// assert_eq!(blocks[0].line_start, 3);
+/// ```
+pub fn extract_code_blocks(md: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+
+    for (event, range) in Parser::new(md).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .next()
+                        .filter(|l| !l.is_empty())
+                        .map(|l| l.to_string()),
+                    CodeBlockKind::Indented => None,
+                };
+                current = Some(CodeBlock {
+                    language,
+                    content: String::new(),
+                    line_start: md[..range.start].matches('\n').count() + 1,
+                });
+            }
+            Event::Text(t) => {
+                if let Some(block) = current.as_mut() {
+                    block.content.push_str(&t);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(block) = current.take() {
+                    blocks.push(block);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+/// Saves code blocks as separate files named `<page>_block_<n>.<ext>`
+///
+/// The directory is created if needed and blocks are numbered from 1.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file names)
+/// * `blocks` - The code blocks to save
+/// * `dir` - The directory in which the files are written
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or if a file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_code_blocks(
+    url: &str,
+    blocks: &[CodeBlock],
+    dir: &Path,
+) -> Result<(), GitBookError> {
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).await?;
+    let page = url_to_filename(strip_md_suffix(url));
+    for (i, block) in blocks.iter().enumerate() {
+        let filename = format!("{}_block_{}.{}", page, i + 1, block.extension());
+        fs::write(dir.join(filename), &block.content).await?;
+    }
+    Ok(())
+}
+
+/// Detects the language of a text
+///
+/// Returns `None` when the detection confidence is below `min_confidence`
+///
+/// # Arguments
+///
+/// * `text` - The text to analyze
+/// * `min_confidence` - The minimum confidence (0.0 to 1.0) required
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{detect_language, Lang};
+///
+/// let text = "This page explains how to install and configure the command line tool.";
+/// assert_eq!(detect_language(text, 0.5), Some(Lang::Eng));
+/// ```
+pub fn detect_language(text: &str, min_confidence: f64) -> Option<Lang> {
+    whatlang::detect(text)
+        .filter(|info| info.confidence() >= min_confidence)
+        .map(|info| info.lang())
+}
+
+/// Navigation phrases removed by [`strip_navigation_boilerplate`]
+///
+/// Each pattern is a case-insensitive regex matched against a whole trimmed line
+pub const DEFAULT_NAVIGATION_PATTERNS: &[&str] = &[
+    r"home",
+    r"(next|previous)(\s*[→←>»«<])?",
+    r"[←<«]\s*previous",
+    r"next\s*[→>»]",
+    r"on this page",
+    r"table of contents",
+    r"search\.*",
+    r"powered by gitbook",
+    r"last updated.*",
+    r"was this helpful\??",
+    r"edit (this page )?on github",
+    r"copy link",
+];
+
+/// The [`DEFAULT_NAVIGATION_PATTERNS`], compiled once
+static NAVIGATION_REGEXES: LazyLock<Vec<Regex>> =
+    LazyLock::new(|| navigation_regexes(DEFAULT_NAVIGATION_PATTERNS));
+
+/// Minimum number of consecutive single-word lines considered as a navigation menu
+const NAVIGATION_RUN_MIN_LEN: usize = 3;
+
+/// Compiles navigation patterns into regexes matching whole lines, without case
+fn navigation_regexes(patterns: &[&str]) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|p| Regex::new(&format!("(?i)^(?:{})$", p)).ok())
+        .collect()
+}
+
+/// Removes GitBook navigation boilerplate from a plain text
+///
+/// Drops the lines matching [`DEFAULT_NAVIGATION_PATTERNS`] and the runs of
+/// consecutive single-word lines left by sidebars and menus. Must be applied
+/// before [`txt_sanitize`], which may join the lines.
+///
+/// # Arguments
+///
+/// * `text` - The plain text to clean
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::strip_navigation_boilerplate;
+///
+/// let text = "Home\nInstallation\nRun the installer to get started.\nNext →";
+/// let clean = strip_navigation_boilerplate(text);
+/// assert_eq!(clean, "Installation\nRun the installer to get started.");
+/// ```
+pub fn strip_navigation_boilerplate(text: &str) -> String {
+    strip_navigation_lines(text, &NAVIGATION_REGEXES)
+}
+
+/// Removes navigation boilerplate from a plain text using custom patterns
+///
+/// See [`strip_navigation_boilerplate`] for the details.
+/// Invalid patterns are ignored.
+///
+/// # Arguments
+///
+/// * `text` - The plain text to clean
+/// * `patterns` - Case-insensitive regexes matched against whole trimmed lines
+pub fn strip_navigation_boilerplate_with_patterns(text: &str, patterns: &[&str]) -> String {
+    strip_navigation_lines(text, &navigation_regexes(patterns))
+}
+
+/// Removes the lines matching `regexes` and the runs of single-word lines
+fn strip_navigation_lines(text: &str, regexes: &[Regex]) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !regexes.iter().any(|re| re.is_match(line.trim())))
+        .collect();
+
+    let is_single_word = |line: &str| {
+        let line = line.trim();
+        !line.is_empty()
+            && line != CODE_MARK
+            && !line.contains(char::is_whitespace)
+            && !line.ends_with(['.', ':', '?', '!'])
+    };
+
+    let mut kept = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let run_len = lines[i..]
+            .iter()
+            .take_while(|line| is_single_word(line))
+            .count();
+
+        if run_len >= NAVIGATION_RUN_MIN_LEN {
+            i += run_len;
+        } else {
+            kept.push(lines[i]);
+            i += 1;
+        }
+    }
+
+    kept.join("\n")
+}
+
+/// Cleans and sanitizes the text by removing special GitBook tags
+///
+/// Removes `{% code %}`, `{% endcode %}`, and other special GitBook tags,
+/// normalizes spaces, and removes quotation marks and `|`. Dashes are kept, see
+/// [`SanitizeConfig::remove_dashes`]. The lines and their indentation are kept, runs
+/// of blank lines are collapsed to one, see [`SanitizeConfig::join_lines`].
+/// `{% swagger %}` and `{% openapi %}` blocks are rendered as
+/// `<METHOD> <path> — <summary>` so API endpoints are kept in the output, and
+/// `{% content-ref %}` blocks as `<inner text> [See: <url>]`.
+///
+/// # Arguments
+///
+/// * `txt` - The text to clean
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::txt_sanitize;
+///
+/// let dirty = r#"{% code title="example.rs" %}fn main() {}{% endcode %}"#;
+/// let clean = txt_sanitize(dirty);
+/// assert!(clean.contains("example.rs"));
+/// assert!(clean.contains("fn main()"));
+/// ```
+#[must_use = "the input is left unchanged, the sanitized text is returned"]
+pub fn txt_sanitize(txt: &str) -> String {
+    txt_sanitize_with_config(txt, &SanitizeConfig::default())
+}
+
+/// Cleans and sanitizes the text, following the given configuration
+///
+/// With `remove_gitbook_tags` disabled, the `{% ... %}` tags are kept verbatim;
+/// spaces are still normalized and quotation marks still removed. With `remove_dashes`
+/// enabled, the `-` characters are removed too.
+///
+/// # Arguments
+///
+/// * `txt` - The text to clean
+/// * `config` - The sanitization configuration
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{txt_sanitize_with_config, SanitizeConfig};
+///
+/// let config = SanitizeConfig {
+///     remove_gitbook_tags: false,
+///     ..Default::default()
+/// };
+/// let text = txt_sanitize_with_config("{% hint style='info' %}Note{% endhint %}", &config);
+/// assert_eq!(text, "{% hint style='info' %}Note{% endhint %}");
+///
+/// let config = SanitizeConfig {
+///     remove_dashes: true,
+///     ..Default::default()
+/// };
+/// assert_eq!(txt_sanitize_with_config("type-safe", &config), "typesafe");
+/// ```
+#[must_use = "the input is left unchanged, the sanitized text is returned"]
+pub fn txt_sanitize_with_config(txt: &str, config: &SanitizeConfig) -> String {
+    txt_sanitize_with_context(txt, config, &SanitizeContext::default())
+}
+
+/// The page being sanitized
+#[derive(Debug, Clone, Default)]
+pub struct SanitizeContext {
+    /// The URL of the page, against which relative `{% content-ref %}` URLs are resolved
+    pub base_url: Option<String>,
+}
+
+/// Cleans and sanitizes the text of a page, following the given configuration
+///
+/// Works like [`txt_sanitize_with_config`], and resolves the relative URLs of
+/// `{% content-ref %}` blocks against `context.base_url`. Without a base URL
+/// they are kept as written.
+///
+/// # Arguments
+///
+/// * `txt` - The text to clean
+/// * `config` - The sanitization configuration
+/// * `context` - The page the text comes from
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{txt_sanitize_with_context, SanitizeConfig, SanitizeContext};
+///
+/// let context = SanitizeContext {
+///     base_url: Some("https://docs.example.com/guide/intro.md".to_string()),
+/// };
+/// let text = txt_sanitize_with_context(
+///     r#"{% content-ref url="setup.md" %}Setup{% endcontent-ref %}"#,
+///     &SanitizeConfig::default(),
+///     &context,
+/// );
+/// assert_eq!(text, "Setup [See: https://docs.example.com/guide/setup.md]");
+/// ```
+#[must_use = "the input is left unchanged, the sanitized text is returned"]
+pub fn txt_sanitize_with_context(
+    txt: &str,
+    config: &SanitizeConfig,
+    context: &SanitizeContext,
+) -> String {
+    let mut result = String::from(txt);
+
+    if config.remove_gitbook_tags {
+        let base = context.base_url.as_deref().and_then(|u| Url::parse(u).ok());
+        result = RE_CONTENT_REF
+            .replace_all(&result, |caps: &regex::Captures| {
+                let url = match &base {
+                    Some(base) => base
+                        .join(&caps[1])
+                        .map_or_else(|_| caps[1].to_string(), String::from),
+                    None => caps[1].to_string(),
+                };
+                let text = format!("{} [See: {}]", caps[2].trim(), url);
+                pad_replacement(&result, caps, text)
+            })
+            .to_string();
+
+        // Hints keep their style as a label, e.g. `[WARNING] Read this first`
+        result = RE_HINT
+            .replace_all(&result, |caps: &regex::Captures| {
+                let text = format!("[{}] {}", caps[1].to_uppercase(), caps[2].trim());
+                pad_replacement(&result, caps, text)
+            })
+            .to_string();
+
+        result = RE_CODE
+            .replace_all(&result, |caps: &regex::Captures| {
+                format!("{} {}", &caps[1], &caps[2])
+            })
+            .to_string();
+
+        result = RE_CODE_NO_TITLE
+            .replace_all(&result, |caps: &regex::Captures| caps[1].to_string())
+            .to_string();
+
+        result = RE_API
+            .replace_all(&result, |caps: &regex::Captures| {
+                let mut method = "";
+                let mut path = "";
+                let mut summary = "";
+                for attr in RE_ATTR.captures_iter(&caps[1]) {
+                    match attr.get(1).map(|m| m.as_str()) {
+                        Some("method") => method = attr.get(2).map_or("", |m| m.as_str()),
+                        Some("path") => path = attr.get(2).map_or("", |m| m.as_str()),
+                        Some("summary") => summary = attr.get(2).map_or("", |m| m.as_str()),
+                        _ => {}
+                    }
+                }
+
+                let endpoint = format!("{} {}", method.to_uppercase(), path);
+                let text = if summary.is_empty() {
+                    endpoint.trim().to_string()
+                } else {
+                    format!("{} — {}", endpoint.trim(), summary)
+                };
+                pad_replacement(&result, caps, text)
+            })
+            .to_string();
+
+        result = RE_TITLE.replace_all(&result, "$1").to_string();
+
+        result = RE_GENERIC.replace_all(&result, "").to_string();
+    }
+
+    // Cross-reference URLs are kept intact
+    result = RE_DASH
+        .replace_all(&result, |caps: &regex::Captures| {
+            if caps[0].starts_with('[') || (&caps[0] == "-" && !config.remove_dashes) {
+                caps[0].to_string()
+            } else {
+                String::new()
+            }
+        })
+        .to_string();
+    if config.join_lines {
+        return RE_SPACE.replace_all(&result, " ").trim().to_string();
+    }
+
+    normalize_lines(&result)
+}
+
+/// Surrounds the replacement of a tag with spaces where it would stick to the text around it
+fn pad_replacement(haystack: &str, caps: &regex::Captures, text: String) -> String {
+    let Some(tag) = caps.get(0) else {
+        return text;
+    };
+    let sticks = |c: Option<char>| c.is_some_and(|c| !c.is_whitespace());
+    let before = if sticks(haystack[..tag.start()].chars().next_back()) {
+        " "
+    } else {
+        ""
+    };
+    let after = if sticks(haystack[tag.end()..].chars().next()) {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}", before, text, after)
+}
+
+/// Normalizes the spaces of each line and collapses the runs of blank lines to one
+///
+/// The indentation of the lines is kept, e.g. for nested list items and code.
+fn normalize_lines(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let content = line.trim();
+        let line = if content.is_empty() {
+            String::new()
+        } else {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            format!("{}{}", indent, RE_SPACE.replace_all(content, " "))
+        };
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Writes a text to a terminal, coloring the hint labels written by [`txt_sanitize`]
+///
+/// `[INFO]` is blue, `[SUCCESS]` green, `[WARNING]` yellow and `[DANGER]` red.
+/// The colors are only emitted if the stream supports them, see [`ColorMode`](crate::ColorMode).
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::write_text_colored;
+/// use termcolor::Buffer;
+///
+/// let mut plain = Buffer::no_color();
+/// write_text_colored(&mut plain, "[INFO] Note").unwrap();
+/// assert_eq!(plain.as_slice(), b"[INFO] Note");
+///
+/// let mut colored = Buffer::ansi();
+/// write_text_colored(&mut colored, "[DANGER] Careful").unwrap();
+/// assert!(colored.as_slice().starts_with(b"\x1b["));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing to the stream fails
+pub fn write_text_colored<W: WriteColor>(out: &mut W, text: &str) -> std::io::Result<()> {
+    let mut last = 0;
+    for caps in RE_LABEL.captures_iter(text) {
+        let label = caps.get(0).unwrap();
+        let color = match &caps[1] {
+            "INFO" => Color::Blue,
+            "SUCCESS" => Color::Green,
+            "WARNING" => Color::Yellow,
+            _ => Color::Red,
+        };
+        write!(out, "{}", &text[last..label.start()])?;
+        out.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+        write!(out, "{}", label.as_str())?;
+        out.reset()?;
+        last = label.end();
+    }
+    write!(out, "{}", &text[last..])
+}
+
+/// Saves the text content to a file
+///
+/// The file will be created in the `data/txt/` directory with a name based on the URL
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The text content to save
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::save_text;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     save_text("https://example.com/page", "Contenu texte").await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing the file fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_text(url: &str, content: &str) -> Result<(), GitBookError> {
+    save_text_with_config(url, content, &CrawlConfig::default()).await
+}
+
+/// Saves the text content to a file, following the given configuration
+///
+/// See [`save_markdown_with_config`] for how `preserve_path_structure` affects the file location.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The text content to save
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or if the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_text_with_config(
+    url: &str,
+    content: &str,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, &config.output_dir.join("txt"), "txt", config);
+    write_output(&path, content, config).await
+}
+
+/// Saves text read from `reader` to a file, without holding it in memory
+///
+/// The file is named like with [`save_text_with_config`]. Returns the number of bytes written.
+/// As with [`save_markdown_streaming`], a read error leaves no partial file.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `reader` - The source of the text content
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if reading from `reader` or writing the file fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_text_streaming(
+    url: &str,
+    mut reader: impl AsyncRead + Unpin,
+    config: &CrawlConfig,
+) -> Result<u64, GitBookError> {
+    let path = output_path(url, &config.output_dir.join("txt"), "txt", config);
+    create_parent_dir(&path, config).await?;
+    write_atomic_with(&path, async |file| {
+        Ok::<_, GitBookError>(tokio::io::copy(&mut reader, file).await?)
+    })
+    .await
+}
+
+/// Rewrites the link and image URLs of a markdown document that start with `old_base`
+///
+/// The matching prefix is replaced with `new_base`. Inline links, autolinks and
+/// reference definitions are rewritten in place, leaving the rest of the
+/// document untouched; URLs in plain text and code are not links and are kept.
+///
+/// # Arguments
+///
+/// * `md` - The markdown document
+/// * `old_base` - The URL prefix to replace, e.g. `https://docs.example.com/`
+/// * `new_base` - The new prefix, e.g. `file:///data/md/`
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::rewrite_absolute_urls;
+///
+/// let md = "See [setup](https://docs.example.com/guide/setup) and `https://docs.example.com/`.";
+/// let rewritten =
+///     rewrite_absolute_urls(md, "https://docs.example.com/", "file:///data/md/");
+/// assert_eq!(rewritten, "See [setup](file:///data/md/guide/setup) and `https://docs.example.com/`.");
+/// ```
+pub fn rewrite_absolute_urls(md: &str, old_base: &str, new_base: &str) -> String {
+    if old_base.is_empty() {
+        return md.to_string();
+    }
+
+    // Byte ranges of the URLs to rewrite
+    let mut ranges: Vec<(usize, usize)> = link_destinations(md)
+        .into_iter()
+        .filter(|link| {
+            matches!(
+                link.link_type,
+                None | Some(LinkType::Inline | LinkType::Autolink)
+            )
+        })
+        .filter(|link| link.dest.starts_with(old_base))
+        .map(|link| (link.range.start, link.range.end))
+        .collect();
+
+    ranges.sort();
+    ranges.dedup();
+
+    let mut result = String::with_capacity(md.len());
+    let mut last = 0;
+    for (start, end) in ranges {
+        if start < last {
+            continue;
+        }
+        result.push_str(&md[last..start]);
+        result.push_str(new_base);
+        result.push_str(&md[start + old_base.len()..end]);
+        last = end;
+    }
+    result.push_str(&md[last..]);
+    result
+}
+
+/// The destination of a link, an image or a reference definition of a markdown document
+struct LinkDestination {
+    /// The destination, unescaped
+    dest: String,
+
+    /// Where the destination is written in the document
+    range: std::ops::Range<usize>,
+
+    /// How the link is written, `None` for a reference definition
+    link_type: Option<LinkType>,
+
+    is_image: bool,
+}
+
+/// Returns the destinations of the links, images and reference definitions of `md`
+/// that are written as-is in the document
+///
+/// A destination is searched after the text of its link (after the `]:` of a
+/// reference definition), so a link whose text is its destination, like
+/// `[api](api)`, gives the range of the destination. Autolinks are their own text.
+fn link_destinations(md: &str) -> Vec<LinkDestination> {
+    let find = |dest: &str, from: usize, to: usize| {
+        md[from..to]
+            .find(dest)
+            .map(|pos| from + pos..from + pos + dest.len())
+    };
+    let mut found = Vec::new();
+
+    let parser = Parser::new(md).into_offset_iter();
+    for (_, def) in parser.reference_definitions().iter() {
+        let span = def.span.clone();
+        let from = md[span.clone()]
+            .find("]:")
+            .map_or(span.start, |pos| span.start + pos + 2);
+        if let Some(range) = find(&def.dest, from, span.end) {
+            found.push(LinkDestination {
+                dest: def.dest.to_string(),
+                range,
+                link_type: None,
+                is_image: false,
+            });
+        }
+    }
+
+    // The open links and images, with the end of the text read so far
+    let mut open: Vec<(LinkDestination, usize)> = Vec::new();
+    for (event, range) in parser {
+        match event {
+            Event::Start(tag @ (Tag::Link { .. } | Tag::Image { .. })) => {
+                let is_image = matches!(tag, Tag::Image { .. });
+                let (Tag::Link {
+                    link_type,
+                    dest_url,
+                    ..
+                }
+                | Tag::Image {
+                    link_type,
+                    dest_url,
+                    ..
+                }) = tag
+                else {
+                    continue;
+                };
+                let link = LinkDestination {
+                    dest: dest_url.to_string(),
+                    range: range.clone(),
+                    link_type: Some(link_type),
+                    is_image,
+                };
+                open.push((link, range.start));
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                let Some((mut link, text_end)) = open.pop() else {
+                    continue;
+                };
+                let span = link.range.clone();
+                let from = match link.link_type {
+                    Some(LinkType::Autolink | LinkType::Email) => span.start,
+                    _ => text_end,
+                };
+                if let Some(range) = find(&link.dest, from, span.end) {
+                    link.range = range;
+                    found.push(link);
+                }
+                if let Some((_, parent_text_end)) = open.last_mut() {
+                    *parent_text_end = (*parent_text_end).max(span.end);
+                }
+            }
+            _ => {
+                if let Some((_, text_end)) = open.last_mut() {
+                    *text_end = (*text_end).max(range.end);
+                }
+            }
+        }
+    }
+
+    found
+}
+
+/// Rewrites the links between downloaded pages as relative paths between their files
+///
+/// Links are resolved against `base_url`, the URL of the page containing `md`, then
+/// looked up in `pages` without their `#fragment`, trailing `/` or `.md` suffix; the
+/// fragment is kept on the relative path. Links to pages missing from `pages`, links
+/// to other sites and anchors within the page are left as they are, and so is the
+/// whole document when `base_url` is not in `pages`.
+///
+/// # Arguments
+///
+/// * `md` - The markdown document
+/// * `base_url` - The URL of the page containing the document
+/// * `pages` - The file each downloaded page was saved to, by URL
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::rewrite_markdown_links;
+/// use std::collections::HashMap;
+/// use std::path::PathBuf;
+///
+/// let pages = HashMap::from([
+///     ("https://docs.example.com/guide/intro".to_string(), PathBuf::from("md/guide/intro.md")),
+///     ("https://docs.example.com/api/auth".to_string(), PathBuf::from("md/api/auth.md")),
+/// ]);
+/// let md = "See [auth](https://docs.example.com/api/auth#tokens) and [home](https://example.org).";
+/// assert_eq!(
+///     rewrite_markdown_links(md, "https://docs.example.com/guide/intro", &pages),
+///     "See [auth](../api/auth.md#tokens) and [home](https://example.org)."
+/// );
+/// ```
+pub fn rewrite_markdown_links(
+    md: &str,
+    base_url: &str,
+    pages: &HashMap<String, PathBuf>,
+) -> String {
+    let Ok(base) = Url::parse(base_url) else {
+        return md.to_string();
+    };
+    let pages: HashMap<String, &PathBuf> = pages
+        .iter()
+        .map(|(url, path)| (page_key(url), path))
+        .collect();
+    let Some(current) = pages.get(&page_key(base.as_str())) else {
+        return md.to_string();
+    };
+    let current_dir = current.parent().unwrap_or(Path::new(""));
+
+    // Byte ranges of the destinations to rewrite, with their replacement
+    let rewrite = |dest: &str| {
+        if dest.is_empty() || dest.starts_with('#') {
+            return None;
+        }
+        let target = base
+            .join(dest)
+            .ok()
+            .filter(|t| t.origin() == base.origin())?;
+        let path = pages.get(&page_key(target.as_str()))?;
+        let mut relative = relative_path(current_dir, path);
+        if let Some(fragment) = target.fragment().filter(|f| !f.is_empty()) {
+            relative.push('#');
+            relative.push_str(fragment);
+        }
+        Some(relative)
+    };
+    let mut ranges: Vec<(usize, usize, String)> = link_destinations(md)
+        .into_iter()
+        .filter(|link| !link.is_image && matches!(link.link_type, None | Some(LinkType::Inline)))
+        .filter_map(|link| Some((link.range.start, link.range.end, rewrite(&link.dest)?)))
+        .collect();
+
+    ranges.sort();
+    let mut result = String::with_capacity(md.len());
+    let mut last = 0;
+    for (start, end, relative) in ranges {
+        if start < last {
+            continue;
+        }
+        result.push_str(&md[last..start]);
+        result.push_str(&relative);
+        last = end;
+    }
+    result.push_str(&md[last..]);
+    result
+}
+
+/// The form of a page URL used by [`rewrite_markdown_links`] to find its file
+fn page_key(url: &str) -> String {
+    let page = url.split('#').next().unwrap_or(url).trim_end_matches('/');
+    page.strip_suffix(".md").unwrap_or(page).to_string()
+}
+
+/// Returns the path of `to` relative to the directory `from_dir`, with `/` separators
+fn relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<_> = from_dir.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+/// Default separator written before each page of `combined.txt`
+pub const DEFAULT_PAGE_SEPARATOR: &str = "\n--- {url} ---\n";
+
+/// Concatenates the text of several pages, writing a separator before each one
+///
+/// The separator is a template: `{url}`, `{title}`, `{index}` (starting at 1)
+/// and `{total}` are replaced with the values of each page.
+///
+/// # Arguments
+///
+/// * `pages` - The `(url, title, text)` of each page, in order
+/// * `separator` - The separator template, e.g. [`DEFAULT_PAGE_SEPARATOR`]
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{combine_texts, DEFAULT_PAGE_SEPARATOR};
+///
+/// let pages = [
+///     ("https://docs.example.com/a", "A", "First"),
+///     ("https://docs.example.com/b", "B", "Second"),
+/// ];
+/// assert_eq!(
+///     combine_texts(&pages, DEFAULT_PAGE_SEPARATOR),
+///     "\n--- https://docs.example.com/a ---\nFirst\n--- https://docs.example.com/b ---\nSecond"
+/// );
+/// ```
+pub fn combine_texts(pages: &[(&str, &str, &str)], separator: &str) -> String {
+    let total = pages.len().to_string();
+    let mut combined = String::new();
+    for (i, (url, title, text)) in pages.iter().enumerate() {
+        combined.push_str(
+            &separator
+                .replace("{url}", url)
+                .replace("{title}", title)
+                .replace("{index}", &(i + 1).to_string())
+                .replace("{total}", &total),
+        );
+        combined.push_str(text);
+    }
+    combined
+}
+
+/// Default declaration written by `--encoding-declaration`
+pub const DEFAULT_ENCODING_DECLARATION: &str = "# -*- coding: utf-8 -*-";
+
+/// Prepends an encoding declaration line to a text
+///
+/// The declaration is not added again if the text already starts with it.
+///
+/// # Arguments
+///
+/// * `text` - The plain text
+/// * `declaration` - The declaration line, e.g. [`DEFAULT_ENCODING_DECLARATION`]
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::{with_encoding_declaration, DEFAULT_ENCODING_DECLARATION};
+///
+/// let text = with_encoding_declaration("Hello", DEFAULT_ENCODING_DECLARATION);
+/// assert_eq!(text, "# -*- coding: utf-8 -*-\nHello");
+/// ```
+pub fn with_encoding_declaration(text: &str, declaration: &str) -> String {
+    if text.lines().next() == Some(declaration) {
+        return text.to_string();
+    }
+    format!("{}\n{}", declaration, text)
+}
+
+/// Hard-wraps a text at the given number of columns
+///
+/// Lines are broken on word boundaries only. Code is left untouched: lines
+/// between ```` ``` ```` markers and lines indented with a tab or four spaces.
+/// A width of 0 disables wrapping.
+///
+/// # Arguments
+///
+/// * `text` - The text to wrap
+/// * `width` - The maximum number of columns
+///
+/// # Exemples
+///
+/// ```
+/// use gitbook2text::wrap_text;
+///
+/// let wrapped = wrap_text("one two three four", 9);
+/// assert_eq!(wrapped, "one two\nthree\nfour");
+/// ```
+pub fn wrap_text(text: &str, width: usize) -> String {
+    if width == 0 {
+        return text.to_string();
+    }
+    wrap_lines(text, width, false)
+}
+
+/// Hard-wraps a text returned by [`markdown_to_text_marked`] like [`wrap_text`],
+/// leaving its code blocks untouched and removing their marks
+pub(crate) fn wrap_marked_text(text: &str, width: usize) -> String {
+    wrap_lines(text, width, true)
+}
+
+fn wrap_lines(text: &str, width: usize, marked: bool) -> String {
+    let options = textwrap::Options::new(width.max(1)).break_words(false);
+    let mut in_code = false;
+    let mut lines = Vec::new();
+
+    for line in text.lines() {
+        if marked && line == CODE_MARK {
+            in_code = !in_code;
+        } else if !marked && line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            lines.push(line.to_string());
+        } else if width == 0 || in_code || line.starts_with('\t') || line.starts_with("    ") {
+            lines.push(line.to_string());
+        } else if line.is_empty() {
+            lines.push(String::new());
+        } else {
+            lines.extend(
+                textwrap::wrap(line, &options)
+                    .into_iter()
+                    .map(|l| l.into_owned()),
+            );
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Saves the text content hard-wrapped at the given number of columns
+///
+/// The file is written in `dir` with a name based on the URL, see [`wrap_text`]
+/// for the wrapping rules.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The text content to save
+/// * `dir` - The directory in which the file is written
+/// * `wrap` - The maximum number of columns (0 disables wrapping)
+/// * `config` - The crawl configuration
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{save_text_wrapped, CrawlConfig};
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = CrawlConfig::default();
+///     save_text_wrapped("https://example.com/page", "Contenu", Path::new("data/txt"), 80, &config)
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or if the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_text_wrapped(
+    url: &str,
+    content: &str,
+    dir: &Path,
+    wrap: usize,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, dir, "txt", config);
+    write_output(&path, wrap_text(content, wrap), config).await
+}
+
+/// Saves the text content to a Zstd-compressed file
+///
+/// Works like [`save_text_with_config`] but writes a `.txt.zst` file
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The text content to save
+/// * `level` - The Zstd compression level (1 to 22)
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if the compression or the file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_text_compressed(
+    url: &str,
+    content: &str,
+    level: i32,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, &config.output_dir.join("txt"), "txt.zst", config);
+    write_output(&path, &compress(content, level)?, config).await
+}
+
+/// Saves the text content to a file, along with a `.sha256` sidecar file
+///
+/// Works like [`save_text_with_config`]; see [`save_markdown_with_hash`] for the sidecar.
+///
+/// # Arguments
+///
+/// * `url` - The source URL (used to generate the file name)
+/// * `content` - The text content to save
+/// * `config` - The crawl configuration
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or if a file write fails
+#[must_use = "the file may not have been saved"]
+pub async fn save_text_with_hash(
+    url: &str,
+    content: &str,
+    config: &CrawlConfig,
+) -> Result<(), GitBookError> {
+    let path = output_path(url, &config.output_dir.join("txt"), "txt", config);
+    write_output(&path, content, config).await?;
+    write_hash_sidecar(&path, content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OrderedListStyle;
+    use crate::test_server::{MockResponse, MockServer};
+
+    #[test]
+    fn test_url_to_filename() {
+        let url = "https://example.com/path/to/page";
+        let filename = url_to_filename(url);
+        assert_eq!(filename, "https___example.com_path_to_page");
+    }
+
+    #[test]
+    fn test_url_to_filename_with_max() {
+        let prefix = format!("https://docs.example.com/{}", "guide/".repeat(40));
+        let a = url_to_filename_with_max(&format!("{}installation", prefix), 200);
+        let b = url_to_filename_with_max(&format!("{}configuration", prefix), 200);
+        assert_ne!(a, b);
+        assert_eq!(a.chars().count(), 109);
+        assert_eq!(a[..100], b[..100]);
+        assert!(a[100..].starts_with('_'));
+
+        let short = "https://docs.example.com/guide";
+        assert_eq!(url_to_filename_with_max(short, 200), url_to_filename(short));
+        assert_eq!(url_to_filename_with_max(&prefix, 20).chars().count(), 20);
+    }
+
+    #[test]
+    fn test_url_to_filename_windows_safe() {
+        assert_eq!(
+            url_to_filename_windows_safe(r#"https://x.com/a\b?c=<"d">*|e"#),
+            "https___x.com_a_b_c=__d____e"
+        );
+        assert_eq!(url_to_filename_windows_safe("a\tb"), "a_b");
+
+        let long = format!("https://x.com/{}", "é".repeat(300));
+        let filename = url_to_filename_windows_safe(&long);
+        assert_eq!(filename.chars().count(), 200);
+        assert!(filename.starts_with("https___x.com_é"));
+    }
+
+    #[test]
+    fn test_url_to_filename_strip_scheme() {
+        assert_eq!(
+            url_to_filename_strip_scheme("https://example.com/docs/page", '_'),
+            "example.com_docs_page"
+        );
+        assert_eq!(
+            url_to_filename_strip_scheme("http://example.com:8080/a", '-'),
+            "example.com-8080-a"
+        );
+        assert_eq!(
+            url_to_filename_strip_scheme("example.com/a", '_'),
+            "example.com_a"
+        );
+    }
+
+    #[test]
+    fn test_content_type_from_header() {
+        assert_eq!(
+            content_type_from_header("text/html; charset=utf-8"),
+            ContentType::Html
+        );
+        assert_eq!(
+            content_type_from_header("Text/Markdown"),
+            ContentType::Markdown
+        );
+        assert_eq!(
+            content_type_from_header("application/vnd.gitbook+json"),
+            ContentType::Json
+        );
+        assert_eq!(
+            content_type_from_header("image/png"),
+            ContentType::Other("image/png".to_string())
+        );
+        assert_eq!(
+            content_type_from_header(""),
+            ContentType::Other(String::new())
+        );
+    }
+
+    #[test]
+    fn test_html_main_text() {
+        let html = "<html><body><nav>Menu</nav><main><h1>Title</h1><script>var x;</script>\
+                    <p>First <b>bold</b></p><p>Second</p></main></body></html>";
+        assert_eq!(html_main_text(html), "Title\nFirst bold\nSecond");
+        assert_eq!(html_main_text("<p>Only body</p>"), "Only body");
+    }
+
+    /// Yields some bytes, then fails as if the connection was lost
+    struct InterruptedReader(bool);
+
+    impl AsyncRead for InterruptedReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if self.0 {
+                return std::task::Poll::Ready(Err(std::io::Error::other("interrupted")));
+            }
+            self.0 = true;
+            buf.put_slice(b"https://docs.example.com/partial");
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("links.txt");
+        std::fs::write(&path, "https://docs.example.com/a\n").unwrap();
+
+        let result = write_atomic_from(&path, InterruptedReader(false)).await;
+        assert!(result.is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "https://docs.example.com/a\n"
+        );
+        assert!(!dir.path().join("links.txt.tmp").exists());
+
+        write_atomic(&path, "https://docs.example.com/b")
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "https://docs.example.com/b"
+        );
+        assert!(!dir.path().join("links.txt.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_page_json() {
+        let server = MockServer::start(|request| match request.header("accept") {
+            Some(accept) if accept.contains("application/json") => {
+                MockResponse::ok(r#"{"title": "Intro"}"#)
+            }
+            _ => MockResponse::ok("<html></html>"),
+        })
+        .await;
+
+        let json = download_page_json(&format!("{}/intro", server.url))
+            .await
+            .unwrap();
+        assert_eq!(json["title"], "Intro");
+    }
+
+    #[test]
+    fn test_gitbook_json_text() {
+        let json = r#"{"document": {"object": "document", "nodes": [
+            {"object": "block", "type": "heading-1", "nodes": [
+                {"object": "text", "leaves": [{"text": "Intro"}]}
+            ]},
+            {"object": "block", "type": "paragraph", "nodes": [
+                {"object": "text", "leaves": [{"text": "Hello "}, {"text": "world"}]},
+                {"object": "inline", "type": "link", "nodes": [
+                    {"object": "text", "leaves": [{"text": "!"}]}
+                ]}
+            ]}
+        ]}}"#;
+        assert_eq!(gitbook_json_text(json).unwrap(), "Intro\n\nHello world!");
+        assert_eq!(
+            gitbook_json_text(r##"{"markdown": "# Intro"}"##).unwrap(),
+            "# Intro"
+        );
+        assert!(matches!(
+            gitbook_json_text("not json"),
+            Err(GitBookError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Authentication"), "authentication");
+        assert_eq!(slugify("  Rate-limits & Quotas "), "rate-limits-quotas");
+        assert_eq!(slugify("Café au lait"), "café-au-lait");
+        assert_eq!(slugify("???"), "");
+        assert_eq!(
+            slugify_with_separator("Error_codes list", '.'),
+            "error.codes.list"
+        );
+    }
+
+    #[test]
+    fn test_first_h1() {
+        assert_eq!(
+            first_h1("Intro\n\n## Sub\n\n# The `main` title\n\n# Other").as_deref(),
+            Some("The main title")
+        );
+        assert_eq!(first_h1("## Only a subsection"), None);
+    }
+
+    #[test]
+    fn test_url_to_path() {
+        let base = Path::new("base");
+        assert_eq!(
+            url_to_path("https://docs.example.com/guide/intro", base),
+            Path::new("base/guide/intro")
+        );
+        assert_eq!(
+            url_to_path("https://docs.example.com/", base),
+            Path::new("base/index")
+        );
+        assert_eq!(
+            url_to_path("https://docs.example.com/a/../../b/", base),
+            Path::new("base/b")
+        );
+    }
+
+    #[test]
+    fn test_output_path_preserve_structure() {
+        let config = CrawlConfig {
+            preserve_path_structure: true,
+            ..Default::default()
+        };
+        let path = output_path(
+            "https://docs.example.com/guide/intro.md",
+            Path::new("data/md"),
+            "md",
+            &config,
+        );
+        assert_eq!(path, Path::new("data/md/guide/intro.md"));
+    }
+
+    #[tokio::test]
+    async fn test_save_with_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().join("docs.example.com"),
+            strip_scheme: true,
+            ..Default::default()
+        };
+        std::fs::create_dir_all(config.output_dir.join("md")).unwrap();
+        std::fs::create_dir_all(config.output_dir.join("txt")).unwrap();
+        save_markdown_with_config("https://docs.example.com/intro", "# Intro", &config)
+            .await
+            .unwrap();
+        save_text_with_config("https://docs.example.com/intro", "Intro", &config)
+            .await
+            .unwrap();
+        let site = dir.path().join("docs.example.com");
+        assert!(site.join("md/docs.example.com_intro.md").exists());
+        assert!(site.join("txt/docs.example.com_intro.txt").exists());
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let text = "a long line that should wrap\n```\ncode line that is way too long\n```\n    indented code that stays\nincomprehensibilities";
+        let wrapped = wrap_text(text, 10);
+        assert_eq!(
+            wrapped,
+            "a long\nline that\nshould\nwrap\n```\ncode line that is way too long\n```\n    indented code that stays\nincomprehensibilities"
+        );
+        assert_eq!(wrap_text("unchanged text", 0), "unchanged text");
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        let compressed = compress("# Title\n\nSome text", 3).unwrap();
+        let decompressed = zstd::stream::decode_all(compressed.as_slice()).unwrap();
+        assert_eq!(decompressed, b"# Title\n\nSome text");
+    }
+
+    #[test]
+    fn test_markdown_to_text() {
+        let md = "# Title\n\nSome **bold** text";
+        let text = markdown_to_text(md);
+        assert!(text.contains("Title"));
+        assert!(text.contains("bold"));
+    }
+
+    #[test]
+    fn test_markdown_to_text_blank_lines() {
+        let md = "# Title\n\nFirst paragraph\n\n- one\n- two\n\n```\ncode\n```\n\nLast";
+        let text = markdown_to_text(md);
+        assert_eq!(
+            text,
+            "Title\n\nFirst paragraph\n\n• one\n• two\n\ncode\n\nLast"
+        );
+
+        let config = TextConfig {
+            blank_lines_between_blocks: false,
+            ..Default::default()
+        };
+        let dense = markdown_to_text_with_config(md, &config);
+        assert_eq!(dense, "TitleFirst paragraph\n• one\n• two\ncode\nLast");
+    }
+
+    #[test]
+    fn test_markdown_to_text_strip_images() {
+        let md = "![alt text](img.png)";
+        assert_eq!(markdown_to_text(md), "alt text");
+
+        let config = TextConfig {
+            strip_images: true,
+            ..Default::default()
+        };
+        assert_eq!(markdown_to_text_with_config(md, &config), "");
+        assert_eq!(
+            markdown_to_text_with_config("See ![the `diagram`](a.png) below", &config),
+            "See  below"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_text_inline_html() {
+        let md = "<details>\n<summary>Advanced</summary>\n\nSet <kbd>Ctrl</kbd>+<kbd>C</kbd><!-- todo -->.\n\n</details>";
+        assert_eq!(markdown_to_text(md), "Set Ctrl+C.");
+
+        let config = TextConfig {
+            strip_inline_html: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            markdown_to_text_with_config(md, &config),
+            "Advanced\n\nSet Ctrl+C."
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_text_lists() {
+        let md = "Steps:\n\n3. Install\n4. Configure\n   - `config.yml`\n     1. Edit it\n   - env vars\n\nDone";
+        assert_eq!(
+            markdown_to_text(md),
+            "Steps:\n\n3. Install\n4. Configure\n  • config.yml\n    1. Edit it\n  • env vars\n\nDone"
+        );
+
+        let config = TextConfig {
+            list_bullet: "* ".to_string(),
+            ordered_list_prefix: OrderedListStyle::Roman,
+            indent_width: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            markdown_to_text_with_config(md, &config),
+            "Steps:\n\niii. Install\niv. Configure\n    * config.yml\n        i. Edit it\n    * env vars\n\nDone"
+        );
+
+        let config = TextConfig {
+            ordered_list_prefix: OrderedListStyle::Alpha,
+            ..Default::default()
+        };
+        assert_eq!(
+            markdown_to_text_with_config("1. One\n2. Two", &config),
+            "a. One\nb. Two"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_text_preserve_urls() {
+        let md = "Read [the guide](https://example.com/guide), \
+                  [https://example.com](https://example.com) and <https://example.org>.";
+        assert_eq!(
+            markdown_to_text(md),
+            "Read the guide, https://example.com and https://example.org."
+        );
+
+        let config = TextConfig {
+            preserve_urls: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            markdown_to_text_with_config(md, &config),
+            "Read the guide (https://example.com/guide), https://example.com and https://example.org."
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_text_unicode_normalization() {
+        let md = "# Cafe\u{301}\n\n\u{ff76}\u{ff9e} \u{1100}\u{1161}";
+        assert_eq!(
+            markdown_to_text(md),
+            "Cafe\u{301}\n\n\u{ff76}\u{ff9e} \u{1100}\u{1161}"
+        );
+
+        let normalized = |form| {
+            let config = TextConfig {
+                unicode_normalization: Some(form),
+                ..Default::default()
+            };
+            markdown_to_text_with_config(md, &config)
+        };
+        assert_eq!(
+            normalized(UnicodeForm::Nfc),
+            "Caf\u{e9}\n\n\u{ff76}\u{ff9e} \u{ac00}"
+        );
+        assert_eq!(
+            normalized(UnicodeForm::Nfkc),
+            "Caf\u{e9}\n\n\u{30ac} \u{ac00}"
+        );
+        assert_eq!(
+            normalized(UnicodeForm::Nfd),
+            "Cafe\u{301}\n\n\u{ff76}\u{ff9e} \u{1100}\u{1161}"
+        );
+        assert_eq!(
+            normalized(UnicodeForm::Nfkd),
+            "Cafe\u{301}\n\n\u{30ab}\u{3099} \u{1100}\u{1161}"
+        );
+    }
+
+    #[test]
+    fn test_extract_code_blocks() {
+        let md = "# Title\n\n```rust,ignore\nfn main() {}\n```\n\nText\n\n    indented code\n";
+        let blocks = extract_code_blocks(md);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].content, "fn main() {}\n");
+        assert_eq!(blocks[0].line_start, 3);
+        assert_eq!(blocks[0].extension(), "rs");
+        assert_eq!(blocks[1].language, None);
+        assert_eq!(blocks[1].content, "indented code\n");
+        assert_eq!(blocks[1].line_start, 9);
+        assert_eq!(blocks[1].extension(), "txt");
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let french =
+            "Cette page explique comment installer et configurer l'outil en ligne de commande.";
+        assert_eq!(detect_language(french, 0.5), Some(Lang::Fra));
+        assert_eq!(detect_language("", 0.5), None);
+        assert_eq!(detect_language(french, 1.1), None);
+    }
+
+    #[test]
+    fn test_strip_navigation_boilerplate() {
+        let text = "Home\nGuide\nAPI\nFAQ\nChangelog\n\nInstallation\nRun the installer.\n← Previous\nNext →\nLast updated 2 days ago";
+        assert_eq!(
+            strip_navigation_boilerplate(text),
+            "\nInstallation\nRun the installer."
+        );
+
+        let custom =
+            strip_navigation_boilerplate_with_patterns("Back to top\nContent", &["back to top"]);
+        assert_eq!(custom, "Content");
+
+        // Every default pattern compiles
+        assert_eq!(NAVIGATION_REGEXES.len(), DEFAULT_NAVIGATION_PATTERNS.len());
+    }
+
+    #[test]
+    fn test_txt_sanitize() {
+        let input = r#"{% code title="test.rs" %}fn main(){}{% endcode %}"#;
+        let output = txt_sanitize(input);
+        assert!(output.contains("test.rs"));
+        assert!(output.contains("fn main(){}"));
+    }
+
+    #[test]
+    fn test_txt_sanitize_hints() {
+        let input = "{% hint style=\"warning\" %}\nBack up first\n{% endhint %}\nThen {% hint style='info' %}run it{% endhint %}";
+        assert_eq!(
+            txt_sanitize(input),
+            "[WARNING] Back up first\nThen [INFO] run it"
+        );
+    }
+
+    #[test]
+    fn test_txt_sanitize_blank_lines() {
+        let input = "Title\n\n\n\nFirst  paragraph\n \n{% endhint %}\n\nSecond\n\n";
+        assert_eq!(txt_sanitize(input), "Title\n\nFirst paragraph\n\nSecond");
+
+        assert_eq!(
+            txt_sanitize("Steps:\n  • one  {% hint style=\"info\" %}two{% endhint %}\n\t• three "),
+            "Steps:\n  • one [INFO] two\n\t• three"
+        );
+
+        let config = SanitizeConfig {
+            join_lines: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            txt_sanitize_with_config(input, &config),
+            "Title First paragraph Second"
+        );
+    }
+
+    #[test]
+    fn test_combine_texts() {
+        let pages = [
+            ("https://x.com/intro", "Intro", "Hello"),
+            ("https://x.com/setup", "", "Install"),
+        ];
+        assert_eq!(
+            combine_texts(&pages, "<PAGE {index}/{total} {title} {url}>\n"),
+            "<PAGE 1/2 Intro https://x.com/intro>\nHello<PAGE 2/2  https://x.com/setup>\nInstall"
+        );
+        assert_eq!(combine_texts(&pages, "\x0c"), "\x0cHello\x0cInstall");
+        assert_eq!(combine_texts(&[], DEFAULT_PAGE_SEPARATOR), "");
+    }
+
+    #[test]
+    fn test_write_text_colored() {
+        let mut buffer = termcolor::Buffer::ansi();
+        write_text_colored(&mut buffer, "a [WARNING] b [NOTE] c").unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.into_inner()).unwrap(),
+            "a \x1b[0m\x1b[1m\x1b[33m[WARNING]\x1b[0m b [NOTE] c"
+        );
+    }
+
+    #[test]
+    fn test_txt_sanitize_swagger() {
+        let input = r#"{% swagger method="get" path="/users" summary="List users" %}{% swagger-description %}Returns all users{% endswagger-description %}{% endswagger %}"#;
+        let output = txt_sanitize(input);
+        assert_eq!(output, "GET /users — List users Returns all users");
+
+        let input = r#"{% openapi src="api.yaml" path="/pets" method="post" %}{% endopenapi %}"#;
+        assert_eq!(txt_sanitize(input), "POST /pets");
+
+        let input =
+            r#"{% swagger method="delete" path="/users/{id}/keys/{key}" %}{% endswagger %}"#;
+        assert_eq!(txt_sanitize(input), "DELETE /users/{id}/keys/{key}");
+    }
+
+    #[test]
+    fn test_txt_sanitize_keep_gitbook_tags() {
+        let config = SanitizeConfig {
+            remove_gitbook_tags: false,
+            ..Default::default()
+        };
+        let input = "{% code title=main.rs %}fn main()  {}{% endcode %}
+{% swagger method=get %}{% endswagger %}";
+        assert_eq!(
+            txt_sanitize_with_config(input, &config),
+            "{% code title=main.rs %}fn main() {}{% endcode %}\n{% swagger method=get %}{% endswagger %}"
+        );
+        assert_eq!(txt_sanitize(input), "fn main() {}");
+    }
+
+    #[test]
+    fn test_txt_sanitize_dashes() {
+        let input = r#"Use the "--output" flag, the Content-Type header | type-safe"#;
+        assert_eq!(
+            txt_sanitize(input),
+            "Use the --output flag, the Content-Type header type-safe"
+        );
+
+        let config = SanitizeConfig {
+            remove_dashes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            txt_sanitize_with_config(input, &config),
+            "Use the output flag, the ContentType header typesafe"
+        );
+    }
+
+    #[test]
+    fn test_txt_sanitize_content_ref() {
+        let input = "Next:\n{% content-ref url=\"../api/rate-limits.md\" %}\nrate-limits.md\n{% endcontent-ref %}\nDone";
+        assert_eq!(
+            txt_sanitize(input),
+            "Next:\nrate-limits.md [See: ../api/rate-limits.md]\nDone"
+        );
+
+        let context = SanitizeContext {
+            base_url: Some("https://docs.example.com/guide/intro.md".to_string()),
+        };
+        assert_eq!(
+            txt_sanitize_with_context(input, &SanitizeConfig::default(), &context),
+            "Next:\nrate-limits.md [See: https://docs.example.com/api/rate-limits.md]\nDone"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_page_timeout() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/slow" => MockResponse::ok("ok").delay(Duration::from_secs(5)),
+            "/brotli" => MockResponse::ok(*b"\x0b\x00").header("Content-Encoding", "br"),
+            "/missing" => MockResponse::not_found(),
+            "/unavailable" => MockResponse::new(503, "ok"),
+            _ => MockResponse::ok("ok"),
+        })
+        .await;
+
+        let client = reqwest::Client::new();
+        let timeout = Some(Duration::from_millis(200));
+
+        let fast = format!("{}/fast", server.url);
+        let content = download_page_with_client(&client, &fast, timeout).await;
+        assert_eq!(content.unwrap(), "ok");
+
+        let slow = format!("{}/slow", server.url);
+        match download_page_with_client(&client, &slow, timeout).await {
+            Err(GitBookError::NetworkError(e)) => assert!(e.is_timeout()),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+
+        let missing = format!("{}/missing", server.url);
+        match download_page_with_client(&client, &missing, timeout).await {
+            Err(GitBookError::HttpError(404, url)) => assert_eq!(url, missing),
+            other => panic!("expected a 404, got {:?}", other),
+        }
+
+        let unavailable = format!("{}/unavailable", server.url);
+        match download_page_with_client(&client, &unavailable, timeout).await {
+            Err(GitBookError::HttpError(503, _)) => {}
+            other => panic!("expected a 503, got {:?}", other),
+        }
+
+        let brotli = format!("{}/brotli", server.url);
+        match download_page_with_client(&client, &brotli, timeout).await {
+            Err(GitBookError::UnsupportedEncoding(encoding, _)) => assert_eq!(encoding, "br"),
+            other => panic!("expected an unsupported encoding, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_markdown_streaming() {
+        let server = MockServer::pages(vec![("/page.md", "# Page")]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        std::fs::create_dir(dir.path().join("md")).unwrap();
+
+        let url = format!("{}/page.md", server.url);
+        assert_eq!(save_markdown_streaming(&url, &config).await.unwrap(), 6);
+        let path = output_path(&url, &dir.path().join("md"), "md", &config);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "# Page");
+
+        // A failed download leaves no file behind
+        let url = format!("{}/gone.md", server.url);
+        assert!(matches!(
+            save_markdown_streaming(&url, &config).await,
+            Err(GitBookError::HttpError(404, _))
+        ));
+        assert_eq!(std::fs::read_dir(dir.path().join("md")).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_download_page_compressed() {
+        let text = "# Title\n\nHello, hello, hello!\n";
+        let zstd = zstd::stream::encode_all(text.as_bytes(), 3).unwrap();
+        let server = MockServer::start(move |request| {
+            let response = match request.path.as_str() {
+                "/gzip.md" => MockResponse::ok(*b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x53\x56\x08\xc9\x2c\xc9\x49\xe5\xe2\xf2\x48\xcd\xc9\xc9\xd7\x51\xc8\x40\xa2\x14\xb9\x00\xe7\xb9\x10\x40\x1e\x00\x00\x00")
+                    .header("Content-Encoding", "gzip"),
+                "/deflate.md" => MockResponse::ok(*b"\x78\xda\x53\x56\x08\xc9\x2c\xc9\x49\xe5\xe2\xf2\x48\xcd\xc9\xc9\xd7\x51\xc8\x40\xa2\x14\xb9\x00\x8d\x12\x09\x39")
+                    .header("Content-Encoding", "deflate"),
+                "/zstd.md" => MockResponse::ok(zstd.clone()).header("Content-Encoding", "zstd"),
+                "/corrupt.md" => MockResponse::ok("# Title").header("Content-Encoding", "gzip"),
+                _ => MockResponse::not_found(),
+            };
+            response.header("Content-Type", "text/markdown")
+        })
+        .await;
+        let client = reqwest::Client::new();
+
+        for encoding in ["gzip", "deflate", "zstd"] {
+            let url = format!("{}/{}.md", server.url, encoding);
+            let content = download_page_with_client(&client, &url, None)
+                .await
+                .unwrap();
+            assert_eq!(content, text, "{}", encoding);
+            assert_eq!(download_page(&url).await.unwrap(), text, "{}", encoding);
+        }
+        assert!(server.requests()[0].contains("accept-encoding: gzip, deflate, zstd"));
+
+        let corrupt = format!("{}/corrupt.md", server.url);
+        match download_page_with_client(&client, &corrupt, None).await {
+            Err(GitBookError::ParseError(e)) => assert!(e.contains("invalid gzip body")),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let bomb = zstd::stream::encode_all(vec![0u8; 1 << 20].as_slice(), 19).unwrap();
+        let encodings = ["zstd".to_string()];
+        let body = decompress(&bomb, &encodings, "u", 1 << 20).unwrap();
+        assert_eq!(body.len(), 1 << 20);
+        match decompress(&bomb, &encodings, "u", 1000) {
+            Err(GitBookError::ParseError(e)) => assert!(e.contains("larger than 1000 bytes")),
+            other => panic!("expected a parse error, got {:?}", other.map(|b| b.len())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_page_streaming() {
+        let body = "# Big page\n".repeat(10_000);
+        let served = body.clone();
+        let server = MockServer::start(move |request| match request.path.as_str() {
+            "/missing" => MockResponse::not_found(),
+            _ => MockResponse::ok(served.clone()),
+        })
+        .await;
+
+        let url = format!("{}/page.md", server.url);
+        let mut written = Vec::new();
+        let size = download_page_streaming(&url, &mut written).await.unwrap();
+        assert_eq!(size, body.len() as u64);
+        assert_eq!(written, body.as_bytes());
+
+        assert_eq!(download_page(&url).await.unwrap(), body);
+
+        let missing = format!("{}/missing", server.url);
+        let result = download_page_streaming(&missing, Vec::new()).await;
+        assert!(matches!(result, Err(GitBookError::HttpError(404, _))));
+    }
+
+    #[tokio::test]
+    async fn test_download_page_verified() {
+        let server = MockServer::start(|_| MockResponse::ok("# Page\n")).await;
+        let url = format!("{}/page.md", server.url);
+        let dir = tempfile::tempdir().unwrap();
+        let config = CrawlConfig {
+            output_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(dir.path().join("md")).unwrap();
+        std::fs::create_dir_all(dir.path().join("txt")).unwrap();
+
+        assert_eq!(read_markdown_hash(&url, &config).await.unwrap(), None);
+        save_markdown_with_hash(&url, "# Page\n", &config)
+            .await
+            .unwrap();
+        let sidecar = output_path(&url, &dir.path().join("md"), "md.sha256", &config);
+        assert!(std::fs::read_to_string(sidecar).unwrap().ends_with(".md\n"));
+
+        let hash = read_markdown_hash(&url, &config).await.unwrap().unwrap();
+        assert_eq!(
+            download_page_verified(&url, &hash).await.unwrap(),
+            "# Page\n"
+        );
+
+        save_markdown_with_hash(&url, "# Old page\n", &config)
+            .await
+            .unwrap();
+        let hash = read_markdown_hash(&url, &config).await.unwrap().unwrap();
+        let result = download_page_verified(&url, &hash).await;
+        assert!(matches!(result, Err(GitBookError::ChecksumMismatch { .. })));
+
+        save_text_with_hash(&url, "Page", &config).await.unwrap();
+        let sidecar = output_path(&url, &dir.path().join("txt"), "txt.sha256", &config);
+        let expected = hex(&crate::dedup::content_hash("Page"));
+        assert!(std::fs::read_to_string(sidecar)
+            .unwrap()
+            .starts_with(&expected));
+    }
+
+    #[test]
+    fn test_markdown_to_asciidoc() {
+        let cases = [
+            ("# Title", "= Title"),
+            ("## Section\n\n### Sub", "== Section\n\n=== Sub"),
+            ("Some **bold** and *italic*", "Some **bold** and __italic__"),
+            (
+                "un**break**able *intra*word",
+                "un**break**able __intra__word",
+            ),
+            (
+                "| Name | Value |\n|------|-------|\n| a | b \\| c |\n| d | *e* |",
+                "|===\n| Name | Value\n\n| a | b \\| c\n| d | __e__\n|===",
+            ),
+            (
+                "| L | C | R |\n|:--|:-:|--:|\n| 1 | 2 | 3 |",
+                "[cols=\"<,^,>\"]\n|===\n| L | C | R\n\n| 1 | 2 | 3\n|===",
+            ),
+            (
+                "| a |\n|---|\n| b |\n\nafter",
+                "|===\n| a\n\n| b\n|===\n\nafter",
+            ),
+            ("Run `make`", "Run `make`"),
+            (
+                "```rust\nfn main() {}\n```",
+                "[source,rust]\n----\nfn main() {}\n----",
+            ),
+            ("```\nplain\n```", "----\nplain\n----"),
+            ("    indented", "----\nindented\n----"),
+            (
+                "[docs](https://x.com/docs) [setup](setup.md) <https://x.com>",
+                "https://x.com/docs[docs] link:setup.md[setup] https://x.com",
+            ),
+            ("![Logo](logo.png)", "image:logo.png[Logo]"),
+            ("- a\n- b\n  - c", "* a\n* b\n** c"),
+            ("1. one\n2. two", ". one\n. two"),
+            ("> quoted\n> text", "____\nquoted\ntext\n____"),
+            ("a\n\n---\n\nb", "a\n\n'''\n\nb"),
+            ("line  \nbreak", "line +\nbreak"),
+        ];
+        for (md, adoc) in cases {
+            assert_eq!(markdown_to_asciidoc(md), adoc, "markdown: {:?}", md);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_absolute_urls() {
+        let md = "[a](https://x.com/a) ![img](https://x.com/img.png \"T\") <https://x.com/b>\n\
+                  [c](https://other.com/c) [ref]\n\n```\nhttps://x.com/code\n```\n\n\
+                  [ref]: https://x.com/ref\n";
+        assert_eq!(
+            rewrite_absolute_urls(md, "https://x.com/", "file:///mirror/"),
+            "[a](file:///mirror/a) ![img](file:///mirror/img.png \"T\") <file:///mirror/b>\n\
+             [c](https://other.com/c) [ref]\n\n```\nhttps://x.com/code\n```\n\n\
+             [ref]: file:///mirror/ref\n"
+        );
+        assert_eq!(rewrite_absolute_urls(md, "", "file:///"), md);
+
+        // The link text is left alone when it is the URL itself
+        assert_eq!(
+            rewrite_absolute_urls(
+                "[https://x.com/a](https://x.com/a) [![https://x.com/i](https://x.com/i)](https://x.com/b)",
+                "https://x.com/",
+                "/m/"
+            ),
+            "[https://x.com/a](/m/a) [![https://x.com/i](/m/i)](/m/b)"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_markdown_links() {
+        let pages = HashMap::from([
+            (
+                "https://x.com".to_string(),
+                PathBuf::from("data/md/index.md"),
+            ),
+            (
+                "https://x.com/guide/setup.md".to_string(),
+                PathBuf::from("data/md/guide/setup.md"),
+            ),
+            (
+                "https://x.com/api".to_string(),
+                PathBuf::from("data/md/api.md"),
+            ),
+        ]);
+        let md =
+            "[home](/) [api](../api/#keys) [self](#top) [gone](/gone) [out](https://y.com/api)\n\
+                  `https://x.com/api` [ref]\n\n[ref]: https://x.com/api\n";
+        assert_eq!(
+            rewrite_markdown_links(md, "https://x.com/guide/setup", &pages),
+            "[home](../index.md) [api](../api.md#keys) [self](#top) [gone](/gone) [out](https://y.com/api)\n\
+             `https://x.com/api` [ref]\n\n[ref]: ../api.md\n"
+        );
+        assert_eq!(
+            rewrite_markdown_links(md, "https://x.com/unknown", &pages),
+            md
+        );
+
+        // The link text is left alone when it is the destination itself
+        assert_eq!(
+            rewrite_markdown_links(
+                "[../api](../api) [https://x.com/api](https://x.com/api)",
+                "https://x.com/guide/setup",
+                &pages
+            ),
+            "[../api](../api.md) [https://x.com/api](../api.md)"
+        );
+        assert_eq!(
+            rewrite_markdown_links("[api](api)", "https://x.com", &pages),
+            "[api](api.md)"
+        );
+    }
+
+    #[test]
+    fn test_with_encoding_declaration() {
+        let decl = DEFAULT_ENCODING_DECLARATION;
+
+        let text = with_encoding_declaration("# Title comment\nBody", decl);
+        assert_eq!(text, format!("{}\n# Title comment\nBody", decl));
+        assert_eq!(text.matches(decl).count(), 1);
+
+        let again = with_encoding_declaration(&text, decl);
+        assert_eq!(again, text);
+
+        assert_eq!(with_encoding_declaration("", decl), format!("{}\n", decl));
     }
 }
@@ -0,0 +1,343 @@
+use crate::config::{CrawlConfig, DownloadConfig};
+use crate::crawler::build_client;
+use crate::download::override_base_url;
+use crate::manifest::{hex_content_hash, Manifest, ManifestPage};
+use crate::search::read_output_file;
+use crate::utils::download_page_with_client;
+use crate::GitBookError;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use url::Url;
+
+/// Above this number of compared word pairs, a changed section is shown as fully replaced
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// The state of a downloaded page compared with the live site
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageStatus {
+    /// The page still has the content recorded in the manifest
+    Unchanged,
+
+    /// The content of the page changed; `diff` is a word diff of its first differing
+    /// section against the local copy, the removed words as `[-words-]` and the added
+    /// ones as `{+words+}`
+    Changed { diff: String },
+
+    /// The page answers with a 404 or 410 status
+    Deleted,
+
+    /// The page could not be downloaded
+    Failed { error: String },
+}
+
+/// The result of [`verify_pages`] for a page of the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PageVerification {
+    /// The URL of the page, as recorded in the manifest
+    pub url: String,
+
+    /// How the live page compares with the downloaded one
+    pub status: PageStatus,
+}
+
+/// Downloads the pages of a manifest again and compares them with the recorded hashes
+///
+/// Only the pages with a `content_hash` are checked. The diff of a changed page is
+/// computed against its `file_path`, or against an empty page if the file is gone.
+/// The pages are downloaded as [`download_pages`](crate::download_pages) does: with
+/// the `per_request_timeout`, `base_url_override` rewritten before hashing, and at most
+/// `parallel_downloads` pages at once. The results are sorted by URL.
+///
+/// # Arguments
+///
+/// * `manifest` - The manifest of the downloaded pages
+/// * `config` - The crawl configuration, used to build the HTTP client
+/// * `download_config` - The download configuration the pages were downloaded with
+///
+/// # Errors
+///
+/// Returns an error if the HTTP client cannot be built
+///
+/// # Exemples
+///
+/// ```no_run
+/// use gitbook2text::{verify_pages, CrawlConfig, DownloadConfig, Manifest, PageStatus};
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let manifest = Manifest::load(Path::new("data/manifest.json"))?;
+///     let config = CrawlConfig::default();
+///     for page in verify_pages(&manifest, &config, &DownloadConfig::default()).await? {
+///         if let PageStatus::Changed { diff } = &page.status {
+///             println!("{}\n{}", page.url, diff);
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn verify_pages(
+    manifest: &Manifest,
+    config: &CrawlConfig,
+    download_config: &DownloadConfig,
+) -> Result<Vec<PageVerification>, GitBookError> {
+    let pages: Vec<&ManifestPage> = manifest
+        .pages
+        .iter()
+        .filter(|page| page.content_hash.is_some())
+        .collect();
+    let parsed: Vec<Url> = pages
+        .iter()
+        .filter_map(|p| Url::parse(&p.url).ok())
+        .collect();
+    let client = build_client(config, &parsed)?;
+    let client = &client;
+
+    // The pages are started as the previous ones finish, `parallel_downloads` at most
+    let mut pages = pages.into_iter();
+    let mut futures = FuturesUnordered::new();
+    let mut results = Vec::new();
+    loop {
+        while futures.len() < download_config.parallel_downloads.max(1) {
+            match pages.next() {
+                Some(page) => futures.push(verify_page(client, page, download_config)),
+                None => break,
+            }
+        }
+        match futures.next().await {
+            Some(result) => results.push(result),
+            None => break,
+        }
+    }
+    results.sort_by(|a, b| a.url.cmp(&b.url));
+    Ok(results)
+}
+
+async fn verify_page(
+    client: &reqwest::Client,
+    page: &ManifestPage,
+    download_config: &DownloadConfig,
+) -> PageVerification {
+    let url = match page.url.ends_with(".md") {
+        true => page.url.clone(),
+        false => format!("{}.md", page.url),
+    };
+
+    let markdown = download_page_with_client(client, &url, download_config.per_request_timeout)
+        .await
+        .map(|md| override_base_url(md, &url, download_config));
+    let status = match markdown {
+        Ok(markdown) if page.content_hash.as_deref() == Some(&hex_content_hash(&markdown)) => {
+            PageStatus::Unchanged
+        }
+        Ok(markdown) => {
+            let local = page
+                .file_path
+                .as_deref()
+                .and_then(|path| read_output_file(path).ok())
+                .unwrap_or_default();
+            PageStatus::Changed {
+                diff: first_section_diff(&local, &markdown),
+            }
+        }
+        Err(GitBookError::HttpError(404 | 410, _)) => PageStatus::Deleted,
+        Err(e) => PageStatus::Failed {
+            error: e.to_string(),
+        },
+    };
+    PageVerification {
+        url: page.url.clone(),
+        status,
+    }
+}
+
+/// Splits a markdown page before each heading outside of the code blocks
+fn sections(md: &str) -> Vec<String> {
+    let mut sections = vec![String::new()];
+    let mut in_code = false;
+    for line in md.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        let current = sections.last_mut().expect("at least one section");
+        if !in_code && line.starts_with('#') && !current.trim().is_empty() {
+            sections.push(String::new());
+        }
+        let current = sections.last_mut().expect("at least one section");
+        current.push_str(line);
+        current.push('\n');
+    }
+    sections
+}
+
+/// Returns the word diff of the first section whose words differ, empty if none does
+fn first_section_diff(old: &str, new: &str) -> String {
+    let (old, new) = (sections(old), sections(new));
+    fn words(sections: &[String], i: usize) -> Vec<&str> {
+        sections
+            .get(i)
+            .map(|s| s.split_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    (0..old.len().max(new.len()))
+        .find(|&i| words(&old, i) != words(&new, i))
+        .map(|i| word_diff(&words(&old, i), &words(&new, i)))
+        .unwrap_or_default()
+}
+
+/// Diffs two lists of words, in the format of `git diff --word-diff`
+fn word_diff(old: &[&str], new: &[&str]) -> String {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut ops: Vec<(char, &str)> = old[..prefix].iter().map(|w| ('=', *w)).collect();
+    if a.len() * b.len() > MAX_DIFF_CELLS {
+        ops.extend(a.iter().map(|w| ('-', *w)));
+        ops.extend(b.iter().map(|w| ('+', *w)));
+    } else {
+        // Length of the longest common subsequence of a[i..] and b[j..]
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = match a[i] == b[j] {
+                    true => lcs[i + 1][j + 1] + 1,
+                    false => lcs[i + 1][j].max(lcs[i][j + 1]),
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push(('=', a[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(('-', a[i]));
+                i += 1;
+            } else {
+                ops.push(('+', b[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|w| ('=', *w)));
+
+    ops.chunk_by(|x, y| x.0 == y.0)
+        .map(|run| {
+            let words: Vec<&str> = run.iter().map(|(_, w)| *w).collect();
+            match run[0].0 {
+                '-' => format!("[-{}-]", words.join(" ")),
+                '+' => format!("{{+{}+}}", words.join(" ")),
+                _ => words.join(" "),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{MockResponse, MockServer};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn test_first_section_diff() {
+        let old = "# Intro\n\nHello world.\n\n## Setup\n\nRun the old installer now.\n";
+        let new = "# Intro\n\nHello   world.\n\n## Setup\n\nRun the new installer today.\n";
+        assert_eq!(
+            first_section_diff(old, new),
+            "## Setup Run the [-old-] {+new+} installer [-now.-] {+today.+}"
+        );
+        assert_eq!(first_section_diff(old, old), "");
+        assert_eq!(first_section_diff("# A\n", "# A\n\n# B\n"), "{+# B+}");
+    }
+
+    #[tokio::test]
+    async fn test_verify_pages() {
+        let server = MockServer::pages(vec![("/same.md", "# Same"), ("/edited.md", "# New")]).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let local = dir.path().join("edited.md");
+        std::fs::write(&local, "# Old").unwrap();
+
+        let base = server.url;
+        let mut manifest = Manifest::new(&base);
+        for (path, content, file_path) in [
+            ("/same", "# Same", None),
+            ("/edited", "# Old", Some(local)),
+            ("/gone", "# Gone", None::<PathBuf>),
+        ] {
+            manifest.upsert(ManifestPage {
+                content_hash: Some(hex_content_hash(content)),
+                file_path,
+                ..ManifestPage::new(format!("{}{}", base, path), 1)
+            });
+        }
+        manifest.upsert(ManifestPage::new(format!("{}/never", base), 1));
+
+        let results = verify_pages(
+            &manifest,
+            &CrawlConfig::default(),
+            &DownloadConfig::default(),
+        )
+        .await
+        .unwrap();
+        let statuses: Vec<_> = results.iter().map(|r| r.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                PageStatus::Changed {
+                    diff: "# [-Old-] {+New+}".to_string()
+                },
+                PageStatus::Deleted,
+                PageStatus::Unchanged,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_pages_download_config() {
+        let server = MockServer::start(|request| {
+            let host = request.header("host").unwrap_or_default();
+            let body = format!("[Next](http://{}/next)", host);
+            match request.path.as_str() {
+                "/fast.md" => MockResponse::ok(body),
+                _ => MockResponse::ok(body).delay(Duration::from_secs(5)),
+            }
+        })
+        .await;
+
+        let base = server.url;
+        let mut manifest = Manifest::new(&base);
+        for path in ["/fast", "/slow"] {
+            manifest.upsert(ManifestPage {
+                content_hash: Some(hex_content_hash("[Next](https://docs.example.com/next)")),
+                ..ManifestPage::new(format!("{}{}", base, path), 1)
+            });
+        }
+
+        let download_config = DownloadConfig {
+            base_url_override: Some("https://docs.example.com/".to_string()),
+            per_request_timeout: Some(Duration::from_millis(200)),
+            ..DownloadConfig::default()
+        };
+        let results = verify_pages(&manifest, &CrawlConfig::default(), &download_config)
+            .await
+            .unwrap();
+        assert_eq!(results[0].status, PageStatus::Unchanged);
+        assert!(matches!(results[1].status, PageStatus::Failed { .. }));
+    }
+}
@@ -0,0 +1,80 @@
+//! Every public type must stay reachable from the crate root
+
+use gitbook2text::{
+    CharacterTokenizer, CircuitBreaker, CircuitBreakerState, CodeBlock, ColorMode, ContentType,
+    CrawlCache, CrawlConfig, CrawlOutputFormat, DedupStrategy, Deduplicator, DocumentIndex,
+    DownloadConfig, DownloadEvent, DownloadReport, ExtensionFilter, GitBookConfig, GitBookError,
+    GitBookJsonPage, GitBookPage, GitBookPageReader, GitBookPageWriter, GitBookSite,
+    HtmlStripRenderer, HtmlToMdConfig, IndexEntry, Lang, LinkFilter, LinkSorter, LintIssue,
+    LlmChunkRenderer, Manifest, ManifestPage, ManifestPageStatus, MergedLinks, OrderedListStyle,
+    OutputFormat, PageChain, PageInfo, PageMetadata, PageScore, PageStats, PageStatus,
+    PageVerification, PathPrefixFilter, PreVisited, PulldownRenderer, SameDomainFilter,
+    SanitizeConfig, SanitizeContext, SearchHit, SearchResult, Severity, Source, TextConfig,
+    TextRenderer, TextStats, Tokenize, UnicodeForm, WordCount,
+};
+
+fn exported<T: ?Sized>() {}
+
+#[test]
+fn test_public_types_are_exported_from_the_crate_root() {
+    exported::<CharacterTokenizer>();
+    exported::<CircuitBreaker>();
+    exported::<CircuitBreakerState>();
+    exported::<CodeBlock>();
+    exported::<ColorMode>();
+    exported::<ContentType>();
+    exported::<CrawlCache>();
+    exported::<CrawlConfig>();
+    exported::<CrawlOutputFormat>();
+    exported::<DedupStrategy>();
+    exported::<Deduplicator>();
+    exported::<DocumentIndex>();
+    exported::<DownloadConfig>();
+    exported::<DownloadEvent>();
+    exported::<DownloadReport>();
+    exported::<ExtensionFilter>();
+    exported::<GitBookConfig>();
+    exported::<GitBookError>();
+    exported::<GitBookJsonPage>();
+    exported::<GitBookPage>();
+    exported::<GitBookPageReader>();
+    exported::<GitBookPageWriter>();
+    exported::<GitBookSite>();
+    exported::<HtmlStripRenderer>();
+    exported::<HtmlToMdConfig>();
+    exported::<IndexEntry>();
+    exported::<Lang>();
+    exported::<dyn LinkFilter>();
+    exported::<LinkSorter>();
+    exported::<LintIssue>();
+    exported::<LlmChunkRenderer>();
+    exported::<Manifest>();
+    exported::<ManifestPage>();
+    exported::<ManifestPageStatus>();
+    exported::<MergedLinks>();
+    exported::<OrderedListStyle>();
+    exported::<OutputFormat>();
+    exported::<PageChain>();
+    exported::<PageInfo>();
+    exported::<PageMetadata>();
+    exported::<PageScore>();
+    exported::<PageStats>();
+    exported::<PageStatus>();
+    exported::<PageVerification>();
+    exported::<PathPrefixFilter>();
+    exported::<PreVisited>();
+    exported::<PulldownRenderer>();
+    exported::<SameDomainFilter>();
+    exported::<SanitizeConfig>();
+    exported::<SanitizeContext>();
+    exported::<SearchHit>();
+    exported::<SearchResult>();
+    exported::<Severity>();
+    exported::<Source>();
+    exported::<TextConfig>();
+    exported::<dyn TextRenderer>();
+    exported::<TextStats>();
+    exported::<dyn Tokenize>();
+    exported::<UnicodeForm>();
+    exported::<WordCount>();
+}
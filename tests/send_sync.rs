@@ -0,0 +1,114 @@
+//! The public types and the futures of the public async functions must be usable from
+//! `tokio::spawn` on a multi-threaded runtime
+
+use futures::stream;
+use gitbook2text::*;
+use std::future::Future;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+fn send_sync<T: Send + Sync + ?Sized>() {}
+
+/// Accepts the futures `tokio::spawn` accepts, apart from the `'static` bound
+fn spawnable<F>(_: F)
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+}
+
+#[test]
+fn test_public_types_are_send_and_sync() {
+    send_sync::<CircuitBreaker>();
+    send_sync::<CrawlCache>();
+    send_sync::<CrawlConfig>();
+    send_sync::<Deduplicator>();
+    send_sync::<DocumentIndex>();
+    send_sync::<DownloadConfig>();
+    send_sync::<DownloadEvent>();
+    send_sync::<DownloadReport>();
+    send_sync::<GitBookError>();
+    send_sync::<GitBookPage>();
+    send_sync::<GitBookPageReader>();
+    send_sync::<GitBookPageWriter>();
+    send_sync::<GitBookSite>();
+    send_sync::<dyn LinkFilter>();
+    send_sync::<Manifest>();
+    send_sync::<PageChain>();
+    send_sync::<PageInfo>();
+    send_sync::<PageVerification>();
+    send_sync::<PreVisited>();
+    send_sync::<Source>();
+    send_sync::<dyn TextRenderer>();
+}
+
+// The futures are built to check their type, never polled
+#[test]
+fn test_public_futures_are_send() {
+    let url = "https://docs.example.com";
+    let config = CrawlConfig::default();
+    let download_config = DownloadConfig::default();
+    let page = GitBookPage::parse(url, None, "# Intro");
+    let manifest = Manifest::new(url);
+    let urls = || vec![url.to_string()];
+
+    spawnable(is_gitbook(url));
+    spawnable(is_gitbook_with_config(url, &config));
+    spawnable(detect_gitbook_confidence(url));
+    spawnable(detect_gitbook_confidence_with_config(url, &config));
+    spawnable(extract_gitbook_links(url));
+    spawnable(extract_gitbook_links_with_config(url, &config));
+    spawnable(extract_gitbook_links_with_info(url, &config));
+    spawnable(extract_gitbook_links_parallel(url, &config));
+    spawnable(crawl_pages(url, &config));
+    spawnable(crawl_sitemap_only(url, &config));
+    spawnable(find_gitbooks(url, 1));
+    spawnable(find_gitbooks_with_config(url, 1, &config));
+    spawnable(crawl_and_save(url, "links.txt"));
+    spawnable(crawl_and_save_with_config(url, "links.txt", &config));
+    spawnable(GitBookSite::fetch(url, &config));
+    let source = Source::from_url(url);
+    spawnable(source.markdown_urls(&config));
+    spawnable(source.fetch(&config));
+    spawnable(verify_pages(&manifest, &config, &download_config));
+
+    spawnable(download_pages(urls(), &config, &download_config));
+    let (tx, _rx) = mpsc::unbounded_channel();
+    spawnable(download_pages_with_events(
+        urls(),
+        &config,
+        &download_config,
+        tx,
+    ));
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let stream = stream::iter(urls());
+    spawnable(download_pages_stream(stream, &config, &download_config, tx));
+
+    spawnable(download_page(url));
+    spawnable(download_page_verified(url, &[0; 32]));
+    spawnable(download_page_checked(url, Some([0; 32])));
+    spawnable(download_page_streaming(url, Vec::new()));
+    spawnable(download_page_json(url));
+    spawnable(read_markdown_hash(url, &config));
+
+    spawnable(save_page(&page, &config));
+    spawnable(save_markdown(url, "# Intro"));
+    spawnable(save_markdown_with_config(url, "# Intro", &config));
+    spawnable(save_markdown_compressed(url, "# Intro", 3, &config));
+    spawnable(save_markdown_with_hash(url, "# Intro", &config));
+    spawnable(save_markdown_streaming(url, &config));
+    spawnable(save_text(url, "Intro"));
+    spawnable(save_text_with_config(url, "Intro", &config));
+    spawnable(save_text_streaming(url, "Intro".as_bytes(), &config));
+    spawnable(save_text_wrapped(
+        url,
+        "Intro",
+        Path::new("txt"),
+        80,
+        &config,
+    ));
+    spawnable(save_text_compressed(url, "Intro", 3, &config));
+    spawnable(save_text_with_hash(url, "Intro", &config));
+    spawnable(save_asciidoc(url, "# Intro", Path::new("adoc")));
+    spawnable(save_code_blocks(url, &[], Path::new("code")));
+}